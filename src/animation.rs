@@ -0,0 +1,358 @@
+// Camera moves described by a few poses at given times, with the frames in between
+// interpolated, and rendering whole animations to files or a video
+use crate::{camera::Camera, canvas::Canvas, matrix::Matrix, tuple::Tuple, world::World};
+use std::{
+    io::Write,
+    process::{Child, Command, Stdio},
+    time::{Duration, Instant},
+};
+
+// Where the camera is, what it looks at and which way is up at a moment of the
+// animation, as taken by Matrix::view_transform
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Keyframe {
+    pub time: f64,
+    pub from: Tuple,
+    pub to: Tuple,
+    pub up: Tuple,
+}
+
+impl Keyframe {
+    pub fn new(time: f64, from: Tuple, to: Tuple, up: Tuple) -> Self {
+        assert!(from.is_point() && to.is_point() && up.is_vector());
+        Keyframe { time, from, to, up }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Interpolation {
+    // Straight lines between keyframes, changing direction abruptly at each one
+    Linear,
+    // A Catmull-Rom spline through the keyframes, so the camera glides through them
+    #[default]
+    Smooth,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraPath {
+    keyframes: Vec<Keyframe>,
+    pub interpolation: Interpolation,
+}
+
+// The Catmull-Rom segment from p1 to p2 at t between 0 and 1
+fn catmull_rom(p0: Tuple, p1: Tuple, p2: Tuple, p3: Tuple, t: f64) -> Tuple {
+    // Relative to p1, so points are only ever offset by vectors
+    let (a, c, d) = (p0 - p1, p2 - p1, p3 - p1);
+    let (t2, t3) = (t * t, t * t * t);
+    p1 + ((c - a) * t + (a * 2. + c * 4. - d) * t2 + (c * -3. - a + d) * t3) * 0.5
+}
+
+impl CameraPath {
+    // The keyframes are sorted by time
+    pub fn new(mut keyframes: Vec<Keyframe>) -> Self {
+        assert!(!keyframes.is_empty());
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        CameraPath {
+            keyframes,
+            interpolation: Interpolation::default(),
+        }
+    }
+
+    pub fn start(&self) -> f64 {
+        self.keyframes[0].time
+    }
+
+    pub fn end(&self) -> f64 {
+        self.keyframes[self.keyframes.len() - 1].time
+    }
+
+    // The pose at the given time, held still before the first keyframe and after the
+    // last one
+    pub fn at(&self, time: f64) -> Keyframe {
+        let keyframes = &self.keyframes;
+        let last = keyframes.len() - 1;
+        if time <= self.start() {
+            return Keyframe {
+                time,
+                ..keyframes[0]
+            };
+        }
+        if time >= self.end() {
+            return Keyframe {
+                time,
+                ..keyframes[last]
+            };
+        }
+        let i = keyframes.partition_point(|k| k.time <= time) - 1;
+        let (k1, k2) = (&keyframes[i], &keyframes[i + 1]);
+        let t = (time - k1.time) / (k2.time - k1.time);
+        let (k0, k3) = (
+            &keyframes[i.saturating_sub(1)],
+            &keyframes[(i + 2).min(last)],
+        );
+        let blend = |pick: fn(&Keyframe) -> Tuple| match self.interpolation {
+            Interpolation::Linear => pick(k1) + (pick(k2) - pick(k1)) * t,
+            Interpolation::Smooth => catmull_rom(pick(k0), pick(k1), pick(k2), pick(k3), t),
+        };
+        Keyframe {
+            time,
+            from: blend(|k| k.from),
+            to: blend(|k| k.to),
+            up: blend(|k| k.up),
+        }
+    }
+
+    pub fn transform_at(&self, time: f64) -> Matrix {
+        let pose = self.at(time);
+        Matrix::view_transform(pose.from, pose.to, pose.up)
+    }
+}
+
+impl Camera {
+    // Renders `frames` frames evenly spaced from the path's first keyframe to its
+    // last, one at a time as the iterator is advanced, so they can be saved as they
+    // come. The camera's own transform is ignored, and each frame's number and time
+    // fill in its annotation
+    pub fn render_sequence<'a>(
+        &'a self,
+        world: &'a World,
+        path: &'a CameraPath,
+        frames: usize,
+    ) -> impl Iterator<Item = Canvas> + 'a {
+        let step = if frames > 1 {
+            (path.end() - path.start()) / (frames - 1) as f64
+        } else {
+            0.
+        };
+        (0..frames).map(move |frame| {
+            let mut camera = self.clone();
+            let time = path.start() + step * frame as f64;
+            camera.transform = path.transform_at(time);
+            camera.frame = Some(frame);
+            camera.time = Some(time);
+            camera.render(world)
+        })
+    }
+}
+
+// Where render_animation puts the frames
+#[derive(Debug, Clone, PartialEq)]
+pub enum FrameOutput {
+    // A file per frame, named by replacing `{}` in the pattern with the frame number
+    // padded to 4 digits. PPM unless the name ends in .png and the png feature is on
+    Files(String),
+    // Raw RGB piped into an `ffmpeg` process found on the PATH, which encodes it into
+    // the given video file, replacing any file already there
+    Ffmpeg { path: String, fps: f64 },
+}
+
+fn save_frame(canvas: &Canvas, path: &str) -> Result<(), String> {
+    #[cfg(feature = "png")]
+    if path.ends_with(".png") {
+        return canvas.save_png(path);
+    }
+    std::fs::write(path, canvas.to_ppm()).map_err(|e| format!("{}: {}", path, e))
+}
+
+fn spawn_ffmpeg(path: &str, fps: f64, width: usize, height: usize) -> Result<Child, String> {
+    Command::new("ffmpeg")
+        .args([
+            "-y",
+            "-loglevel",
+            "error",
+            "-f",
+            "rawvideo",
+            "-pix_fmt",
+            "rgb24",
+        ])
+        .args(["-s", &format!("{}x{}", width, height)])
+        .args([
+            "-r",
+            &fps.to_string(),
+            "-i",
+            "-",
+            "-pix_fmt",
+            "yuv420p",
+            path,
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("can't run ffmpeg: {}", e))
+}
+
+// Renders `frames` frames, asking `scene` for the camera and world of each, and
+// sends them to the output as they're done. Cameras without a frame number are
+// given theirs, for their annotations. `progress` gets each frame's number and
+// how long it took to render, and all the times are returned at the end
+pub fn render_animation<S, P>(
+    frames: usize,
+    output: &FrameOutput,
+    mut scene: S,
+    mut progress: P,
+) -> Result<Vec<Duration>, String>
+where
+    S: FnMut(usize) -> (Camera, World),
+    P: FnMut(usize, Duration),
+{
+    let mut ffmpeg: Option<Child> = None;
+    let mut size = None;
+    let mut times = Vec::with_capacity(frames);
+    for frame in 0..frames {
+        let start = Instant::now();
+        let (mut camera, world) = scene(frame);
+        camera.frame.get_or_insert(frame);
+        let canvas = camera.render(&world);
+        let time = start.elapsed();
+        times.push(time);
+        progress(frame, time);
+
+        match output {
+            FrameOutput::Files(pattern) => {
+                save_frame(&canvas, &pattern.replace("{}", &format!("{:04}", frame)))?
+            }
+            FrameOutput::Ffmpeg { path, fps } => {
+                let (width, height) = *size.get_or_insert((canvas.width, canvas.height));
+                if (canvas.width, canvas.height) != (width, height) {
+                    return Err(format!("frame {} isn't {}x{}", frame, width, height));
+                }
+                if ffmpeg.is_none() {
+                    ffmpeg = Some(spawn_ffmpeg(path, *fps, width, height)?);
+                }
+                let stdin = ffmpeg.as_mut().and_then(|child| child.stdin.as_mut());
+                stdin
+                    .unwrap()
+                    .write_all(&canvas.to_rgb8())
+                    .map_err(|e| format!("ffmpeg: {}", e))?;
+            }
+        }
+    }
+
+    if let Some(mut child) = ffmpeg {
+        // Closing its input tells ffmpeg the video is over
+        drop(child.stdin.take());
+        let status = child.wait().map_err(|e| format!("ffmpeg: {}", e))?;
+        if !status.success() {
+            return Err(format!("ffmpeg failed: {}", status));
+        }
+    }
+    Ok(times)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{approx::ApproxEq, assert_approx_eq, camera::SuperSamplingMode, PI};
+
+    fn path() -> CameraPath {
+        let up = Tuple::vector(0., 1., 0.);
+        let origin = Tuple::point(0., 0., 0.);
+        CameraPath::new(vec![
+            Keyframe::new(2., Tuple::point(5., 0., 0.), origin, up),
+            Keyframe::new(0., Tuple::point(0., 0., -5.), origin, up),
+            Keyframe::new(1., Tuple::point(-5., 0., 0.), origin, up),
+        ])
+    }
+
+    #[test]
+    fn interpolation() {
+        let mut path = path();
+        assert_eq!((path.start(), path.end()), (0., 2.));
+        // Through every keyframe, and still outside them
+        for (time, x, z) in [(0., 0., -5.), (1., -5., 0.), (2., 5., 0.), (3., 5., 0.)] {
+            assert_approx_eq!(path.at(time).from, Tuple::point(x, 0., z));
+        }
+        assert_eq!(path.at(-1.).time, -1.);
+
+        let smooth = path.at(0.5).from;
+        path.interpolation = Interpolation::Linear;
+        assert_approx_eq!(path.at(0.5).from, Tuple::point(-2.5, 0., -2.5));
+        // The spline swings wide, heading into the turn at the next keyframe
+        assert!(smooth.x < -3.);
+        assert_eq!(
+            path.transform_at(1.),
+            Matrix::view_transform(
+                Tuple::point(-5., 0., 0.),
+                Tuple::point(0., 0., 0.),
+                Tuple::vector(0., 1., 0.)
+            )
+        );
+    }
+
+    #[test]
+    fn render_sequence() {
+        let w = World::default();
+        let mut c = Camera::new(11, 11, PI / 2., SuperSamplingMode::None);
+        let path = path();
+        let frames: Vec<Canvas> = c.render_sequence(&w, &path, 5).collect();
+        assert_eq!(frames.len(), 5);
+        c.transform = path.transform_at(1.);
+        assert_eq!(frames[2].pixels, c.render(&w).pixels);
+        assert_ne!(frames[0].pixels, frames[2].pixels);
+        assert_eq!(c.render_sequence(&w, &path, 1).count(), 1);
+
+        // Each frame's annotation is stamped with its number and time
+        let mut c = Camera::new(40, 20, PI / 2., SuperSamplingMode::None);
+        c.annotation = Some(String::from("{frame} {time}"));
+        let frames: Vec<Canvas> = c.render_sequence(&w, &path, 5).collect();
+        c.transform = path.transform_at(1.5);
+        c.annotation = Some(String::from("3 1.50"));
+        assert_eq!(frames[3].pixels, c.render(&w).pixels);
+        c.annotation = Some(String::from("2 1.50"));
+        assert_ne!(frames[3].pixels, c.render(&w).pixels);
+    }
+
+    #[test]
+    fn render_animation_to_files() {
+        let directory = std::env::temp_dir().join(format!("frames-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let pattern = directory.join("frame-{}.ppm");
+        let output = FrameOutput::Files(pattern.to_str().unwrap().to_string());
+        let path = path();
+        let mut reported = vec![];
+        let times = render_animation(
+            3,
+            &output,
+            |frame| {
+                let mut camera = Camera::new(5, 5, PI / 2., SuperSamplingMode::None);
+                camera.transform = path.transform_at(frame as f64);
+                (camera, World::default())
+            },
+            |frame, _| reported.push(frame),
+        )
+        .unwrap();
+        assert_eq!(times.len(), 3);
+        assert_eq!(reported, vec![0, 1, 2]);
+
+        let mut camera = Camera::new(5, 5, PI / 2., SuperSamplingMode::None);
+        camera.transform = path.transform_at(2.);
+        let ppm = std::fs::read_to_string(directory.join("frame-0002.ppm")).unwrap();
+        let expected = camera.render(&World::default());
+        assert!(Canvas::from_ppm(&ppm)
+            .unwrap()
+            .approx_eq_eps(&expected, 0.5 / 255.));
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn render_animation_to_ffmpeg() {
+        let video = std::env::temp_dir().join(format!("video-{}.mp4", std::process::id()));
+        let output = FrameOutput::Ffmpeg {
+            path: video.to_str().unwrap().to_string(),
+            fps: 24.,
+        };
+        let scene = |_| {
+            let camera = Camera::new(16, 16, PI / 2., SuperSamplingMode::None);
+            (camera, World::default())
+        };
+        // Only encodes where ffmpeg is installed
+        match render_animation(2, &output, scene, |_, _| ()) {
+            Ok(times) => {
+                assert_eq!(times.len(), 2);
+                assert!(video.exists());
+                std::fs::remove_file(video).unwrap();
+            }
+            Err(e) => assert!(e.contains("ffmpeg")),
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use crate::EPSILON;
+
+// Equality up to a tolerance on every component. `PartialEq` for the math types is
+// `approx_eq` with the crate wide EPSILON
+pub trait ApproxEq {
+    fn approx_eq_eps(&self, other: &Self, epsilon: f64) -> bool;
+
+    fn approx_eq(&self, other: &Self) -> bool {
+        self.approx_eq_eps(other, EPSILON)
+    }
+}
+
+impl ApproxEq for f64 {
+    fn approx_eq_eps(&self, other: &Self, epsilon: f64) -> bool {
+        // Equal infinities are equal even though their difference is NaN
+        self == other || (self - other).abs() <= epsilon
+    }
+}
+
+// Like assert_eq!, but compares with ApproxEq, optionally with a custom epsilon:
+// `assert_approx_eq!(a, b)` or `assert_approx_eq!(a, b, epsilon = 0.01)`
+#[macro_export]
+macro_rules! assert_approx_eq {
+    ($left:expr, $right:expr $(,)?) => {
+        $crate::assert_approx_eq!($left, $right, epsilon = $crate::EPSILON)
+    };
+    ($left:expr, $right:expr, epsilon = $epsilon:expr $(,)?) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                if !$crate::approx::ApproxEq::approx_eq_eps(left, right, $epsilon) {
+                    panic!(
+                        "assertion `left ~= right` failed (epsilon {})\n  left: {:?}\n right: {:?}",
+                        $epsilon, left, right
+                    );
+                }
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{canvas::Canvas, color::Color, matrix::Matrix, tuple::Tuple};
+
+    #[test]
+    fn floats() {
+        assert!(1.0.approx_eq(&1.00005));
+        assert!(!1.0.approx_eq(&1.0002));
+        assert!(1.0.approx_eq_eps(&1.0002, 0.001));
+        assert!(f64::INFINITY.approx_eq(&f64::INFINITY));
+        assert!(!f64::NAN.approx_eq(&f64::NAN));
+        assert_approx_eq!(0.1 + 0.2, 0.3);
+        assert_approx_eq!(2., 2.05, epsilon = 0.1);
+    }
+
+    #[test]
+    fn types() {
+        assert_approx_eq!(Tuple::point(1., 2., 3.), Tuple::point(1., 2., 3.00001));
+        assert!(!Tuple::point(1., 2., 3.).approx_eq(&Tuple::vector(1., 2., 3.)));
+        assert_approx_eq!(
+            Color::new(0.5, 0.5, 0.5),
+            Color::new(0.52, 0.5, 0.5),
+            epsilon = 0.05
+        );
+        assert!(!Matrix::identity(4).approx_eq(&Matrix::identity(3)));
+        assert_approx_eq!(Matrix::identity(4), Matrix::scaling(1., 1., 1.00001));
+
+        let mut a = Canvas::new(2, 2);
+        let b = Canvas::new(2, 2);
+        assert_approx_eq!(a, b);
+        a.write_pixel(1, 1, Color::new(0.01, 0., 0.));
+        assert!(!a.approx_eq(&b));
+        assert_approx_eq!(a, b, epsilon = 0.1);
+        assert!(!a.approx_eq(&Canvas::new(2, 3)));
+    }
+
+    #[test]
+    #[should_panic(expected = "left ~= right")]
+    fn assertion_failure() {
+        assert_approx_eq!(Tuple::vector(1., 0., 0.), Tuple::vector(0., 1., 0.));
+    }
+}
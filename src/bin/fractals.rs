@@ -0,0 +1,7 @@
+use raytracer::scenes;
+
+fn main() {
+    let (world, camera) = scenes::fractals();
+    let canvas = camera.render(&world);
+    canvas.save_ppm("fractals.ppm");
+}
@@ -0,0 +1,167 @@
+use crate::{matrix::Matrix, ray::Ray, tuple::Tuple};
+
+// Axis aligned box, used to skip groups of objects a ray can't hit. Unbounded shapes
+// such as planes have infinite extents
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min: Tuple,
+    pub max: Tuple,
+}
+
+impl BoundingBox {
+    pub fn new(min: Tuple, max: Tuple) -> Self {
+        assert!(min.is_point() && max.is_point());
+        Self { min, max }
+    }
+
+    // Contains nothing, so that adding points or boxes to it gives just those
+    pub fn empty() -> Self {
+        Self::new(
+            Tuple::point(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            Tuple::point(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        )
+    }
+
+    pub fn infinite() -> Self {
+        Self::new(
+            Tuple::point(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+            Tuple::point(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+        )
+    }
+
+    pub fn is_finite(&self) -> bool {
+        [self.min, self.max]
+            .iter()
+            .all(|p| p.x.is_finite() && p.y.is_finite() && p.z.is_finite())
+    }
+
+    pub fn add_point(&mut self, point: Tuple) {
+        self.min = Tuple::point(
+            self.min.x.min(point.x),
+            self.min.y.min(point.y),
+            self.min.z.min(point.z),
+        );
+        self.max = Tuple::point(
+            self.max.x.max(point.x),
+            self.max.y.max(point.y),
+            self.max.z.max(point.z),
+        );
+    }
+
+    pub fn merge(&mut self, other: &BoundingBox) {
+        self.add_point(other.min);
+        self.add_point(other.max);
+    }
+
+    pub fn centroid(&self) -> Tuple {
+        Tuple::point(
+            (self.min.x + self.max.x) / 2.,
+            (self.min.y + self.max.y) / 2.,
+            (self.min.z + self.max.z) / 2.,
+        )
+    }
+
+    // Box around all eight transformed corners. Infinite boxes stay infinite, since
+    // their corners can't be transformed
+    pub fn transform(&self, m: &Matrix) -> Self {
+        if !self.is_finite() {
+            return Self::infinite();
+        }
+        let mut bounds = Self::empty();
+        for x in [self.min.x, self.max.x] {
+            for y in [self.min.y, self.max.y] {
+                for z in [self.min.z, self.max.z] {
+                    bounds.add_point(m * Tuple::point(x, y, z));
+                }
+            }
+        }
+        bounds
+    }
+
+    // Whether the ray's line crosses the box. Intersections behind the origin still
+    // count, since refraction needs every surface the line crosses
+    pub fn intersects(&self, ray: &Ray) -> bool {
+        self.range(ray).is_some()
+    }
+
+    // Span of t over which the ray's line is inside the box, if it crosses it at all
+    pub fn range(&self, ray: &Ray) -> Option<(f64, f64)> {
+        let axes = [
+            (ray.origin.x, ray.direction.x, self.min.x, self.max.x),
+            (ray.origin.y, ray.direction.y, self.min.y, self.max.y),
+            (ray.origin.z, ray.direction.z, self.min.z, self.max.z),
+        ];
+        let (mut tmin, mut tmax) = (f64::NEG_INFINITY, f64::INFINITY);
+        for (origin, direction, min, max) in axes {
+            if direction == 0. {
+                // Parallel to the slab, so either always or never inside it
+                if origin < min || origin > max {
+                    return None;
+                }
+                continue;
+            }
+            let (t0, t1) = ((min - origin) / direction, (max - origin) / direction);
+            tmin = tmin.max(t0.min(t1));
+            tmax = tmax.min(t0.max(t1));
+        }
+        (tmin <= tmax).then_some((tmin, tmax))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::{PI, SQRT_2};
+
+    #[test]
+    fn grow() {
+        let mut b = BoundingBox::empty();
+        b.add_point(Tuple::point(-5., 2., 0.));
+        b.add_point(Tuple::point(7., 0., -3.));
+        assert_eq!(b.min, Tuple::point(-5., 0., -3.));
+        assert_eq!(b.max, Tuple::point(7., 2., 0.));
+        b.merge(&BoundingBox::new(
+            Tuple::point(-1., -1., -1.),
+            Tuple::point(1., 1., 1.),
+        ));
+        assert_eq!(b.min, Tuple::point(-5., -1., -3.));
+        assert_eq!(b.centroid(), Tuple::point(1., 0.5, -1.));
+        assert!(b.is_finite());
+        assert!(!BoundingBox::infinite().is_finite());
+    }
+
+    #[test]
+    fn transform() {
+        let b = BoundingBox::new(Tuple::point(-1., -1., -1.), Tuple::point(1., 1., 1.));
+        let m = Matrix::rotation_x(PI / 4.) * &Matrix::rotation_y(PI / 4.);
+        let t = b.transform(&m);
+        assert_eq!(t.min, Tuple::point(-SQRT_2, -1.70711, -1.70711));
+        assert_eq!(t.max, Tuple::point(SQRT_2, 1.70711, 1.70711));
+        assert!(!BoundingBox::infinite().transform(&m).is_finite());
+    }
+
+    #[test]
+    fn intersects() {
+        let b = BoundingBox::new(Tuple::point(5., -2., 0.), Tuple::point(11., 4., 7.));
+        let cases = [
+            (Tuple::point(15., 1., 2.), Tuple::vector(-1., 0., 0.), true),
+            (Tuple::point(-5., -1., 4.), Tuple::vector(1., 0., 0.), true),
+            (Tuple::point(7., 6., 5.), Tuple::vector(0., -1., 0.), true),
+            (Tuple::point(9., 0., 5.), Tuple::vector(0., 0., 1.), true),
+            (Tuple::point(8., 2., 12.), Tuple::vector(0., 0., -1.), true),
+            (Tuple::point(6., 0., 5.), Tuple::vector(0., 0., 1.), true),
+            (Tuple::point(15., -2., 2.), Tuple::vector(1., 0., 0.), true),
+            (Tuple::point(9., -1., -8.), Tuple::vector(2., 4., 6.), false),
+            (Tuple::point(8., 3., -4.), Tuple::vector(6., 2., 4.), false),
+            (Tuple::point(4., 0., 9.), Tuple::vector(0., 0., -1.), false),
+            (Tuple::point(8., 6., -1.), Tuple::vector(0., -1., 0.), false),
+            (Tuple::point(12., 5., 4.), Tuple::vector(-1., 0., 0.), false),
+        ];
+        for (origin, direction, expected) in cases {
+            let r = Ray::new(origin, direction.normalize());
+            assert_eq!(b.intersects(&r), expected, "{:?}", r);
+        }
+        let r = Ray::new(Tuple::point(0., 100., 0.), Tuple::vector(0., 0., 1.));
+        assert!(BoundingBox::infinite().intersects(&r));
+    }
+}
@@ -0,0 +1,213 @@
+use crate::{bounds::BoundingBox, ray::Ray, shape::Object, tuple::Tuple, EPSILON};
+
+// Most objects a leaf holds before it gets split
+const LEAF_SIZE: usize = 4;
+// Worlds with fewer objects are faster to test one by one, see World::prepare
+pub const BVH_MIN_OBJECTS: usize = 16;
+
+#[derive(Debug, Clone)]
+enum BvhNode {
+    Leaf {
+        bounds: BoundingBox,
+        objects: Vec<usize>,
+    },
+    Split {
+        bounds: BoundingBox,
+        left: usize,
+        right: usize,
+    },
+}
+
+impl BvhNode {
+    fn bounds(&self) -> &BoundingBox {
+        match self {
+            BvhNode::Leaf { bounds, .. } | BvhNode::Split { bounds, .. } => bounds,
+        }
+    }
+}
+
+// Bounding volume hierarchy over the objects of a world, or anything else with
+// bounds such as the faces of a mesh, by index. Its nodes live in one Vec and refer
+// to their children by index. Objects without finite bounds (planes, infinite
+// cylinders) can't be placed in it and are tested by every ray
+#[derive(Debug, Clone)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    unbounded: Vec<usize>,
+}
+
+impl Bvh {
+    pub fn new(objects: &[Object]) -> Self {
+        Bvh::from_bounds(objects.iter().map(|object| object.bounds()))
+    }
+
+    pub fn from_bounds(bounds: impl IntoIterator<Item = BoundingBox>) -> Self {
+        let mut unbounded = vec![];
+        let mut items = vec![];
+        for (index, bounds) in bounds.into_iter().enumerate() {
+            if bounds.is_finite() {
+                items.push((index, bounds, bounds.centroid()));
+            } else {
+                unbounded.push(index);
+            }
+        }
+
+        let mut bvh = Self {
+            nodes: vec![],
+            unbounded,
+        };
+        if !items.is_empty() {
+            bvh.build(&mut items);
+        }
+        bvh
+    }
+
+    // Splits the items at the median centroid along the axis their centroids spread
+    // over the most, returning the index of the new node
+    fn build(&mut self, items: &mut [(usize, BoundingBox, Tuple)]) -> usize {
+        let mut bounds = BoundingBox::empty();
+        let mut centroids = BoundingBox::empty();
+        for (_, object_bounds, centroid) in items.iter() {
+            bounds.merge(object_bounds);
+            centroids.add_point(*centroid);
+        }
+
+        let extent = centroids.max - centroids.min;
+        let spread = extent.x.max(extent.y).max(extent.z);
+        if items.len() <= LEAF_SIZE || spread == 0. {
+            self.nodes.push(BvhNode::Leaf {
+                bounds,
+                objects: items.iter().map(|(index, _, _)| *index).collect(),
+            });
+            return self.nodes.len() - 1;
+        }
+
+        let axis = |p: &Tuple| {
+            if spread == extent.x {
+                p.x
+            } else if spread == extent.y {
+                p.y
+            } else {
+                p.z
+            }
+        };
+        items.sort_by(|a, b| axis(&a.2).total_cmp(&axis(&b.2)));
+        let (left_items, right_items) = items.split_at_mut(items.len() / 2);
+        let left = self.build(left_items);
+        let right = self.build(right_items);
+        self.nodes.push(BvhNode::Split {
+            bounds,
+            left,
+            right,
+        });
+        self.nodes.len() - 1
+    }
+
+    // Calls `visit` with the index of every object the ray might hit, and returns how
+    // many nodes were visited
+    pub fn candidates(&self, ray: &Ray, mut visit: impl FnMut(usize)) -> u64 {
+        for index in self.unbounded.iter() {
+            visit(*index);
+        }
+        let mut visited = 0;
+        // The root is the last node built
+        let mut stack: Vec<usize> = self.nodes.len().checked_sub(1).into_iter().collect();
+        while let Some(node) = stack.pop() {
+            visited += 1;
+            let node = &self.nodes[node];
+            if !node.bounds().intersects(ray) {
+                continue;
+            }
+            match node {
+                BvhNode::Leaf { objects, .. } => objects.iter().for_each(|index| visit(*index)),
+                BvhNode::Split { left, right, .. } => {
+                    stack.push(*right);
+                    stack.push(*left);
+                }
+            }
+        }
+        visited
+    }
+
+    // Calls `visit` with the index of every item whose bounds are within EPSILON of
+    // the point
+    pub fn candidates_at(&self, point: Tuple, mut visit: impl FnMut(usize)) {
+        for index in self.unbounded.iter() {
+            visit(*index);
+        }
+        let near = |bounds: &BoundingBox| {
+            let (min, max) = (bounds.min, bounds.max);
+            min.x - EPSILON <= point.x
+                && point.x <= max.x + EPSILON
+                && min.y - EPSILON <= point.y
+                && point.y <= max.y + EPSILON
+                && min.z - EPSILON <= point.z
+                && point.z <= max.z + EPSILON
+        };
+        let mut stack: Vec<usize> = self.nodes.len().checked_sub(1).into_iter().collect();
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            if !near(node.bounds()) {
+                continue;
+            }
+            match node {
+                BvhNode::Leaf { objects, .. } => objects.iter().for_each(|index| visit(*index)),
+                BvhNode::Split { left, right, .. } => {
+                    stack.push(*right);
+                    stack.push(*left);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        matrix::Matrix,
+        shapes::{Plane, Sphere},
+    };
+
+    fn grid() -> Vec<Object> {
+        let mut objects = vec![Plane::new(None)];
+        for x in 0..8 {
+            for z in 0..8 {
+                let transform = Matrix::translation(x as f64 * 3., 1., z as f64 * 3.);
+                objects.push(Sphere::new(None).with_transform(&transform));
+            }
+        }
+        objects
+    }
+
+    #[test]
+    fn candidates() {
+        let objects = grid();
+        let bvh = Bvh::new(&objects);
+        assert_eq!(bvh.unbounded, [0]);
+
+        // Along one row of spheres, only those are candidates besides the plane
+        let r = Ray::new(Tuple::point(-5., 1., 6.), Tuple::vector(1., 0., 0.));
+        let mut found = vec![];
+        let visited = bvh.candidates(&r, |index| found.push(index));
+        assert!(visited > 0 && visited < bvh.nodes.len() as u64);
+        assert!(found.contains(&0));
+        for x in 0..8 {
+            assert!(found.contains(&(1 + x * 8 + 2)));
+        }
+        assert!(found.len() < objects.len());
+
+        // Missing everything but the plane
+        let r = Ray::new(Tuple::point(-5., 10., -5.), Tuple::vector(-1., 0., 0.));
+        let mut found = vec![];
+        bvh.candidates(&r, |index| found.push(index));
+        assert_eq!(found, [0]);
+    }
+
+    #[test]
+    fn empty() {
+        let bvh = Bvh::new(&[]);
+        let r = Ray::new(Tuple::point(0., 0., 0.), Tuple::vector(1., 0., 0.));
+        assert_eq!(bvh.candidates(&r, |_| panic!("no objects")), 0);
+    }
+}
@@ -1,15 +1,326 @@
 use crate::{
-    canvas::Canvas, color::BLACK, matrix::Matrix, ray::Ray, shape::MAX_REFLECTIONS, tuple::Tuple,
+    canvas::{heat, Accumulator, Canvas, RenderMetadata},
+    color::{Color, BLACK, WHITE},
+    environment::from_map,
+    material::Material,
+    matrix::Matrix,
+    pattern::UvMapping,
+    ray::Ray,
+    rng::Sampler,
+    shape::{Object, ShapeType, MAX_REFLECTIONS},
+    stats::Counter,
+    tuple::Tuple,
     world::World,
+    EPSILON, PI,
 };
-use rand::Rng;
+#[cfg(feature = "rayon")]
 use rayon::prelude::*;
+#[cfg(feature = "rayon")]
+use std::sync::Arc;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+// Samples adaptive supersampling takes between checks of a pixel's noise
+const ADAPTIVE_BATCH: usize = 4;
+// Width and height in pixels of the blocks of the image traced by one thread
+const TILE_SIZE: usize = 16;
+// Most samples per pixel render_for takes, however long its budget
+const MAX_PROGRESSIVE_SAMPLES: usize = 256;
+// Relative jump in depth between neighbouring pixels that counts as an edge
+const EDGE_DEPTH_RATIO: f64 = 0.1;
+
+// Pool for renders on a set number of threads. Pools are kept and shared by every later
+// render asking for the same number, rather than starting threads for each pass
+#[cfg(feature = "rayon")]
+fn thread_pool(threads: usize) -> Arc<rayon::ThreadPool> {
+    static POOLS: Mutex<Vec<(usize, Arc<rayon::ThreadPool>)>> = Mutex::new(Vec::new());
+    let mut pools = POOLS.lock().unwrap();
+    if let Some((_, pool)) = pools.iter().find(|(count, _)| *count == threads) {
+        return pool.clone();
+    }
+    let pool = Arc::new(
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .expect("can't start the render threads"),
+    );
+    pools.push((threads, pool.clone()));
+    pool
+}
+
+// How the objects on a render layer take part in a render
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LayerMode {
+    Visible,
+    // Only shows up through the shadows it casts
+    ShadowOnly,
+    Hidden,
+}
+
+// What the coverage masks of render_masks are grouped by
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MaskKey {
+    // Object names, or "object <index>" for unnamed objects
+    Object,
+    // Material names. Unnamed materials are grouped with equal ones and called
+    // "material <index>" after the first object using them
+    Material,
+}
+
+// Position in the world's list of the object, or of the CSG object it is part of
+fn object_index(world: &World, object: &Object) -> Option<usize> {
+    world.objects.iter().position(|o| o.includes(object))
+}
+
+// The names objects are grouped under by render_masks, in order of first use, and
+// which of them each of the world's objects falls under
+fn mask_groups(world: &World, key: MaskKey) -> (Vec<String>, Vec<usize>) {
+    let keys: Vec<String> = world
+        .objects
+        .iter()
+        .enumerate()
+        .map(|(index, object)| match key {
+            MaskKey::Object => object
+                .name
+                .clone()
+                .unwrap_or_else(|| format!("object {}", index)),
+            // Unnamed materials are grouped with the first object whose material
+            // matches in every field, so a mirror and a matte surface of the same
+            // color get masks of their own
+            MaskKey::Material => object.material.name.clone().unwrap_or_else(|| {
+                let first = world
+                    .objects
+                    .iter()
+                    .position(|o| o.material == object.material)
+                    .unwrap_or(index);
+                format!("material {}", first)
+            }),
+        })
+        .collect();
+    let mut names: Vec<&String> = vec![];
+    for key in keys.iter() {
+        if !names.contains(&key) {
+            names.push(key);
+        }
+    }
+    // Which name each object falls under
+    let slots: Vec<usize> = keys
+        .iter()
+        .map(|key| names.iter().position(|name| *name == key).unwrap())
+        .collect();
+    let names = names.into_iter().cloned().collect();
+    (names, slots)
+}
+
+// A color that stays the same for the name from render to render
+fn id_color(name: &str) -> Color {
+    // FNV-1a
+    let hash = name.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    });
+    let channel = |shift: u32| ((hash >> shift) & 0xff) as f64 / 255.;
+    Color::new(channel(0), channel(8), channel(16))
+}
+
+// What render_debug shows instead of the shaded scene
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DebugMode {
+    // World space normal of the visible surface, mapping -1..1 onto 0..1 per channel
+    Normals,
+    // Distance to the visible surface, white for the nearest and black for misses
+    Depth,
+    // Heatmap of how many surfaces the primary ray crosses
+    IntersectionCount,
+    // Surface coordinates of the visible surface as red and green, with the mapping
+    // of its material's pattern or else the one that suits the shape
+    Uv,
+    // Heatmap of how many nodes of the world's BVH the primary ray visits, black
+    // everywhere when the world has none
+    BvhNodeVisits,
+}
+
+// Where in the pixel, from 0 to 1 across and down, sample `index` of `count` falls
+// at `offset` within its cell. The pixel is split into about sqrt(count) rows, with
+// the samples shared out between them as evenly as they go, and each row's height
+// is in proportion to its samples. Every cell then has the same area and together
+// they cover the pixel, whether or not the count is square
+fn grid_cell(index: usize, count: usize, offset: (f64, f64)) -> (f64, f64) {
+    let rows = ((count as f64).sqrt().round() as usize).max(1);
+    let start = |row: usize| row * count / rows;
+    let row = (0..rows).rev().find(|row| start(*row) <= index).unwrap();
+    let columns = start(row + 1) - start(row);
+    (
+        ((index - start(row)) as f64 + offset.0) / columns as f64,
+        (start(row) as f64 + offset.1 * columns as f64) / count as f64,
+    )
+}
+
+// The mapping Uv debug renders show for an object
+fn debug_mapping(object: &Object) -> UvMapping {
+    let pattern_mapping = object.material.pattern.as_ref().and_then(|p| p.mapping());
+    pattern_mapping.unwrap_or(match object.shape {
+        ShapeType::Plane(_) => UvMapping::Planar,
+        ShapeType::Cube(_) => UvMapping::Cube,
+        ShapeType::Cylinder(_) => UvMapping::Cylindrical,
+        _ => UvMapping::Spherical,
+    })
+}
+
+// Auxiliary images render_with_aovs can fill alongside the shaded one, e.g. for
+// denoisers and compositing. They hold raw values rather than displayable colors
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Aov {
+    // Distance along the ray to the visible surface in every channel, infinite where
+    // nothing is hit
+    Depth,
+    // World space normal of the visible surface, black where nothing is hit
+    Normal,
+    // Color of the visible surface before lighting, black where nothing is hit
+    Albedo,
+    // Position in render_masks' list of the name the visible object or its material
+    // goes by, in every channel, and -1 where nothing is hit. Taken from the object
+    // most of the pixel's samples see, as IDs can't be averaged
+    Id(MaskKey),
+    // The same as a color picked from the name, for selecting objects by eye
+    IdColor(MaskKey),
+}
+
+// How points of the canvas map to ray directions
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Projection {
+    #[default]
+    Perspective,
+    // Longitude across and latitude down, covering every direction. The centre looks
+    // along the camera's +z, the top straight up its +y, the same layout Environment
+    // reads, so a panorama from an untransformed camera can light another scene as is
+    Equirectangular,
+}
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum SuperSamplingMode {
     None,
     Stochastic,
+    // Takes ADAPTIVE_BATCH jittered samples at a time, stopping once the standard
+    // error of the pixel's mean luminance drops to `threshold` or it has had
+    // `max_samples`. Flat regions settle after the first batch
+    Adaptive { max_samples: usize, threshold: f64 },
+}
+
+// A finished block of a tiled render, see `Camera::render_tiles`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tile {
+    // Position in the order the tiles cover the image, a row of tiles at a time
+    pub index: usize,
+    // Top left pixel and size
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    // Row major
+    pub pixels: Vec<Color>,
+}
+
+// Successively refined renders, see `Camera::progressive`
+pub struct Progressive<'a> {
+    camera: &'a Camera,
+    world: Cow<'a, World>,
+    accumulator: Accumulator,
+    start: Instant,
+}
+
+impl Progressive<'_> {
+    // Samples every pixel has so far
+    pub fn samples(&self) -> usize {
+        self.accumulator.fewest()
+    }
+
+    // The samples so far, to save as a checkpoint
+    pub fn accumulator(&self) -> &Accumulator {
+        &self.accumulator
+    }
+}
+
+impl Iterator for Progressive<'_> {
+    type Item = Canvas;
+
+    fn next(&mut self) -> Option<Canvas> {
+        let pass = self.accumulator.fewest();
+        let samples = self.camera.trace_pass(&self.world, pass, None);
+        self.accumulator.add_pass(samples);
+        let canvas = self.accumulator.to_canvas();
+        Some(
+            self.camera
+                .finish(&self.world, canvas, self.start, pass + 1),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Eye {
+    Left,
+    Right,
+}
+
+// A pair of eyes `interocular` apart, side by side around the camera and looking the
+// same way. Their images are shifted so that things `convergence` away from the
+// camera line up, appearing at the depth of the screen; nearer things pop out of it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stereo {
+    pub interocular: f64,
+    pub convergence: f64,
+}
+
+impl Stereo {
+    pub fn new(interocular: f64, convergence: f64) -> Self {
+        assert!(interocular >= 0. && convergence > 0.);
+        Stereo {
+            interocular,
+            convergence,
+        }
+    }
+}
+
+// Where the samples of a supersampled pixel fall
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SamplePattern {
+    // Uniformly random points
+    #[default]
+    Random,
+    // Centres of the cells of a regular grid, filled a row at a time. The grid is as
+    // close to square as the number of samples allows
+    Grid,
+    // One uniformly random point in each cell of the same grid, so samples can't
+    // clump together. N * N samples give an N by N grid
+    Stratified,
+}
+
+// How stochastic supersampling samples each pixel. Adaptive supersampling always
+// takes random samples, up to its own maximum
+#[derive(Debug, Clone, PartialEq)]
+pub struct SamplerSettings {
+    pub samples: usize,
+    pub pattern: SamplePattern,
+    // Seeds the random numbers of each pixel from its coordinates, making sampled
+    // renders repeatable whatever the number of threads. Unseeded renders differ
+    // from run to run
+    pub seed: Option<u64>,
 }
 
+impl Default for SamplerSettings {
+    fn default() -> Self {
+        SamplerSettings {
+            samples: 10,
+            pattern: SamplePattern::default(),
+            seed: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Camera {
     hsize: usize,
     vsize: usize,
@@ -19,6 +330,43 @@ pub struct Camera {
     pixel_size: f64,
     pub transform: Matrix,
     supersampling_mode: SuperSamplingMode,
+    // Optional label burned into the corner of every rendered canvas. `{frame}` and
+    // `{time}` in it are replaced by the frame and time below, when they are set
+    pub annotation: Option<String>,
+    // Number and time of the animation frame being rendered. Set by render_sequence
+    // and render_animation, or by hand
+    pub frame: Option<usize>,
+    pub time: Option<f64>,
+    // Color of the outlines drawn over silhouettes and creases when rendering, if any
+    pub edge_overlay: Option<Color>,
+    pub sampling: SamplerSettings,
+    // Mode of each render layer by name. Objects on unlisted layers, or on none, are
+    // visible
+    pub layers: HashMap<String, LayerMode>,
+    // Diameter of the lens in world units, 0 for a pinhole. Only rays that are
+    // jittered anyway (stochastic supersampling, render_for) sample the lens
+    pub aperture: f64,
+    // Distance from the camera to the plane that stays sharp with an aperture
+    pub focal_distance: f64,
+    pub projection: Projection,
+    // Horizontal offset of the centre of the image, in units of the view plane one
+    // unit in front of the camera. Set on the cameras of a stereo pair
+    shift: f64,
+    // Threads rendering uses, or None for one per core. Builds without the rayon
+    // feature always render on the calling thread
+    pub threads: Option<usize>,
+    // Exposure compensation in stops, each one doubling the brightness of the image
+    pub exposure: f64,
+    // Color of the light that should come out neutral, e.g. that of the lamps in a
+    // room lit by warm bulbs. Each channel is divided by it, keeping its luminance
+    pub white_balance: Option<Color>,
+}
+
+// A 640x480 view with a 60 degree field of view, looking down -z from the origin
+impl Default for Camera {
+    fn default() -> Self {
+        Camera::new(640, 480, PI / 3., SuperSamplingMode::None)
+    }
 }
 
 impl Camera {
@@ -47,78 +395,931 @@ impl Camera {
             pixel_size,
             transform: Matrix::identity(4),
             supersampling_mode,
+            annotation: None,
+            frame: None,
+            time: None,
+            edge_overlay: None,
+            sampling: SamplerSettings::default(),
+            layers: HashMap::new(),
+            aperture: 0.,
+            focal_distance: 1.,
+            projection: Projection::default(),
+            shift: 0.,
+            threads: None,
+            exposure: 0.,
+            white_balance: None,
+        }
+    }
+
+    // A camera described like a real one: focal length and sensor size (width,
+    // height) in millimetres, and the f-number. The field of view spans the sensor
+    // side along the longer side of the image, and world units are taken to be metres
+    // for the aperture
+    pub fn physical(
+        hsize: usize,
+        vsize: usize,
+        focal_length: f64,
+        sensor: (f64, f64),
+        f_number: f64,
+        supersampling_mode: SuperSamplingMode,
+    ) -> Self {
+        assert!(focal_length > 0. && f_number > 0.);
+        let span = if hsize >= vsize { sensor.0 } else { sensor.1 };
+        let field_of_view = 2. * (span / (2. * focal_length)).atan();
+        let mut camera = Camera::new(hsize, vsize, field_of_view, supersampling_mode);
+        camera.aperture = focal_length / f_number / 1000.;
+        camera
+    }
+
+    // A 360 degree panorama, usually twice as wide as it is high
+    pub fn panorama(hsize: usize, vsize: usize, supersampling_mode: SuperSamplingMode) -> Self {
+        let mut camera = Camera::new(hsize, vsize, PI / 2., supersampling_mode);
+        camera.projection = Projection::Equirectangular;
+        camera
+    }
+
+    // The camera for one eye of the pair. Panoramas don't shift their images, so only
+    // converge at infinity
+    pub fn eye(&self, eye: Eye, stereo: &Stereo) -> Camera {
+        // The left of the image is along the camera's +x
+        let offset = match eye {
+            Eye::Left => stereo.interocular / 2.,
+            Eye::Right => -stereo.interocular / 2.,
+        };
+        let mut camera = self.clone();
+        camera.transform = Matrix::translation(-offset, 0., 0.) * &self.transform;
+        camera.shift = self.shift - offset / stereo.convergence;
+        camera
+    }
+
+    // The left and right eye images
+    pub fn render_stereo(&self, world: &World, stereo: &Stereo) -> (Canvas, Canvas) {
+        (
+            self.eye(Eye::Left, stereo).render(world),
+            self.eye(Eye::Right, stereo).render(world),
+        )
+    }
+
+    // Sets the focal distance so that the surface seen through the middle of the
+    // given pixel is sharp, returning it. Nothing changes if the pixel sees nothing
+    pub fn focus_on(&mut self, world: &World, x: usize, y: usize) -> Option<f64> {
+        let world = self.layered(world);
+        let ray = self.project_ray(x, y);
+        let hit = ray.intersect_world(&world).visible_hit()?.t;
+        // The plane in focus is square on to the camera, so only the depth counts
+        let depth = -(&self.transform * ray.position(hit)).z;
+        self.focal_distance = depth;
+        Some(depth)
+    }
+
+    // Focuses on whatever is in the middle of the image
+    pub fn autofocus(&mut self, world: &World) -> Option<f64> {
+        self.focus_on(world, self.hsize / 2, self.vsize / 2)
+    }
+
+    pub fn field_of_view(&self) -> f64 {
+        self.field_of_view
+    }
+
+    // Short human readable summary of the render settings, handy as an annotation
+    pub fn describe(&self) -> String {
+        let sampling = match self.supersampling_mode {
+            SuperSamplingMode::None => "none",
+            SuperSamplingMode::Stochastic => "stochastic",
+            SuperSamplingMode::Adaptive { .. } => "adaptive",
+        };
+        format!(
+            "{}x{} fov {:.3} ss {}",
+            self.hsize, self.vsize, self.field_of_view, sampling
+        )
+    }
+
+    // The most samples a pixel gets
+    pub fn samples_per_pixel(&self) -> usize {
+        match self.supersampling_mode {
+            SuperSamplingMode::None => 1,
+            SuperSamplingMode::Stochastic => self.sampling.samples,
+            SuperSamplingMode::Adaptive { max_samples, .. } => max_samples,
         }
     }
 
     pub fn project_subsample_rays(&self, x: usize, y: usize) -> Vec<Ray> {
-        let mut subsamples = vec![];
-        for _ in 0..10 {
-            subsamples.push((
-                (x as f64 + rand::thread_rng().gen_range(0_f64..1.)) * self.pixel_size,
-                (y as f64 + rand::thread_rng().gen_range(0_f64..1.)) * self.pixel_size,
-            ));
-        }
-        subsamples
-            .into_iter()
-            .map(|(x, y)| {
-                let world_x = self.half_width - x;
-                let world_y = self.half_height - y;
-
-                let pixel = self.transform.inverse() * Tuple::point(world_x, world_y, -1.);
-                let origin = self.transform.inverse() * Tuple::point(0., 0., 0.);
-                let direction = (pixel - origin).normalize();
-
-                Ray::new(origin, direction)
+        self.subsample_rays(x, y, &mut self.sampler(x, y, 0))
+    }
+
+    // The supersampling rays through the pixel, jittered with the given sampler
+    fn subsample_rays(&self, x: usize, y: usize, sampler: &mut Sampler) -> Vec<Ray> {
+        let count = self.sampling.samples;
+        (0..count)
+            .map(|index| match self.sampling.pattern {
+                SamplePattern::Random => self.jittered_ray(x, y, sampler),
+                SamplePattern::Grid | SamplePattern::Stratified => {
+                    let offset = if self.sampling.pattern == SamplePattern::Grid {
+                        (0.5, 0.5)
+                    } else {
+                        (sampler.random(), sampler.random())
+                    };
+                    let cell = grid_cell(index, count, offset);
+                    self.subpixel_ray(x, y, cell, sampler)
+                }
             })
             .collect()
     }
 
+    // Rays for passes that only look at the visible surfaces: the pixel centre, or
+    // the stochastic samples when supersampling
+    fn coverage_rays(&self, x: usize, y: usize) -> Vec<Ray> {
+        match self.supersampling_mode {
+            SuperSamplingMode::None => vec![self.project_ray(x, y)],
+            SuperSamplingMode::Stochastic | SuperSamplingMode::Adaptive { .. } => {
+                self.project_subsample_rays(x, y)
+            }
+        }
+    }
+
     pub fn project_ray(&self, x: usize, y: usize) -> Ray {
-        let x_offset = (x as f64 + 0.5) * self.pixel_size;
-        let y_offset = (y as f64 + 0.5) * self.pixel_size;
-        let world_x = self.half_width - x_offset;
+        self.ray_through(
+            (x as f64 + 0.5) * self.pixel_size,
+            (y as f64 + 0.5) * self.pixel_size,
+            (0., 0.),
+        )
+    }
+
+    // A ray through a uniformly random point of the pixel, leaving from a random point
+    // of the lens when the camera has an aperture
+    fn jittered_ray(&self, x: usize, y: usize, sampler: &mut Sampler) -> Ray {
+        let cell = (sampler.random(), sampler.random());
+        self.subpixel_ray(x, y, cell, sampler)
+    }
+
+    // A ray through the point of the pixel `cell` places, from 0 to 1 across and
+    // down, with the lens sampled as for jittered rays
+    fn subpixel_ray(&self, x: usize, y: usize, cell: (f64, f64), sampler: &mut Sampler) -> Ray {
+        let x_offset = (x as f64 + cell.0) * self.pixel_size;
+        let y_offset = (y as f64 + cell.1) * self.pixel_size;
+        let lens = if self.aperture > 0. {
+            let r = self.aperture / 2. * sampler.random().sqrt();
+            let theta = 2. * PI * sampler.random();
+            (r * theta.cos(), r * theta.sin())
+        } else {
+            (0., 0.)
+        };
+        self.ray_through(x_offset, y_offset, lens)
+    }
+
+    // Random numbers for one pass over the pixel, see `SamplerSettings::seed`
+    fn sampler(&self, x: usize, y: usize, pass: usize) -> Sampler {
+        Sampler::for_pixel(self.sampling.seed, x, y, pass)
+    }
+
+    // Ray from the given point of the lens through the point of the canvas offset from
+    // its top left corner. All rays through a canvas point meet on the plane in focus.
+    // Panoramas have no lens
+    fn ray_through(&self, x_offset: f64, y_offset: f64, lens: (f64, f64)) -> Ray {
+        if self.projection == Projection::Equirectangular {
+            let u = x_offset / (self.hsize as f64 * self.pixel_size);
+            let v = y_offset / (self.vsize as f64 * self.pixel_size);
+            let origin = self.transform.inverse() * Tuple::point(0., 0., 0.);
+            let direction = self.transform.inverse() * from_map(u, v);
+            return Ray::new(origin, direction.normalize());
+        }
+        let world_x = self.half_width - x_offset + self.shift;
         let world_y = self.half_height - y_offset;
+        let focus = Tuple::point(
+            world_x * self.focal_distance,
+            world_y * self.focal_distance,
+            -self.focal_distance,
+        );
 
-        let pixel = self.transform.inverse() * Tuple::point(world_x, world_y, -1.);
-        let origin = self.transform.inverse() * Tuple::point(0., 0., 0.);
+        let pixel = self.transform.inverse() * focus;
+        let origin = self.transform.inverse() * Tuple::point(lens.0, lens.1, 0.);
         let direction = (pixel - origin).normalize();
 
         Ray::new(origin, direction)
     }
 
-    pub fn render(&self, world: &World) -> Canvas {
+    fn color_at(&self, world: &World, col: usize, row: usize) -> Color {
+        self.sample_pixel(world, col, row, |ray, sampler| {
+            ray.color_hit_sampled(world, MAX_REFLECTIONS, sampler)
+        })
+    }
+
+    // Averages `shade` over the primary rays the supersampling mode traces through
+    // the pixel. It's handed the pixel's sampler, the same one the rays were
+    // jittered with
+    fn sample_pixel<S>(&self, world: &World, col: usize, row: usize, mut shade: S) -> Color
+    where
+        S: FnMut(&Ray, &mut Sampler) -> Color,
+    {
+        match self.supersampling_mode {
+            SuperSamplingMode::None => {
+                world.count(Counter::PrimaryRays, 1);
+                shade(&self.project_ray(col, row), &mut self.sampler(col, row, 0))
+            }
+            SuperSamplingMode::Stochastic => {
+                let mut sampler = self.sampler(col, row, 0);
+                let rays = self.subsample_rays(col, row, &mut sampler);
+                world.count(Counter::PrimaryRays, rays.len() as u64);
+                rays.iter()
+                    .map(|ray| shade(ray, &mut sampler))
+                    .fold(BLACK, |a, b| a + b)
+                    * (1.0 / rays.len() as f64)
+            }
+            SuperSamplingMode::Adaptive {
+                max_samples,
+                threshold,
+            } => self.adaptive_sample_pixel(world, col, row, max_samples, threshold, shade),
+        }
+    }
+
+    fn adaptive_sample_pixel<S>(
+        &self,
+        world: &World,
+        col: usize,
+        row: usize,
+        max_samples: usize,
+        threshold: f64,
+        mut shade: S,
+    ) -> Color
+    where
+        S: FnMut(&Ray, &mut Sampler) -> Color,
+    {
+        let mut sampler = self.sampler(col, row, 0);
+        let mut sum = BLACK;
+        let (mut luminance, mut luminance_squared) = (0., 0.);
+        let mut count = 0;
+        while count < max_samples {
+            for _ in 0..ADAPTIVE_BATCH.min(max_samples - count) {
+                let ray = self.jittered_ray(col, row, &mut sampler);
+                let color = shade(&ray, &mut sampler);
+                sum = sum + color;
+                luminance += color.luminance();
+                luminance_squared += color.luminance() * color.luminance();
+                count += 1;
+            }
+            let n = count as f64;
+            let mean = luminance / n;
+            let variance = (luminance_squared / n - mean * mean).max(0.) * n / (n - 1.).max(1.);
+            if (variance / n).sqrt() <= threshold {
+                break;
+            }
+        }
+        world.count(Counter::PrimaryRays, count as u64);
+        sum * (1. / count as f64)
+    }
+
+    // Splits the image into TILE_SIZE squares, as (x, y, width, height), clipped at
+    // the right and bottom edges
+    pub fn tiles(&self) -> Vec<(usize, usize, usize, usize)> {
+        self.tiles_of(TILE_SIZE)
+    }
+
+    fn tiles_of(&self, size: usize) -> Vec<(usize, usize, usize, usize)> {
+        assert!(size > 0);
+        let mut tiles = vec![];
+        for y in (0..self.vsize).step_by(size) {
+            for x in (0..self.hsize).step_by(size) {
+                tiles.push((x, y, size.min(self.hsize - x), size.min(self.vsize - y)));
+            }
+        }
+        tiles
+    }
+
+    // The world as this camera's layer settings show it. Only copied when some layer
+    // isn't plainly visible
+    fn layered<'a>(&self, world: &'a World) -> Cow<'a, World> {
+        let mode = |object: &Object| {
+            object
+                .layer
+                .as_ref()
+                .and_then(|layer| self.layers.get(layer))
+                .copied()
+                .unwrap_or(LayerMode::Visible)
+        };
+        if world.objects.iter().all(|o| mode(o) == LayerMode::Visible) {
+            return Cow::Borrowed(world);
+        }
+
+        let mut layered = world.clone();
+        layered.objects.retain(|o| mode(o) != LayerMode::Hidden);
+        for object in layered.objects.iter_mut() {
+            object.shadow_only |= mode(object) == LayerMode::ShadowOnly;
+        }
+        if world.sphere_batch().is_some() || world.bvh().is_some() {
+            layered.prepare();
+        }
+        Cow::Owned(layered)
+    }
+
+    // Each thread traces a whole tile at a time, so consecutive primary rays are close
+    // together and touch the same parts of the scene. Results are in row major order
+    fn trace_tiles<T, F>(&self, trace: F) -> Vec<T>
+    where
+        T: Send,
+        F: Fn(usize, usize) -> T + Sync,
+    {
+        self.trace_tile_list(self.tiles(), trace, |_, _, _| ())
+    }
+
+    // Traces the given tiles, calling `done` with each tile's index, position and
+    // values as soon as it's finished
+    fn trace_tile_list<T, F, D>(
+        &self,
+        tiles: Vec<(usize, usize, usize, usize)>,
+        trace: F,
+        done: D,
+    ) -> Vec<T>
+    where
+        T: Send,
+        F: Fn(usize, usize) -> T + Sync,
+        D: Fn(usize, (usize, usize, usize, usize), &[T]) + Sync,
+    {
+        let trace_tile = |(index, (x, y, width, height)): (usize, (usize, usize, usize, usize))| {
+            let mut values = Vec::with_capacity(width * height);
+            for row in y..y + height {
+                for col in x..x + width {
+                    values.push(trace(col, row));
+                }
+            }
+            done(index, (x, y, width, height), &values);
+            ((x, y, width), values)
+        };
+        #[cfg(feature = "rayon")]
+        let tiles: Vec<_> = match self.threads {
+            Some(threads) => thread_pool(threads)
+                .install(|| tiles.into_par_iter().enumerate().map(trace_tile).collect()),
+            None => tiles.into_par_iter().enumerate().map(trace_tile).collect(),
+        };
+        #[cfg(not(feature = "rayon"))]
+        let tiles: Vec<_> = tiles.into_iter().enumerate().map(trace_tile).collect();
+
+        let mut pixels: Vec<Option<T>> = (0..self.hsize * self.vsize).map(|_| None).collect();
+        for ((x, y, width), values) in tiles {
+            for (i, value) in values.into_iter().enumerate() {
+                pixels[(y + i / width) * self.hsize + x + i % width] = Some(value);
+            }
+        }
+        pixels.into_iter().map(Option::unwrap).collect()
+    }
+
+    fn trace_canvas<F>(&self, color_at: F) -> Canvas
+    where
+        F: Fn(usize, usize) -> Color + Sync,
+    {
         let mut canvas = Canvas::new(self.hsize, self.vsize);
+        canvas.pixels = self.trace_tiles(color_at);
         canvas
-            .pixels
-            .par_iter_mut()
-            .enumerate()
-            .for_each(|(index, color)| {
-                let row = index / canvas.width;
-                let col = index % canvas.width;
-                match self.supersampling_mode {
-                    SuperSamplingMode::None => {
-                        let ray = self.project_ray(col, row);
-                        *color = ray.color_hit(&world, MAX_REFLECTIONS);
+    }
+
+    // The visible object (as an index into the world's objects) and its distance along
+    // the primary ray, for every pixel
+    fn trace_ids(&self, world: &World) -> Vec<Option<(usize, f64)>> {
+        self.trace_tiles(|col, row| {
+            let ray = self.project_ray(col, row);
+            let xs = ray.intersect_world(world);
+            let hit = xs.visible_hit()?;
+            Some((object_index(world, hit.object)?, hit.t))
+        })
+    }
+
+    // Pixels where the visible object changes, or where the depth jumps by more than
+    // EDGE_DEPTH_RATIO, compared to the pixel to the right or below
+    pub fn edges(&self, world: &World) -> Vec<bool> {
+        let world = &*self.layered(world);
+        let ids = self.trace_ids(world);
+        let differs = |a: Option<(usize, f64)>, b: Option<(usize, f64)>| match (a, b) {
+            (Some((a, ta)), Some((b, tb))) => {
+                a != b || (ta - tb).abs() > EDGE_DEPTH_RATIO * ta.min(tb)
+            }
+            (None, None) => false,
+            _ => true,
+        };
+        (0..ids.len())
+            .map(|index| {
+                let (col, row) = (index % self.hsize, index / self.hsize);
+                (col + 1 < self.hsize && differs(ids[index], ids[index + 1]))
+                    || (row + 1 < self.vsize && differs(ids[index], ids[index + self.hsize]))
+            })
+            .collect()
+    }
+
+    // Renders one primary ray per pixel showing a property of the scene rather than
+    // its shading, to help work out why pixels look the way they do
+    pub fn render_debug(&self, world: &World, mode: DebugMode) -> Canvas {
+        let world = &*self.layered(world);
+        // Scalar modes store their raw value in the red channel and are normalised
+        // against the whole frame afterwards
+        let mut canvas = self.trace_canvas(|col, row| {
+            let ray = self.project_ray(col, row);
+            let xs = ray.intersect_world(world);
+            match mode {
+                DebugMode::Normals => match xs.visible_hit() {
+                    Some(hit) => {
+                        let n = hit.object.normal_at(ray.position(hit.t));
+                        Color::new((n.x + 1.) / 2., (n.y + 1.) / 2., (n.z + 1.) / 2.)
                     }
-                    SuperSamplingMode::Stochastic => {
-                        let rays = self.project_subsample_rays(col, row);
-                        *color = rays
-                            .iter()
-                            .map(|ray| ray.color_hit(world, MAX_REFLECTIONS))
-                            .fold(BLACK, |a, b| a + b)
-                            * (1.0 / rays.len() as f64);
+                    None => BLACK,
+                },
+                DebugMode::Depth => {
+                    let t = xs.visible_hit().map_or(f64::INFINITY, |hit| hit.t);
+                    Color::new(t, 0., 0.)
+                }
+                DebugMode::IntersectionCount => {
+                    let count = xs.intersections.iter().filter(|i| i.t > 0.).count();
+                    Color::new(count as f64, 0., 0.)
+                }
+                DebugMode::Uv => match xs.visible_hit() {
+                    Some(hit) => {
+                        let point = hit.object.inverse() * ray.position(hit.t);
+                        // The cube mapping's u also counts faces, only the part
+                        // across the face is shown
+                        let (u, v) = debug_mapping(hit.object).uv(point);
+                        Color::new(u.fract(), v, 0.)
                     }
+                    None => BLACK,
+                },
+                DebugMode::BvhNodeVisits => {
+                    let visits = world.bvh().map_or(0, |bvh| bvh.candidates(&ray, |_| ()));
+                    Color::new(visits as f64, 0., 0.)
+                }
+            }
+        });
+
+        let values = canvas
+            .pixels
+            .iter()
+            .map(|c| c.red)
+            .filter(|v| v.is_finite());
+        let (min, max) = values.fold((f64::INFINITY, 0_f64), |(min, max), v| {
+            (min.min(v), max.max(v))
+        });
+        match mode {
+            DebugMode::Normals | DebugMode::Uv => {}
+            DebugMode::Depth => {
+                for c in canvas.pixels.iter_mut() {
+                    let shade = if c.red.is_finite() {
+                        1. - (c.red - min) / (max - min).max(EPSILON) * 0.9
+                    } else {
+                        0.
+                    };
+                    *c = Color::new(shade, shade, shade);
+                }
+            }
+            DebugMode::IntersectionCount | DebugMode::BvhNodeVisits => {
+                for c in canvas.pixels.iter_mut() {
+                    *c = heat(c.red / max.max(1.));
                 }
+            }
+        }
+
+        canvas.metadata = Some(RenderMetadata::new(self.hsize, self.vsize));
+        canvas
+    }
+
+    // Greyscale ambient occlusion: the fraction of `samples` cosine weighted rays from
+    // each visible point that escape further than `radius`. Materials and lights are
+    // ignored and pixels that miss everything are white
+    pub fn render_ao(&self, world: &World, radius: f64, samples: usize) -> Canvas {
+        let world = &*self.layered(world);
+        assert!(samples > 0);
+        let mut canvas = self.trace_canvas(|col, row| {
+            let ray = self.project_ray(col, row);
+            let xs = ray.intersect_world(world);
+            let hit = match xs.visible_hit() {
+                Some(hit) => hit.context(&ray, Some(&xs)),
+                None => return WHITE,
+            };
+
+            // Orthonormal basis around the normal on the side facing the camera
+            let normal = hit.normal_vector;
+            let helper = if normal.x.abs() > 0.9 {
+                Tuple::vector(0., 1., 0.)
+            } else {
+                Tuple::vector(1., 0., 0.)
+            };
+            let tangent = helper.cross(&normal).normalize();
+            let bitangent = normal.cross(&tangent);
+
+            let mut sampler = self.sampler(col, row, 0);
+            let occluded = (0..samples)
+                .filter(|_| {
+                    let phi = 2. * PI * sampler.random();
+                    let r2 = sampler.random();
+                    let r = r2.sqrt();
+                    let direction = tangent * (r * phi.cos())
+                        + bitangent * (r * phi.sin())
+                        + normal * (1. - r2).sqrt();
+                    let occlusion_ray = Ray::new(hit.over_point, direction.normalize());
+                    occlusion_ray
+                        .intersect_world(world)
+                        .hit()
+                        .is_some_and(|h| h.t < radius)
+                })
+                .count();
+            let open = 1. - occluded as f64 / samples as f64;
+            Color::new(open, open, open)
+        });
+
+        let mut metadata = RenderMetadata::new(self.hsize, self.vsize);
+        metadata.samples = samples;
+        metadata.seed = self.sampling.seed;
+        canvas.metadata = Some(metadata);
+        canvas
+    }
+
+    // Fast layout preview: every object gets the same matte grey material, with no
+    // reflection or refraction. Shadow rays are only traced when `shadows` is set
+    pub fn render_clay(&self, world: &World, shadows: bool) -> Canvas {
+        let world = &*self.layered(world);
+        let clay = Material {
+            color: Color::new(0.7, 0.7, 0.7),
+            specular: 0.,
+            ..Material::new()
+        };
+        let mut canvas = self.trace_canvas(|col, row| {
+            let ray = self.project_ray(col, row);
+            let xs = ray.intersect_world(world);
+            let hit = match xs.visible_hit() {
+                Some(hit) => hit.context(&ray, Some(&xs)),
+                None => return BLACK,
+            };
+            let mut sampler = self.sampler(col, row, 0);
+            world
+                .lights
+                .iter()
+                .filter(|light| light.illuminates(hit.object))
+                .map(|light| {
+                    let visibility = if shadows {
+                        world.visibility(light, hit.over_point, &mut sampler)
+                    } else {
+                        WHITE
+                    };
+                    clay.lighting_visible(
+                        light,
+                        hit.object,
+                        hit.over_point,
+                        hit.eye_vector,
+                        hit.normal_vector,
+                        visibility,
+                    )
+                })
+                .fold(BLACK, |a, b| a + b)
+        });
+
+        canvas.metadata = Some(RenderMetadata::new(self.hsize, self.vsize));
+        canvas
+    }
+
+    pub fn render(&self, world: &World) -> Canvas {
+        let world = &*self.layered(world);
+        let start = Instant::now();
+        if let Some(stats) = world.stats() {
+            stats.reset();
+        }
+        let canvas = self.trace_canvas(|col, row| self.color_at(world, col, row));
+        self.finish(world, canvas, start, self.samples_per_pixel())
+    }
+
+    // Renders like `render`, in square tiles of the given size. The callback gets
+    // every tile as soon as it's traced, one at a time, with the percentage of the
+    // image done so far, e.g. to report progress or update a preview. Tiles arrive in
+    // whatever order the threads finish them, before any edge overlay or annotation
+    pub fn render_tiles<F>(&self, world: &World, tile_size: usize, callback: F) -> Canvas
+    where
+        F: FnMut(&Tile, f64) + Send,
+    {
+        let world = &*self.layered(world);
+        let start = Instant::now();
+        if let Some(stats) = world.stats() {
+            stats.reset();
+        }
+        let tiles = self.tiles_of(tile_size);
+        let total = (self.hsize * self.vsize) as f64;
+        let progress = Mutex::new((0_usize, callback));
+        let pixels = self.trace_tile_list(
+            tiles,
+            |col, row| self.color_at(world, col, row),
+            |index, (x, y, width, height), pixels: &[Color]| {
+                let tile = Tile {
+                    index,
+                    x,
+                    y,
+                    width,
+                    height,
+                    pixels: pixels.iter().map(|c| *c * self.film_response()).collect(),
+                };
+                let mut progress = progress.lock().unwrap();
+                let (traced, callback) = &mut *progress;
+                *traced += width * height;
+                callback(&tile, 100. * *traced as f64 / total);
+            },
+        );
+        let mut canvas = Canvas::new(self.hsize, self.vsize);
+        canvas.pixels = pixels;
+        self.finish(world, canvas, start, self.samples_per_pixel())
+    }
+
+    // Progressive render that keeps adding one jittered sample to every pixel until
+    // the time budget runs out or MAX_PROGRESSIVE_SAMPLES is reached. Each pixel gets
+    // its centre sample however short the budget, and the metadata records the fewest
+    // samples any pixel received
+    pub fn render_for(&self, world: &World, budget: Duration) -> Canvas {
+        let world = &*self.layered(world);
+        let start = Instant::now();
+        let deadline = start + budget;
+        if let Some(stats) = world.stats() {
+            stats.reset();
+        }
+        let mut accumulator = Accumulator::new(self.hsize, self.vsize);
+        for pass in 0..MAX_PROGRESSIVE_SAMPLES {
+            if pass > 0 && Instant::now() >= deadline {
+                break;
+            }
+            accumulator.add_pass(self.trace_pass(world, pass, Some(deadline)));
+        }
+        let canvas = accumulator.to_canvas();
+        self.finish(world, canvas, start, accumulator.fewest())
+    }
+
+    // Images that get one more sample per pixel each time, starting from the pixel
+    // centres and then jittered like render_for, so a noisy preview can be shown
+    // straight away and refined for as long as the caller keeps asking. Never ends,
+    // use `take` to stop after a number of passes
+    pub fn progressive<'a>(&'a self, world: &'a World) -> Progressive<'a> {
+        self.resume(world, Accumulator::new(self.hsize, self.vsize))
+    }
+
+    // Carries on refining the samples taken so far, e.g. ones loaded from a
+    // checkpoint. Seeded renders continue exactly as they would have without a break
+    pub fn resume<'a>(&'a self, world: &'a World, accumulator: Accumulator) -> Progressive<'a> {
+        assert!(accumulator.width == self.hsize && accumulator.height == self.vsize);
+        let world = self.layered(world);
+        if let Some(stats) = world.stats() {
+            stats.reset();
+        }
+        Progressive {
+            camera: self,
+            world,
+            accumulator,
+            start: Instant::now(),
+        }
+    }
+
+    // A progressive render of `samples` passes that saves its progress to the
+    // checkpoint file after every pass, picking up from it when the file already
+    // exists. A finished checkpoint gives the final image straight away
+    pub fn render_resumable(
+        &self,
+        world: &World,
+        samples: usize,
+        checkpoint: &str,
+    ) -> Result<Canvas, String> {
+        assert!(samples > 0);
+        let accumulator = if std::path::Path::new(checkpoint).exists() {
+            let accumulator = Accumulator::load(checkpoint)?;
+            if accumulator.width != self.hsize || accumulator.height != self.vsize {
+                return Err(format!("{}: checkpoint of a different size", checkpoint));
+            }
+            accumulator
+        } else {
+            Accumulator::new(self.hsize, self.vsize)
+        };
+        let mut passes = self.resume(world, accumulator);
+        let done = passes.samples();
+        if done >= samples {
+            let canvas = passes.accumulator.to_canvas();
+            return Ok(self.finish(&passes.world, canvas, passes.start, done));
+        }
+        let mut image = None;
+        for _ in done..samples {
+            image = passes.next();
+            passes.accumulator.save(checkpoint)?;
+        }
+        Ok(image.unwrap())
+    }
+
+    // One sample for every pixel: the centre on the first pass, jittered after that.
+    // Pixels reached after the deadline are skipped, except on the first pass
+    fn trace_pass(
+        &self,
+        world: &World,
+        pass: usize,
+        deadline: Option<Instant>,
+    ) -> Vec<Option<Color>> {
+        self.trace_tiles(|col, row| {
+            if pass > 0 && deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return None;
+            }
+            world.count(Counter::PrimaryRays, 1);
+            let mut sampler = self.sampler(col, row, pass);
+            let ray = if pass == 0 {
+                self.project_ray(col, row)
+            } else {
+                self.jittered_ray(col, row, &mut sampler)
+            };
+            Some(ray.color_hit_sampled(world, MAX_REFLECTIONS, &mut sampler))
+        })
+    }
+
+    // Renders like `render`, also filling the given AOVs, which are returned in the
+    // same order. They come from the same primary rays as the shaded image: averaged
+    // over them, ids taken from the object most of them see. AOVs aren't touched by
+    // exposure, overlays or annotations
+    pub fn render_with_aovs(&self, world: &World, aovs: &[Aov]) -> (Canvas, Vec<Canvas>) {
+        let world = &*self.layered(world);
+        let start = Instant::now();
+        if let Some(stats) = world.stats() {
+            stats.reset();
+        }
+        let groups: Vec<_> = aovs
+            .iter()
+            .map(|aov| match aov {
+                Aov::Id(key) | Aov::IdColor(key) => Some(mask_groups(world, *key)),
+                _ => None,
+            })
+            .collect();
+        let pixels = self.trace_tiles(|col, row| {
+            let mut sums = vec![BLACK; aovs.len()];
+            let (mut samples, mut hits) = (0, 0);
+            // Samples seeing each object, by index into the world
+            let mut seen: HashMap<usize, usize> = HashMap::new();
+            let color = self.sample_pixel(world, col, row, |ray, sampler| {
+                samples += 1;
+                let xs = ray.intersect_world(world);
+                let Some(hit) = xs.visible_hit() else {
+                    return world.background(ray.direction);
+                };
+                hits += 1;
+                if let Some(index) = object_index(world, hit.object) {
+                    *seen.entry(index).or_default() += 1;
+                }
+                let point = ray.position(hit.t);
+                for (sum, aov) in sums.iter_mut().zip(aovs) {
+                    let value = match aov {
+                        Aov::Depth => Color::new(hit.t, hit.t, hit.t),
+                        Aov::Normal => {
+                            let n = hit.object.normal_at(point);
+                            Color::new(n.x, n.y, n.z)
+                        }
+                        Aov::Albedo => hit.object.material.color_at(hit.object, point),
+                        Aov::Id(_) | Aov::IdColor(_) => BLACK,
+                    };
+                    *sum = *sum + value;
+                }
+                hit.context(ray, Some(&xs))
+                    .shade_hit(world, MAX_REFLECTIONS, sampler)
             });
+            // The object most samples see, the first in the world on a tie
+            let majority = seen
+                .into_iter()
+                .max_by_key(|&(index, count)| (count, std::cmp::Reverse(index)))
+                .map(|(index, _)| index);
+            // Depth is averaged over the samples that hit something
+            let values: Vec<Color> = sums
+                .into_iter()
+                .zip(aovs)
+                .zip(&groups)
+                .map(|((sum, aov), groups)| match aov {
+                    Aov::Depth if hits == 0 => {
+                        Color::new(f64::INFINITY, f64::INFINITY, f64::INFINITY)
+                    }
+                    Aov::Depth => sum * (1. / hits as f64),
+                    Aov::Id(_) | Aov::IdColor(_) => {
+                        let (names, slots) = groups.as_ref().unwrap();
+                        match (aov, majority.map(|index| slots[index])) {
+                            (Aov::Id(_), Some(slot)) => {
+                                Color::new(slot as f64, slot as f64, slot as f64)
+                            }
+                            (Aov::Id(_), None) => Color::new(-1., -1., -1.),
+                            (_, Some(slot)) => id_color(&names[slot]),
+                            (_, None) => BLACK,
+                        }
+                    }
+                    _ => sum * (1. / samples as f64),
+                })
+                .collect();
+            (color, values)
+        });
+
+        let mut beauty = Canvas::new(self.hsize, self.vsize);
+        let mut passes: Vec<Canvas> = aovs
+            .iter()
+            .map(|_| {
+                let mut canvas = Canvas::new(self.hsize, self.vsize);
+                canvas.metadata = Some(RenderMetadata::new(self.hsize, self.vsize));
+                canvas
+            })
+            .collect();
+        for (i, (color, values)) in pixels.into_iter().enumerate() {
+            beauty.pixels[i] = color;
+            for (pass, value) in passes.iter_mut().zip(values) {
+                pass.pixels[i] = value;
+            }
+        }
+        let beauty = self.finish(world, beauty, start, self.samples_per_pixel());
+        (beauty, passes)
+    }
+
+    // Cryptomatte style masks for compositing: for every object or material name, the
+    // fraction of each pixel's primary rays whose visible surface has that name
+    pub fn render_masks(&self, world: &World, key: MaskKey) -> Vec<(String, Canvas)> {
+        let world = &*self.layered(world);
+        let (names, slots) = mask_groups(world, key);
 
+        let coverage = self.trace_tiles(|col, row| {
+            let rays = self.coverage_rays(col, row);
+            let mut weights = vec![0.; names.len()];
+            for ray in rays.iter() {
+                let xs = ray.intersect_world(world);
+                if let Some(index) = xs.visible_hit().and_then(|h| object_index(world, h.object)) {
+                    weights[slots[index]] += 1. / rays.len() as f64;
+                }
+            }
+            weights
+        });
+
+        names
+            .iter()
+            .enumerate()
+            .map(|(slot, name)| {
+                let mut mask = Canvas::new(self.hsize, self.vsize);
+                mask.pixels = coverage
+                    .iter()
+                    .map(|weights| Color::new(weights[slot], weights[slot], weights[slot]))
+                    .collect();
+                (name.clone(), mask)
+            })
+            .collect()
+    }
+
+    // What every channel of the radiance is multiplied by for the exposure and white
+    // balance
+    fn film_response(&self) -> Color {
+        let gain = 2_f64.powf(self.exposure);
+        match self.white_balance {
+            Some(white) => {
+                let luminance = white.luminance();
+                Color::new(
+                    luminance / white.red,
+                    luminance / white.green,
+                    luminance / white.blue,
+                ) * gain
+            }
+            None => Color::new(gain, gain, gain),
+        }
+    }
+
+    // The annotation with the frame and time filled in
+    fn annotation_text(&self) -> Option<String> {
+        let mut text = self.annotation.clone()?;
+        if let Some(frame) = self.frame {
+            text = text.replace("{frame}", &frame.to_string());
+        }
+        if let Some(time) = self.time {
+            text = text.replace("{time}", &format!("{:.2}", time));
+        }
+        Some(text)
+    }
+
+    // Overlays, annotation and metadata shared by the beauty renders
+    fn finish(&self, world: &World, mut canvas: Canvas, start: Instant, samples: usize) -> Canvas {
+        let response = self.film_response();
+        if response != WHITE {
+            for pixel in canvas.pixels.iter_mut() {
+                *pixel = *pixel * response;
+            }
+        }
+        if let Some(color) = self.edge_overlay {
+            for (pixel, edge) in canvas.pixels.iter_mut().zip(self.edges(world)) {
+                if edge {
+                    *pixel = color;
+                }
+            }
+        }
+
+        if let Some(annotation) = self.annotation_text() {
+            canvas.stamp(&annotation);
+        }
+
+        let mut metadata = RenderMetadata::new(self.hsize, self.vsize);
+        metadata.samples = samples;
+        metadata.seed = self.sampling.seed;
+        metadata.render_time = start.elapsed();
+        metadata.stats = world
+            .stats()
+            .map(|stats| stats.snapshot(metadata.render_time));
+        canvas.metadata = Some(metadata);
         canvas
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{color::Color, EPSILON, PI};
-    use float_cmp::approx_eq;
+    use crate::{
+        assert_approx_eq,
+        light::SphereLight,
+        shapes::{Plane, Sphere},
+    };
 
     use super::*;
     #[test]
@@ -133,9 +1334,9 @@ mod tests {
     #[test]
     fn pixel_size() {
         let c = Camera::new(200, 125, PI / 2., SuperSamplingMode::None);
-        approx_eq!(f64, c.pixel_size, 0.01, epsilon = EPSILON);
+        assert_approx_eq!(c.pixel_size, 0.01);
         let c = Camera::new(125, 200, PI / 2., SuperSamplingMode::None);
-        approx_eq!(f64, c.pixel_size, 0.01, epsilon = EPSILON);
+        assert_approx_eq!(c.pixel_size, 0.01);
     }
 
     #[test]
@@ -166,6 +1367,38 @@ mod tests {
         );
     }
 
+    #[test]
+    fn panorama() {
+        let c = Camera::panorama(41, 21, SuperSamplingMode::None);
+        assert_eq!(
+            c.project_ray(20, 10),
+            Ray::new(Tuple::point(0., 0., 0.), Tuple::vector(0., 0., 1.))
+        );
+        assert!(c.project_ray(20, 0).direction.y > 0.99);
+        assert_approx_eq!(
+            c.project_ray(30, 10).direction,
+            Tuple::vector(1., 0., 0.),
+            epsilon = 0.1
+        );
+
+        // An empty world renders back to its environment
+        let mut w = World::new(vec![], vec![]);
+        w.environment = Some(crate::environment::Environment::from_fn(41, 21, |d| {
+            Color::new(d.x.max(0.), d.y.max(0.), d.z.max(0.))
+        }));
+        let canvas = c.render(&w);
+        assert_approx_eq!(
+            canvas.get_pixel(20, 10),
+            Color::new(0., 0., 1.),
+            epsilon = 0.01
+        );
+        assert_approx_eq!(
+            canvas.get_pixel(30, 10),
+            Color::new(1., 0., 0.),
+            epsilon = 0.05
+        );
+    }
+
     #[test]
     fn render() {
         let w = World::default();
@@ -176,5 +1409,725 @@ mod tests {
         c.transform = Matrix::view_transform(from, to, up);
         let canvas = c.render(&w);
         assert_eq!(canvas.get_pixel(5, 5), Color::new(0.38066, 0.47583, 0.2855));
+        let metadata = canvas.metadata.unwrap();
+        assert_eq!((metadata.width, metadata.height), (11, 11));
+        assert_eq!(metadata.samples, 1);
+    }
+
+    #[test]
+    fn annotation() {
+        let w = World::new(vec![], vec![]);
+        let mut c = Camera::new(11, 11, PI / 2., SuperSamplingMode::None);
+        assert_eq!(c.describe(), "11x11 fov 1.571 ss none");
+        c.annotation = Some(String::from("."));
+        let canvas = c.render(&w);
+        assert_eq!(canvas.get_pixel(0, 10), BLACK);
+        assert_eq!(canvas.get_pixel(4, 8), Color::new(1., 1., 1.));
+    }
+
+    #[test]
+    fn tiles() {
+        let c = Camera::new(40, 20, PI / 2., SuperSamplingMode::None);
+        let tiles = c.tiles();
+        assert_eq!(tiles.len(), 6);
+        assert_eq!(tiles[0], (0, 0, 16, 16));
+        assert_eq!(tiles[2], (32, 0, 8, 16));
+        assert_eq!(tiles[5], (32, 16, 8, 4));
+        let covered: usize = tiles.iter().map(|(_, _, w, h)| w * h).sum();
+        assert_eq!(covered, 40 * 20);
+
+        let mut c = Camera::new(40, 20, PI / 3., SuperSamplingMode::None);
+        c.transform = Matrix::view_transform(
+            Tuple::point(0., 0., -5.),
+            Tuple::point(0., 0., 0.),
+            Tuple::vector(0., 1., 0.),
+        );
+        let w = World::default();
+        let canvas = c.render(&w);
+        for (x, y) in [(0, 0), (20, 10), (35, 18)] {
+            let expected = c.project_ray(x, y).color_hit(&w, MAX_REFLECTIONS);
+            assert_eq!(canvas.get_pixel(x, y), expected);
+        }
+    }
+
+    #[test]
+    fn render_stats() {
+        let mut w = World::default();
+        let mut c = Camera::new(11, 11, PI / 2., SuperSamplingMode::None);
+        assert!(c.render(&w).metadata.unwrap().stats.is_none());
+
+        w.enable_stats();
+        c.transform = Matrix::view_transform(
+            Tuple::point(0., 0., -5.),
+            Tuple::point(0., 0., 0.),
+            Tuple::vector(0., 1., 0.),
+        );
+        for _ in 0..2 {
+            let stats = c.render(&w).metadata.unwrap().stats.unwrap();
+            assert_eq!(stats.primary_rays, 121);
+            assert_eq!(stats.secondary_rays, 0);
+            assert!(stats.shadow_rays > 0 && stats.shadow_rays <= 121);
+            assert!(stats.intersection_tests >= 2 * 121);
+        }
+    }
+
+    #[test]
+    fn adaptive_supersampling() {
+        let mut w = World::default();
+        w.enable_stats();
+        let mode = SuperSamplingMode::Adaptive {
+            max_samples: 32,
+            threshold: 0.01,
+        };
+        let mut c = Camera::new(11, 11, PI / 2., mode);
+        c.transform = Matrix::view_transform(
+            Tuple::point(0., 0., -5.),
+            Tuple::point(0., 0., 0.),
+            Tuple::vector(0., 1., 0.),
+        );
+        c.sampling.seed = Some(1);
+        let canvas = c.render(&w);
+        assert_eq!(canvas.get_pixel(0, 0), BLACK);
+        // The background settles after one batch, the sphere's edges take more
+        let stats = canvas.metadata.unwrap().stats.unwrap();
+        assert!(stats.primary_rays > 121 * ADAPTIVE_BATCH as u64);
+        assert!(stats.primary_rays < 121 * 32 / 2);
+    }
+
+    #[test]
+    fn sampler_settings() {
+        let mut c = Camera::new(2, 2, PI / 2., SuperSamplingMode::Stochastic);
+        c.sampling = SamplerSettings {
+            samples: 4,
+            pattern: SamplePattern::Grid,
+            seed: None,
+        };
+        assert_eq!(c.samples_per_pixel(), 4);
+        // The centres of the pixel's quarters, a row at a time
+        let rays = c.project_subsample_rays(0, 0);
+        let expected = [(0.25, 0.25), (0.75, 0.25), (0.25, 0.75), (0.75, 0.75)];
+        assert_eq!(rays.len(), 4);
+        for (ray, (x, y)) in rays.iter().zip(expected) {
+            let direction = Tuple::vector(1. - x, 1. - y, -1.).normalize();
+            assert_approx_eq!(ray.direction, direction);
+        }
+
+        // One sample somewhere in each quarter
+        c.sampling.pattern = SamplePattern::Stratified;
+        c.sampling.seed = Some(5);
+        let rays = c.project_subsample_rays(0, 0);
+        for (ray, (x, y)) in rays.iter().zip(expected) {
+            let p = ray.position(-1. / ray.direction.z);
+            assert!((1. - p.x - x).abs() < 0.25 && (1. - p.y - y).abs() < 0.25);
+        }
+
+        // Counts that aren't square still cover the whole pixel evenly
+        c.sampling.pattern = SamplePattern::Grid;
+        for count in [2, 3, 5, 10, 17] {
+            c.sampling.samples = count;
+            let rays = c.project_subsample_rays(0, 0);
+            assert_eq!(rays.len(), count);
+            let centre = rays
+                .iter()
+                .map(|ray| ray.position(-1. / ray.direction.z))
+                .fold(Tuple::vector(0., 0., 0.), |sum, p| {
+                    sum + Tuple::vector(p.x, p.y, 0.)
+                })
+                / count as f64;
+            assert_approx_eq!(centre, Tuple::vector(0.5, 0.5, 0.));
+        }
+        let mut areas: Vec<f64> = vec![];
+        for index in 0..10 {
+            let (x0, y0) = grid_cell(index, 10, (0., 0.));
+            let (x1, y1) = grid_cell(index, 10, (1., 1.));
+            assert!(x0 >= 0. && y0 >= 0. && x1 <= 1. + EPSILON && y1 <= 1. + EPSILON);
+            areas.push((x1 - x0) * (y1 - y0));
+        }
+        assert!(areas.iter().all(|area| (area - 0.1).abs() < EPSILON));
+
+        let mut w = World::default();
+        w.enable_stats();
+        c.sampling.pattern = SamplePattern::Random;
+        c.sampling.samples = 3;
+        let stats = c.render(&w).metadata.unwrap().stats.unwrap();
+        assert_eq!(stats.primary_rays, 4 * 3);
+    }
+
+    #[test]
+    fn render_tiles() {
+        let w = World::default();
+        let mut c = Camera::new(25, 20, PI / 2., SuperSamplingMode::None);
+        c.transform = Matrix::view_transform(
+            Tuple::point(0., 0., -5.),
+            Tuple::point(0., 0., 0.),
+            Tuple::vector(0., 1., 0.),
+        );
+        let mut tiles = vec![];
+        let image = c.render_tiles(&w, 10, |tile, percent| tiles.push((tile.clone(), percent)));
+        assert_eq!(image.pixels, c.render(&w).pixels);
+
+        // 3 by 2 tiles, the last one 5 by 10
+        assert_eq!(tiles.len(), 6);
+        assert_eq!(tiles.last().unwrap().1, 100.);
+        assert!(tiles.windows(2).all(|pair| pair[0].1 < pair[1].1));
+        let (last, _) = tiles.iter().find(|(tile, _)| tile.index == 5).unwrap();
+        assert_eq!((last.x, last.y, last.width, last.height), (20, 10, 5, 10));
+        assert_eq!(last.pixels[0], image.get_pixel(20, 10));
+        assert_eq!(last.pixels[5 * 9 + 4], image.get_pixel(24, 19));
+    }
+
+    #[test]
+    fn progressive() {
+        let mut w = World::default();
+        w.enable_stats();
+        let mut c = Camera::new(11, 11, PI / 2., SuperSamplingMode::None);
+        c.transform = Matrix::view_transform(
+            Tuple::point(0., 0., -5.),
+            Tuple::point(0., 0., 0.),
+            Tuple::vector(0., 1., 0.),
+        );
+        c.sampling.seed = Some(2);
+        let mut passes = c.progressive(&w);
+        // The first pass traces the pixel centres, like a plain render
+        let first = passes.next().unwrap();
+        assert_eq!(first.pixels, c.render(&w).pixels);
+
+        let mut passes = c.progressive(&w);
+        let images: Vec<Canvas> = passes.by_ref().take(4).collect();
+        assert_eq!(passes.samples(), 4);
+        let metadata = images[3].metadata.as_ref().unwrap();
+        assert_eq!(metadata.samples, 4);
+        assert_eq!(metadata.stats.as_ref().unwrap().primary_rays, 4 * 121);
+        // Later passes smooth the sphere's edges, but leave the background alone
+        assert_ne!(images[3].pixels, images[0].pixels);
+        assert_eq!(images[3].get_pixel(0, 0), BLACK);
+    }
+
+    #[test]
+    fn stereo() {
+        let c = Camera::new(201, 101, PI / 2., SuperSamplingMode::None);
+        let stereo = Stereo::new(0.1, 4.);
+        let (left, right) = (c.eye(Eye::Left, &stereo), c.eye(Eye::Right, &stereo));
+        assert_approx_eq!(left.project_ray(100, 50).origin, Tuple::point(0.05, 0., 0.));
+        assert_approx_eq!(
+            right.project_ray(100, 50).origin,
+            Tuple::point(-0.05, 0., 0.)
+        );
+        // Both centre pixels look at the point of convergence
+        for eye in [&left, &right] {
+            let ray = eye.project_ray(100, 50);
+            assert_approx_eq!(
+                ray.position(4. / -ray.direction.z),
+                Tuple::point(0., 0., -4.)
+            );
+        }
+
+        // A sphere nearer than the convergence distance sits further right in the left
+        // eye's image
+        let mut w = World::default();
+        w.objects.truncate(1);
+        let mut c = Camera::new(101, 51, PI / 3., SuperSamplingMode::None);
+        c.transform = Matrix::view_transform(
+            Tuple::point(0., 0., -3.),
+            Tuple::point(0., 0., 0.),
+            Tuple::vector(0., 1., 0.),
+        );
+        let (left, right) = c.render_stereo(&w, &Stereo::new(0.5, 10.));
+        let leftmost = |canvas: &Canvas| (0..101).find(|&x| canvas.get_pixel(x, 25) != BLACK);
+        assert!(leftmost(&left).unwrap() > leftmost(&right).unwrap());
+    }
+
+    #[test]
+    fn resume() {
+        let w = World::default();
+        let mut c = Camera::new(11, 11, PI / 2., SuperSamplingMode::None);
+        c.transform = Matrix::view_transform(
+            Tuple::point(0., 0., -5.),
+            Tuple::point(0., 0., 0.),
+            Tuple::vector(0., 1., 0.),
+        );
+        c.sampling.seed = Some(4);
+        let uninterrupted = c.progressive(&w).nth(5).unwrap();
+
+        let mut passes = c.progressive(&w);
+        passes.nth(2);
+        let saved = passes.accumulator().clone();
+        let resumed = c.resume(&w, saved).nth(2).unwrap();
+        assert_eq!(resumed.pixels, uninterrupted.pixels);
+
+        let path = std::env::temp_dir().join(format!("checkpoint-{}", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+        c.render_resumable(&w, 3, path).unwrap();
+        assert_eq!(Accumulator::load(path).unwrap().fewest(), 3);
+        let finished = c.render_resumable(&w, 6, path).unwrap();
+        assert_eq!(finished.pixels, uninterrupted.pixels);
+        assert_eq!(finished.metadata.unwrap().samples, 6);
+        assert_eq!(
+            c.render_resumable(&w, 6, path).unwrap().pixels,
+            uninterrupted.pixels
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn exposure_and_white_balance() {
+        let w = World::default();
+        let mut c = Camera::new(11, 11, PI / 2., SuperSamplingMode::None);
+        c.transform = Matrix::view_transform(
+            Tuple::point(0., 0., -5.),
+            Tuple::point(0., 0., 0.),
+            Tuple::vector(0., 1., 0.),
+        );
+        let plain = c.render(&w).get_pixel(5, 5);
+        c.exposure = -1.;
+        assert_approx_eq!(c.render(&w).get_pixel(5, 5), plain * 0.5);
+
+        // Warm light turns neutral
+        c.exposure = 0.;
+        c.white_balance = Some(Color::new(1., 0.8, 0.5));
+        let mut tiles = vec![];
+        let image = c.render_tiles(&w, 4, |tile, _| tiles.push(tile.clone()));
+        let luminance = Color::new(1., 0.8, 0.5).luminance();
+        assert_approx_eq!(
+            image.get_pixel(5, 5),
+            plain * Color::new(luminance, luminance / 0.8, luminance / 0.5)
+        );
+        let centre = tiles.iter().find(|t| t.x == 4 && t.y == 4).unwrap();
+        assert_eq!(centre.pixels[4 + 1], image.get_pixel(5, 5));
+    }
+
+    #[test]
+    fn autofocus() {
+        let w = World::default();
+        let mut c = Camera::new(11, 11, PI / 2., SuperSamplingMode::Stochastic);
+        c.transform = Matrix::view_transform(
+            Tuple::point(0., 0., -5.),
+            Tuple::point(0., 0., 0.),
+            Tuple::vector(0., 1., 0.),
+        );
+        c.aperture = 0.5;
+        assert_approx_eq!(c.autofocus(&w).unwrap(), 4.);
+        assert_approx_eq!(c.focal_distance, 4.);
+        // Off centre, the depth is less than the distance along the ray
+        let depth = c.focus_on(&w, 6, 5).unwrap();
+        let ray = c.project_ray(6, 5);
+        let t = ray.intersect_world(&w).hit().unwrap().t;
+        assert!(depth < t && depth > 4.);
+        // Missing everything leaves it alone
+        assert_eq!(c.focus_on(&w, 0, 0), None);
+        assert_eq!(c.focal_distance, depth);
+    }
+
+    #[test]
+    fn aovs() {
+        let w = World::default();
+        let mut c = Camera::new(11, 11, PI / 2., SuperSamplingMode::None);
+        c.transform = Matrix::view_transform(
+            Tuple::point(0., 0., -5.),
+            Tuple::point(0., 0., 0.),
+            Tuple::vector(0., 1., 0.),
+        );
+        let (beauty, passes) = c.render_with_aovs(&w, &[Aov::Normal, Aov::Depth, Aov::Albedo]);
+        assert_eq!(beauty.pixels, c.render(&w).pixels);
+        let [normal, depth, albedo] = &passes[..] else {
+            panic!("expected three passes");
+        };
+        assert_approx_eq!(normal.get_pixel(5, 5), Color::new(0., 0., -1.));
+        assert_approx_eq!(depth.get_pixel(5, 5), Color::new(4., 4., 4.));
+        assert_approx_eq!(albedo.get_pixel(5, 5), Color::new(0.8, 1.0, 0.6));
+        assert_eq!(normal.get_pixel(0, 0), BLACK);
+        assert_eq!(depth.get_pixel(0, 0).red, f64::INFINITY);
+
+        // Supersampled passes average the samples, here partly covering the sphere
+        let mut c = Camera::new(11, 11, PI / 2., SuperSamplingMode::Stochastic);
+        c.transform = Matrix::view_transform(
+            Tuple::point(0., 0., -5.),
+            Tuple::point(0., 0., 0.),
+            Tuple::vector(0., 1., 0.),
+        );
+        c.sampling.seed = Some(1);
+        let (_, passes) = c.render_with_aovs(&w, &[Aov::Albedo, Aov::Depth]);
+        let edge = (0..11)
+            .map(|x| passes[0].get_pixel(x, 5))
+            .find(|a| a.green > 0. && a.green < 1.);
+        assert!(edge.is_some());
+        assert!(passes[1].pixels.iter().all(|d| d.red > 3.9));
+
+        // Adaptive passes use the samples the pixel was shaded with, all three of them
+        // along the edge
+        c.supersampling_mode = SuperSamplingMode::Adaptive {
+            max_samples: 3,
+            threshold: 0.,
+        };
+        let (beauty, passes) = c.render_with_aovs(&w, &[Aov::Albedo]);
+        assert_eq!(beauty.pixels, c.render(&w).pixels);
+        let edge = (0..11)
+            .map(|x| passes[0].get_pixel(x, 5).green)
+            .find(|&g| g > 0. && g < 1.)
+            .unwrap();
+        assert_approx_eq!((edge * 3.).round(), edge * 3.);
+    }
+
+    #[test]
+    fn id_aovs() {
+        let mut w = World::default();
+        w.objects[0].name = Some("outer".to_string());
+        let mut c = Camera::new(11, 11, PI / 2., SuperSamplingMode::Stochastic);
+        c.transform = Matrix::view_transform(
+            Tuple::point(0., 0., -5.),
+            Tuple::point(0., 0., 0.),
+            Tuple::vector(0., 1., 0.),
+        );
+        let (_, passes) = c.render_with_aovs(
+            &w,
+            &[
+                Aov::Id(MaskKey::Object),
+                Aov::IdColor(MaskKey::Object),
+                Aov::IdColor(MaskKey::Material),
+            ],
+        );
+        let masks = c.render_masks(&w, MaskKey::Object);
+        assert_eq!(masks[0].0, "outer");
+        assert_eq!(passes[0].get_pixel(5, 5), Color::new(0., 0., 0.));
+        assert_eq!(passes[0].get_pixel(0, 0), Color::new(-1., -1., -1.));
+        assert_eq!(passes[1].get_pixel(5, 5), id_color("outer"));
+        assert_eq!(passes[1].get_pixel(0, 0), BLACK);
+        assert_eq!(passes[2].get_pixel(5, 5), id_color("material 0"));
+        assert_ne!(id_color("outer"), id_color("object 1"));
+    }
+
+    #[test]
+    fn default_camera() {
+        let c = Camera::default();
+        assert_eq!((c.hsize, c.vsize), (640, 480));
+        assert_eq!(c.field_of_view(), PI / 3.);
+        assert_eq!(c.transform, Matrix::identity(4));
+
+        let mut copy = c.clone();
+        assert_eq!(copy, c);
+        copy.transform = Matrix::translation(0., 0., -5.);
+        assert_ne!(copy, c);
+    }
+
+    #[test]
+    fn debug_modes() {
+        let w = World::default();
+        let mut c = Camera::new(11, 11, PI / 2., SuperSamplingMode::None);
+        c.transform = Matrix::view_transform(
+            Tuple::point(0., 0., -5.),
+            Tuple::point(0., 0., 0.),
+            Tuple::vector(0., 1., 0.),
+        );
+
+        // The centre of the outer sphere faces straight back at the camera
+        let normals = c.render_debug(&w, DebugMode::Normals);
+        assert_eq!(normals.get_pixel(5, 5), Color::new(0.5, 0.5, 0.));
+        assert_eq!(normals.get_pixel(0, 0), BLACK);
+
+        let depth = c.render_debug(&w, DebugMode::Depth);
+        assert_eq!(depth.get_pixel(5, 5), Color::new(1., 1., 1.));
+        assert_eq!(depth.get_pixel(0, 0), BLACK);
+        assert!(depth.get_pixel(5, 2).red < 1.);
+
+        // Both spheres are crossed through the middle, only the outer one at the edge
+        let count = c.render_debug(&w, DebugMode::IntersectionCount);
+        assert_eq!(count.get_pixel(5, 5), Color::new(1., 1., 1.));
+        assert_eq!(count.get_pixel(5, 4), heat(0.5));
+        assert_eq!(count.get_pixel(0, 0), BLACK);
+
+        // Halfway up the sphere, whatever way round it the seam falls
+        let uv = c.render_debug(&w, DebugMode::Uv);
+        assert_approx_eq!(uv.get_pixel(5, 5).green, 0.5);
+        assert_eq!(uv.get_pixel(5, 5).blue, 0.);
+        assert!(uv.get_pixel(5, 4).green > 0.5);
+        assert_eq!(uv.get_pixel(0, 0), BLACK);
+
+        // No BVH, nothing visited
+        let visits = c.render_debug(&w, DebugMode::BvhNodeVisits);
+        assert!(visits.pixels.iter().all(|c| *c == BLACK));
+    }
+
+    #[test]
+    fn debug_bvh_visits() {
+        // A row of spheres along x, so rays along the row visit more of the tree
+        let spheres = (0..20)
+            .map(|i| {
+                Sphere::new(None).with_transform(
+                    &(&Matrix::translation(i as f64 - 9.5, 0., 0.)
+                        * &Matrix::scaling(0.4, 0.4, 0.4)),
+                )
+            })
+            .collect();
+        let mut w = World::new(spheres, vec![]);
+        w.build_bvh();
+        let mut c = Camera::new(11, 11, PI / 2., SuperSamplingMode::None);
+        c.transform = Matrix::view_transform(
+            Tuple::point(0., 0., -5.),
+            Tuple::point(0., 0., 0.),
+            Tuple::vector(0., 1., 0.),
+        );
+
+        let visits = c.render_debug(&w, DebugMode::BvhNodeVisits);
+        let brightness = |c: Color| c.red + c.green + c.blue;
+        assert!(brightness(visits.get_pixel(5, 5)) > brightness(visits.get_pixel(5, 0)));
+        assert!(visits.pixels.iter().all(|c| *c != BLACK));
+    }
+
+    #[test]
+    fn ambient_occlusion() {
+        // A sphere resting on a floor, seen from above and in front
+        let floor = Plane::new(None);
+        let ball = Sphere::new(None).with_transform(&Matrix::translation(0., 1., 0.));
+        let w = World::new(vec![floor, ball], vec![]);
+        let mut c = Camera::new(21, 21, PI / 3., SuperSamplingMode::None);
+        c.transform = Matrix::view_transform(
+            Tuple::point(0., 6., -6.),
+            Tuple::point(0., 0., 0.),
+            Tuple::vector(0., 1., 0.),
+        );
+        let canvas = c.render_ao(&w, 2., 64);
+
+        // The top of the ball sees the whole sky, the floor next to it doesn't
+        let ray = c.project_ray(10, 8);
+        let top = ray.intersect_world(&w).hit().unwrap().t;
+        assert!(ray.position(top).y > 1.5);
+        assert_eq!(canvas.get_pixel(10, 8), WHITE);
+        let crease = c.project_ray(10, 11);
+        let t = crease.intersect_world(&w).hit().unwrap().t;
+        assert!(crease.position(t).y.abs() < EPSILON);
+        assert!(canvas.get_pixel(10, 11).red < 0.9);
+        assert_eq!(canvas.get_pixel(0, 0), WHITE);
+    }
+
+    #[test]
+    fn edge_overlay() {
+        let w = World::default();
+        let mut c = Camera::new(21, 21, PI / 6., SuperSamplingMode::None);
+        c.transform = Matrix::view_transform(
+            Tuple::point(0., 0., -5.),
+            Tuple::point(0., 0., 0.),
+            Tuple::vector(0., 1., 0.),
+        );
+        let edges = c.edges(&w);
+        // Only the outline of the sphere is an edge, not its middle or the background
+        assert!(!edges[10 * 21 + 10]);
+        assert!(!edges[0]);
+        assert!(edges.iter().any(|e| *e));
+        let outline = (0..21).find(|col| edges[10 * 21 + col]).unwrap();
+
+        let plain = c.render(&w);
+        let red = Color::new(1., 0., 0.);
+        c.edge_overlay = Some(red);
+        let outlined = c.render(&w);
+        assert_eq!(outlined.get_pixel(outline, 10), red);
+        assert_eq!(outlined.get_pixel(10, 10), plain.get_pixel(10, 10));
+    }
+
+    #[test]
+    fn clay() {
+        let mut w = World::default();
+        let mut c = Camera::new(11, 11, PI / 2., SuperSamplingMode::None);
+        c.transform = Matrix::view_transform(
+            Tuple::point(0., 0., -5.),
+            Tuple::point(0., 0., 0.),
+            Tuple::vector(0., 1., 0.),
+        );
+        // The outer sphere's green material is ignored
+        let center = c.render_clay(&w, true).get_pixel(5, 5);
+        assert!(center.red > 0.);
+        assert_eq!(center.red, center.green);
+        assert_eq!(center.green, center.blue);
+        assert_eq!(c.render_clay(&w, false).get_pixel(0, 0), BLACK);
+
+        // A blocker between the light and the sphere only darkens it with shadows on
+        w.objects
+            .push(Sphere::new(None).with_transform(&Matrix::translation(-5., 5., -5.)));
+        assert!(c.render_clay(&w, true).get_pixel(5, 5).red < center.red);
+        assert_eq!(c.render_clay(&w, false).get_pixel(5, 5), center);
+    }
+
+    #[test]
+    fn time_budget() {
+        let w = World::default();
+        let mut c = Camera::new(5, 5, PI / 2., SuperSamplingMode::None);
+        c.transform = Matrix::view_transform(
+            Tuple::point(0., 0., -5.),
+            Tuple::point(0., 0., 0.),
+            Tuple::vector(0., 1., 0.),
+        );
+        // Without any time, the render is the single centre sample of each pixel
+        let quick = c.render_for(&w, Duration::ZERO);
+        assert_eq!(quick.pixels, c.render(&w).pixels);
+        assert_eq!(quick.metadata.as_ref().unwrap().samples, 1);
+
+        let slow = c.render_for(&w, Duration::from_millis(200));
+        let samples = slow.metadata.as_ref().unwrap().samples;
+        assert!(samples > 1 && samples <= MAX_PROGRESSIVE_SAMPLES);
+    }
+
+    #[test]
+    fn seeded_renders() {
+        let w = World::default();
+        let mut c = Camera::new(8, 8, PI / 2., SuperSamplingMode::Stochastic);
+        c.transform = Matrix::view_transform(
+            Tuple::point(0., 0., -5.),
+            Tuple::point(0., 0., 0.),
+            Tuple::vector(0., 1., 0.),
+        );
+        c.sampling.seed = Some(7);
+        let image = c.render(&w);
+        assert_eq!(image.pixels, c.render(&w).pixels);
+        assert_eq!(image.metadata.as_ref().unwrap().seed, Some(7));
+        #[cfg(feature = "rayon")]
+        {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(1)
+                .build()
+                .unwrap();
+            assert_eq!(image.pixels, pool.install(|| c.render(&w)).pixels);
+        }
+
+        c.threads = Some(3);
+        assert_eq!(image.pixels, c.render(&w).pixels);
+        #[cfg(feature = "rayon")]
+        assert!(c
+            .trace_tiles(|_, _| rayon::current_num_threads())
+            .iter()
+            .all(|&threads| threads == 3));
+        #[cfg(feature = "rayon")]
+        assert!(Arc::ptr_eq(&thread_pool(3), &thread_pool(3)));
+
+        c.sampling.seed = Some(8);
+        assert_ne!(image.pixels, c.render(&w).pixels);
+    }
+
+    #[test]
+    fn seeded_soft_shadows() {
+        // A ball over a floor, lit by a sphere light big enough for a wide penumbra
+        let floor = Plane::new(None).with_transform(&Matrix::translation(0., -1., 0.));
+        let light = SphereLight::new(Tuple::point(-4., 4., -4.), 2., WHITE);
+        let w = World::new(vec![floor, Sphere::new(None)], vec![light]);
+        let mut c = Camera::new(16, 16, PI / 2., SuperSamplingMode::Stochastic);
+        c.transform = Matrix::view_transform(
+            Tuple::point(0., 4., -3.),
+            Tuple::point(0., -1., 0.),
+            Tuple::vector(0., 1., 0.),
+        );
+        c.sampling.samples = 2;
+        c.sampling.seed = Some(42);
+        let bits = |canvas: Canvas| -> Vec<[u64; 3]> {
+            canvas
+                .pixels
+                .iter()
+                .map(|p| [p.red.to_bits(), p.green.to_bits(), p.blue.to_bits()])
+                .collect()
+        };
+        let image = bits(c.render(&w));
+        assert_eq!(image, bits(c.render(&w)));
+        c.threads = Some(1);
+        assert_eq!(image, bits(c.render(&w)));
+    }
+
+    #[test]
+    fn render_layers() {
+        let mut w = World::default();
+        w.objects[0] = w.objects[0].clone().with_layer("shell");
+        let mut c = Camera::new(11, 11, PI / 2., SuperSamplingMode::None);
+        c.transform = Matrix::view_transform(
+            Tuple::point(0., 0., -5.),
+            Tuple::point(0., 0., 0.),
+            Tuple::vector(0., 1., 0.),
+        );
+        let outer = c.render(&w).get_pixel(5, 5);
+
+        // Without the outer sphere, the lit inner sphere shows through
+        c.layers.insert(String::from("shell"), LayerMode::Hidden);
+        let inner = c.render(&w).get_pixel(5, 5);
+        assert_ne!(inner, outer);
+
+        // As a shadow caster only, the outer sphere leaves the inner one in its shadow
+        c.layers
+            .insert(String::from("shell"), LayerMode::ShadowOnly);
+        let shadowed = c.render(&w).get_pixel(5, 5);
+        assert!(shadowed.red > 0. && shadowed.red < inner.red);
+        assert_eq!(w.objects.len(), 2);
+        assert!(!w.objects[0].shadow_only);
+    }
+
+    #[test]
+    fn masks() {
+        let mut w = World::default();
+        w.objects[0].name = Some(String::from("shell"));
+        w.objects
+            .push(Sphere::new(None).with_transform(&Matrix::translation(0., 0., 10.)));
+        let mut c = Camera::new(11, 11, PI / 2., SuperSamplingMode::None);
+        c.transform = Matrix::view_transform(
+            Tuple::point(0., 0., -5.),
+            Tuple::point(0., 0., 0.),
+            Tuple::vector(0., 1., 0.),
+        );
+
+        let masks = c.render_masks(&w, MaskKey::Object);
+        let names: Vec<_> = masks.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["shell", "object 1", "object 2"]);
+        assert_eq!(masks[0].1.get_pixel(5, 5), WHITE);
+        assert_eq!(masks[0].1.get_pixel(0, 0), BLACK);
+        assert_eq!(masks[1].1.get_pixel(5, 5), BLACK);
+
+        // The two spheres with default materials share one mask
+        let masks = c.render_masks(&w, MaskKey::Material);
+        let names: Vec<_> = masks.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["material 0", "material 1"]);
+
+        // Until one of them becomes a mirror
+        w.objects[2].material.reflective = 1.;
+        let masks = c.render_masks(&w, MaskKey::Material);
+        let names: Vec<_> = masks.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["material 0", "material 1", "material 2"]);
+
+        // Coverage is fractional along the silhouette when supersampling
+        let mut c = Camera::new(11, 11, PI / 2., SuperSamplingMode::Stochastic);
+        c.transform = Matrix::view_transform(
+            Tuple::point(0., 0., -5.),
+            Tuple::point(0., 0., 0.),
+            Tuple::vector(0., 1., 0.),
+        );
+        c.sampling.seed = Some(1);
+        let masks = c.render_masks(&w, MaskKey::Object);
+        let total: f64 = (0..3).map(|i| masks[i].1.get_pixel(3, 5).red).sum();
+        assert!(total <= 1. + EPSILON);
+        assert!((0..121).any(|p| {
+            let v = masks[0].1.pixels[p].red;
+            v > 0. && v < 1.
+        }));
+    }
+
+    #[test]
+    fn physical_camera() {
+        // A 50mm lens on a full frame sensor at f/2
+        let c = Camera::physical(300, 200, 50., (36., 24.), 2., SuperSamplingMode::None);
+        assert_approx_eq!(c.field_of_view(), 0.69111, epsilon = 1e-5);
+        assert_approx_eq!(c.aperture, 0.025);
+        // Portrait images span the sensor height
+        let c = Camera::physical(200, 300, 50., (36., 24.), 2., SuperSamplingMode::None);
+        assert_approx_eq!(c.field_of_view(), 0.47109, epsilon = 1e-5);
+    }
+
+    #[test]
+    fn depth_of_field() {
+        let mut c = Camera::new(11, 11, PI / 2., SuperSamplingMode::Stochastic);
+        c.aperture = 1.;
+        c.focal_distance = 5.;
+        c.sampling.seed = Some(3);
+        // Rays through the same pixel leave from different points of the lens, but all
+        // pass within a pixel of each other on the plane in focus
+        let rays = c.project_subsample_rays(5, 5);
+        assert!(rays.iter().any(|r| r.origin != rays[0].origin));
+        for ray in rays.iter() {
+            let p = ray.position(-5. / ray.direction.z);
+            assert!(p.x.abs() < 5. * c.pixel_size && p.y.abs() < 5. * c.pixel_size);
+        }
+        assert_approx_eq!(c.project_ray(5, 5).origin, Tuple::point(0., 0., 0.));
     }
 }
@@ -1,9 +1,98 @@
-use crate::color::Color;
+use crate::{
+    approx::ApproxEq,
+    color::{Color, WHITE},
+    font,
+    stats::RenderStats,
+    PI,
+};
 
+use std::time::Duration;
+
+// Describes how a canvas was produced so that saved images are self-describing
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderMetadata {
+    pub scene: Option<String>,
+    pub width: usize,
+    pub height: usize,
+    pub samples: usize,
+    pub seed: Option<u64>,
+    pub render_time: Duration,
+    pub version: String,
+    pub stats: Option<RenderStats>,
+}
+
+impl RenderMetadata {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            scene: None,
+            width,
+            height,
+            samples: 1,
+            seed: None,
+            render_time: Duration::ZERO,
+            version: String::from(env!("CARGO_PKG_VERSION")),
+            stats: None,
+        }
+    }
+
+    // Key value pairs in the order they are written out
+    pub fn entries(&self) -> Vec<(&'static str, String)> {
+        let mut entries = vec![];
+        if let Some(scene) = &self.scene {
+            entries.push(("scene", scene.clone()));
+        }
+        entries.push(("resolution", format!("{}x{}", self.width, self.height)));
+        entries.push(("samples", format!("{}", self.samples)));
+        if let Some(seed) = self.seed {
+            entries.push(("seed", format!("{}", seed)));
+        }
+        entries.push((
+            "render_time",
+            format!("{:.3}s", self.render_time.as_secs_f64()),
+        ));
+        if let Some(stats) = &self.stats {
+            entries.push((
+                "stats",
+                format!(
+                    "{} rays, {:.0} rays/s, {:.2} tests/ray",
+                    stats.rays(),
+                    stats.rays_per_second(),
+                    stats.intersections_per_ray()
+                ),
+            ));
+        }
+        entries.push(("version", format!("raytracer {}", self.version)));
+        entries
+    }
+}
+
+#[derive(Debug)]
 pub struct Canvas {
     pub width: usize,
     pub height: usize,
     pub pixels: Vec<Color>,
+    pub metadata: Option<RenderMetadata>,
+    // Whether 8 bit output (PPM, PNG, GIF and `image` buffers) is ordered dithered,
+    // which hides the banding of smooth gradients at the cost of a fine pattern
+    pub dither: bool,
+}
+
+// 4x4 Bayer matrix, the order in which ordered dithering turns pixels up a level
+// Luminance of an 18% grey card, the usual target for metering a scene
+const MID_GREY: f64 = 0.18;
+
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+impl ApproxEq for Canvas {
+    fn approx_eq_eps(&self, other: &Self, epsilon: f64) -> bool {
+        self.width == other.width
+            && self.height == other.height
+            && self
+                .pixels
+                .iter()
+                .zip(other.pixels.iter())
+                .all(|(a, b)| a.approx_eq_eps(b, epsilon))
+    }
 }
 
 impl Canvas {
@@ -12,6 +101,8 @@ impl Canvas {
             width,
             height,
             pixels: vec![Color::new(0., 0., 0.); width * height],
+            metadata: None,
+            dither: false,
         }
     }
 
@@ -28,11 +119,11 @@ impl Canvas {
 
     fn add_component_to_line(&self, line: &mut String, ppm: &mut String, component: u8) {
         let c = format!("{}", component);
-        if line.len() == 0 {
+        if line.is_empty() {
             line.push_str(c.as_str());
         } else {
             // +1 for space at the start
-            if c.len() + line.len() + 1 <= 70 {
+            if c.len() + line.len() < 70 {
                 line.push(' ');
                 line.push_str(c.as_str());
             } else {
@@ -45,20 +136,36 @@ impl Canvas {
         }
     }
 
+    // The pixel scaled from [0, 1] to [0, 255], clamped and rounded, or with dithering
+    // nudged up or down by less than a level first
+    fn quantize(&self, x: usize, y: usize) -> [u8; 3] {
+        let mut scaled_pixel = self.get_pixel(x, y) * 255.;
+        if self.dither {
+            let offset = (BAYER[y % 4][x % 4] as f64 + 0.5) / 16. - 0.5;
+            scaled_pixel = scaled_pixel + Color::new(offset, offset, offset);
+        }
+        scaled_pixel.clamp();
+        [scaled_pixel.red, scaled_pixel.green, scaled_pixel.blue].map(|c| c.round() as u8)
+    }
+
     fn write_ppm(&self) -> String {
         let mut ppm = String::new();
-        ppm.push_str(format!("P3\n{} {}\n255\n", self.width, self.height).as_str());
-        for chunk in self.pixels.chunks(self.width) {
+        ppm.push_str("P3\n");
+        if let Some(metadata) = &self.metadata {
+            for (key, value) in metadata.entries() {
+                ppm.push_str(format!("# {}: {}\n", key, value).as_str());
+            }
+        }
+        ppm.push_str(format!("{} {}\n255\n", self.width, self.height).as_str());
+        for y in 0..self.height {
             let mut line = String::new();
-            for pixel in chunk {
-                let mut scaled_pixel = pixel * 255.;
-                scaled_pixel.clamp();
-                self.add_component_to_line(&mut line, &mut ppm, scaled_pixel.red.round() as u8);
-                self.add_component_to_line(&mut line, &mut ppm, scaled_pixel.green.round() as u8);
-                self.add_component_to_line(&mut line, &mut ppm, scaled_pixel.blue.round() as u8);
+            for x in 0..self.width {
+                for component in self.quantize(x, y) {
+                    self.add_component_to_line(&mut line, &mut ppm, component);
+                }
             }
             // Row over, so flush line again
-            if line.len() > 0 {
+            if !line.is_empty() {
                 ppm.push_str(line.as_str());
                 ppm.push('\n');
             }
@@ -70,14 +177,773 @@ impl Canvas {
         self.write_ppm()
     }
 
+    // Reads a plain (P3) PPM, ignoring comments. Returns None if it is malformed
+    pub fn from_ppm(ppm: &str) -> Option<Canvas> {
+        let mut tokens = ppm
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or(""))
+            .flat_map(|line| line.split_whitespace());
+        if tokens.next()? != "P3" {
+            return None;
+        }
+        let mut number = || tokens.next()?.parse::<usize>().ok();
+        let (width, height, max) = (number()?, number()?, number()?);
+        if max == 0 {
+            return None;
+        }
+        let mut canvas = Canvas::new(width, height);
+        for pixel in canvas.pixels.iter_mut() {
+            let (r, g, b) = (number()?, number()?, number()?);
+            *pixel = Color::new(r as f64, g as f64, b as f64) * (1. / max as f64);
+        }
+        Some(canvas)
+    }
+
+    // Plain PPMs are always readable. With the image feature, so are PNGs, binary
+    // PPMs and anything else `image` reads, and OpenEXR and Radiance HDR files keep
+    // their values above 1
+    pub fn load(path: &str) -> Result<Canvas, String> {
+        let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+        if bytes.starts_with(b"P3") {
+            return std::str::from_utf8(&bytes)
+                .ok()
+                .and_then(Canvas::from_ppm)
+                .ok_or_else(|| format!("{}: malformed PPM", path));
+        }
+        #[cfg(feature = "image")]
+        {
+            Canvas::decode(&bytes).map_err(|e| format!("{}: {}", path, e))
+        }
+        #[cfg(not(feature = "image"))]
+        Err(format!(
+            "{}: only plain PPMs can be read without the image feature",
+            path
+        ))
+    }
+
+    #[cfg(feature = "image")]
+    fn decode(bytes: &[u8]) -> image::ImageResult<Canvas> {
+        let format = image::guess_format(bytes)?;
+        if format != image::ImageFormat::Hdr {
+            let image = image::load_from_memory_with_format(bytes, format)?;
+            return Ok(Canvas::from_dynamic_image(&image));
+        }
+        // `image` reads HDR files as 8 bit images, so decode the floats directly
+        let decoder = image::codecs::hdr::HdrDecoder::new(bytes)?;
+        let size = decoder.metadata();
+        let mut canvas = Canvas::new(size.width as usize, size.height as usize);
+        for (pixel, rgb) in canvas.pixels.iter_mut().zip(decoder.read_image_hdr()?) {
+            let [r, g, b] = rgb.0;
+            *pixel = Color::new(r as f64, g as f64, b as f64);
+        }
+        Ok(canvas)
+    }
+
     pub fn save_ppm(&self, path: &str) {
         std::fs::write(String::from("images/") + path, self.to_ppm()).unwrap();
     }
+
+    // Red, green and blue bytes for each pixel, clamped and scaled like `to_ppm`
+    pub fn to_rgb8(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(self.pixels.len() * 3);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                data.extend(self.quantize(x, y));
+            }
+        }
+        data
+    }
+
+    // 8 bit RGB PNG, clamped and scaled like `to_ppm`, with the metadata as text
+    // chunks
+    #[cfg(feature = "png")]
+    pub fn to_png(&self) -> Result<Vec<u8>, String> {
+        let data = self.to_rgb8();
+        let mut bytes = vec![];
+        let mut encoder = png::Encoder::new(&mut bytes, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        if let Some(metadata) = &self.metadata {
+            for (key, value) in metadata.entries() {
+                encoder
+                    .add_text_chunk(key.to_string(), value)
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&data))
+            .map_err(|e| e.to_string())?;
+        Ok(bytes)
+    }
+
+    // Unlike save_ppm, the path isn't relative to images/
+    #[cfg(feature = "png")]
+    pub fn save_png(&self, path: &str) -> Result<(), String> {
+        self.to_png()
+            .and_then(|png| std::fs::write(path, png).map_err(|e| e.to_string()))
+            .map_err(|e| format!("{}: {}", path, e))
+    }
+
+    // Animated PNG playing the frames at `fps` frames per second, looping forever.
+    // Viewers that don't know APNG show the first frame
+    #[cfg(feature = "png")]
+    pub fn save_apng(frames: &[Canvas], fps: f64, path: &str) -> Result<(), String> {
+        let (width, height) = animation_size(frames);
+        let delay = (1000. / fps).round() as u16;
+        let write = || -> Result<(), png::EncodingError> {
+            let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+            let mut encoder = png::Encoder::new(file, width as u32, height as u32);
+            encoder.set_color(png::ColorType::Rgb);
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder.set_animated(frames.len() as u32, 0)?;
+            encoder.set_frame_delay(delay, 1000)?;
+            let mut writer = encoder.write_header()?;
+            for frame in frames {
+                writer.write_image_data(&frame.to_rgb8())?;
+            }
+            writer.finish()
+        };
+        write().map_err(|e| format!("{}: {}", path, e))
+    }
+
+    // Animated GIF playing the frames at `fps` frames per second, looping forever.
+    // GIFs have 256 colors per frame, picked for each frame, and delays in hundredths
+    // of a second
+    #[cfg(feature = "gif")]
+    pub fn save_gif(frames: &[Canvas], fps: f64, path: &str) -> Result<(), String> {
+        let (width, height) = animation_size(frames);
+        assert!(width <= u16::MAX as usize && height <= u16::MAX as usize);
+        let delay = (100. / fps).round() as u16;
+        let write = || -> Result<(), gif::EncodingError> {
+            let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+            let mut encoder = gif::Encoder::new(file, width as u16, height as u16, &[])?;
+            encoder.set_repeat(gif::Repeat::Infinite)?;
+            for frame in frames {
+                let rgb = frame.to_rgb8();
+                let mut frame = gif::Frame::from_rgb_speed(width as u16, height as u16, &rgb, 10);
+                frame.delay = delay;
+                encoder.write_frame(&frame)?;
+            }
+            Ok(())
+        };
+        write().map_err(|e| format!("{}: {}", path, e))
+    }
+
+    // Any `image` image, with channels mapped onto [0, 1] the same way PPMs are.
+    // Floating point images keep values above 1 and alpha is dropped
+    #[cfg(feature = "image")]
+    pub fn from_dynamic_image(image: &image::DynamicImage) -> Canvas {
+        let rgb = image.to_rgb32f();
+        let mut canvas = Canvas::new(rgb.width() as usize, rgb.height() as usize);
+        for (pixel, rgb) in canvas.pixels.iter_mut().zip(rgb.pixels()) {
+            let [r, g, b] = rgb.0;
+            *pixel = Color::new(r as f64, g as f64, b as f64);
+        }
+        canvas
+    }
+
+    // Opaque 8 bit image, clamped and scaled like `to_ppm`
+    #[cfg(feature = "image")]
+    pub fn to_rgba_image(&self) -> image::RgbaImage {
+        image::RgbaImage::from_fn(self.width as u32, self.height as u32, |x, y| {
+            let [red, green, blue] = self.quantize(x as usize, y as usize);
+            image::Rgba([red, green, blue, 255])
+        })
+    }
+
+    // Float image keeping the full range of every channel
+    #[cfg(feature = "image")]
+    pub fn to_rgb32f_image(&self) -> image::Rgb32FImage {
+        image::Rgb32FImage::from_fn(self.width as u32, self.height as u32, |x, y| {
+            let pixel = self.get_pixel(x as usize, y as usize);
+            image::Rgb([pixel.red as f32, pixel.green as f32, pixel.blue as f32])
+        })
+    }
+
+    // OpenEXR with 32 bit float channels, for tone mapping and compositing elsewhere.
+    // Nothing is clamped
+    #[cfg(feature = "image")]
+    pub fn save_exr(&self, path: &str) -> Result<(), String> {
+        self.to_rgb32f_image()
+            .save_with_format(path, image::ImageFormat::OpenExr)
+            .map_err(|e| format!("{}: {}", path, e))
+    }
+
+    // Radiance HDR, which shares an 8 bit exponent between the channels of a pixel,
+    // so it keeps about 1% precision. Negative values become 0
+    #[cfg(feature = "image")]
+    pub fn save_hdr(&self, path: &str) -> Result<(), String> {
+        let image = self.to_rgb32f_image();
+        let pixels: Vec<image::Rgb<f32>> = image.pixels().copied().collect();
+        std::fs::File::create(path)
+            .map_err(image::ImageError::IoError)
+            .and_then(|file| {
+                image::codecs::hdr::HdrEncoder::new(std::io::BufWriter::new(file)).encode(
+                    &pixels,
+                    self.width,
+                    self.height,
+                )
+            })
+            .map_err(|e| format!("{}: {}", path, e))
+    }
+
+    // The image resampled to the given size. Shrinking averages over the source
+    // pixels each new one covers, so rendering at twice the size and halving it
+    // antialiases the render
+    pub fn resize(&self, width: usize, height: usize, filter: Filter) -> Canvas {
+        assert!(width > 0 && height > 0 && self.width > 0 && self.height > 0);
+        let columns = filter.weights(self.width, width);
+        let rows = filter.weights(self.height, height);
+        let mut wide = Canvas::new(width, self.height);
+        for y in 0..self.height {
+            for (x, weights) in columns.iter().enumerate() {
+                let color = weights
+                    .iter()
+                    .map(|(from, weight)| self.get_pixel(*from, y) * *weight)
+                    .fold(Color::new(0., 0., 0.), |a, b| a + b);
+                wide.write_pixel(x, y, color);
+            }
+        }
+        let mut resized = Canvas::new(width, height);
+        for (y, weights) in rows.iter().enumerate() {
+            for x in 0..width {
+                let color = weights
+                    .iter()
+                    .map(|(from, weight)| wide.get_pixel(x, *from) * *weight)
+                    .fold(Color::new(0., 0., 0.), |a, b| a + b);
+                resized.write_pixel(x, y, color);
+            }
+        }
+        resized.metadata = self.metadata.clone();
+        resized.dither = self.dither;
+        resized
+    }
+
+    // Tiles several canvases into a grid with `cols` columns, separated and surrounded
+    // by `padding` black pixels. Cells are sized to fit the largest canvas and each
+    // canvas is centered within its cell
+    pub fn montage(canvases: &[Canvas], cols: usize, padding: usize) -> Canvas {
+        assert!(cols > 0);
+        let cols = cols.min(canvases.len().max(1));
+        let rows = canvases.len().div_ceil(cols);
+        let cell_width = canvases.iter().map(|c| c.width).max().unwrap_or(0);
+        let cell_height = canvases.iter().map(|c| c.height).max().unwrap_or(0);
+
+        let mut montage = Canvas::new(
+            cols * (cell_width + padding) + padding,
+            rows * (cell_height + padding) + padding,
+        );
+        for (index, canvas) in canvases.iter().enumerate() {
+            let left = padding + (index % cols) * (cell_width + padding);
+            let top = padding + (index / cols) * (cell_height + padding);
+            let left = left + (cell_width - canvas.width) / 2;
+            let top = top + (cell_height - canvas.height) / 2;
+            for y in 0..canvas.height {
+                for x in 0..canvas.width {
+                    montage.write_pixel(left + x, top + y, canvas.get_pixel(x, y));
+                }
+            }
+        }
+        montage
+    }
+
+    // Draws text with its top-left corner at (x, y) using the built-in bitmap font.
+    // Pixels falling outside the canvas are clipped
+    pub fn draw_text(&mut self, x: usize, y: usize, text: &str, color: Color) {
+        self.draw_text_scaled(x, y, text, color, 1);
+    }
+
+    pub fn draw_text_scaled(&mut self, x: usize, y: usize, text: &str, color: Color, scale: usize) {
+        for (line_index, line) in text.lines().enumerate() {
+            let top = y + line_index * font::LINE_HEIGHT * scale;
+            for (char_index, c) in line.chars().enumerate() {
+                let left = x + char_index * font::ADVANCE * scale;
+                for (row, bits) in font::glyph(c).iter().enumerate() {
+                    for col in 0..font::GLYPH_WIDTH {
+                        if bits & (1 << (font::GLYPH_WIDTH - 1 - col)) == 0 {
+                            continue;
+                        }
+                        for dy in 0..scale {
+                            for dx in 0..scale {
+                                let px = left + col * scale + dx;
+                                let py = top + row * scale + dy;
+                                if px < self.width && py < self.height {
+                                    self.write_pixel(px, py, color);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Burns a label into the bottom-left corner over a darkened backing so it stays
+    // legible regardless of the image underneath
+    pub fn stamp(&mut self, text: &str) {
+        let scale = (self.width / 400).max(1);
+        let margin = 2 * scale;
+        let (text_width, text_height) = font::text_size(text);
+        let (box_width, box_height) = (
+            text_width * scale + 2 * margin,
+            text_height * scale + 2 * margin,
+        );
+        let top = self.height.saturating_sub(box_height);
+        for y in top..self.height {
+            for x in 0..box_width.min(self.width) {
+                let darkened = self.get_pixel(x, y) * 0.25;
+                self.write_pixel(x, y, darkened);
+            }
+        }
+        self.draw_text_scaled(margin, top + margin, text, WHITE, scale);
+    }
+
+    // Heatmap of the per-pixel differences against another canvas, along with the
+    // mean squared error over all channels. Colors are clamped to [0, 1] first so
+    // that the comparison matches what would be written out
+    pub fn diff(&self, other: &Canvas) -> (Canvas, f64) {
+        assert_eq!(self.width, other.width);
+        assert_eq!(self.height, other.height);
+        let mut heatmap = Canvas::new(self.width, self.height);
+        let mut total = 0.;
+        for (index, (a, b)) in self.pixels.iter().zip(other.pixels.iter()).enumerate() {
+            let error = squared_error(a, b);
+            total += error;
+            // Largest possible per-pixel error is 1, so scale up to make small errors visible
+            heatmap.pixels[index] = heat((error * 3.).sqrt());
+        }
+        (heatmap, total / self.pixels.len() as f64)
+    }
+
+    pub fn mse(&self, other: &Canvas) -> f64 {
+        self.diff(other).1
+    }
+
+    // Peak signal-to-noise ratio in decibels. Identical canvases are infinitely similar
+    pub fn psnr(&self, other: &Canvas) -> f64 {
+        let mse = self.mse(other);
+        if mse == 0. {
+            f64::INFINITY
+        } else {
+            10. * (1. / mse).log10()
+        }
+    }
+
+    // Mean structural similarity over 8x8 windows of luminance, in [-1, 1]
+    pub fn ssim(&self, other: &Canvas) -> f64 {
+        self.ssim_of(other, Color::luminance)
+    }
+
+    // Structural similarity of the red, green and blue channels on their own, which
+    // catches changes of hue that leave the luminance alone
+    pub fn channel_ssim(&self, other: &Canvas) -> [f64; 3] {
+        [
+            self.ssim_of(other, |c| c.red),
+            self.ssim_of(other, |c| c.green),
+            self.ssim_of(other, |c| c.blue),
+        ]
+    }
+
+    fn ssim_of(&self, other: &Canvas, value: fn(&Color) -> f64) -> f64 {
+        assert_eq!(self.width, other.width);
+        assert_eq!(self.height, other.height);
+        const WINDOW: usize = 8;
+        const C1: f64 = 0.01 * 0.01;
+        const C2: f64 = 0.03 * 0.03;
+
+        let value_at = |canvas: &Canvas, x: usize, y: usize| {
+            let mut c = canvas.get_pixel(x, y);
+            c.clamp_unit();
+            value(&c)
+        };
+
+        let mut total = 0.;
+        let mut windows = 0;
+        for y0 in (0..self.height).step_by(WINDOW) {
+            for x0 in (0..self.width).step_by(WINDOW) {
+                let x1 = (x0 + WINDOW).min(self.width);
+                let y1 = (y0 + WINDOW).min(self.height);
+                let n = ((x1 - x0) * (y1 - y0)) as f64;
+
+                let (mut sum_a, mut sum_b) = (0., 0.);
+                for y in y0..y1 {
+                    for x in x0..x1 {
+                        sum_a += value_at(self, x, y);
+                        sum_b += value_at(other, x, y);
+                    }
+                }
+                let (mean_a, mean_b) = (sum_a / n, sum_b / n);
+
+                let (mut var_a, mut var_b, mut covariance) = (0., 0., 0.);
+                for y in y0..y1 {
+                    for x in x0..x1 {
+                        let da = value_at(self, x, y) - mean_a;
+                        let db = value_at(other, x, y) - mean_b;
+                        var_a += da * da;
+                        var_b += db * db;
+                        covariance += da * db;
+                    }
+                }
+                let (var_a, var_b, covariance) = (var_a / n, var_b / n, covariance / n);
+
+                total += ((2. * mean_a * mean_b + C1) * (2. * covariance + C2))
+                    / ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2));
+                windows += 1;
+            }
+        }
+        total / windows as f64
+    }
+
+    // Luminances of the pixels counted in `bins` equal bins from 0 to 1, with the
+    // pixels outside that range counted apart, since those are what come out black
+    // or blown out
+    pub fn histogram(&self, bins: usize) -> Histogram {
+        assert!(bins > 0);
+        let mut histogram = Histogram {
+            bins: vec![0; bins],
+            below: 0,
+            above: 0,
+        };
+        for pixel in self.pixels.iter() {
+            let luminance = pixel.luminance();
+            if luminance < 0. {
+                histogram.below += 1;
+            } else if luminance > 1. {
+                histogram.above += 1;
+            } else {
+                let bin = ((luminance * bins as f64) as usize).min(bins - 1);
+                histogram.bins[bin] += 1;
+            }
+        }
+        histogram
+    }
+
+    // The luminance that `percentile` percent of the pixels are no brighter than
+    pub fn luminance_percentile(&self, percentile: f64) -> f64 {
+        assert!((0. ..=100.).contains(&percentile) && !self.pixels.is_empty());
+        let mut luminances: Vec<f64> = self.pixels.iter().map(Color::luminance).collect();
+        luminances.sort_by(f64::total_cmp);
+        let rank = (percentile / 100. * (luminances.len() - 1) as f64).round() as usize;
+        luminances[rank]
+    }
+
+    // The factor to multiply the pixels by so that the luminance at `percentile`
+    // becomes mid-grey, e.g. 50 to expose for the median or 90 to keep highlights
+    // from clipping. Its log2 is the change to a camera's exposure in stops. Images
+    // that are black at the percentile are left alone
+    pub fn auto_exposure(&self, percentile: f64) -> f64 {
+        let luminance = self.luminance_percentile(percentile);
+        if luminance > 0. {
+            MID_GREY / luminance
+        } else {
+            1.
+        }
+    }
+
+    // Merges renders of the same view at different exposures, each given with the
+    // factor its radiance was scaled by, into one image. Every exposure is clipped to
+    // [0, 1] as it would be written out, and each channel trusts the exposures where
+    // it is furthest from black and from clipping. The merged radiance is then tone
+    // mapped with x / (1 + x), so highlights roll off instead of clipping
+    pub fn merge_exposures(exposures: &[(Canvas, f64)]) -> Canvas {
+        assert!(!exposures.is_empty());
+        let (first, _) = &exposures[0];
+        assert!(exposures
+            .iter()
+            .all(|(c, scale)| c.width == first.width && c.height == first.height && *scale > 0.));
+        // Channels clipped in every exposure fall back to the least exposed one
+        let (darkest, darkest_scale) = exposures.iter().min_by(|a, b| a.1.total_cmp(&b.1)).unwrap();
+
+        let merge = |index: usize, channel: fn(&Color) -> f64| {
+            let (mut total, mut weights) = (0., 0.);
+            for (canvas, scale) in exposures {
+                let value = channel(&canvas.pixels[index]).clamp(0., 1.);
+                let weight = 1. - (2. * value - 1.).abs();
+                total += weight * value / scale;
+                weights += weight;
+            }
+            let radiance = if weights > 0. {
+                total / weights
+            } else {
+                channel(&darkest.pixels[index]).clamp(0., 1.) / darkest_scale
+            };
+            radiance / (1. + radiance)
+        };
+
+        let mut merged = Canvas::new(first.width, first.height);
+        for index in 0..merged.pixels.len() {
+            merged.pixels[index] = Color::new(
+                merge(index, |c| c.red),
+                merge(index, |c| c.green),
+                merge(index, |c| c.blue),
+            );
+        }
+        merged
+    }
+
+    // Red-cyan anaglyph of a stereo pair: the red channel from the left eye's image
+    // and green and blue from the right eye's
+    pub fn anaglyph(left: &Canvas, right: &Canvas) -> Canvas {
+        assert!(left.width == right.width && left.height == right.height);
+        let mut canvas = Canvas::new(left.width, left.height);
+        for (pixel, (l, r)) in canvas
+            .pixels
+            .iter_mut()
+            .zip(left.pixels.iter().zip(&right.pixels))
+        {
+            *pixel = Color::new(l.red, r.green, r.blue);
+        }
+        canvas
+    }
+}
+
+fn squared_error(a: &Color, b: &Color) -> f64 {
+    let (mut a, mut b) = (*a, *b);
+    a.clamp_unit();
+    b.clamp_unit();
+    let d = a - b;
+    (d.red * d.red + d.green * d.green + d.blue * d.blue) / 3.
+}
+
+const ACCUMULATOR_MAGIC: &[u8] = b"RTACC1";
+
+// Running sums of the samples taken for each pixel, for images refined over several
+// passes. Pixels can have different numbers of samples
+#[derive(Debug, Clone, PartialEq)]
+pub struct Accumulator {
+    pub width: usize,
+    pub height: usize,
+    sums: Vec<Color>,
+    counts: Vec<usize>,
+}
+
+impl Accumulator {
+    pub fn new(width: usize, height: usize) -> Self {
+        Accumulator {
+            width,
+            height,
+            sums: vec![Color::new(0., 0., 0.); width * height],
+            counts: vec![0; width * height],
+        }
+    }
+
+    pub fn add(&mut self, x: usize, y: usize, color: Color) {
+        let index = y * self.width + x;
+        self.sums[index] = self.sums[index] + color;
+        self.counts[index] += 1;
+    }
+
+    // Adds one pass over the image in row major order, skipping missing samples
+    pub fn add_pass(&mut self, pass: impl IntoIterator<Item = Option<Color>>) {
+        for ((sum, count), color) in self.sums.iter_mut().zip(self.counts.iter_mut()).zip(pass) {
+            if let Some(color) = color {
+                *sum = *sum + color;
+                *count += 1;
+            }
+        }
+    }
+
+    pub fn samples(&self, x: usize, y: usize) -> usize {
+        self.counts[y * self.width + x]
+    }
+
+    // The fewest samples any pixel has
+    pub fn fewest(&self) -> usize {
+        self.counts.iter().copied().min().unwrap_or(0)
+    }
+
+    // Little endian binary: ACCUMULATOR_MAGIC, the width and height, then the red,
+    // green and blue sums and the sample count of each pixel
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = ACCUMULATOR_MAGIC.to_vec();
+        bytes.extend((self.width as u64).to_le_bytes());
+        bytes.extend((self.height as u64).to_le_bytes());
+        for (sum, count) in self.sums.iter().zip(&self.counts) {
+            for value in [sum.red, sum.green, sum.blue] {
+                bytes.extend(value.to_le_bytes());
+            }
+            bytes.extend((*count as u64).to_le_bytes());
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let rest = bytes.strip_prefix(ACCUMULATOR_MAGIC)?;
+        let mut words = rest
+            .chunks_exact(8)
+            .map(|word| <[u8; 8]>::try_from(word).unwrap());
+        let (width, height) = (
+            u64::from_le_bytes(words.next()?) as usize,
+            u64::from_le_bytes(words.next()?) as usize,
+        );
+        if rest.len() != 8 * (2 + 4 * width.checked_mul(height)?) {
+            return None;
+        }
+        let mut accumulator = Accumulator::new(width, height);
+        for (sum, count) in accumulator
+            .sums
+            .iter_mut()
+            .zip(accumulator.counts.iter_mut())
+        {
+            let [red, green, blue] = [0; 3].map(|_| f64::from_le_bytes(words.next().unwrap()));
+            *sum = Color::new(red, green, blue);
+            *count = u64::from_le_bytes(words.next().unwrap()) as usize;
+        }
+        Some(accumulator)
+    }
+
+    // Writes to a temporary file first and renames it over `path`, so a crash while
+    // saving leaves the previous checkpoint intact
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let temporary = format!("{}.tmp", path);
+        std::fs::write(&temporary, self.to_bytes())
+            .and_then(|_| std::fs::rename(&temporary, path))
+            .map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+        Accumulator::from_bytes(&bytes).ok_or_else(|| format!("{}: not an accumulator", path))
+    }
+
+    // The average of each pixel's samples, black where there are none
+    pub fn to_canvas(&self) -> Canvas {
+        let mut canvas = Canvas::new(self.width, self.height);
+        for (pixel, (sum, count)) in canvas
+            .pixels
+            .iter_mut()
+            .zip(self.sums.iter().zip(&self.counts))
+        {
+            if *count > 0 {
+                *pixel = *sum * (1. / *count as f64);
+            }
+        }
+        canvas
+    }
+}
+
+// Pixel counts by luminance, from Canvas::histogram
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    pub bins: Vec<usize>,
+    // Pixels darker than black and brighter than white
+    pub below: usize,
+    pub above: usize,
+}
+
+impl Histogram {
+    pub fn total(&self) -> usize {
+        self.bins.iter().sum::<usize>() + self.below + self.above
+    }
+
+    // The luminances the given bin counts, from the first up to the second
+    pub fn range(&self, bin: usize) -> (f64, f64) {
+        let width = 1. / self.bins.len() as f64;
+        (bin as f64 * width, (bin + 1) as f64 * width)
+    }
+
+    // Fraction of the pixels that are clipped to white when written out
+    pub fn clipped(&self) -> f64 {
+        self.above as f64 / self.total() as f64
+    }
+}
+
+// How Canvas::resize weighs the source pixels around each new one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    // The average of the pixels covered, or the nearest one when enlarging
+    Box,
+    // Blends neighbours linearly with distance, a tent around each new pixel
+    Bilinear,
+    // Windowed sinc reaching 3 pixels each way. The sharpest, though it can ring
+    // around hard edges
+    Lanczos,
+}
+
+impl Filter {
+    fn radius(&self) -> f64 {
+        match self {
+            Filter::Box => 0.5,
+            Filter::Bilinear => 1.,
+            Filter::Lanczos => 3.,
+        }
+    }
+
+    fn kernel(&self, x: f64) -> f64 {
+        let sinc = |x: f64| {
+            if x.abs() < 1e-8 {
+                1.
+            } else {
+                (PI * x).sin() / (PI * x)
+            }
+        };
+        match self {
+            Filter::Box if x.abs() <= 0.5 => 1.,
+            Filter::Bilinear => (1. - x.abs()).max(0.),
+            Filter::Lanczos if x.abs() < 3. => sinc(x) * sinc(x / 3.),
+            _ => 0.,
+        }
+    }
+
+    // For each of the `to` new pixels along an axis, the source pixels it draws on
+    // out of `from` and their weights, which add up to 1. When shrinking, the kernel
+    // is stretched to cover every source pixel
+    fn weights(&self, from: usize, to: usize) -> Vec<Vec<(usize, f64)>> {
+        let scale = from as f64 / to as f64;
+        let stretch = scale.max(1.);
+        let reach = self.radius() * stretch;
+        (0..to)
+            .map(|i| {
+                let centre = (i as f64 + 0.5) * scale - 0.5;
+                let first = (centre - reach).floor() as i64;
+                let last = (centre + reach).ceil() as i64;
+                let mut weights: Vec<(usize, f64)> = vec![];
+                for j in first..=last {
+                    let weight = self.kernel((j as f64 - centre) / stretch);
+                    if weight == 0. {
+                        continue;
+                    }
+                    // Past the edges, the edge pixels repeat
+                    let j = j.clamp(0, from as i64 - 1) as usize;
+                    match weights.iter_mut().find(|(k, _)| *k == j) {
+                        Some((_, w)) => *w += weight,
+                        None => weights.push((j, weight)),
+                    }
+                }
+                let total: f64 = weights.iter().map(|(_, w)| w).sum();
+                if total.abs() < 1e-12 {
+                    // A box narrower than the gap between samples
+                    let nearest = (centre.round().max(0.) as usize).min(from - 1);
+                    return vec![(nearest, 1.)];
+                }
+                weights.iter().map(|(j, w)| (*j, w / total)).collect()
+            })
+            .collect()
+    }
+}
+
+// The size shared by every frame of an animation
+#[cfg(any(feature = "png", feature = "gif"))]
+fn animation_size(frames: &[Canvas]) -> (usize, usize) {
+    assert!(!frames.is_empty());
+    let (width, height) = (frames[0].width, frames[0].height);
+    assert!(frames
+        .iter()
+        .all(|frame| frame.width == width && frame.height == height));
+    (width, height)
+}
+
+// Maps a value in [0, 1] onto a black -> red -> yellow -> white ramp
+pub(crate) fn heat(value: f64) -> Color {
+    let v = value.clamp(0., 1.) * 3.;
+    Color::new(v.min(1.), (v - 1.).clamp(0., 1.), (v - 2.).clamp(0., 1.))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::assert_approx_eq;
     #[test]
     fn canvas() {
         let c = Canvas::new(10, 20);
@@ -149,4 +1015,475 @@ mod tests {
         153 255 204 153 255 204 153 255 204 153 255 204 153\n"
         );
     }
+
+    #[test]
+    fn diff() {
+        let a = Canvas::new(4, 4);
+        let mut b = Canvas::new(4, 4);
+        let (heatmap, mse) = a.diff(&b);
+        assert_eq!(mse, 0.);
+        assert_eq!(a.psnr(&b), f64::INFINITY);
+        assert_eq!(heatmap.get_pixel(1, 2), Color::new(0., 0., 0.));
+
+        b.write_pixel(1, 2, Color::new(1., 1., 1.));
+        let (heatmap, mse) = a.diff(&b);
+        assert_eq!(mse, 1. / 16.);
+        assert_eq!(heatmap.get_pixel(1, 2), Color::new(1., 1., 1.));
+        assert_eq!(heatmap.get_pixel(0, 0), Color::new(0., 0., 0.));
+        assert!((a.psnr(&b) - 12.0412).abs() < 0.0001);
+
+        // Out of range values are clamped before comparing
+        b.write_pixel(1, 2, Color::new(5., 5., 5.));
+        assert_eq!(a.mse(&b), 1. / 16.);
+    }
+
+    #[test]
+    fn ssim() {
+        let mut a = Canvas::new(16, 16);
+        for x in 0..a.width {
+            for y in 0..a.height {
+                let v = (x + y) as f64 / 30.;
+                a.write_pixel(x, y, Color::new(v, v, v));
+            }
+        }
+        let mut b = Canvas::new(16, 16);
+        b.pixels = a.pixels.clone();
+        assert!((a.ssim(&b) - 1.).abs() < 1e-9);
+
+        b.write_pixel(3, 3, Color::new(1., 0., 0.));
+        assert!(a.ssim(&b) < 1.);
+        assert!(a.ssim(&Canvas::new(16, 16)) < a.ssim(&b));
+
+        // Red turned into green of the same luminance only shows per channel
+        let green = 0.2126 / 0.7152;
+        let red = a.pixels.iter().map(|c| Color::new(c.red, 0., 0.)).collect();
+        let mut b = Canvas::new(16, 16);
+        b.pixels = a
+            .pixels
+            .iter()
+            .map(|c| Color::new(0., c.red * green, 0.))
+            .collect();
+        a.pixels = red;
+        assert!((a.ssim(&b) - 1.).abs() < 1e-9);
+        let [r, g, blue] = a.channel_ssim(&b);
+        assert!(r < 0.5 && g < 0.5);
+        assert!((blue - 1.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn draw_text() {
+        let mut c = Canvas::new(20, 10);
+        c.draw_text(1, 1, "1", Color::new(1., 0., 0.));
+        // Top row of the '1' glyph is a single pixel in the middle column
+        assert_eq!(c.get_pixel(3, 1), Color::new(1., 0., 0.));
+        assert_eq!(c.get_pixel(1, 1), Color::new(0., 0., 0.));
+        // Bottom row is three pixels wide
+        assert_eq!(c.get_pixel(2, 7), Color::new(1., 0., 0.));
+        assert_eq!(c.get_pixel(4, 7), Color::new(1., 0., 0.));
+
+        // Text running off the edge is clipped rather than panicking
+        c.draw_text_scaled(15, 5, "WW\nW", WHITE, 2);
+        assert_eq!(c.get_pixel(15, 5), WHITE);
+    }
+
+    #[test]
+    fn stamp() {
+        let mut c = Canvas::new(40, 20);
+        for pixel in c.pixels.iter_mut() {
+            *pixel = Color::new(1., 1., 1.);
+        }
+        c.stamp("-");
+        // Backing is darkened and the dash is drawn on top of it
+        assert_eq!(c.get_pixel(0, 19), Color::new(0.25, 0.25, 0.25));
+        assert_eq!(c.get_pixel(2, 14), WHITE);
+        assert_eq!(c.get_pixel(39, 0), WHITE);
+    }
+
+    #[test]
+    fn montage() {
+        let mut a = Canvas::new(2, 2);
+        a.pixels = vec![Color::new(1., 0., 0.); 4];
+        let mut b = Canvas::new(2, 2);
+        b.pixels = vec![Color::new(0., 1., 0.); 4];
+        let mut c = Canvas::new(2, 1);
+        c.pixels = vec![Color::new(0., 0., 1.); 2];
+
+        let m = Canvas::montage(&[a, b, c], 2, 1);
+        assert_eq!(m.width, 7);
+        assert_eq!(m.height, 7);
+        assert_eq!(m.get_pixel(0, 0), Color::new(0., 0., 0.));
+        assert_eq!(m.get_pixel(1, 1), Color::new(1., 0., 0.));
+        assert_eq!(m.get_pixel(2, 2), Color::new(1., 0., 0.));
+        assert_eq!(m.get_pixel(3, 1), Color::new(0., 0., 0.));
+        assert_eq!(m.get_pixel(4, 1), Color::new(0., 1., 0.));
+        // The shorter canvas is centered vertically within its cell
+        assert_eq!(m.get_pixel(1, 4), Color::new(0., 0., 1.));
+        assert_eq!(m.get_pixel(1, 5), Color::new(0., 0., 0.));
+        assert_eq!(m.get_pixel(5, 5), Color::new(0., 0., 0.));
+    }
+
+    #[test]
+    fn read_ppm() {
+        let mut c = Canvas::new(30, 2);
+        c.write_pixel(0, 0, Color::new(1., 0.5, 0.));
+        c.write_pixel(29, 1, Color::new(0.2, 0.4, 1.));
+        c.metadata = Some(RenderMetadata::new(30, 2));
+        let read = Canvas::from_ppm(&c.to_ppm()).unwrap();
+        assert_eq!((read.width, read.height), (30, 2));
+        assert!(read.approx_eq_eps(&c, 0.5 / 255.));
+
+        assert!(Canvas::from_ppm("P6\n1 1\n255\n0 0 0\n").is_none());
+        assert!(Canvas::from_ppm("P3\n2 1\n255\n0 0 0\n").is_none());
+    }
+
+    #[test]
+    fn ppm_metadata() {
+        let mut c = Canvas::new(1, 1);
+        let mut metadata = RenderMetadata::new(1, 1);
+        metadata.scene = Some(String::from("glass"));
+        metadata.samples = 10;
+        metadata.seed = Some(42);
+        metadata.render_time = Duration::from_millis(1500);
+        c.metadata = Some(metadata);
+        assert_eq!(
+            c.to_ppm(),
+            format!(
+                "P3\n\
+                # scene: glass\n\
+                # resolution: 1x1\n\
+                # samples: 10\n\
+                # seed: 42\n\
+                # render_time: 1.500s\n\
+                # version: raytracer {}\n\
+                1 1\n\
+                255\n\
+                0 0 0\n",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+    }
+
+    #[test]
+    fn merge_exposures() {
+        // Radiance from deep shadow to a light source far brighter than white
+        let mut radiance = Canvas::new(3, 1);
+        radiance.pixels = vec![
+            Color::new(0.05, 0.05, 0.05),
+            Color::new(0.5, 0.25, 0.),
+            Color::new(6., 6., 6.),
+        ];
+        let expose = |scale: f64| {
+            let mut c = Canvas::new(3, 1);
+            c.pixels = radiance.pixels.iter().map(|p| p * scale).collect();
+            (c, scale)
+        };
+        let merged = Canvas::merge_exposures(&[expose(0.125), expose(1.), expose(4.)]);
+        for (merged, radiance) in merged.pixels.iter().zip(radiance.pixels.iter()) {
+            let tone = |x: f64| x / (1. + x);
+            let expected = Color::new(
+                tone(radiance.red),
+                tone(radiance.green),
+                tone(radiance.blue),
+            );
+            assert_approx_eq!(*merged, expected);
+        }
+
+        // Clipped in every exposure, the least exposed one is the best guess
+        let merged = Canvas::merge_exposures(&[expose(1.), expose(4.)]);
+        assert_approx_eq!(merged.get_pixel(2, 0).red, 0.5);
+    }
+
+    #[test]
+    fn histogram() {
+        let mut c = Canvas::new(4, 1);
+        c.pixels = vec![
+            Color::new(0.1, 0.1, 0.1),
+            Color::new(0.3, 0.3, 0.3),
+            Color::new(0.35, 0.35, 0.35),
+            Color::new(3., 3., 3.),
+        ];
+        let histogram = c.histogram(4);
+        assert_eq!(histogram.bins, vec![1, 2, 0, 0]);
+        assert_eq!((histogram.below, histogram.above), (0, 1));
+        assert_eq!(histogram.total(), 4);
+        assert_eq!(histogram.range(1), (0.25, 0.5));
+        assert_eq!(histogram.clipped(), 0.25);
+    }
+
+    #[test]
+    fn auto_exposure() {
+        let mut c = Canvas::new(5, 1);
+        for x in 0..5 {
+            c.write_pixel(x, 0, WHITE * (0.01 * (x + 1) as f64));
+        }
+        assert_approx_eq!(c.luminance_percentile(50.), 0.03);
+        assert_approx_eq!(c.luminance_percentile(100.), 0.05);
+        assert_approx_eq!(c.auto_exposure(50.), 6.);
+        // Exposing for the brightest pixel keeps it from clipping
+        let scale = c.auto_exposure(100.);
+        assert!(c
+            .pixels
+            .iter()
+            .all(|p| p.luminance() * scale <= MID_GREY + 1e-9));
+        assert_eq!(Canvas::new(2, 2).auto_exposure(50.), 1.);
+    }
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn image_buffers() {
+        let mut c = Canvas::new(3, 2);
+        c.write_pixel(0, 0, Color::new(1., 0.5, 0.));
+        c.write_pixel(2, 1, Color::new(1.5, -0.5, 0.2));
+        let rgba = c.to_rgba_image();
+        assert_eq!(rgba.dimensions(), (3, 2));
+        assert_eq!(rgba.get_pixel(0, 0).0, [255, 128, 0, 255]);
+        assert_eq!(rgba.get_pixel(2, 1).0, [255, 0, 51, 255]);
+
+        let back = Canvas::from_dynamic_image(&image::DynamicImage::ImageRgba8(rgba));
+        assert_eq!((back.width, back.height), (3, 2));
+        assert_approx_eq!(
+            back.get_pixel(0, 0),
+            Color::new(1., 0.5, 0.),
+            epsilon = 0.01
+        );
+        assert_approx_eq!(back.get_pixel(1, 1), Color::new(0., 0., 0.));
+
+        // Floating point images keep their range
+        let hdr = image::Rgb32FImage::from_pixel(1, 1, image::Rgb([4., 0.25, 0.]));
+        let c = Canvas::from_dynamic_image(&image::DynamicImage::ImageRgb32F(hdr));
+        assert_approx_eq!(c.get_pixel(0, 0), Color::new(4., 0.25, 0.));
+    }
+
+    #[test]
+    fn accumulator() {
+        let mut acc = Accumulator::new(2, 1);
+        acc.add_pass([Some(Color::new(1., 0., 0.)), None]);
+        acc.add_pass([Some(Color::new(0., 1., 0.)), Some(Color::new(0., 0., 1.))]);
+        acc.add(1, 0, Color::new(0., 0., 0.5));
+        assert_eq!(
+            (acc.samples(0, 0), acc.samples(1, 0), acc.fewest()),
+            (2, 2, 2)
+        );
+        let canvas = acc.to_canvas();
+        assert_eq!(canvas.get_pixel(0, 0), Color::new(0.5, 0.5, 0.));
+        assert_eq!(canvas.get_pixel(1, 0), Color::new(0., 0., 0.75));
+        assert_eq!(
+            Accumulator::new(1, 1).to_canvas().get_pixel(0, 0),
+            Color::new(0., 0., 0.)
+        );
+    }
+
+    #[test]
+    fn anaglyph() {
+        let mut left = Canvas::new(2, 1);
+        let mut right = Canvas::new(2, 1);
+        left.write_pixel(0, 0, Color::new(1., 0.5, 0.5));
+        right.write_pixel(1, 0, Color::new(0.5, 1., 0.25));
+        let anaglyph = Canvas::anaglyph(&left, &right);
+        assert_eq!(anaglyph.get_pixel(0, 0), Color::new(1., 0., 0.));
+        assert_eq!(anaglyph.get_pixel(1, 0), Color::new(0., 1., 0.25));
+    }
+
+    #[test]
+    fn accumulator_bytes() {
+        let mut acc = Accumulator::new(3, 2);
+        acc.add(2, 1, Color::new(0.1, 0.2, 0.3));
+        acc.add(2, 1, Color::new(1., 2., 3.));
+        let bytes = acc.to_bytes();
+        assert_eq!(Accumulator::from_bytes(&bytes), Some(acc));
+        assert_eq!(Accumulator::from_bytes(&bytes[..bytes.len() - 1]), None);
+        assert_eq!(Accumulator::from_bytes(b"P3\n1 1\n255\n0 0 0\n"), None);
+    }
+
+    #[test]
+    #[cfg(feature = "png")]
+    fn png() {
+        let mut c = Canvas::new(3, 2);
+        c.write_pixel(0, 0, Color::new(1.5, 0.5, -1.));
+        c.write_pixel(2, 1, Color::new(0., 0.2, 1.));
+        let mut metadata = RenderMetadata::new(3, 2);
+        metadata.seed = Some(42);
+        c.metadata = Some(metadata);
+
+        let path = std::env::temp_dir().join(format!("canvas-{}.png", std::process::id()));
+        let path = path.to_str().unwrap();
+        c.save_png(path).unwrap();
+        let decoder = png::Decoder::new(std::fs::File::open(path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let info = reader.info();
+        assert_eq!((info.width, info.height), (3, 2));
+        assert!(info
+            .uncompressed_latin1_text
+            .iter()
+            .any(|chunk| chunk.keyword == "seed" && chunk.text == "42"));
+        let mut data = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut data).unwrap();
+        assert_eq!(&data[..3], &[255, 128, 0]);
+        assert_eq!(&data[15..], &[0, 51, 255]);
+        std::fs::remove_file(path).unwrap();
+
+        assert!(c.save_png("/nonexistent/canvas.png").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn float_images() {
+        let mut c = Canvas::new(4, 3);
+        c.write_pixel(0, 0, Color::new(12.5, 0.25, 3.));
+        c.write_pixel(3, 2, Color::new(0.001, 100., 0.5));
+        for (extension, epsilon) in [("exr", 1e-6), ("hdr", 0.01)] {
+            let path =
+                std::env::temp_dir().join(format!("canvas-{}.{}", std::process::id(), extension));
+            let path = path.to_str().unwrap();
+            if extension == "exr" {
+                c.save_exr(path).unwrap();
+            } else {
+                c.save_hdr(path).unwrap();
+            }
+            let loaded = Canvas::load(path).unwrap();
+            std::fs::remove_file(path).unwrap();
+            assert_eq!((loaded.width, loaded.height), (4, 3));
+            // Relative to the brightest channel of each pixel
+            for (a, b) in loaded.pixels.iter().zip(c.pixels.iter()) {
+                let scale = b.red.max(b.green).max(b.blue).max(1.);
+                assert_approx_eq!(*a * (1. / scale), *b * (1. / scale), epsilon = epsilon);
+            }
+        }
+    }
+
+    #[test]
+    fn load() {
+        let mut c = Canvas::new(3, 2);
+        c.write_pixel(0, 0, Color::new(1., 0.2, 0.));
+        c.write_pixel(2, 1, Color::new(0.4, 0.6, 1.));
+        let path = std::env::temp_dir().join(format!("load-{}", std::process::id()));
+        let path = path.to_str().unwrap();
+        std::fs::write(path, c.to_ppm()).unwrap();
+        assert!(Canvas::load(path).unwrap().approx_eq_eps(&c, 0.5 / 255.));
+        #[cfg(feature = "png")]
+        {
+            c.save_png(path).unwrap();
+            let loaded = Canvas::load(path);
+            #[cfg(feature = "image")]
+            assert!(loaded.unwrap().approx_eq_eps(&c, 0.5 / 255.));
+            #[cfg(not(feature = "image"))]
+            assert!(loaded.is_err());
+        }
+        std::fs::write(path, "P3\n2 2\n255\n").unwrap();
+        assert!(Canvas::load(path).is_err());
+        std::fs::remove_file(path).unwrap();
+        assert!(Canvas::load(path).is_err());
+    }
+
+    #[cfg(any(feature = "png", feature = "gif"))]
+    fn frames() -> Vec<Canvas> {
+        (0..3)
+            .map(|i| {
+                let mut frame = Canvas::new(4, 2);
+                frame.write_pixel(i, 0, Color::new(1., 0., 0.));
+                frame
+            })
+            .collect()
+    }
+
+    #[test]
+    #[cfg(feature = "png")]
+    fn apng() {
+        let path = std::env::temp_dir().join(format!("animation-{}.png", std::process::id()));
+        let path = path.to_str().unwrap();
+        Canvas::save_apng(&frames(), 25., path).unwrap();
+        let decoder = png::Decoder::new(std::fs::File::open(path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let control = reader.info().animation_control.unwrap();
+        assert_eq!((control.num_frames, control.num_plays), (3, 0));
+        let mut data = vec![0; reader.output_buffer_size()];
+        for i in 0..3 {
+            reader.next_frame(&mut data).unwrap();
+            assert_eq!(&data[3 * i..3 * i + 3], &[255, 0, 0]);
+            assert_eq!(reader.info().frame_control.unwrap().delay_num, 40);
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "gif")]
+    fn gif() {
+        let path = std::env::temp_dir().join(format!("animation-{}.gif", std::process::id()));
+        let path = path.to_str().unwrap();
+        Canvas::save_gif(&frames(), 25., path).unwrap();
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options
+            .read_info(std::fs::File::open(path).unwrap())
+            .unwrap();
+        let mut count = 0;
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            assert_eq!((frame.width, frame.height, frame.delay), (4, 2, 4));
+            assert_eq!(&frame.buffer[4 * count..4 * count + 4], &[255, 0, 0, 255]);
+            count += 1;
+        }
+        assert_eq!(count, 3);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn dither() {
+        // A value a quarter of the way between two levels
+        let level = (100. + 0.25) / 255.;
+        let mut c = Canvas::new(4, 4);
+        c.pixels = vec![Color::new(level, level, level); 16];
+        assert!(c.to_rgb8().iter().all(|&v| v == 100));
+
+        c.dither = true;
+        let data = c.to_rgb8();
+        assert!(data.iter().all(|&v| v == 100 || v == 101));
+        // A quarter of the pixels go up, keeping the average
+        assert_eq!(data.iter().filter(|&&v| v == 101).count(), 3 * 4);
+        assert_eq!(
+            Canvas::from_ppm(&c.to_ppm()).unwrap().get_pixel(0, 0).red,
+            100. / 255.
+        );
+    }
+
+    #[test]
+    fn resize() {
+        // Halving a checkerboard averages it to grey
+        let mut c = Canvas::new(4, 4);
+        for y in 0..4 {
+            for x in 0..4 {
+                if (x + y) % 2 == 0 {
+                    c.write_pixel(x, y, Color::new(1., 1., 1.));
+                }
+            }
+        }
+        let small = c.resize(2, 2, Filter::Box);
+        assert_eq!((small.width, small.height), (2, 2));
+        for pixel in small.pixels.iter() {
+            assert_approx_eq!(*pixel, Color::new(0.5, 0.5, 0.5));
+        }
+        // Wider filters reach into the neighbouring pixels, where edges repeat
+        let small = c.resize(2, 2, Filter::Bilinear);
+        assert!(small.pixels.iter().all(|p| (p.red - 0.5).abs() < 0.1));
+
+        // Enlarging a flat image keeps it flat, whatever the filter
+        let mut flat = Canvas::new(3, 2);
+        flat.pixels = vec![Color::new(0.2, 0.4, 0.6); 6];
+        for filter in [Filter::Box, Filter::Bilinear, Filter::Lanczos] {
+            let big = flat.resize(7, 5, filter);
+            for pixel in big.pixels.iter() {
+                assert_approx_eq!(*pixel, Color::new(0.2, 0.4, 0.6));
+            }
+        }
+
+        // Box enlarging repeats pixels, bilinear blends between them
+        let mut ramp = Canvas::new(2, 1);
+        ramp.write_pixel(1, 0, Color::new(1., 1., 1.));
+        let boxed = ramp.resize(4, 1, Filter::Box);
+        assert_eq!(boxed.get_pixel(1, 0).red, 0.);
+        assert_eq!(boxed.get_pixel(2, 0).red, 1.);
+        let blended = ramp.resize(4, 1, Filter::Bilinear);
+        assert_approx_eq!(blended.get_pixel(1, 0).red, 0.25);
+        assert_approx_eq!(blended.get_pixel(2, 0).red, 0.75);
+        assert!(ramp.resize(4, 1, Filter::Lanczos).get_pixel(0, 0).red < 0.);
+    }
 }
@@ -1,5 +1,4 @@
-use crate::EPSILON;
-use float_cmp::approx_eq;
+use crate::approx::ApproxEq;
 use std::ops::{Add, Mul, Sub};
 
 #[derive(Debug, Copy, Clone)]
@@ -15,9 +14,20 @@ impl Color {
     }
 
     pub fn clamp(&mut self) {
-        self.red = self.red.max(0.).min(255.);
-        self.green = self.green.max(0.).min(255.);
-        self.blue = self.blue.max(0.).min(255.);
+        self.red = self.red.clamp(0., 255.);
+        self.green = self.green.clamp(0., 255.);
+        self.blue = self.blue.clamp(0., 255.);
+    }
+
+    pub fn clamp_unit(&mut self) {
+        self.red = self.red.clamp(0., 1.);
+        self.green = self.green.clamp(0., 1.);
+        self.blue = self.blue.clamp(0., 1.);
+    }
+
+    // Relative luminance using Rec. 709 weights
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.red + 0.7152 * self.green + 0.0722 * self.blue
     }
 }
 
@@ -69,7 +79,7 @@ impl Mul for Color {
     }
 }
 
-impl<'a> Mul<f64> for &'a Color {
+impl Mul<f64> for &Color {
     type Output = Color;
 
     fn mul(self, rhs: f64) -> Self::Output {
@@ -81,11 +91,17 @@ impl<'a> Mul<f64> for &'a Color {
     }
 }
 
+impl ApproxEq for Color {
+    fn approx_eq_eps(&self, other: &Self, epsilon: f64) -> bool {
+        self.red.approx_eq_eps(&other.red, epsilon)
+            && self.green.approx_eq_eps(&other.green, epsilon)
+            && self.blue.approx_eq_eps(&other.blue, epsilon)
+    }
+}
+
 impl PartialEq for Color {
     fn eq(&self, other: &Self) -> bool {
-        approx_eq!(f64, self.red, other.red, epsilon = EPSILON)
-            && approx_eq!(f64, self.green, other.green, epsilon = EPSILON)
-            && approx_eq!(f64, self.blue, other.blue, epsilon = EPSILON)
+        self.approx_eq(other)
     }
 }
 
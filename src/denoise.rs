@@ -0,0 +1,133 @@
+// Cleans up the noise of low sample renders with Intel Open Image Denoise, guided by
+// the normal and albedo AOVs, which are smooth even when the beauty image isn't.
+// Needs the OIDN library, found through OIDN_DIR or pkg-config when building with
+// the `oidn` feature
+use crate::{
+    camera::{Aov, Camera},
+    canvas::Canvas,
+    color::Color,
+    world::World,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Denoiser {
+    // Whether the normal and albedo are free of noise, letting OIDN trust them fully.
+    // They are for renders whose only randomness is the position of each sample in
+    // its pixel, but not with depth of field
+    pub clean_aux: bool,
+}
+
+// The canvas as the packed RGB floats OIDN works on
+fn to_buffer(canvas: &Canvas, channel: impl Fn(f64) -> f64) -> Vec<f32> {
+    canvas
+        .pixels
+        .iter()
+        .flat_map(|c| [c.red, c.green, c.blue])
+        .map(|value| channel(value) as f32)
+        .collect()
+}
+
+impl Denoiser {
+    // The AOVs are as render_with_aovs fills Aov::Normal and Aov::Albedo. The beauty
+    // image is taken to be linear HDR, as renders are before they're saved
+    pub fn denoise(
+        &self,
+        beauty: &Canvas,
+        normal: &Canvas,
+        albedo: &Canvas,
+    ) -> Result<Canvas, String> {
+        let (width, height) = (beauty.width, beauty.height);
+        assert!(normal.width == width && normal.height == height);
+        assert!(albedo.width == width && albedo.height == height);
+
+        let color = to_buffer(beauty, |c| c);
+        // OIDN wants albedos in 0..1 and normals in -1..1
+        let albedo = to_buffer(albedo, |a| a.clamp(0., 1.));
+        let normal = to_buffer(normal, |n| n.clamp(-1., 1.));
+        let mut output = vec![0.; color.len()];
+
+        let device = oidn::Device::new().map_err(|e| format!("can't start OIDN: {}", e))?;
+        let mut filter = oidn::RayTracing::try_new(&device)
+            .map_err(|e| format!("can't create the OIDN filter: {}", e))?;
+        filter
+            .hdr(true)
+            .clean_aux(self.clean_aux)
+            .albedo_normal(&albedo, &normal)
+            .image_dimensions(width, height);
+        filter
+            .filter(&color, &mut output)
+            .map_err(|e| format!("OIDN failed: {}", e))?;
+
+        let mut denoised = Canvas::new(width, height);
+        for (pixel, rgb) in denoised.pixels.iter_mut().zip(output.chunks_exact(3)) {
+            *pixel = Color::new(rgb[0] as f64, rgb[1] as f64, rgb[2] as f64);
+        }
+        denoised.metadata = beauty.metadata.clone();
+        Ok(denoised)
+    }
+}
+
+impl Camera {
+    // A render with its normal and albedo AOVs, denoised with them
+    pub fn render_denoised(&self, world: &World, denoiser: &Denoiser) -> Result<Canvas, String> {
+        let (beauty, aovs) = self.render_with_aovs(world, &[Aov::Normal, Aov::Albedo]);
+        denoiser.denoise(&beauty, &aovs[0], &aovs[1])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        camera::SuperSamplingMode, color::BLACK, matrix::Matrix, rng::XorShift, tuple::Tuple, PI,
+    };
+
+    #[test]
+    fn smooths_noise() {
+        // A flat, evenly lit wall with noise on top
+        let (width, height) = (32, 32);
+        let mut beauty = Canvas::new(width, height);
+        let mut normal = Canvas::new(width, height);
+        let mut albedo = Canvas::new(width, height);
+        let color = Color::new(0.8, 0.4, 0.2);
+        let mut generator = XorShift::new(3);
+        for y in 0..height {
+            for x in 0..width {
+                beauty.write_pixel(x, y, color * (0.5 * generator.range(0.5, 1.5)));
+                albedo.write_pixel(x, y, color);
+                normal.write_pixel(x, y, Color::new(0., 0., -1.));
+            }
+        }
+        let denoised = Denoiser::default()
+            .denoise(&beauty, &normal, &albedo)
+            .unwrap();
+
+        let error = |canvas: &Canvas| {
+            canvas
+                .pixels
+                .iter()
+                .map(|c| {
+                    let d = *c - color * 0.5;
+                    d.red * d.red + d.green * d.green + d.blue * d.blue
+                })
+                .sum::<f64>()
+        };
+        assert!(error(&denoised) < error(&beauty) / 4.);
+    }
+
+    #[test]
+    fn render_denoised() {
+        let w = World::default();
+        let mut c = Camera::new(11, 11, PI / 2., SuperSamplingMode::Stochastic);
+        c.transform = Matrix::view_transform(
+            Tuple::point(0., 0., -5.),
+            Tuple::point(0., 0., 0.),
+            Tuple::vector(0., 1., 0.),
+        );
+        c.sampling.seed = Some(1);
+        let denoised = c.render_denoised(&w, &Denoiser::default()).unwrap();
+        assert_eq!((denoised.width, denoised.height), (11, 11));
+        assert_eq!(denoised.get_pixel(0, 0), BLACK);
+        assert!(denoised.metadata.is_some());
+    }
+}
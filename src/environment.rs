@@ -0,0 +1,304 @@
+// Light arriving from infinitely far away, stored as a latitude-longitude
+// (equirectangular) image. The top row looks straight up along +y, the bottom row
+// straight down, and the centre of the image looks along +z
+use crate::{canvas::Canvas, color::Color, rng::Sampler, tuple::Tuple, PI};
+
+// Size of the table of diffuse light arriving at surfaces facing each direction
+const IRRADIANCE_WIDTH: usize = 32;
+const IRRADIANCE_HEIGHT: usize = 16;
+// The map is averaged down to this size before integrating it for the table
+const SAMPLE_WIDTH: usize = 64;
+const SAMPLE_HEIGHT: usize = 32;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Environment {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+    // Multiplies every value of the map
+    pub intensity: f64,
+    irradiance: Vec<Color>,
+}
+
+// Where a direction lands on the map, from 0 to 1 across and down
+fn to_map(direction: Tuple) -> (f64, f64) {
+    let d = direction.normalize();
+    let u = 0.5 + d.x.atan2(d.z) / (2. * PI);
+    let v = d.y.clamp(-1., 1.).acos() / PI;
+    (u, v)
+}
+
+// The direction a point of the map looks in
+pub(crate) fn from_map(u: f64, v: f64) -> Tuple {
+    let (theta, phi) = (v * PI, (u - 0.5) * 2. * PI);
+    Tuple::vector(
+        theta.sin() * phi.sin(),
+        theta.cos(),
+        theta.sin() * phi.cos(),
+    )
+}
+
+// Bilinear lookup in a lat-long grid, wrapping around horizontally
+fn sample(pixels: &[Color], width: usize, height: usize, u: f64, v: f64) -> Color {
+    let x = u * width as f64 - 0.5;
+    let y = (v * height as f64 - 0.5).clamp(0., (height - 1) as f64);
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let column = |x: f64| (x as i64).rem_euclid(width as i64) as usize;
+    let (left, right) = (column(x0), column(x0 + 1.));
+    let (top, bottom) = (y0 as usize, (y0 as usize + 1).min(height - 1));
+    let at = |x: usize, y: usize| pixels[y * width + x];
+    let upper = at(left, top) * (1. - fx) + at(right, top) * fx;
+    let lower = at(left, bottom) * (1. - fx) + at(right, bottom) * fx;
+    upper * (1. - fy) + lower * fy
+}
+
+impl Environment {
+    pub fn new(map: &Canvas) -> Self {
+        assert!(map.width > 0 && map.height > 0);
+        let mut environment = Environment {
+            width: map.width,
+            height: map.height,
+            pixels: map.pixels.clone(),
+            intensity: 1.,
+            irradiance: vec![],
+        };
+        environment.irradiance = environment.integrate();
+        environment
+    }
+
+    // A map of the given size filled with the color seen in each pixel's direction
+    pub fn from_fn(width: usize, height: usize, radiance: impl Fn(Tuple) -> Color) -> Self {
+        let mut map = Canvas::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let u = (x as f64 + 0.5) / width as f64;
+                let v = (y as f64 + 0.5) / height as f64;
+                map.write_pixel(x, y, radiance(from_map(u, v)));
+            }
+        }
+        Environment::new(&map)
+    }
+
+    // The same color in every direction
+    pub fn uniform(color: Color) -> Self {
+        let mut map = Canvas::new(1, 1);
+        map.write_pixel(0, 0, color);
+        Environment::new(&map)
+    }
+
+    // Any image Canvas::load reads, including Radiance HDR and OpenEXR files whose
+    // values go above 1
+    pub fn load(path: &str) -> Result<Self, String> {
+        Ok(Environment::new(&Canvas::load(path)?))
+    }
+
+    // Light arriving from the given direction
+    pub fn radiance(&self, direction: Tuple) -> Color {
+        let (u, v) = to_map(direction);
+        sample(&self.pixels, self.width, self.height, u, v) * self.intensity
+    }
+
+    // Light a white matte surface facing the given direction scatters, i.e. the
+    // cosine weighted average of the light over the hemisphere it can see
+    pub fn diffuse(&self, normal: Tuple) -> Color {
+        let (u, v) = to_map(normal);
+        sample(&self.irradiance, IRRADIANCE_WIDTH, IRRADIANCE_HEIGHT, u, v) * self.intensity
+    }
+
+    fn integrate(&self) -> Vec<Color> {
+        // The map averaged down, with the solid angle each sample covers. Maps smaller
+        // than the samples repeat their pixels
+        let span = |i: usize, count: usize, size: usize| {
+            let start = i * size / count;
+            (start, ((i + 1) * size / count).max(start + 1))
+        };
+        let mut samples = vec![];
+        for row in 0..SAMPLE_HEIGHT {
+            let v = (row as f64 + 0.5) / SAMPLE_HEIGHT as f64;
+            let solid_angle =
+                (2. * PI / SAMPLE_WIDTH as f64) * (PI / SAMPLE_HEIGHT as f64) * (v * PI).sin();
+            for column in 0..SAMPLE_WIDTH {
+                let u = (column as f64 + 0.5) / SAMPLE_WIDTH as f64;
+                let (x0, x1) = span(column, SAMPLE_WIDTH, self.width);
+                let (y0, y1) = span(row, SAMPLE_HEIGHT, self.height);
+                let mut sum = Color::new(0., 0., 0.);
+                for y in y0..y1 {
+                    for x in x0..x1 {
+                        sum = sum + self.pixels[y * self.width + x];
+                    }
+                }
+                let count = ((x1 - x0) * (y1 - y0)) as f64;
+                samples.push((from_map(u, v), sum * (solid_angle / count)));
+            }
+        }
+
+        let mut irradiance = Vec::with_capacity(IRRADIANCE_WIDTH * IRRADIANCE_HEIGHT);
+        for row in 0..IRRADIANCE_HEIGHT {
+            for column in 0..IRRADIANCE_WIDTH {
+                let normal = from_map(
+                    (column as f64 + 0.5) / IRRADIANCE_WIDTH as f64,
+                    (row as f64 + 0.5) / IRRADIANCE_HEIGHT as f64,
+                );
+                let total = samples
+                    .iter()
+                    .map(|(direction, light)| *light * direction.dot(&normal).max(0.))
+                    .fold(Color::new(0., 0., 0.), |a, b| a + b);
+                irradiance.push(total * (1. / PI));
+            }
+        }
+        irradiance
+    }
+}
+
+// A window or other opening the environment shines into an interior through, as
+// the parallelogram spanned by two edges from a corner. While a world has portals,
+// surfaces see the environment only through them, with `samples` shadow rays to
+// each, so rooms are lit from their windows and the walls around them shade it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Portal {
+    pub corner: Tuple,
+    pub edge1: Tuple,
+    pub edge2: Tuple,
+    pub samples: usize,
+}
+
+impl Portal {
+    pub fn new(corner: Tuple, edge1: Tuple, edge2: Tuple) -> Self {
+        assert!(corner.is_point() && edge1.is_vector() && edge2.is_vector());
+        assert!(edge1.cross(&edge2).magnitude() > 0.);
+        Portal {
+            corner,
+            edge1,
+            edge2,
+            samples: 16,
+        }
+    }
+
+    pub fn area(&self) -> f64 {
+        self.edge1.cross(&self.edge2).magnitude()
+    }
+
+    pub fn normal(&self) -> Tuple {
+        self.edge1.cross(&self.edge2).normalize()
+    }
+
+    // Points spread evenly over the portal with an offset drawn from the sampler in
+    // each cell of a grid
+    pub fn sample_points(&self, sampler: &mut Sampler) -> Vec<Tuple> {
+        let side = ((self.samples as f64).sqrt().ceil() as usize).max(1);
+        let mut points = Vec::with_capacity(side * side);
+        for i in 0..side {
+            for j in 0..side {
+                let a = (i as f64 + sampler.random()) / side as f64;
+                let b = (j as f64 + sampler.random()) / side as f64;
+                points.push(self.corner + self.edge1 * a + self.edge2 * b);
+            }
+        }
+        points
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_approx_eq;
+
+    #[test]
+    fn directions() {
+        for d in [
+            Tuple::vector(0., 0., 1.),
+            Tuple::vector(1., 0., 0.),
+            Tuple::vector(0.3, -0.8, -0.5).normalize(),
+        ] {
+            let (u, v) = to_map(d);
+            assert_approx_eq!(from_map(u, v), d);
+        }
+        assert_eq!(to_map(Tuple::vector(0., 0., 1.)), (0.5, 0.5));
+        assert_eq!(to_map(Tuple::vector(0., 1., 0.)).1, 0.);
+    }
+
+    #[test]
+    fn sky_and_ground() {
+        // A bright upper half over a black lower half
+        let mut map = Canvas::new(8, 4);
+        for x in 0..8 {
+            for y in 0..2 {
+                map.write_pixel(x, y, Color::new(1., 1., 1.));
+            }
+        }
+        let mut environment = Environment::new(&map);
+        assert_eq!(
+            environment.radiance(Tuple::vector(0., 1., 0.)),
+            Color::new(1., 1., 1.)
+        );
+        assert_eq!(
+            environment.radiance(Tuple::vector(0., -1., 0.)),
+            Color::new(0., 0., 0.)
+        );
+
+        // Upward surfaces see all of the sky, sideways ones half of it
+        assert_approx_eq!(
+            environment.diffuse(Tuple::vector(0., 1., 0.)).red,
+            1.,
+            epsilon = 0.02
+        );
+        assert_approx_eq!(
+            environment.diffuse(Tuple::vector(1., 0., 0.)).red,
+            0.5,
+            epsilon = 0.05
+        );
+        assert_approx_eq!(
+            environment.diffuse(Tuple::vector(0., -1., 0.)).red,
+            0.,
+            epsilon = 0.02
+        );
+
+        environment.intensity = 2.;
+        assert_eq!(
+            environment.radiance(Tuple::vector(0., 1., 0.)),
+            Color::new(2., 2., 2.)
+        );
+    }
+
+    #[test]
+    fn from_fn() {
+        let environment = Environment::from_fn(16, 8, |d| Color::new(d.y.max(0.), 0., 0.));
+        assert_approx_eq!(
+            environment.radiance(Tuple::vector(0., 1., 0.)).red,
+            1.,
+            epsilon = 0.05
+        );
+        assert_eq!(environment.radiance(Tuple::vector(1., -1., 0.)).red, 0.);
+    }
+
+    #[test]
+    fn portal() {
+        let portal = Portal::new(
+            Tuple::point(0., 0., 0.),
+            Tuple::vector(2., 0., 0.),
+            Tuple::vector(0., 3., 0.),
+        );
+        assert_eq!(portal.area(), 6.);
+        assert_eq!(portal.normal(), Tuple::vector(0., 0., 1.));
+        let points = portal.sample_points(&mut Sampler::Unseeded);
+        assert_eq!(points.len(), 16);
+        for p in points {
+            assert!((0. ..=2.).contains(&p.x) && (0. ..=3.).contains(&p.y) && p.z == 0.);
+        }
+    }
+
+    #[test]
+    fn uniform() {
+        let environment = Environment::uniform(Color::new(0.5, 0.25, 1.));
+        assert_eq!(
+            environment.radiance(Tuple::vector(1., 2., 3.)),
+            Color::new(0.5, 0.25, 1.)
+        );
+        assert_approx_eq!(
+            environment.diffuse(Tuple::vector(0., 0., -1.)),
+            Color::new(0.5, 0.25, 1.),
+            epsilon = 0.01
+        );
+    }
+}
@@ -1,9 +1,13 @@
-use std::ops::Add;
+use smallvec::SmallVec;
+use std::{borrow::Cow, ops::Add};
 
 use crate::{
-    color::{Color, BLACK},
+    color::{Color, BLACK, WHITE},
+    material::Material,
     ray::Ray,
+    rng::Sampler,
     shape::Object,
+    stats::Counter,
     tuple::Tuple,
     world::World,
     EPSILON,
@@ -16,10 +20,14 @@ pub struct Intersection<'a> {
     pub object: &'a Object,
 }
 
+// Most rays hit only a handful of surfaces, so keep that many inline and only
+// spill onto the heap for longer lists
+pub type Intersections<'a> = SmallVec<[Intersection<'a>; 4]>;
+
 // A list of intersections
 #[derive(Debug)]
 pub struct IntersectionList<'a> {
-    pub intersections: Vec<Intersection<'a>>,
+    pub intersections: Intersections<'a>,
 }
 
 // Contexts assosciated with an intersection
@@ -36,6 +44,11 @@ pub struct IntersectionContext<'a> {
     pub under_point: Tuple,
     pub n1: f64,
     pub n2: f64,
+    // The channel the ray carries, if dispersion has split it
+    pub channel: Option<usize>,
+    // The n1 and n2 each color channel sees, when the ray is still white and either
+    // side of the surface disperses light
+    pub dispersed: Option<[(f64, f64); 3]>,
 }
 
 impl<'a> Intersection<'a> {
@@ -43,7 +56,7 @@ impl<'a> Intersection<'a> {
         Self { t, object }
     }
 
-    pub fn context(&'a self, ray: &Ray, xs: Option<&IntersectionList>) -> IntersectionContext {
+    pub fn context(&'a self, ray: &Ray, xs: Option<&IntersectionList>) -> IntersectionContext<'a> {
         let point = ray.position(self.t);
         let eye_vector = -ray.direction;
         let inside = self.object.normal_at(point).dot(&eye_vector) < 0.;
@@ -58,16 +71,16 @@ impl<'a> Intersection<'a> {
 
         let mut n1 = 0.;
         let mut n2 = 0.;
+        let (mut n1s, mut n2s) = ([0.; 3], [0.; 3]);
+        let indices =
+            |containers: &[&Object]| [0, 1, 2].map(|c| refractive_index(containers, Some(c)));
 
         if let Some(xs) = xs {
             let mut containers: Vec<&Object> = vec![];
             for i in xs.intersections.iter() {
                 if i == self {
-                    if containers.len() == 0 {
-                        n1 = 1.;
-                    } else {
-                        n1 = containers.last().unwrap().material.refractive_index;
-                    }
+                    n1 = refractive_index(&containers, ray.channel);
+                    n1s = indices(&containers);
                 }
 
                 let index = containers
@@ -80,15 +93,18 @@ impl<'a> Intersection<'a> {
                 }
 
                 if i == self {
-                    if containers.len() == 0 {
-                        n2 = 1.;
-                    } else {
-                        n2 = containers.last().unwrap().material.refractive_index;
-                    }
+                    n2 = refractive_index(&containers, ray.channel);
+                    n2s = indices(&containers);
                     break;
                 }
             }
         }
+        let uniform = |n: [f64; 3]| n[0] == n[1] && n[1] == n[2];
+        let dispersed = if ray.channel.is_none() && !(uniform(n1s) && uniform(n2s)) {
+            Some([0, 1, 2].map(|c| (n1s[c], n2s[c])))
+        } else {
+            None
+        };
 
         IntersectionContext {
             t: self.t,
@@ -102,60 +118,168 @@ impl<'a> Intersection<'a> {
             under_point,
             n1,
             n2,
+            channel: ray.channel,
+            dispersed,
         }
     }
 }
 
+// The medium a ray is travelling through is the container with the highest priority,
+// preferring the most recently entered one on ties
+fn current_medium<'a>(containers: &[&'a Object]) -> Option<&'a Object> {
+    containers
+        .iter()
+        .copied()
+        .max_by_key(|object| object.media_priority)
+}
+
+fn refractive_index(containers: &[&Object], channel: Option<usize>) -> f64 {
+    match current_medium(containers) {
+        None => 1.,
+        Some(object) => object.material.refractive_index_for(channel),
+    }
+}
+
 impl<'a> IntersectionContext<'a> {
-    pub fn reflected_color(&self, world: &World, remaining: u8) -> Color {
-        if self.object.material.reflective == 0. || remaining == 0 {
+    pub fn reflected_color(&self, world: &World, remaining: u8, sampler: &mut Sampler) -> Color {
+        let reflective = self.material().reflective;
+        if reflective == 0. || remaining == 0 {
             BLACK
         } else {
-            let reflect_ray = Ray::new(self.over_point, self.reflect_vector);
-            reflect_ray.color_hit(world, remaining - 1) * self.object.material.reflective
+            let mut reflect_ray = Ray::new(self.over_point, self.reflect_vector);
+            reflect_ray.channel = self.channel;
+            world.count(Counter::SecondaryRays, 1);
+            reflect_ray.color_hit_sampled(world, remaining - 1, sampler) * reflective
         }
     }
 
-    pub fn refracted_color(&self, world: &World, remaining: u8) -> Color {
-        if self.object.material.transparency == 0. || remaining == 0 {
-            BLACK
-        } else {
-            let n_ratio = self.n1 / self.n2;
-            let cos_i = self.eye_vector.dot(&self.normal_vector);
-            let sin2_t = n_ratio * n_ratio * (1. - cos_i * cos_i);
-            if sin2_t > 1. {
-                return BLACK;
+    pub fn refracted_color(&self, world: &World, remaining: u8, sampler: &mut Sampler) -> Color {
+        let transparency = self.material().transparency;
+        if transparency == 0. || remaining == 0 {
+            return BLACK;
+        }
+        match self.dispersed {
+            // Each channel bends by its own amount, and is followed on its own from here
+            Some(indices) => {
+                let [red, green, blue] = [0, 1, 2].map(|c| {
+                    let (n1, n2) = indices[c];
+                    self.refract(world, remaining, sampler, n1, n2, Some(c))
+                });
+                Color::new(red.red, green.green, blue.blue) * transparency
+            }
+            None => {
+                self.refract(world, remaining, sampler, self.n1, self.n2, self.channel)
+                    * transparency
             }
-
-            let cos_t = (1.0 - sin2_t).sqrt();
-            let direction =
-                self.normal_vector * (n_ratio * cos_i - cos_t) - self.eye_vector * n_ratio;
-            let refracted_ray = Ray::new(self.under_point, direction);
-            refracted_ray.color_hit(world, remaining - 1) * self.object.material.transparency
         }
     }
 
-    pub fn shade_hit(&self, world: &World, remaining: u8) -> Color {
-        assert_eq!(world.lights.len(), 1);
-        let in_shadow = world.is_shadowed(self.over_point);
-        let surface = self.object.material.lighting(
-            &world.lights[0],
-            self.object,
-            self.over_point,
-            self.eye_vector,
-            self.normal_vector,
-            in_shadow,
-        );
+    fn refract(
+        &self,
+        world: &World,
+        remaining: u8,
+        sampler: &mut Sampler,
+        n1: f64,
+        n2: f64,
+        channel: Option<usize>,
+    ) -> Color {
+        let n_ratio = n1 / n2;
+        let cos_i = self.eye_vector.dot(&self.normal_vector);
+        let sin2_t = n_ratio * n_ratio * (1. - cos_i * cos_i);
+        if sin2_t > 1. {
+            return BLACK;
+        }
 
-        let reflected = self.reflected_color(world, remaining);
-        let refracted = self.refracted_color(world, remaining);
+        let cos_t = (1.0 - sin2_t).sqrt();
+        let direction = self.normal_vector * (n_ratio * cos_i - cos_t) - self.eye_vector * n_ratio;
+        let mut refracted_ray = Ray::new(self.under_point, direction);
+        refracted_ray.channel = channel;
+        world.count(Counter::SecondaryRays, 1);
+        refracted_ray.color_hit_sampled(world, remaining - 1, sampler)
+    }
 
-        let material = &self.object.material;
-        if material.reflective > 0. && material.transparency > 0. {
-            let reflectance = self.schlick();
-            surface + reflected * reflectance + refracted * (1. - reflectance)
-        } else {
-            surface + reflected + refracted
+    // The object's material as it is at the hit, with any maps applied
+    fn material(&self) -> Cow<'a, Material> {
+        self.object.material.at(self.object, self.over_point)
+    }
+
+    pub fn shade_hit(&self, world: &World, remaining: u8, sampler: &mut Sampler) -> Color {
+        let surface = world
+            .lights
+            .iter()
+            .filter(|light| light.illuminates(self.object))
+            .map(|light| {
+                let visibility = world.visibility(light, self.over_point, sampler);
+                self.object.material.lighting_in(
+                    world.shading_precision,
+                    light,
+                    self.object,
+                    self.over_point,
+                    self.eye_vector,
+                    self.normal_vector,
+                    visibility,
+                )
+            })
+            .fold(BLACK, |a, b| a + b);
+        // The environment lights the surface like the ambient term of a light
+        // coming from everywhere
+        let surface = match &world.environment {
+            Some(_) => {
+                let material = self.material();
+                surface
+                    + material.color_at(self.object, self.over_point)
+                        * world.environment_diffuse(self.over_point, self.normal_vector, sampler)
+                        * material.ambient
+            }
+            None => surface,
+        };
+        // Fill light shows the surface's own color, whatever the material's ambient
+        let surface = match &world.ambient_light {
+            Some(fill) => {
+                surface
+                    + self.object.material.color_at(self.object, self.over_point)
+                        * fill.intensity_at(self.normal_vector)
+            }
+            None => surface,
+        };
+        let surface = surface + self.object.material.emission;
+
+        let reflected = self.reflected_color(world, remaining, sampler);
+        let refracted = self.refracted_color(world, remaining, sampler);
+
+        let material = self.material();
+        // The film's interference reflectance between the media on either side of
+        // the surface. Contexts made without the intersections don't know them, and
+        // are taken to be entering the material from a vacuum
+        let film = material.thin_film.as_ref().map(|film| {
+            let (n1, n2) = match (self.n1, self.n2) {
+                (n1, n2) if n1 > 0. && n2 > 0. => (n1, n2),
+                _ => (1., material.refractive_index),
+            };
+            film.reflectance(self.eye_vector.dot(&self.normal_vector), n1, n2)
+        });
+        let glass = material.reflective > 0. && material.transparency > 0.;
+        match film {
+            // On glass it takes the place of the Schlick term
+            Some(reflectance) if glass => {
+                surface + reflected * reflectance + refracted * (WHITE - reflectance)
+            }
+            // Opaque mirrors keep the strength `reflective` gives them in the channel
+            // the film reflects most, and the film's colors in the others
+            Some(reflectance) if material.transparency == 0. => {
+                let strongest = reflectance.red.max(reflectance.green).max(reflectance.blue);
+                let tint = if strongest > 0. {
+                    reflectance * (1. / strongest)
+                } else {
+                    BLACK
+                };
+                surface + reflected * tint + refracted
+            }
+            _ if glass => {
+                let reflectance = self.schlick();
+                surface + reflected * reflectance + refracted * (1. - reflectance)
+            }
+            _ => surface + reflected + refracted,
         }
     }
 
@@ -174,7 +298,7 @@ impl<'a> IntersectionContext<'a> {
 
         let r0 = (self.n1 - self.n2) / (self.n1 + self.n2);
         let r0 = r0 * r0;
-        return r0 + (1. - r0) * (1. - cos).powf(5.);
+        r0 + (1. - r0) * (1. - cos).powf(5.)
     }
 }
 
@@ -188,29 +312,60 @@ impl<'a> Eq for Intersection<'a> {}
 
 impl<'a> PartialOrd for Intersection<'a> {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.t.partial_cmp(&other.t)
+        Some(self.cmp(other))
     }
 }
 
 impl<'a> Ord for Intersection<'a> {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.partial_cmp(&other).unwrap()
+        self.t.partial_cmp(&other.t).unwrap()
     }
 }
 
 impl<'a> IntersectionList<'a> {
-    pub fn new(mut intersections: Vec<Intersection<'a>>) -> Self {
+    pub fn new<I: IntoIterator<Item = Intersection<'a>>>(intersections: I) -> Self {
+        let mut intersections: Intersections<'a> = intersections.into_iter().collect();
         intersections.sort();
         Self { intersections }
     }
 
-    pub fn hit(&self) -> Option<&Intersection> {
+    pub fn hit(&self) -> Option<&Intersection<'_>> {
         let filtered: Vec<_> = self.intersections.iter().filter(|x| x.t > 0.).collect();
         match filtered.len() {
             0 => None,
-            _ => Some(&filtered[0]),
+            _ => Some(filtered[0]),
         }
     }
+
+    // Like `hit`, but skips the boundaries of transparent objects that are inside a
+    // medium of higher priority, since the ray never actually crosses those surfaces.
+    // Opaque surfaces are always seen
+    pub fn visible_hit(&self) -> Option<&Intersection<'_>> {
+        let mut containers: Vec<&Object> = vec![];
+        for i in self.intersections.iter() {
+            let index = containers
+                .iter()
+                .position(|&object| std::ptr::eq(object, i.object));
+            let priority = match index {
+                Some(index) => {
+                    containers.remove(index);
+                    current_medium(&containers)
+                }
+                None => {
+                    let medium = current_medium(&containers);
+                    containers.push(i.object);
+                    medium
+                }
+            }
+            .map_or(0, |medium| medium.media_priority);
+
+            let transmissive = i.object.material.transparency > 0.;
+            if i.t > 0. && (!transmissive || i.object.media_priority >= priority) {
+                return Some(i);
+            }
+        }
+        None
+    }
 }
 
 impl<'a> Add for IntersectionList<'a> {
@@ -218,8 +373,7 @@ impl<'a> Add for IntersectionList<'a> {
 
     fn add(self, rhs: Self) -> Self::Output {
         let mut sorted_intersections = self.intersections;
-        let mut rhs = rhs;
-        sorted_intersections.append(&mut rhs.intersections);
+        sorted_intersections.extend(rhs.intersections);
         sorted_intersections.sort();
         IntersectionList {
             intersections: sorted_intersections,
@@ -235,14 +389,15 @@ mod tests {
     use crate::{
         color::{BLACK, RED},
         intersection::{Intersection, IntersectionList},
-        light::PointLight,
-        material::Material,
+        light::{AmbientLight, PointLight},
+        material::{Dispersion, Material, Parameter, ParameterMap, ThinFilm},
         matrix::Matrix,
-        pattern::TestPattern,
+        pattern::{GradientPattern, StripePattern, TestPattern},
         ray::Ray,
         shape::{MAX_REFLECTIONS, MAX_REFRACTIONS},
         shapes::Plane,
         shapes::Sphere,
+        PI,
     };
 
     #[test]
@@ -262,6 +417,34 @@ mod tests {
         );
     }
 
+    #[test]
+    fn mapped_reflection() {
+        let reflected = |stripes: Vec<Color>| {
+            let mut w = World::default();
+            let mut material = Material::new();
+            material.reflective = 0.5;
+            material.maps = vec![ParameterMap::new(
+                Parameter::Reflective,
+                StripePattern::new(stripes),
+            )];
+            let mut shape = Plane::new(Some(material));
+            shape.set_transform(&Matrix::translation(0., -1., 0.));
+            w.objects.push(shape);
+            let r = Ray::new(
+                Tuple::point(0., 0., -3.),
+                Tuple::vector(0., 2_f64.sqrt() / -2., 2_f64.sqrt() / 2.),
+            );
+            let i = Intersection::new(2_f64.sqrt(), w.objects.last().unwrap());
+            i.context(&r, None)
+                .reflected_color(&w, MAX_REFLECTIONS, &mut Sampler::Unseeded)
+        };
+        assert_eq!(
+            reflected(vec![WHITE, BLACK]),
+            Color::new(0.190332, 0.237915, 0.14274)
+        );
+        assert_eq!(reflected(vec![BLACK, WHITE]), BLACK);
+    }
+
     #[test]
     fn reflect_color() {
         let mut w = World::default();
@@ -269,7 +452,8 @@ mod tests {
         w.objects[1].material.ambient = 1.;
         let i = Intersection::new(1., &w.objects[1]);
         assert_eq!(
-            i.context(&r, None).reflected_color(&w, MAX_REFLECTIONS),
+            i.context(&r, None)
+                .reflected_color(&w, MAX_REFLECTIONS, &mut Sampler::Unseeded),
             BLACK
         );
 
@@ -277,7 +461,7 @@ mod tests {
         let mut material = Material::new();
         material.reflective = 0.5;
         let mut shape = Plane::new(Some(material));
-        shape.transform = Matrix::translation(0., -1., 0.);
+        shape.set_transform(&Matrix::translation(0., -1., 0.));
         w.objects.push(shape);
         let r = Ray::new(
             Tuple::point(0., 0., -3.),
@@ -285,24 +469,51 @@ mod tests {
         );
         let i = Intersection::new(2_f64.sqrt(), w.objects.last().unwrap());
         assert_eq!(
-            i.context(&r, None).reflected_color(&w, MAX_REFLECTIONS),
+            i.context(&r, None)
+                .reflected_color(&w, MAX_REFLECTIONS, &mut Sampler::Unseeded),
             Color::new(0.190332, 0.237915, 0.14274)
         );
         assert_eq!(
-            i.context(&r, None).shade_hit(&w, MAX_REFLECTIONS),
+            i.context(&r, None)
+                .shade_hit(&w, MAX_REFLECTIONS, &mut Sampler::Unseeded),
             Color::new(0.876757, 0.92434, 0.82917)
         );
     }
 
+    #[test]
+    fn dispersion() {
+        // A ray through the edge of a glass ball onto a wall shading from white to
+        // black, lit only by fill light
+        let trace = |dispersion: Option<Dispersion>| {
+            let mut glass = Sphere::glass_new();
+            glass.material.color = BLACK;
+            glass.material.dispersion = dispersion;
+            let mut material = Material::new();
+            material.pattern = Some(GradientPattern::new(WHITE, BLACK));
+            let mut wall = Plane::new(Some(material));
+            wall.set_transform(&(&Matrix::translation(0.5, 0., 5.) * &Matrix::rotation_x(PI / 2.)));
+            let mut w = World::new(vec![glass, wall], vec![]);
+            w.ambient_light = Some(AmbientLight::new(WHITE));
+            Ray::new(Tuple::point(0.6, 0., -5.), Tuple::vector(0., 0., 1.))
+                .color_hit(&w, MAX_REFRACTIONS)
+        };
+        let white = trace(None);
+        assert!(white.red > 0. && white.red == white.blue);
+        // Blue bends more, landing further across the wall
+        let split = trace(Some(Dispersion::new(0.05)));
+        assert!(split.red != split.green && split.green != split.blue);
+        assert!(split.red < split.blue);
+    }
+
     #[test]
     fn infinite_reflection() {
         let mut material = Material::new();
         material.reflective = 1.;
         let mut lower = Plane::new(Some(material.clone()));
-        lower.transform = Matrix::translation(0., -1., 0.);
+        lower.set_transform(&Matrix::translation(0., -1., 0.));
 
         let mut upper = Plane::new(Some(material.clone()));
-        upper.transform = Matrix::translation(0., 1., 0.);
+        upper.set_transform(&Matrix::translation(0., 1., 0.));
         let r = Ray::new(Tuple::point(0., 0., 0.), Tuple::vector(0., 1., 0.));
 
         let w = World::new(
@@ -319,15 +530,15 @@ mod tests {
     fn refractive_indices() {
         let mut a = Sphere::glass_new();
         a.material.refractive_index = 1.5;
-        a.transform = Matrix::scaling(2., 2., 2.);
+        a.set_transform(&Matrix::scaling(2., 2., 2.));
 
         let mut b = Sphere::glass_new();
         b.material.refractive_index = 2.;
-        b.transform = Matrix::translation(0., 0., -0.25);
+        b.set_transform(&Matrix::translation(0., 0., -0.25));
 
         let mut c = Sphere::glass_new();
         c.material.refractive_index = 2.5;
-        c.transform = Matrix::translation(0., 0., 0.25);
+        c.set_transform(&Matrix::translation(0., 0., 0.25));
 
         let r = Ray::new(Tuple::point(0., 0., -4.), Tuple::vector(0., 0., 1.));
         let xs = IntersectionList::new(vec![
@@ -359,9 +570,11 @@ mod tests {
         let r = Ray::new(Tuple::point(0., 0., -5.), Tuple::vector(0., 0., 1.));
         let xs = r.intersect_world(&w);
         assert_eq!(
-            xs.intersections[0]
-                .context(&r, Some(&xs))
-                .refracted_color(&w, MAX_REFRACTIONS),
+            xs.intersections[0].context(&r, Some(&xs)).refracted_color(
+                &w,
+                MAX_REFRACTIONS,
+                &mut Sampler::Unseeded
+            ),
             BLACK
         );
 
@@ -370,9 +583,11 @@ mod tests {
         w.objects[0].material.refractive_index = 1.5;
         let xs = r.intersect_world(&w);
         assert_eq!(
-            xs.intersections[0]
-                .context(&r, Some(&xs))
-                .refracted_color(&w, 0),
+            xs.intersections[0].context(&r, Some(&xs)).refracted_color(
+                &w,
+                0,
+                &mut Sampler::Unseeded
+            ),
             BLACK
         );
     }
@@ -389,9 +604,11 @@ mod tests {
         );
         let xs = r.intersect_world(&w);
         assert_eq!(
-            xs.intersections[1]
-                .context(&r, Some(&xs))
-                .refracted_color(&w, MAX_REFRACTIONS),
+            xs.intersections[1].context(&r, Some(&xs)).refracted_color(
+                &w,
+                MAX_REFRACTIONS,
+                &mut Sampler::Unseeded
+            ),
             BLACK
         );
     }
@@ -408,9 +625,11 @@ mod tests {
         let r = Ray::new(Tuple::point(0., 0., 0.1), Tuple::vector(0., 1., 0.));
         let xs = r.intersect_world(&w);
         assert_eq!(
-            xs.intersections[2]
-                .context(&r, Some(&xs))
-                .refracted_color(&w, MAX_REFRACTIONS),
+            xs.intersections[2].context(&r, Some(&xs)).refracted_color(
+                &w,
+                MAX_REFRACTIONS,
+                &mut Sampler::Unseeded
+            ),
             Color::new(0., 0.9988, 0.04725)
         );
     }
@@ -422,14 +641,14 @@ mod tests {
         floor_material.transparency = 0.5;
         floor_material.refractive_index = 1.5;
         let mut floor = Plane::new(Some(floor_material));
-        floor.transform = Matrix::translation(0., -1., 0.);
+        floor.set_transform(&Matrix::translation(0., -1., 0.));
         w.objects.push(floor);
 
         let mut ball_material = Material::new();
         ball_material.color = RED;
         ball_material.ambient = 0.5;
         let mut ball = Sphere::new(Some(ball_material));
-        ball.transform = Matrix::translation(0., -3.5, -0.5);
+        ball.set_transform(&Matrix::translation(0., -3.5, -0.5));
         w.objects.push(ball);
 
         let r = Ray::new(
@@ -438,9 +657,11 @@ mod tests {
         );
         let xs = r.intersect_world(&w);
         assert_eq!(
-            xs.intersections[0]
-                .context(&r, Some(&xs))
-                .shade_hit(&w, MAX_REFRACTIONS),
+            xs.intersections[0].context(&r, Some(&xs)).shade_hit(
+                &w,
+                MAX_REFRACTIONS,
+                &mut Sampler::Unseeded
+            ),
             Color::new(0.93642, 0.68642, 0.68642)
         );
     }
@@ -473,4 +694,211 @@ mod tests {
             epsilon = EPSILON
         );
     }
+
+    #[test]
+    fn shade_thin_film() {
+        let w = World::default();
+        let mut material = Material::new();
+        material.reflective = 1.;
+        material.refractive_index = 1.5;
+        let r = Ray::new(
+            Tuple::point(0., 0., -3.),
+            Tuple::vector(0., 2_f64.sqrt() / -2., 2_f64.sqrt() / 2.),
+        );
+        // The default world with a floor of the material below it
+        let shade = |material: &Material| {
+            let mut w = World::default();
+            let floor = Plane::new(Some(material.clone()))
+                .with_transform(&Matrix::translation(0., -1., 0.));
+            w.objects.push(floor);
+            let floor = w.objects.last().unwrap();
+            let xs = IntersectionList::new([Intersection::new(2_f64.sqrt(), floor)]);
+            xs.intersections[0].context(&r, Some(&xs)).shade_hit(
+                &w,
+                MAX_REFLECTIONS,
+                &mut Sampler::Unseeded,
+            )
+        };
+
+        // A film on an opaque mirror, like oil on a wet road, tints its reflection.
+        // The channel the film favours keeps the mirror's strength, and the others
+        // are dimmed by different amounts
+        let mirror = shade(&material);
+        let mut coated = material.clone();
+        coated.thin_film = Some(ThinFilm::new(350., 1.33));
+        let slick = shade(&coated);
+        let surface = material.lighting(
+            &w.lights[0],
+            &Plane::new(Some(material.clone())),
+            r.position(2_f64.sqrt()),
+            -r.direction,
+            Tuple::vector(0., 1., 0.),
+            false,
+        );
+        let [red, green, blue] = [
+            (slick.red - surface.red) / (mirror.red - surface.red),
+            (slick.green - surface.green) / (mirror.green - surface.green),
+            (slick.blue - surface.blue) / (mirror.blue - surface.blue),
+        ];
+        assert!(approx_eq!(
+            f64,
+            red.max(green).max(blue),
+            1.,
+            epsilon = EPSILON
+        ));
+        assert!(red.min(green).min(blue) < 0.99);
+        assert!((red - green).abs() > 0.01 || (green - blue).abs() > 0.01);
+
+        // On glass it takes the place of the Fresnel term
+        material.transparency = 1.;
+        coated.transparency = 1.;
+        let plain = shade(&material);
+        let tinted = shade(&coated);
+
+        // and reflects unevenly across channels
+        let surface = coated.lighting(
+            &w.lights[0],
+            &Plane::new(Some(coated.clone())),
+            r.position(2_f64.sqrt()),
+            -r.direction,
+            Tuple::vector(0., 1., 0.),
+            false,
+        );
+        let plain_reflection = plain - surface;
+        let tinted_reflection = tinted - surface;
+        assert!(tinted_reflection.red < plain_reflection.red);
+        assert!(
+            (tinted_reflection.red / plain_reflection.red
+                - tinted_reflection.blue / plain_reflection.blue)
+                .abs()
+                > 0.01
+        );
+    }
+
+    #[test]
+    fn media_priority() {
+        // A glass of water: the glass (priority 2) overlaps the water (priority 1)
+        let mut glass = Sphere::glass_new();
+        glass.media_priority = 2;
+        glass.set_transform(&Matrix::scaling(2., 2., 2.));
+
+        let mut water = Sphere::glass_new();
+        water.material.refractive_index = 1.33;
+        water.media_priority = 1;
+        water.set_transform(&Matrix::translation(0., 0., 1.5));
+
+        let r = Ray::new(Tuple::point(0., 0., -4.), Tuple::vector(0., 0., 1.));
+        let xs = IntersectionList::new(vec![
+            Intersection::new(2., &glass),
+            Intersection::new(4.5, &water),
+            Intersection::new(6., &glass),
+            Intersection::new(6.5, &water),
+        ]);
+
+        assert_eq!(xs.intersections[0].context(&r, Some(&xs)).n1, 1.);
+        assert_eq!(xs.intersections[0].context(&r, Some(&xs)).n2, 1.5);
+        // Entering the water while still inside the glass doesn't change the medium
+        assert_eq!(xs.intersections[1].context(&r, Some(&xs)).n1, 1.5);
+        assert_eq!(xs.intersections[1].context(&r, Some(&xs)).n2, 1.5);
+        assert_eq!(xs.intersections[2].context(&r, Some(&xs)).n1, 1.5);
+        assert_eq!(xs.intersections[2].context(&r, Some(&xs)).n2, 1.33);
+        assert_eq!(xs.intersections[3].context(&r, Some(&xs)).n1, 1.33);
+        assert_eq!(xs.intersections[3].context(&r, Some(&xs)).n2, 1.);
+
+        // From inside the glass, the water boundary is not a visible surface
+        let xs = IntersectionList::new(vec![
+            Intersection::new(-1., &glass),
+            Intersection::new(1.5, &water),
+            Intersection::new(3., &glass),
+            Intersection::new(3.5, &water),
+        ]);
+        assert_eq!(xs.hit(), Some(&xs.intersections[1]));
+        assert_eq!(xs.visible_hit(), Some(&xs.intersections[2]));
+    }
+
+    #[test]
+    fn opaque_inside_prioritized_medium() {
+        let mut glass = Sphere::glass_new();
+        glass.media_priority = 1;
+        glass.set_transform(&Matrix::scaling(3., 3., 3.));
+        let mut ball = Material::new();
+        ball.color = RED;
+        let w = World::new(vec![glass, Sphere::new(Some(ball))], vec![]);
+
+        // Travelling inside the glass, the ball in it is still in the way
+        let r = Ray::new(Tuple::point(0., 0., -2.), Tuple::vector(0., 0., 1.));
+        let xs = r.intersect_world(&w);
+        let hit = xs.visible_hit().unwrap();
+        assert_eq!(hit.t, 1.);
+        assert!(std::ptr::eq(hit.object, &w.objects[1]));
+    }
+
+    #[test]
+    fn shade_linked_lights() {
+        let mut w = World::default();
+        w.objects[0].name = Some(String::from("hero"));
+        let mut rim = PointLight::new(Tuple::point(10., 10., -10.), Color::new(1., 1., 1.));
+        rim.include = vec![String::from("hero")];
+        w.lights.push(rim);
+
+        let r = Ray::new(Tuple::point(0., 0., -5.), Tuple::vector(0., 0., 1.));
+        let shade = |w: &World| {
+            let xs = r.intersect_object(&w.objects[0]);
+            let c = xs.hit().unwrap().context(&r, None).shade_hit(
+                w,
+                MAX_REFLECTIONS,
+                &mut Sampler::Unseeded,
+            );
+            c
+        };
+        // Both lights sit symmetrically either side of the hit, so the rim light doubles it
+        assert_eq!(shade(&w), Color::new(0.38066, 0.47583, 0.2855) * 2.);
+
+        // Unlinked objects only see the key light
+        w.objects[0].name = None;
+        assert_eq!(shade(&w), Color::new(0.38066, 0.47583, 0.2855));
+    }
+
+    #[test]
+    fn emission() {
+        let mut w = World::default();
+        w.objects[0].material.emission = Color::new(0.5, 0.2, 0.);
+        let r = Ray::new(Tuple::point(0., 0., -5.), Tuple::vector(0., 0., 1.));
+        let c = r.color_hit(&w, MAX_REFLECTIONS);
+        assert_eq!(c, Color::new(0.88066, 0.67583, 0.2855));
+
+        // Still glowing with no lights at all
+        w.lights.clear();
+        let c = r.color_hit(&w, MAX_REFLECTIONS);
+        assert_eq!(c, Color::new(0.5, 0.2, 0.));
+    }
+
+    #[test]
+    fn ambient_light() {
+        let mut w = World::default();
+        w.ambient_light = Some(AmbientLight::new(Color::new(0.1, 0.1, 0.1)));
+        let r = Ray::new(Tuple::point(0., 0., -5.), Tuple::vector(0., 0., 1.));
+        let c = r.color_hit(&w, MAX_REFLECTIONS);
+        assert_eq!(c, Color::new(0.46066, 0.57583, 0.3455));
+
+        // Surfaces in shadow still get the fill light
+        w.lights.clear();
+        let c = r.color_hit(&w, MAX_REFLECTIONS);
+        assert_eq!(c, Color::new(0.08, 0.1, 0.06));
+    }
+
+    #[test]
+    fn inline_intersections() {
+        let w = World::default();
+        let r = Ray::new(Tuple::point(0., 0., -5.), Tuple::vector(0., 0., 1.));
+        let xs = r.intersect_world(&w);
+        assert_eq!(xs.intersections.len(), 4);
+        assert!(!xs.intersections.spilled());
+
+        let s = Sphere::new(None);
+        let xs = IntersectionList::new((0..6).map(|t| Intersection::new(5. - t as f64, &s)));
+        assert!(xs.intersections.spilled());
+        assert_eq!(xs.intersections[0].t, 0.);
+        assert_eq!(xs.intersections[5].t, 5.);
+    }
 }
@@ -1,14 +1,36 @@
+// Shape and pattern constructors (e.g. `Sphere::new`) deliberately return the
+// wrapping `Object`/`Pattern` rather than `Self`
+#![allow(clippy::new_ret_no_self)]
+
+pub mod animation;
+pub mod approx;
+pub mod bounds;
+pub mod bvh;
 pub mod camera;
 pub mod canvas;
 pub mod color;
+#[cfg(feature = "oidn")]
+pub mod denoise;
+pub mod environment;
+pub mod font;
 pub mod intersection;
 pub mod light;
 pub mod material;
 pub mod matrix;
+pub mod mesh;
 pub mod pattern;
+pub mod ply;
 pub mod ray;
+pub mod rng;
+pub mod scenes;
 pub mod shape;
 pub mod shapes;
+pub mod simd;
+pub mod sky;
+pub mod stats;
+pub mod testing;
+#[cfg(feature = "text")]
+pub mod text;
 pub mod transformations;
 pub mod tuple;
 pub mod world;
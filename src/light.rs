@@ -1,10 +1,84 @@
-use crate::color::Color;
+use crate::color::{Color, WHITE};
+use crate::pattern::Pattern;
+use crate::rng::Sampler;
+use crate::shape::Object;
 use crate::tuple::Tuple;
+use crate::PI;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct PointLight {
     pub intensity: Color,
     pub position: Tuple,
+    // Names of the only objects this light illuminates. Empty means every object
+    pub include: Vec<String>,
+    // Names of objects this light never illuminates
+    pub exclude: Vec<String>,
+    // Names of the only objects that cast shadows from this light. Empty means every
+    // object
+    pub shadow_include: Vec<String>,
+    // Names of objects that never cast shadows from this light
+    pub shadow_exclude: Vec<String>,
+    // Limits the light to a cone when set
+    pub spot: Option<SpotLight>,
+    pub attenuation: Attenuation,
+    // Gives the light a size, for soft shadows, when set
+    pub sphere: Option<SphereLight>,
+}
+
+// Whether the object passes a pair of include and exclude lists of names
+fn linked(object: &Object, include: &[String], exclude: &[String]) -> bool {
+    let named = |names: &[String]| match &object.name {
+        Some(name) => names.contains(name),
+        None => false,
+    };
+    (include.is_empty() || named(include)) && !named(exclude)
+}
+
+// How a light dims with distance d, dividing its intensity by
+// constant + linear * d + quadratic * d^2
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Attenuation {
+    pub constant: f64,
+    pub linear: f64,
+    pub quadratic: f64,
+}
+
+impl Attenuation {
+    pub fn new(constant: f64, linear: f64, quadratic: f64) -> Self {
+        assert!(constant >= 0. && linear >= 0. && quadratic >= 0.);
+        assert!(constant + linear + quadratic > 0.);
+        Self {
+            constant,
+            linear,
+            quadratic,
+        }
+    }
+
+    // The same brightness everywhere
+    pub fn none() -> Self {
+        Attenuation::new(1., 0., 0.)
+    }
+
+    // Full intensity at `distance` from the light, fading in proportion to distance
+    pub fn linear(distance: f64) -> Self {
+        Attenuation::new(0., 1. / distance, 0.)
+    }
+
+    // Full intensity at `distance` from the light, following the inverse square law
+    pub fn quadratic(distance: f64) -> Self {
+        Attenuation::new(0., 0., 1. / (distance * distance))
+    }
+
+    pub fn factor(&self, distance: f64) -> f64 {
+        let d = distance;
+        1. / (self.constant + self.linear * d + self.quadratic * d * d)
+    }
+}
+
+impl Default for Attenuation {
+    fn default() -> Self {
+        Attenuation::none()
+    }
 }
 
 impl PointLight {
@@ -13,17 +87,465 @@ impl PointLight {
         Self {
             intensity,
             position,
+            include: vec![],
+            exclude: vec![],
+            shadow_include: vec![],
+            shadow_exclude: vec![],
+            spot: None,
+            attenuation: Attenuation::none(),
+            sphere: None,
+        }
+    }
+
+    pub fn illuminates(&self, object: &Object) -> bool {
+        linked(object, &self.include, &self.exclude)
+    }
+
+    pub fn shadowed_by(&self, object: &Object) -> bool {
+        linked(object, &self.shadow_include, &self.shadow_exclude)
+    }
+
+    // How much of the light's direct intensity reaches the point, from 0 to 1
+    pub fn cone(&self, point: Tuple) -> f64 {
+        match &self.spot {
+            None => 1.,
+            Some(spot) => spot.factor(point - self.position),
+        }
+    }
+
+    // The color a spot light's gobo casts on the point, white without one
+    pub fn gobo(&self, point: Tuple) -> Color {
+        match &self.spot {
+            Some(SpotLight {
+                direction,
+                gobo: Some(gobo),
+                ..
+            }) => {
+                let to_point = point - self.position;
+                let forward = to_point.dot(direction);
+                if forward <= 0. {
+                    return WHITE;
+                }
+                let (right, up) = frame(*direction, Tuple::vector(0., 1., 0.));
+                let x = to_point.dot(&right) / forward;
+                let z = to_point.dot(&up) / forward;
+                gobo.color_at(Tuple::point(x, 0., z))
+            }
+            _ => WHITE,
+        }
+    }
+
+    // The fraction of the light's direct intensity that reaches the point
+    pub fn reach(&self, point: Tuple) -> f64 {
+        let distance = (point - self.position).magnitude();
+        self.cone(point) * self.attenuation.factor(distance)
+    }
+}
+
+// A point light that only shines within `outer_angle` of its direction. It is at
+// full strength within `inner_angle`, and fades out between the two with the fade
+// raised to the power `falloff`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpotLight {
+    pub direction: Tuple,
+    pub inner_angle: f64,
+    pub outer_angle: f64,
+    pub falloff: f64,
+    // Cuts the cone down to a rectangle when set
+    pub barn_doors: Option<BarnDoors>,
+    // Full strength up to the first distance from the light, fading out to nothing
+    // at the second, whatever the attenuation
+    pub range: Option<(f64, f64)>,
+    // A pattern the light shines through, like a stencil or a slide in a projector,
+    // coloring the light in each direction. It is laid on the xz plane a unit in
+    // front of the light, with x to the right and z up seen from the light, up being
+    // +y where possible as for barn doors
+    pub gobo: Option<Pattern>,
+}
+
+// Unit vectors to the right and up seen looking along the direction, with up as
+// close to the given one as possible
+fn frame(direction: Tuple, up: Tuple) -> (Tuple, Tuple) {
+    // Any up will do for directions straight along it
+    let up = if up.cross(&direction).magnitude() < 1e-9 {
+        Tuple::vector(1., 0., 0.)
+    } else {
+        up
+    };
+    let right = up.cross(&direction).normalize();
+    (right, direction.cross(&right))
+}
+
+// Flaps in front of a spot light that clip its beam to `horizontal` and `vertical`
+// radians either side of its direction, like those on stage lights. The flaps'
+// shadows fade in over `softness` radians. Vertical is along `up`, or as close to it
+// as the light's direction allows
+#[derive(Debug, Clone, PartialEq)]
+pub struct BarnDoors {
+    pub horizontal: f64,
+    pub vertical: f64,
+    pub softness: f64,
+    pub up: Tuple,
+}
+
+impl BarnDoors {
+    pub fn new(horizontal: f64, vertical: f64) -> Self {
+        assert!(horizontal >= 0. && vertical >= 0.);
+        BarnDoors {
+            horizontal,
+            vertical,
+            softness: 0.,
+            up: Tuple::vector(0., 1., 0.),
+        }
+    }
+
+    // How much light gets past the flaps towards the unit vector, from the light
+    // pointing along `direction`
+    fn factor(&self, direction: Tuple, to_point: Tuple) -> f64 {
+        let forward = to_point.dot(&direction);
+        if forward <= 0. {
+            return 0.;
+        }
+        let (right, up) = frame(direction, self.up);
+        let open = |angle: f64, limit: f64| {
+            let inside = limit - angle.abs();
+            if self.softness > 0. {
+                (inside / self.softness).clamp(0., 1.)
+            } else if inside >= 0. {
+                1.
+            } else {
+                0.
+            }
+        };
+        open(to_point.dot(&right).atan2(forward), self.horizontal)
+            * open(to_point.dot(&up).atan2(forward), self.vertical)
+    }
+}
+
+impl SpotLight {
+    // Angles are in radians from the direction to the edge of the cone
+    pub fn new(
+        position: Tuple,
+        direction: Tuple,
+        intensity: Color,
+        inner_angle: f64,
+        outer_angle: f64,
+    ) -> PointLight {
+        assert!(direction.is_vector());
+        assert!(0. <= inner_angle && inner_angle <= outer_angle);
+        PointLight {
+            spot: Some(SpotLight {
+                direction: direction.normalize(),
+                inner_angle,
+                outer_angle,
+                falloff: 1.,
+                barn_doors: None,
+                range: None,
+                gobo: None,
+            }),
+            ..PointLight::new(position, intensity)
+        }
+    }
+
+    fn factor(&self, to_point: Tuple) -> f64 {
+        let distance = to_point.magnitude();
+        let to_point = to_point / distance;
+        let cos = to_point.dot(&self.direction);
+        let (inner, outer) = (self.inner_angle.cos(), self.outer_angle.cos());
+        let cone = if cos >= inner {
+            1.
+        } else if cos <= outer {
+            0.
+        } else {
+            ((cos - outer) / (inner - outer)).powf(self.falloff)
+        };
+        let doors = match &self.barn_doors {
+            Some(doors) => doors.factor(self.direction, to_point),
+            None => 1.,
+        };
+        let range = match self.range {
+            Some((near, far)) if distance > near => ((far - distance) / (far - near)).max(0.),
+            _ => 1.,
+        };
+        cone * doors * range
+    }
+}
+
+// A light spread over the surface of a ball around its position. It shades like a
+// point light, but shadows are found by testing `samples` points on the side of the
+// ball facing the surface, so their edges soften the closer the light is
+#[derive(Debug, Clone, PartialEq)]
+pub struct SphereLight {
+    pub radius: f64,
+    pub samples: usize,
+}
+
+impl SphereLight {
+    pub fn new(position: Tuple, radius: f64, intensity: Color) -> PointLight {
+        assert!(radius > 0.);
+        PointLight {
+            sphere: Some(SphereLight {
+                radius,
+                samples: 16,
+            }),
+            ..PointLight::new(position, intensity)
+        }
+    }
+
+    // Points on the ball seen from `point`, spread evenly over the cone it covers
+    // with an offset drawn from the sampler in each cell of a grid
+    pub fn samples_towards(
+        &self,
+        centre: Tuple,
+        point: Tuple,
+        sampler: &mut Sampler,
+    ) -> Vec<Tuple> {
+        let to_centre = centre - point;
+        let distance = to_centre.magnitude();
+        if distance <= self.radius {
+            return vec![centre];
+        }
+        let w = to_centre / distance;
+        let helper = if w.x.abs() > 0.9 {
+            Tuple::vector(0., 1., 0.)
+        } else {
+            Tuple::vector(1., 0., 0.)
+        };
+        let u = helper.cross(&w).normalize();
+        let v = w.cross(&u);
+
+        let sin_max = self.radius / distance;
+        let cos_max = (1. - sin_max * sin_max).sqrt();
+        let side = ((self.samples as f64).sqrt().ceil() as usize).max(1);
+        let mut targets = Vec::with_capacity(side * side);
+        for i in 0..side {
+            for j in 0..side {
+                let a = (i as f64 + sampler.random()) / side as f64;
+                let b = (j as f64 + sampler.random()) / side as f64;
+                let cos = 1. - a * (1. - cos_max);
+                let sin = (1. - cos * cos).sqrt();
+                let phi = 2. * PI * b;
+                let direction = u * (sin * phi.cos()) + v * (sin * phi.sin()) + w * cos;
+                // The nearer of the two places the direction meets the ball
+                let along = distance * cos
+                    - (self.radius * self.radius - distance * distance * sin * sin)
+                        .max(0.)
+                        .sqrt();
+                targets.push(point + direction * along);
+            }
+        }
+        targets
+    }
+}
+
+// Fill light that reaches every surface whatever its position, and ignores shadows.
+// Surfaces facing up get the sky color and surfaces facing down the ground color,
+// blending in between
+#[derive(Debug, Clone, PartialEq)]
+pub struct AmbientLight {
+    pub sky: Color,
+    pub ground: Color,
+}
+
+impl AmbientLight {
+    pub fn new(intensity: Color) -> Self {
+        Self {
+            sky: intensity,
+            ground: intensity,
         }
     }
+
+    pub fn hemisphere(sky: Color, ground: Color) -> Self {
+        Self { sky, ground }
+    }
+
+    pub fn intensity_at(&self, normal: Tuple) -> Color {
+        let up = (normal.normalize().y + 1.) / 2.;
+        self.ground * (1. - up) + self.sky * up
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::assert_approx_eq;
+    use crate::pattern::StripePattern;
+    use crate::shapes::Sphere;
+
     #[test]
     fn point_light() {
         let light = PointLight::new(Tuple::point(0., 0., 0.), Color::new(1., 1., 1.));
         assert_eq!(light.position, Tuple::point(0., 0., 0.));
         assert_eq!(light.intensity, Color::new(1., 1., 1.));
     }
+
+    #[test]
+    fn spot_light() {
+        let light = SpotLight::new(
+            Tuple::point(0., 10., 0.),
+            Tuple::vector(0., -1., 0.),
+            Color::new(1., 1., 1.),
+            PI / 8.,
+            PI / 4.,
+        );
+        assert_eq!(light.cone(Tuple::point(0., 0., 0.)), 1.);
+        assert_eq!(light.cone(Tuple::point(1., 0., 0.)), 1.);
+        assert_eq!(light.cone(Tuple::point(10., 0., 0.)), 0.);
+        assert_eq!(light.cone(Tuple::point(0., 20., 0.)), 0.);
+
+        // Half way between the cones, by cosine
+        let cos = ((PI / 8.).cos() + (PI / 4.).cos()) / 2.;
+        let x = 10. * (1. - cos * cos).sqrt() / cos;
+        assert_approx_eq!(light.cone(Tuple::point(x, 0., 0.)), 0.5);
+    }
+
+    #[test]
+    fn barn_doors() {
+        let mut light = SpotLight::new(
+            Tuple::point(0., 10., 0.),
+            Tuple::vector(0., -1., 0.),
+            Color::new(1., 1., 1.),
+            PI / 3.,
+            PI / 3.,
+        );
+        let spot = light.spot.as_mut().unwrap();
+        // Looking down, up is taken as x: the beam is 2 wide along z and 10 along x
+        spot.barn_doors = Some(BarnDoors::new(0.1_f64.atan(), 0.5_f64.atan()));
+        assert_eq!(light.cone(Tuple::point(0., 0., 0.)), 1.);
+        assert_eq!(light.cone(Tuple::point(4.9, 0., 0.9)), 1.);
+        assert_eq!(light.cone(Tuple::point(5.1, 0., 0.)), 0.);
+        assert_eq!(light.cone(Tuple::point(0., 0., -1.1)), 0.);
+
+        // Soft edges fade across the flaps' edge
+        let spot = light.spot.as_mut().unwrap();
+        spot.barn_doors.as_mut().unwrap().softness = 0.05;
+        let edge = light.cone(Tuple::point(4.8, 0., 0.));
+        assert!(0. < edge && edge < 1.);
+        assert_eq!(light.cone(Tuple::point(0., 0., 0.)), 1.);
+
+        // Flaps turned to put the long side along z
+        let doors = light.spot.as_mut().unwrap().barn_doors.as_mut().unwrap();
+        (doors.softness, doors.up) = (0., Tuple::vector(0., 0., 1.));
+        assert_eq!(light.cone(Tuple::point(0.9, 0., 4.9)), 1.);
+        assert_eq!(light.cone(Tuple::point(1.1, 0., 0.)), 0.);
+    }
+
+    #[test]
+    fn gobo() {
+        let mut light = SpotLight::new(
+            Tuple::point(0., 10., 0.),
+            Tuple::vector(0., -1., 0.),
+            Color::new(1., 1., 1.),
+            PI / 3.,
+            PI / 3.,
+        );
+        assert_eq!(light.gobo(Tuple::point(0., 0., 5.)), WHITE);
+        let red = Color::new(1., 0., 0.);
+        light.spot.as_mut().unwrap().gobo = Some(StripePattern::new(vec![WHITE, red]));
+        // Looking down, right is -z, so the stripes cross z
+        assert_eq!(light.gobo(Tuple::point(0., 0., -5.)), WHITE);
+        assert_eq!(light.gobo(Tuple::point(3., 0., 5.)), red);
+        // Projected, so the stripes widen further from the light
+        assert_eq!(light.gobo(Tuple::point(0., -10., 15.)), red);
+        assert_eq!(light.gobo(Tuple::point(0., 20., 0.)), WHITE);
+    }
+
+    #[test]
+    fn range() {
+        let mut light = SpotLight::new(
+            Tuple::point(0., 0., 0.),
+            Tuple::vector(0., 0., 1.),
+            Color::new(1., 1., 1.),
+            PI / 4.,
+            PI / 4.,
+        );
+        light.spot.as_mut().unwrap().range = Some((2., 4.));
+        assert_eq!(light.cone(Tuple::point(0., 0., 1.)), 1.);
+        assert_eq!(light.cone(Tuple::point(0., 0., 3.)), 0.5);
+        assert_eq!(light.cone(Tuple::point(0., 0., 5.)), 0.);
+    }
+
+    #[test]
+    fn attenuation() {
+        let mut light = PointLight::new(Tuple::point(0., 0., 0.), Color::new(1., 1., 1.));
+        assert_eq!(light.reach(Tuple::point(0., 100., 0.)), 1.);
+
+        light.attenuation = Attenuation::linear(2.);
+        assert_eq!(light.reach(Tuple::point(0., 2., 0.)), 1.);
+        assert_eq!(light.reach(Tuple::point(0., 4., 0.)), 0.5);
+
+        light.attenuation = Attenuation::quadratic(2.);
+        assert_eq!(light.reach(Tuple::point(0., 1., 0.)), 4.);
+        assert_eq!(light.reach(Tuple::point(0., 4., 0.)), 0.25);
+
+        // A constant term keeps nearby points from blowing out
+        light.attenuation = Attenuation::new(1., 0., 1.);
+        assert_eq!(light.reach(Tuple::point(0., 0., 0.)), 1.);
+        assert_eq!(light.reach(Tuple::point(0., 1., 0.)), 0.5);
+    }
+
+    #[test]
+    fn sphere_light() {
+        let light = SphereLight::new(Tuple::point(0., 10., 0.), 2., Color::new(1., 1., 1.));
+        let sphere = light.sphere.as_ref().unwrap();
+        let point = Tuple::point(3., 0., 0.);
+        let targets = sphere.samples_towards(light.position, point, &mut Sampler::Unseeded);
+        assert_eq!(targets.len(), 16);
+        for target in targets {
+            // On the ball, and on the side facing the point
+            assert_approx_eq!((target - light.position).magnitude(), 2.);
+            assert!((target - light.position).dot(&(point - light.position)) >= 0.);
+        }
+
+        // Points inside the ball see its centre
+        let inside = sphere.samples_towards(
+            light.position,
+            Tuple::point(0., 11., 0.),
+            &mut Sampler::Unseeded,
+        );
+        assert_eq!(inside, vec![light.position]);
+    }
+
+    #[test]
+    fn ambient_light() {
+        let flat = AmbientLight::new(Color::new(0.2, 0.2, 0.2));
+        assert_eq!(
+            flat.intensity_at(Tuple::vector(0.3, -0.7, 0.1)),
+            Color::new(0.2, 0.2, 0.2)
+        );
+
+        let light = AmbientLight::hemisphere(Color::new(0.4, 0.6, 1.), Color::new(0.2, 0.1, 0.));
+        assert_eq!(
+            light.intensity_at(Tuple::vector(0., 2., 0.)),
+            Color::new(0.4, 0.6, 1.)
+        );
+        assert_eq!(
+            light.intensity_at(Tuple::vector(0., -1., 0.)),
+            Color::new(0.2, 0.1, 0.)
+        );
+        assert_eq!(
+            light.intensity_at(Tuple::vector(1., 0., 0.)),
+            Color::new(0.3, 0.35, 0.5)
+        );
+    }
+
+    #[test]
+    fn light_linking() {
+        let mut hero = Sphere::new(None);
+        hero.name = Some(String::from("hero"));
+        let extra = Sphere::new(None);
+
+        let mut light = PointLight::new(Tuple::point(0., 0., 0.), Color::new(1., 1., 1.));
+        assert!(light.illuminates(&hero));
+        assert!(light.illuminates(&extra));
+
+        light.include = vec![String::from("hero")];
+        assert!(light.illuminates(&hero));
+        assert!(!light.illuminates(&extra));
+
+        light.include = vec![];
+        light.exclude = vec![String::from("hero")];
+        assert!(!light.illuminates(&hero));
+        assert!(light.illuminates(&extra));
+    }
 }
@@ -1,7 +1,13 @@
 use crate::{
-    color::Color, light::PointLight, pattern::Pattern, shape::Object, tuple::Tuple, EPSILON,
+    color::{Color, BLACK, WHITE},
+    light::PointLight,
+    pattern::Pattern,
+    shape::Object,
+    tuple::Tuple,
+    EPSILON, PI,
 };
 use float_cmp::approx_eq;
+use std::borrow::Cow;
 
 #[derive(Debug, Clone)]
 pub struct Material {
@@ -13,7 +19,293 @@ pub struct Material {
     pub reflective: f64,
     pub transparency: f64,
     pub refractive_index: f64,
+    // Makes the refractive index depend on the wavelength, splitting white light
+    pub dispersion: Option<Dispersion>,
     pub pattern: Option<Pattern>,
+    pub thin_film: Option<ThinFilm>,
+    // Shades lights with a physically based model instead of the Phong diffuse,
+    // specular and shininess parameters
+    pub pbr: Option<Pbr>,
+    // Parameters varied across the surface by patterns
+    pub maps: Vec<ParameterMap>,
+    // Light the surface gives off by itself, seen whatever lights the scene has
+    pub emission: Color,
+    // Optional name used to refer to the material, e.g. in material masks
+    pub name: Option<String>,
+}
+
+// Floating point width used for lighting. Intersections are always computed in f64
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ShadingPrecision {
+    #[default]
+    Double,
+    // Does the Phong arithmetic of each light in f32, its inputs narrowed from f64
+    // per call. Materials, patterns and textures are still stored and sampled in
+    // f64, so texturing isn't any cheaper, and physically based materials are still
+    // shaded in f64
+    Single,
+}
+
+// The float types Phong shading can be done in, see ShadingPrecision
+trait ShadingFloat:
+    Copy
+    + PartialOrd
+    + std::ops::Add<Output = Self>
+    + std::ops::Sub<Output = Self>
+    + std::ops::Mul<Output = Self>
+    + std::ops::Div<Output = Self>
+{
+    fn of(value: f64) -> Self;
+    fn to_f64(self) -> f64;
+    fn sqrt(self) -> Self;
+    fn powf(self, n: Self) -> Self;
+}
+
+impl ShadingFloat for f64 {
+    fn of(value: f64) -> Self {
+        value
+    }
+    fn to_f64(self) -> f64 {
+        self
+    }
+    fn sqrt(self) -> Self {
+        f64::sqrt(self)
+    }
+    fn powf(self, n: Self) -> Self {
+        f64::powf(self, n)
+    }
+}
+
+impl ShadingFloat for f32 {
+    fn of(value: f64) -> Self {
+        value as f32
+    }
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+    fn sqrt(self) -> Self {
+        f32::sqrt(self)
+    }
+    fn powf(self, n: Self) -> Self {
+        f32::powf(self, n)
+    }
+}
+
+// The inputs to shading one light at a point, with the surface color already looked up
+#[derive(Clone, Copy)]
+struct Shading<'a> {
+    color: Color,
+    light: &'a PointLight,
+    point: Tuple,
+    eye_vector: Tuple,
+    normal_vector: Tuple,
+    visibility: Color,
+}
+
+// Wavelengths in nanometres used to sample the red, green and blue channels
+const WAVELENGTHS: [f64; 3] = [650., 510., 475.];
+
+// Wavelengths in micrometres of the sodium D line, where glass's refractive index is
+// usually given, and of the hydrogen F and C lines its Abbe number is measured
+// between
+const D_LINE: f64 = 0.5893;
+const F_LINE: f64 = 0.4861;
+const C_LINE: f64 = 0.6563;
+
+// A refractive index varying with the wavelength by Cauchy's equation n = A + B / λ²,
+// with λ in micrometres and A such that the index at the D line is the material's
+// refractive_index
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dispersion {
+    pub cauchy_b: f64,
+}
+
+impl Dispersion {
+    pub fn new(cauchy_b: f64) -> Self {
+        Self { cauchy_b }
+    }
+
+    // From the Abbe number glass catalogues list, about 60 for crown glass and 30 for
+    // dense flint. The lower it is, the more the glass disperses
+    pub fn from_abbe(refractive_index: f64, abbe: f64) -> Self {
+        let spread = 1. / (F_LINE * F_LINE) - 1. / (C_LINE * C_LINE);
+        Self::new((refractive_index - 1.) / (abbe * spread))
+    }
+
+    pub fn refractive_index(&self, refractive_index: f64, wavelength: f64) -> f64 {
+        let micrometres = wavelength / 1000.;
+        refractive_index
+            + self.cauchy_b * (1. / (micrometres * micrometres) - 1. / (D_LINE * D_LINE))
+    }
+}
+
+// A thin transparent coating (soap bubble, oil slick) whose interference between
+// the light reflected off its top and bottom surfaces tints reflections. On
+// transparent materials it takes the place of the Fresnel term, and on opaque
+// reflective ones it colors the reflection
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThinFilm {
+    // Thickness of the film in nanometres
+    pub thickness: f64,
+    pub refractive_index: f64,
+}
+
+impl ThinFilm {
+    pub fn new(thickness: f64, refractive_index: f64) -> Self {
+        Self {
+            thickness,
+            refractive_index,
+        }
+    }
+
+    // Fraction of light reflected per channel for light arriving from a medium with
+    // index `n_outside` at angle `cos_i`, onto a surface with index `n_substrate`
+    pub fn reflectance(&self, cos_i: f64, n_outside: f64, n_substrate: f64) -> Color {
+        let n_film = self.refractive_index;
+        let sin2_i = 1. - cos_i * cos_i;
+        let sin2_film = (n_outside / n_film).powi(2) * sin2_i;
+        if sin2_film > 1. {
+            return Color::new(1., 1., 1.);
+        }
+        let cos_film = (1. - sin2_film).sqrt();
+        let cos_substrate = (1. - (n_outside / n_substrate).powi(2) * sin2_i)
+            .max(0.)
+            .sqrt();
+
+        // Amplitude reflection coefficients at both interfaces for s and p polarisation
+        let rs = |n_a: f64, cos_a: f64, n_b: f64, cos_b: f64| {
+            (n_a * cos_a - n_b * cos_b) / (n_a * cos_a + n_b * cos_b)
+        };
+        let rp = |n_a: f64, cos_a: f64, n_b: f64, cos_b: f64| {
+            (n_b * cos_a - n_a * cos_b) / (n_b * cos_a + n_a * cos_b)
+        };
+        let coefficients = [
+            (
+                rs(n_outside, cos_i, n_film, cos_film),
+                rs(n_film, cos_film, n_substrate, cos_substrate),
+            ),
+            (
+                rp(n_outside, cos_i, n_film, cos_film),
+                rp(n_film, cos_film, n_substrate, cos_substrate),
+            ),
+        ];
+
+        let channel = |wavelength: f64| {
+            let phase = 4. * PI * n_film * self.thickness * cos_film / wavelength;
+            coefficients
+                .iter()
+                .map(|(r12, r23)| {
+                    let cross = 2. * r12 * r23 * phase.cos();
+                    (r12 * r12 + r23 * r23 + cross) / (1. + r12 * r12 * r23 * r23 + cross)
+                })
+                .sum::<f64>()
+                / 2.
+        };
+
+        Color::new(
+            channel(WAVELENGTHS[0]),
+            channel(WAVELENGTHS[1]),
+            channel(WAVELENGTHS[2]),
+        )
+    }
+}
+
+// Metallic-roughness shading, as in glTF. The material's color is the base color:
+// what dielectrics scatter diffusely and what metals tint their reflections with.
+// Highlights come from a GGX microfacet lobe
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pbr {
+    // From 0 for dielectrics to 1 for metals, which have no diffuse part
+    pub metallic: f64,
+    // From 0 for a sharp highlight to 1 for a broad, dull one
+    pub roughness: f64,
+    // Sets how much of the light dielectrics reflect head on
+    pub ior: f64,
+}
+
+impl Pbr {
+    pub fn new(metallic: f64, roughness: f64) -> Self {
+        Self {
+            metallic,
+            roughness,
+            ior: 1.5,
+        }
+    }
+
+    // Light reflected towards the eye for each unit of light arriving from the light
+    // vector, i.e. the BRDF times the cosine of the angle of incidence. Lights here
+    // are scaled so that a white Phong surface with a diffuse of 1 reflects all of
+    // their light head on, so the BRDF is multiplied by PI to match
+    pub fn reflectance(
+        &self,
+        base_color: Color,
+        light_vector: Tuple,
+        eye_vector: Tuple,
+        normal_vector: Tuple,
+    ) -> Color {
+        let light_dot_normal = light_vector.dot(&normal_vector);
+        let eye_dot_normal = eye_vector.dot(&normal_vector);
+        if light_dot_normal <= 0. || eye_dot_normal <= 0. {
+            return BLACK;
+        }
+        let halfway = (light_vector + eye_vector).normalize();
+        let halfway_dot_normal = halfway.dot(&normal_vector).max(0.);
+        let eye_dot_halfway = eye_vector.dot(&halfway).max(0.);
+
+        // Distribution of microfacet normals
+        let alpha = (self.roughness * self.roughness).max(1e-3);
+        let alpha2 = alpha * alpha;
+        let d = alpha2 / (PI * (halfway_dot_normal.powi(2) * (alpha2 - 1.) + 1.).powi(2));
+        // Microfacets shadowing and masking each other, Schlick's approximation of
+        // Smith's function
+        let k = (self.roughness + 1.).powi(2) / 8.;
+        let g1 = |cos: f64| cos / (cos * (1. - k) + k);
+        let g = g1(light_dot_normal) * g1(eye_dot_normal);
+        // Fresnel, with metals reflecting their base color head on
+        let dielectric = ((self.ior - 1.) / (self.ior + 1.)).powi(2);
+        let f0 = WHITE * (dielectric * (1. - self.metallic)) + base_color * self.metallic;
+        let f = f0 + (WHITE - f0) * (1. - eye_dot_halfway).powi(5);
+
+        let specular = f * (d * g / (4. * light_dot_normal * eye_dot_normal));
+        let diffuse = (WHITE - f) * base_color * ((1. - self.metallic) / PI);
+        (diffuse + specular) * (light_dot_normal * PI)
+    }
+}
+
+// Material parameters that patterns can drive
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Parameter {
+    Ambient,
+    Diffuse,
+    Specular,
+    Shininess,
+    Reflective,
+    Transparency,
+    // Of the material's Pbr settings, if it has them
+    Metallic,
+    Roughness,
+}
+
+// Multiplies a parameter by the luminance of the pattern at each point, clamped to
+// [0, 1]. The material's own value is then the most the parameter reaches, e.g. a
+// reflective of 0.8 with a mask of black and white tiles makes only the white tiles
+// shiny
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParameterMap {
+    pub parameter: Parameter,
+    pub pattern: Pattern,
+}
+
+impl ParameterMap {
+    pub fn new(parameter: Parameter, pattern: Pattern) -> Self {
+        Self { parameter, pattern }
+    }
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Material {
@@ -27,10 +319,71 @@ impl Material {
             reflective: 0.,
             transparency: 0.,
             refractive_index: 1.,
+            dispersion: None,
             pattern: None,
+            thin_film: None,
+            pbr: None,
+            maps: vec![],
+            emission: Color::new(0., 0., 0.),
+            name: None,
+        }
+    }
+
+    // The refractive index seen by light of one color channel's wavelength, or by all
+    // of them together for None
+    pub fn refractive_index_for(&self, channel: Option<usize>) -> f64 {
+        match (self.dispersion, channel) {
+            (Some(dispersion), Some(channel)) => {
+                dispersion.refractive_index(self.refractive_index, WAVELENGTHS[channel])
+            }
+            _ => self.refractive_index,
         }
     }
 
+    // The surface's own color at the point, from the pattern if it has one
+    pub fn color_at(&self, object: &Object, point: Tuple) -> Color {
+        match self.pattern {
+            None => self.color,
+            Some(ref pattern) => pattern.pattern_at_object(object, point),
+        }
+    }
+
+    // The material with the maps applied at the point. Materials without maps are
+    // returned as they are, without copying
+    pub fn at(&self, object: &Object, point: Tuple) -> Cow<'_, Material> {
+        if self.maps.is_empty() {
+            return Cow::Borrowed(self);
+        }
+        let mut material = self.clone();
+        material.maps = vec![];
+        for map in self.maps.iter() {
+            let amount = map
+                .pattern
+                .pattern_at_object(object, point)
+                .luminance()
+                .clamp(0., 1.);
+            let pbr = material.pbr.as_mut();
+            let value = match map.parameter {
+                Parameter::Ambient => &mut material.ambient,
+                Parameter::Diffuse => &mut material.diffuse,
+                Parameter::Specular => &mut material.specular,
+                Parameter::Shininess => &mut material.shininess,
+                Parameter::Reflective => &mut material.reflective,
+                Parameter::Transparency => &mut material.transparency,
+                Parameter::Metallic => match pbr {
+                    Some(pbr) => &mut pbr.metallic,
+                    None => continue,
+                },
+                Parameter::Roughness => match pbr {
+                    Some(pbr) => &mut pbr.roughness,
+                    None => continue,
+                },
+            };
+            *value *= amount;
+        }
+        Cow::Owned(material)
+    }
+
     pub fn lighting(
         &self,
         light: &PointLight,
@@ -39,42 +392,150 @@ impl Material {
         eye_vector: Tuple,
         normal_vector: Tuple,
         in_shadow: bool,
+    ) -> Color {
+        let visibility = if in_shadow { BLACK } else { WHITE };
+        self.lighting_visible(light, object, point, eye_vector, normal_vector, visibility)
+    }
+
+    // Same as `lighting`, with `visibility` the fraction of the light that isn't
+    // shadowed in each channel, for lights that are partly hidden or shine through
+    // transparent objects
+    pub fn lighting_visible(
+        &self,
+        light: &PointLight,
+        object: &Object,
+        point: Tuple,
+        eye_vector: Tuple,
+        normal_vector: Tuple,
+        visibility: Color,
+    ) -> Color {
+        self.lighting_in(
+            ShadingPrecision::Double,
+            light,
+            object,
+            point,
+            eye_vector,
+            normal_vector,
+            visibility,
+        )
+    }
+
+    // Same as `lighting_visible`, but done in the given precision
+    #[allow(clippy::too_many_arguments)]
+    pub fn lighting_in(
+        &self,
+        precision: ShadingPrecision,
+        light: &PointLight,
+        object: &Object,
+        point: Tuple,
+        eye_vector: Tuple,
+        normal_vector: Tuple,
+        visibility: Color,
     ) -> Color {
         assert!(point.is_point());
         assert!(eye_vector.is_vector());
         assert!(normal_vector.is_vector());
+        if !self.maps.is_empty() {
+            let material = self.at(object, point);
+            return material.lighting_in(
+                precision,
+                light,
+                object,
+                point,
+                eye_vector,
+                normal_vector,
+                visibility,
+            );
+        }
 
-        let color = match self.pattern {
-            None => self.color,
-            Some(ref pattern) => pattern.pattern_at_object(object, point),
+        let color = self.color_at(object, point);
+        let shading = Shading {
+            color,
+            light,
+            point,
+            eye_vector,
+            normal_vector,
+            visibility,
         };
+        if let Some(pbr) = &self.pbr {
+            return self.physically_based(pbr, &shading);
+        }
+        match precision {
+            ShadingPrecision::Double => self.phong::<f64>(&shading),
+            ShadingPrecision::Single => self.phong::<f32>(&shading),
+        }
+    }
+
+    // Ambient plus the Cook-Torrance reflectance of the light, always in f64
+    fn physically_based(&self, pbr: &Pbr, shading: &Shading) -> Color {
+        let Shading {
+            color,
+            light,
+            point,
+            eye_vector,
+            normal_vector,
+            visibility,
+        } = *shading;
+
+        // Constant ambient contribution
+        let ambient = color * light.intensity * self.ambient;
+        // Spot lights only reach points within their cone, and attenuated lights
+        // dim with distance
+        let reach = light.reach(point);
+        if reach <= 0. || visibility == BLACK {
+            return ambient;
+        }
+        let light_vector = (light.position - point).normalize();
+        let reflectance = pbr.reflectance(color, light_vector, eye_vector, normal_vector);
+        ambient + light.intensity * visibility * reflectance * reach
+    }
+
+    // Phong shading done in F, the inputs being narrowed to it on the way in
+    fn phong<F: ShadingFloat>(&self, shading: &Shading) -> Color {
+        let vector = |t: Tuple| [t.x, t.y, t.z].map(F::of);
+        let rgb = |c: Color| [c.red, c.green, c.blue].map(F::of);
+        let dot = |a: [F; 3], b: [F; 3]| a[0] * b[0] + a[1] * b[1] + a[2] * b[2];
+        let zero = F::of(0.);
 
         // Haddamard multiplication of material and light
-        let effective_color = color * light.intensity;
+        let intensity = rgb(shading.light.intensity);
+        let color = rgb(shading.color);
+        let effective_color: [F; 3] = std::array::from_fn(|i| color[i] * intensity[i]);
         // Direction to light source
-        let light_vector = (light.position - point).normalize();
-        // Constant ambient contribution
-        let ambient = effective_color * self.ambient;
+        let to_light = vector(shading.light.position - shading.point);
+        let length = dot(to_light, to_light).sqrt();
+        let light_vector = to_light.map(|v| v / length);
+        let normal_vector = vector(shading.normal_vector);
         // If light is in front this quantity is positive else negative
-        let light_dot_normal = light_vector.dot(&normal_vector);
+        let light_dot_normal = dot(light_vector, normal_vector);
 
-        let mut diffuse = Color::new(0., 0., 0.);
-        let mut specular = Color::new(0., 0., 0.);
+        // Spot lights only reach points within their cone, and attenuated lights
+        // dim with distance
+        let reach = F::of(shading.light.reach(shading.point));
+        let visibility = rgb(shading.visibility);
 
-        if !in_shadow && light_dot_normal >= 0. {
+        // Constant ambient contribution
+        let mut shade = effective_color.map(|c| c * F::of(self.ambient));
+        if reach > zero && visibility != [zero; 3] && light_dot_normal >= zero {
             // Diffuse contribution depends on angle between light and point
-            diffuse = effective_color * self.diffuse * light_dot_normal;
-
-            let reflect_vector = -light_vector.reflect(&normal_vector);
-            let reflect_dot_eye = reflect_vector.dot(&eye_vector);
-
-            if reflect_dot_eye > 0. {
-                let factor = reflect_dot_eye.powf(self.shininess);
-                specular = light.intensity * self.specular * factor;
+            let diffuse = F::of(self.diffuse) * light_dot_normal * reach;
+            let reflect_vector: [F; 3] = std::array::from_fn(|i| {
+                F::of(2.) * light_dot_normal * normal_vector[i] - light_vector[i]
+            });
+            let reflect_dot_eye = dot(reflect_vector, vector(shading.eye_vector));
+            let specular = if reflect_dot_eye > zero {
+                F::of(self.specular) * reflect_dot_eye.powf(F::of(self.shininess)) * reach
+            } else {
+                zero
+            };
+            for i in 0..3 {
+                shade[i] = shade[i]
+                    + (effective_color[i] * diffuse + intensity[i] * specular) * visibility[i];
             }
         }
 
-        ambient + diffuse + specular
+        let [red, green, blue] = shade.map(F::to_f64);
+        Color::new(red, green, blue)
     }
 }
 
@@ -85,6 +546,26 @@ impl PartialEq for Material {
             && approx_eq!(f64, self.diffuse, other.diffuse, epsilon = EPSILON)
             && approx_eq!(f64, self.specular, other.specular, epsilon = EPSILON)
             && approx_eq!(f64, self.shininess, other.shininess, epsilon = EPSILON)
+            && approx_eq!(f64, self.reflective, other.reflective, epsilon = EPSILON)
+            && approx_eq!(
+                f64,
+                self.transparency,
+                other.transparency,
+                epsilon = EPSILON
+            )
+            && approx_eq!(
+                f64,
+                self.refractive_index,
+                other.refractive_index,
+                epsilon = EPSILON
+            )
+            && self.dispersion == other.dispersion
+            && self.pattern == other.pattern
+            && self.thin_film == other.thin_film
+            && self.pbr == other.pbr
+            && self.maps == other.maps
+            && self.emission == other.emission
+            && self.name == other.name
     }
 }
 
@@ -92,11 +573,13 @@ impl PartialEq for Material {
 mod tests {
     use crate::{
         color::{BLACK, WHITE},
+        light::SpotLight,
         pattern::StripePattern,
         shapes::Sphere,
     };
 
     use super::*;
+    use crate::assert_approx_eq;
     #[test]
     pub fn test_lighting() {
         let m = Material::new();
@@ -211,4 +694,242 @@ mod tests {
         );
         assert_eq!(result, Color::new(0., 0., 0.));
     }
+
+    #[test]
+    fn thin_film() {
+        // A film with no thickness reduces to the bare Fresnel reflectance of the substrate
+        let film = ThinFilm::new(0., 1.33);
+        let r = film.reflectance(1., 1., 1.5);
+        assert_eq!(r, Color::new(0.04, 0.04, 0.04));
+
+        // A film matching its surroundings is invisible
+        let film = ThinFilm::new(300., 1.);
+        assert_eq!(
+            film.reflectance(0.6, 1., 1.5),
+            ThinFilm::new(0., 1.).reflectance(0.6, 1., 1.5)
+        );
+
+        // Interference makes the reflectance depend on wavelength
+        let film = ThinFilm::new(300., 1.33);
+        let r = film.reflectance(1., 1., 1.);
+        assert!((r.red - r.green).abs() > 0.01);
+        assert!((r.green - r.blue).abs() > 0.01);
+        for c in [r.red, r.green, r.blue] {
+            assert!((0. ..=1.).contains(&c));
+        }
+    }
+
+    #[test]
+    fn pbr() {
+        let normal = Tuple::vector(0., 0., -1.);
+        let head_on = Tuple::vector(0., 0., -1.);
+        // A rough dielectric lit head on scatters what the Fresnel term lets through,
+        // plus a little highlight
+        let plastic = Pbr::new(0., 1.);
+        assert_approx_eq!(
+            plastic.reflectance(WHITE, head_on, head_on, normal),
+            Color::new(0.97, 0.97, 0.97)
+        );
+        assert_eq!(
+            plastic.reflectance(WHITE, Tuple::vector(0., 0., 1.), head_on, normal),
+            BLACK
+        );
+
+        // Metals only reflect, tinted by their base color, and smoother ones
+        // concentrate the light around the mirror direction
+        let red = Color::new(1., 0., 0.);
+        let light = Tuple::vector(0., 1., -1.).normalize();
+        let mirror = Tuple::vector(0., -1., -1.).normalize();
+        let off_mirror = Tuple::vector(0., 0.2, -1.).normalize();
+        let polished = Pbr::new(1., 0.2);
+        let brushed = Pbr::new(1., 0.6);
+        let peak = polished.reflectance(red, light, mirror, normal);
+        assert!(peak.green < 0.01 * peak.red);
+        assert!(peak.red > 10. * polished.reflectance(red, light, off_mirror, normal).red);
+        assert!(peak.red > brushed.reflectance(red, light, mirror, normal).red);
+
+        // Materials use it in place of Phong, at either precision
+        let mut m = Material::new();
+        m.pbr = Some(plastic);
+        let s = Sphere::new(None);
+        let light = PointLight::new(Tuple::point(0., 0., -10.), WHITE);
+        let point = Tuple::point(0., 0., 0.);
+        let lit = m.lighting(&light, &s, point, head_on, normal, false);
+        assert_approx_eq!(lit, Color::new(1.07, 1.07, 1.07));
+        let single = m.lighting_in(
+            ShadingPrecision::Single,
+            &light,
+            &s,
+            point,
+            head_on,
+            normal,
+            WHITE,
+        );
+        assert_eq!(single, lit);
+        assert_eq!(
+            m.lighting(&light, &s, point, head_on, normal, true),
+            Color::new(0.1, 0.1, 0.1)
+        );
+    }
+
+    #[test]
+    fn parameter_maps() {
+        // Shiny white stripes and matte black ones
+        let mut m = Material::new();
+        m.reflective = 0.8;
+        m.pbr = Some(Pbr::new(0., 1.));
+        let stripes = StripePattern::new(vec![WHITE, BLACK]);
+        m.maps = vec![
+            ParameterMap::new(Parameter::Reflective, stripes.clone()),
+            ParameterMap::new(Parameter::Roughness, StripePattern::new(vec![BLACK, WHITE])),
+            ParameterMap::new(Parameter::Diffuse, stripes),
+        ];
+        let s = Sphere::new(None);
+        let shiny = m.at(&s, Tuple::point(0.5, 0., 0.));
+        assert_eq!(shiny.reflective, 0.8);
+        assert_eq!(shiny.pbr.unwrap().roughness, 0.);
+        assert!(shiny.maps.is_empty());
+        let matte = m.at(&s, Tuple::point(1.5, 0., 0.));
+        assert_eq!(matte.reflective, 0.);
+        assert_eq!(matte.pbr.unwrap().roughness, 1.);
+        assert_eq!(matte.diffuse, 0.);
+        assert!(matches!(
+            Material::new().at(&s, Tuple::point(0., 0., 0.)),
+            Cow::Borrowed(_)
+        ));
+
+        // Lighting uses the mapped parameters
+        let mut m = Material::new();
+        m.maps = vec![ParameterMap::new(
+            Parameter::Diffuse,
+            StripePattern::new(vec![WHITE, BLACK]),
+        )];
+        // Head on, so the specular is 0.9 and the diffuse whatever the map leaves
+        let (eye, normal) = (Tuple::vector(0., 0., -1.), Tuple::vector(0., 0., -1.));
+        let light = |x: f64| PointLight::new(Tuple::point(x, 0., -10.), WHITE);
+        let lit = |x: f64| m.lighting(&light(x), &s, Tuple::point(x, 0., 0.), eye, normal, false);
+        assert_eq!(lit(0.5), Color::new(1.9, 1.9, 1.9));
+        assert_eq!(lit(1.5), Color::new(1., 1., 1.));
+        let single = m.lighting_in(
+            ShadingPrecision::Single,
+            &light(1.5),
+            &s,
+            Tuple::point(1.5, 0., 0.),
+            eye,
+            normal,
+            WHITE,
+        );
+        assert_eq!(single, lit(1.5));
+    }
+
+    #[test]
+    fn dispersion() {
+        // Crown glass bends blue light more than red
+        let mut m = Material::new();
+        m.refractive_index = 1.5168;
+        m.dispersion = Some(Dispersion::from_abbe(1.5168, 64.17));
+        let [red, green, blue] = [0, 1, 2].map(|c| m.refractive_index_for(Some(c)));
+        assert!(red < green && green < blue);
+        assert_eq!(m.refractive_index_for(None), 1.5168);
+        let d = m.dispersion.unwrap();
+        let abbe = 0.5168 / (d.refractive_index(1.5168, 486.1) - d.refractive_index(1.5168, 656.3));
+        assert_approx_eq!(abbe, 64.17);
+        assert_approx_eq!(d.refractive_index(1.5168, 589.3), 1.5168);
+    }
+
+    #[test]
+    fn spot_lighting() {
+        let s = Sphere::new(None);
+        let m = Material::new();
+        let eye = Tuple::vector(0., 0., -1.);
+        let normal = Tuple::vector(0., 0., -1.);
+        let light = SpotLight::new(
+            Tuple::point(0., 0., -10.),
+            Tuple::vector(0., 0., 1.),
+            Color::new(1., 1., 1.),
+            0.1,
+            0.2,
+        );
+        // Inside the cone it is just a point light
+        let lit = m.lighting(&light, &s, Tuple::point(0., 0., 0.), eye, normal, false);
+        assert_eq!(lit, Color::new(1.9, 1.9, 1.9));
+        // Outside only the ambient term is left
+        let point = Tuple::point(5., 0., 0.);
+        let dark = m.lighting(&light, &s, point, eye, normal, false);
+        assert_eq!(dark, Color::new(0.1, 0.1, 0.1));
+        let single = m.lighting_in(
+            ShadingPrecision::Single,
+            &light,
+            &s,
+            point,
+            eye,
+            normal,
+            WHITE,
+        );
+        assert_eq!(single, dark);
+    }
+
+    #[test]
+    fn single_precision_lighting() {
+        let s = Sphere::new(None);
+        let mut m = Material::new();
+        m.pattern = Some(StripePattern::new(vec![WHITE, BLACK]));
+        let normal = Tuple::vector(0., 0., -1.);
+        let point = Tuple::point(0.3, 0., 0.);
+        for (eye, light, visibility) in [
+            (
+                Tuple::vector(0., 0., -1.),
+                Tuple::point(0., 0., -10.),
+                WHITE,
+            ),
+            (
+                Tuple::vector(0., -2_f64.sqrt() / 2., -2_f64.sqrt() / 2.),
+                Tuple::point(0., 10., -10.),
+                WHITE,
+            ),
+            (Tuple::vector(0., 0., -1.), Tuple::point(0., 0., 10.), WHITE),
+            (
+                Tuple::vector(0., 0., -1.),
+                Tuple::point(0., 0., -10.),
+                BLACK,
+            ),
+            (
+                Tuple::vector(0., 0., -1.),
+                Tuple::point(0., 0., -10.),
+                Color::new(0.5, 0.2, 1.),
+            ),
+        ] {
+            let light = PointLight::new(light, Color::new(1., 0.9, 0.8));
+            let double = m.lighting_visible(&light, &s, point, eye, normal, visibility);
+            let single = m.lighting_in(
+                ShadingPrecision::Single,
+                &light,
+                &s,
+                point,
+                eye,
+                normal,
+                visibility,
+            );
+            assert_eq!(single, double);
+        }
+    }
+
+    #[test]
+    fn equality() {
+        let mut plastic = Material::new();
+        plastic.color = Color::new(1., 0., 0.);
+        let mut glass = plastic.clone();
+        assert_eq!(glass, plastic);
+
+        glass.transparency = 0.9;
+        glass.refractive_index = 1.5;
+        assert_ne!(glass, plastic);
+
+        let mut named = plastic.clone();
+        named.name = Some(String::from("red"));
+        assert_ne!(named, plastic);
+        let mut patterned = plastic.clone();
+        patterned.pattern = Some(StripePattern::new(vec![WHITE, BLACK]));
+        assert_ne!(patterned, plastic);
+    }
 }
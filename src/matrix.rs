@@ -1,4 +1,4 @@
-use crate::EPSILON;
+use crate::approx::ApproxEq;
 use std::ops::{Index, Mul};
 
 use float_cmp::approx_eq;
@@ -31,17 +31,17 @@ impl Matrix {
 
     pub fn identity(rows: usize) -> Self {
         let mut values = vec![vec![0.; rows]; rows];
-        for i in 0..rows {
-            values[i][i] = 1.;
+        for (i, row) in values.iter_mut().enumerate() {
+            row[i] = 1.;
         }
         Matrix { values }
     }
 
     pub fn transpose(&self) -> Self {
         let mut values = vec![vec![0.; self.rows()]; self.cols()];
-        for i in 0..self.rows() {
-            for j in 0..self.cols() {
-                values[j][i] = self.values[i][j];
+        for (i, row) in self.values.iter().enumerate() {
+            for (j, value) in row.iter().enumerate() {
+                values[j][i] = *value;
             }
         }
 
@@ -91,7 +91,7 @@ impl Matrix {
             })
             .collect();
 
-        Matrix { values: values }
+        Matrix { values }
     }
 
     fn minor(&self, row: usize, col: usize) -> f64 {
@@ -236,14 +236,11 @@ impl<'a> Mul<&'a Matrix> for &'a Matrix {
     fn mul(self, rhs: Self) -> Self::Output {
         assert_eq!(self.cols(), rhs.rows());
         let mut values = vec![vec![0.; rhs.cols()]; self.rows()];
-        for row in 0..self.rows() {
-            for col in 0..rhs.cols() {
-                let mut val = 0.;
-
+        for (row, out) in values.iter_mut().enumerate() {
+            for (col, val) in out.iter_mut().enumerate() {
                 for i in 0..self.cols() {
-                    val += self.values[row][i] * rhs.values[i][col];
+                    *val += self.values[row][i] * rhs.values[i][col];
                 }
-                values[row][col] = val;
             }
         }
 
@@ -259,7 +256,7 @@ impl<'a> Mul<&'a Matrix> for Matrix {
     }
 }
 
-impl<'a> Mul<Tuple> for &'a Matrix {
+impl Mul<Tuple> for &Matrix {
     type Output = Tuple;
     fn mul(self, rhs: Tuple) -> Self::Output {
         let result = self * &Matrix::new(&vec![vec![rhs.x], vec![rhs.y], vec![rhs.z], vec![rhs.w]]);
@@ -283,26 +280,21 @@ impl Index<(usize, usize)> for Matrix {
     }
 }
 
+impl ApproxEq for Matrix {
+    fn approx_eq_eps(&self, other: &Self, epsilon: f64) -> bool {
+        self.values.len() == other.values.len()
+            && self.values.iter().zip(other.values.iter()).all(|(a, b)| {
+                a.len() == b.len()
+                    && a.iter()
+                        .zip(b.iter())
+                        .all(|(a, b)| a.approx_eq_eps(b, epsilon))
+            })
+    }
+}
+
 impl PartialEq for Matrix {
     fn eq(&self, other: &Self) -> bool {
-        if self.values.len() != other.values.len() || self.values[0].len() != other.values[0].len()
-        {
-            false
-        } else {
-            for row in 0..self.values.len() {
-                for col in 0..self.values[0].len() {
-                    if !approx_eq!(
-                        f64,
-                        self.values[row][col],
-                        other.values[row][col],
-                        epsilon = EPSILON
-                    ) {
-                        return false;
-                    }
-                }
-            }
-            true
-        }
+        self.approx_eq(other)
     }
 }
 
@@ -0,0 +1,291 @@
+// Polygon meshes that are refined before rendering. A coarse cage is subdivided with
+// Catmull-Clark a few times, optionally displaced, then turned into a smooth
+// TriangleMesh
+use crate::{
+    material::Material, pattern::Displacement, shape::Object, shapes::TriangleMesh, tuple::Tuple,
+};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mesh {
+    pub vertices: Vec<Tuple>,
+    // Indices into the vertices, each face with three or more
+    pub faces: Vec<Vec<usize>>,
+}
+
+fn average(points: impl Iterator<Item = Tuple>) -> Tuple {
+    let (sum, count) = points.fold((Tuple::vector(0., 0., 0.), 0), |(sum, count), p| {
+        (sum + p, count + 1)
+    });
+    sum / count as f64
+}
+
+fn edge(a: usize, b: usize) -> (usize, usize) {
+    (a.min(b), a.max(b))
+}
+
+impl Mesh {
+    pub fn new(vertices: Vec<Tuple>, faces: Vec<Vec<usize>>) -> Self {
+        assert!(faces
+            .iter()
+            .all(|face| face.len() >= 3 && face.iter().all(|i| *i < vertices.len())));
+        Self { vertices, faces }
+    }
+
+    // The cube from -1 to 1, with faces wound like Triangle expects
+    pub fn cube() -> Self {
+        let vertices = (0..8)
+            .map(|i| {
+                let coordinate = |bit| if i & bit == 0 { -1. } else { 1. };
+                Tuple::point(coordinate(1), coordinate(2), coordinate(4))
+            })
+            .collect();
+        let faces = vec![
+            vec![0, 1, 3, 2],
+            vec![4, 6, 7, 5],
+            vec![0, 4, 5, 1],
+            vec![2, 3, 7, 6],
+            vec![0, 2, 6, 4],
+            vec![1, 5, 7, 3],
+        ];
+        Mesh::new(vertices, faces)
+    }
+
+    // One level of Catmull-Clark. Every face becomes one quad per corner, and the
+    // mesh shrinks towards a smooth limit surface. Open edges are smoothed as curves
+    // of their own, so they don't pull away from the rest of the boundary
+    pub fn catmull_clark(&self) -> Mesh {
+        let face_points: Vec<Tuple> = self
+            .faces
+            .iter()
+            .map(|face| average(face.iter().map(|i| self.vertices[*i])))
+            .collect();
+
+        // Edges in the order they're first seen, so the output doesn't depend on hashing
+        let mut edge_index = HashMap::new();
+        let mut edge_faces: Vec<((usize, usize), Vec<usize>)> = vec![];
+        for (f, face) in self.faces.iter().enumerate() {
+            for (i, a) in face.iter().enumerate() {
+                let key = edge(*a, face[(i + 1) % face.len()]);
+                let index = *edge_index.entry(key).or_insert_with(|| {
+                    edge_faces.push((key, vec![]));
+                    edge_faces.len() - 1
+                });
+                edge_faces[index].1.push(f);
+            }
+        }
+
+        // New vertices: the moved originals, then face points, then edge points
+        let mut vertices =
+            Vec::with_capacity(self.vertices.len() + self.faces.len() + edge_faces.len());
+        let mut vertex_faces: Vec<Vec<usize>> = vec![vec![]; self.vertices.len()];
+        let mut vertex_edges = vec![vec![]; self.vertices.len()];
+        for &((a, b), ref faces) in edge_faces.iter() {
+            vertex_faces[a].extend(faces);
+            vertex_faces[b].extend(faces);
+            vertex_edges[a].push((b, faces.len()));
+            vertex_edges[b].push((a, faces.len()));
+        }
+        for (i, &p) in self.vertices.iter().enumerate() {
+            let boundary: Vec<Tuple> = vertex_edges[i]
+                .iter()
+                .filter(|(_, faces)| *faces == 1)
+                .map(|(other, _)| self.vertices[*other])
+                .collect();
+            let moved = match boundary.as_slice() {
+                [] if !vertex_faces[i].is_empty() => {
+                    // Each face was counted once per edge it shares with the vertex
+                    let mut faces = vertex_faces[i].clone();
+                    faces.sort_unstable();
+                    faces.dedup();
+                    let n = faces.len() as f64;
+                    let f = average(faces.iter().map(|f| face_points[*f]));
+                    let r = average(
+                        vertex_edges[i]
+                            .iter()
+                            .map(|(other, _)| (p + self.vertices[*other]) / 2.),
+                    );
+                    (f + r * 2. + p * (n - 3.)) / n
+                }
+                [a, b] => (*a + p * 6. + *b) / 8.,
+                _ => p,
+            };
+            vertices.push(moved);
+        }
+        let face_start = vertices.len();
+        vertices.extend(face_points.iter());
+
+        let edge_start = vertices.len();
+        for ((a, b), faces) in edge_faces.iter() {
+            let (pa, pb) = (self.vertices[*a], self.vertices[*b]);
+            vertices.push(match faces.as_slice() {
+                [f1, f2] => (pa + pb + face_points[*f1] + face_points[*f2]) / 4.,
+                _ => (pa + pb) / 2.,
+            });
+        }
+        let edge_point = |a, b| edge_start + edge_index[&edge(a, b)];
+
+        let mut faces = vec![];
+        for (f, face) in self.faces.iter().enumerate() {
+            for (i, &v) in face.iter().enumerate() {
+                let next = face[(i + 1) % face.len()];
+                let previous = face[(i + face.len() - 1) % face.len()];
+                faces.push(vec![
+                    v,
+                    edge_point(v, next),
+                    face_start + f,
+                    edge_point(previous, v),
+                ]);
+            }
+        }
+        Mesh { vertices, faces }
+    }
+
+    pub fn subdivide(&self, levels: usize) -> Mesh {
+        (0..levels).fold(self.clone(), |mesh, _| mesh.catmull_clark())
+    }
+
+    // Normals at each vertex, the area weighted average of the faces around it
+    pub fn vertex_normals(&self) -> Vec<Tuple> {
+        // Each face's fan with the normal of each triangle, worked out in parallel
+        // with the rayon feature
+        let fan = |face: &Vec<usize>| -> Vec<([usize; 3], Tuple)> {
+            let p1 = self.vertices[face[0]];
+            (1..face.len() - 1)
+                .map(|i| {
+                    let (p2, p3) = (self.vertices[face[i]], self.vertices[face[i + 1]]);
+                    // Same orientation as the normal Triangle works out
+                    ([face[0], face[i], face[i + 1]], (p3 - p1).cross(&(p2 - p1)))
+                })
+                .collect()
+        };
+        #[cfg(feature = "rayon")]
+        let fans: Vec<_> = self.faces.par_iter().map(fan).collect();
+        #[cfg(not(feature = "rayon"))]
+        let fans: Vec<_> = self.faces.iter().map(fan).collect();
+
+        let mut normals = vec![Tuple::vector(0., 0., 0.); self.vertices.len()];
+        for (corners, normal) in fans.into_iter().flatten() {
+            for corner in corners {
+                normals[corner] += normal;
+            }
+        }
+        normals.into_iter().map(|n| n.normalize()).collect()
+    }
+
+    // Every vertex moved out along its normal by the height of the displacement at
+    // it, so the texture changes the silhouette and not only the shading.
+    // Subdivide first, as detail finer than the faces is lost
+    pub fn displace(&self, displacement: &Displacement) -> Mesh {
+        let normals = self.vertex_normals();
+        let vertices = self
+            .vertices
+            .iter()
+            .zip(normals)
+            .map(|(vertex, normal)| *vertex + normal * displacement.height(*vertex))
+            .collect();
+        Mesh::new(vertices, self.faces.clone())
+    }
+
+    // One smooth triangle mesh covering every face, split into fans
+    pub fn triangles(&self, material: Option<Material>) -> Object {
+        let fans = self
+            .faces
+            .iter()
+            .flat_map(|face| (1..face.len() - 1).map(|i| [face[0], face[i], face[i + 1]]));
+        TriangleMesh::new(
+            self.vertices.clone(),
+            Some(self.vertex_normals()),
+            None,
+            fans,
+            material,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        assert_approx_eq,
+        color::{BLACK, WHITE},
+        matrix::Matrix,
+        pattern::StripePattern,
+        shape::ShapeType,
+    };
+
+    #[test]
+    fn subdivide_cube() {
+        let cube = Mesh::cube().catmull_clark();
+        assert_eq!(cube.vertices.len(), 8 + 6 + 12);
+        assert_eq!(cube.faces.len(), 24);
+        assert!(cube.faces.iter().all(|face| face.len() == 4));
+
+        // Corners move in to 5/9, face centres stay put and edges move to 3/4
+        let corner = 5. / 9.;
+        assert_approx_eq!(cube.vertices[7], Tuple::point(corner, corner, corner));
+        assert!(cube.vertices.contains(&Tuple::point(0., 0., 1.)));
+        assert!(cube.vertices.contains(&Tuple::point(0.75, 0.75, 0.)));
+
+        assert_eq!(Mesh::cube().subdivide(2).faces.len(), 96);
+    }
+
+    #[test]
+    fn boundary() {
+        // The edges of an open quad are smoothed as curves of their own, staying flat
+        let quad = Mesh::new(
+            vec![
+                Tuple::point(0., 0., 0.),
+                Tuple::point(1., 0., 0.),
+                Tuple::point(1., 0., 1.),
+                Tuple::point(0., 0., 1.),
+            ],
+            vec![vec![0, 1, 2, 3]],
+        );
+        let refined = quad.catmull_clark();
+        assert_eq!(refined.faces.len(), 4);
+        assert_approx_eq!(refined.vertices[0], Tuple::point(0.125, 0., 0.125));
+        assert!(refined.vertices.contains(&Tuple::point(0.5, 0., 0.)));
+    }
+
+    #[test]
+    fn displace() {
+        // Raised where x is further than 0.5 from the middle
+        let mut pattern = StripePattern::new(vec![BLACK, WHITE]);
+        pattern.set_transform(&Matrix::translation(-0.5, 0., 0.));
+        let displacement = Displacement::new(pattern, 0.25);
+        let cube = Mesh::cube().subdivide(1);
+        let displaced = cube.displace(&displacement);
+        assert_eq!(displaced.faces, cube.faces);
+        let normals = cube.vertex_normals();
+        for ((before, after), normal) in cube.vertices.iter().zip(&displaced.vertices).zip(normals)
+        {
+            if before.x.abs() > 0.5 {
+                assert_approx_eq!(*after, *before + normal * 0.25);
+            } else {
+                assert_eq!(after, before);
+            }
+        }
+    }
+
+    #[test]
+    fn smooth_triangles() {
+        let cube = Mesh::cube().subdivide(1);
+        let object = cube.triangles(None);
+        let ShapeType::TriangleMesh(ref mesh) = object.shape else {
+            panic!("not a mesh");
+        };
+        assert_eq!(mesh.faces().len(), 48);
+
+        // Normals point out of the mesh
+        let normals = cube.vertex_normals();
+        let third = 1. / 3_f64.sqrt();
+        assert_approx_eq!(normals[7], Tuple::vector(third, third, third));
+        for (vertex, normal) in cube.vertices.iter().zip(normals.iter()) {
+            let outward = Tuple::vector(vertex.x, vertex.y, vertex.z);
+            assert!(normal.dot(&outward) > 0.);
+        }
+    }
+}
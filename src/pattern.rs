@@ -1,39 +1,200 @@
-use crate::{color::Color, matrix::Matrix, shape::Object, tuple::Tuple};
+use crate::{
+    color::{Color, WHITE},
+    matrix::Matrix,
+    shape::{Object, ShapeType},
+    tuple::Tuple,
+    PI,
+};
+#[cfg(feature = "noise")]
 use noise::{NoiseFn, Seedable, SuperSimplex};
-use rand::Rng;
+use std::sync::Arc;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum PatternType {
     StripePattern(StripePattern),
     GradientPattern(GradientPattern),
     RingPattern(RingPattern),
     CheckerPattern(CheckerPattern),
     RadialGradientPattern(RadialGradientPattern),
+    PolkaDotPattern(PolkaDotPattern),
+    HexagonPattern(HexagonPattern),
+    VertexColorPattern(VertexColorPattern),
+    BlendPattern(BlendPattern),
+    BrickPattern(BrickPattern),
+    FractalPattern(FractalPattern),
+    AlignCheckPattern(AlignCheckPattern),
+    CubeMapPattern(CubeMapPattern),
+    #[cfg(feature = "noise")]
+    MarblePattern(MarblePattern),
+    #[cfg(feature = "noise")]
+    WoodPattern(WoodPattern),
     TestPattern(TestPattern),
 }
 
+// What fills a slot of a pattern: a plain color, or another pattern, so that e.g.
+// stripes can alternate between gradients. Nested patterns are placed by their own
+// transform within the outer pattern's space
+#[derive(Debug, Clone, PartialEq)]
+pub enum Paint {
+    Color(Color),
+    Pattern(Box<Pattern>),
+}
+
+impl Paint {
+    pub fn at(&self, point: Tuple) -> Color {
+        match self {
+            Paint::Color(color) => *color,
+            Paint::Pattern(pattern) => pattern.pattern_at(pattern.transform.inverse() * point),
+        }
+    }
+}
+
+// A pattern read as a height map, for moving surfaces out along their normals: the
+// luminance of its color at a point, clamped to [0, 1], times `scale`
+#[derive(Debug, Clone)]
+pub struct Displacement {
+    pattern: Arc<Pattern>,
+    pub scale: f64,
+}
+
+impl Displacement {
+    pub fn new(pattern: Pattern, scale: f64) -> Self {
+        Displacement {
+            pattern: Arc::new(pattern),
+            scale,
+        }
+    }
+
+    pub fn height(&self, point: Tuple) -> f64 {
+        self.pattern.color_at(point).luminance().clamp(0., 1.) * self.scale
+    }
+}
+
+// Patterns can't be compared, so only displacements by the same pattern are equal
+impl PartialEq for Displacement {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.pattern, &other.pattern) && self.scale == other.scale
+    }
+}
+
+impl From<Color> for Paint {
+    fn from(color: Color) -> Self {
+        Paint::Color(color)
+    }
+}
+
+impl From<Pattern> for Paint {
+    fn from(pattern: Pattern) -> Self {
+        Paint::Pattern(Box::new(pattern))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Pattern {
     transform: Matrix,
+    #[cfg(feature = "noise")]
     perturb: Option<SuperSimplex>,
-    pattern_type: PatternType,
+    mapping: Option<UvMapping>,
+    // Shared between clones, so objects given copies of one material don't each copy
+    // e.g. a fractal's palette
+    pattern_type: Arc<PatternType>,
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        #[cfg(feature = "noise")]
+        if self.perturb.map(|noise| noise.seed()) != other.perturb.map(|noise| noise.seed()) {
+            return false;
+        }
+        self.transform == other.transform
+            && self.mapping == other.mapping
+            && (Arc::ptr_eq(&self.pattern_type, &other.pattern_type)
+                || self.pattern_type == other.pattern_type)
+    }
+}
+
+// Ways of wrapping a flat pattern around a shape. The surface point in object space
+// becomes (u, v), and the pattern is evaluated at the point (u, 0, v), so patterns
+// drawn on the xz plane lie on the surface
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UvMapping {
+    // Longitude and latitude around the origin, both from 0 to 1, with v = 1 at +y
+    Spherical,
+    // The xz plane, repeating every unit
+    Planar,
+    // Around the y axis from 0 to 1, with v repeating every unit up the axis
+    Cylindrical,
+    // Each face of the unit cube from 0 to 1 both ways, laid side by side along u
+    // in the order +x, -x, +y, -y, +z, -z, so the face is the integer part of u.
+    // See CubeMapPattern to give each face its own pattern
+    Cube,
+}
+
+impl UvMapping {
+    pub fn uv(&self, point: Tuple) -> (f64, f64) {
+        // Fraction of a turn around the y axis, 0 behind at -z and increasing towards +x
+        let around = |point: Tuple| 1. - (point.x.atan2(point.z) / (2. * PI) + 0.5);
+        match self {
+            UvMapping::Spherical => {
+                let radius = Tuple::vector(point.x, point.y, point.z).magnitude();
+                let phi = (point.y / radius).clamp(-1., 1.).acos();
+                (around(point), 1. - phi / PI)
+            }
+            UvMapping::Planar => (point.x.rem_euclid(1.), point.z.rem_euclid(1.)),
+            UvMapping::Cylindrical => (around(point), point.y.rem_euclid(1.)),
+            UvMapping::Cube => {
+                let (x, y, z) = (point.x, point.y, point.z);
+                let largest = x.abs().max(y.abs()).max(z.abs());
+                let (face, u, v) = if largest == x {
+                    (0., 1. - z, y + 1.)
+                } else if largest == -x {
+                    (1., z + 1., y + 1.)
+                } else if largest == y {
+                    (2., x + 1., 1. - z)
+                } else if largest == -y {
+                    (3., x + 1., z + 1.)
+                } else if largest == z {
+                    (4., x + 1., y + 1.)
+                } else {
+                    (5., 1. - x, y + 1.)
+                };
+                // Kept inside the face, which rounding could otherwise leave
+                let inside = |t: f64| (t / 2.).clamp(0., 1. - 1e-9);
+                (face + inside(u), inside(v))
+            }
+        }
+    }
 }
 
 impl Pattern {
     fn new(pattern_type: PatternType) -> Self {
         Self {
             transform: Matrix::identity(4),
+            #[cfg(feature = "noise")]
             perturb: None,
-            pattern_type,
+            mapping: None,
+            pattern_type: Arc::new(pattern_type),
         }
     }
 
+    #[cfg(feature = "noise")]
     pub fn perturb(&mut self) {
-        self.perturb = Some(SuperSimplex::new().set_seed(rand::thread_rng().gen::<u32>()));
+        self.perturb = Some(SuperSimplex::new().set_seed(crate::rng::random_u32()));
+    }
+
+    // Evaluates the pattern at the object's surface coordinates instead of at the
+    // point in space. The transform then moves and scales the pattern across (u, v)
+    pub fn set_mapping(&mut self, mapping: UvMapping) {
+        self.mapping = Some(mapping);
+    }
+
+    pub fn mapping(&self) -> Option<UvMapping> {
+        self.mapping
     }
 
     fn pattern_at(&self, point: Tuple) -> Color {
         assert!(point.is_point());
+        #[cfg(feature = "noise")]
         let point = match self.perturb {
             Some(simplex) => {
                 let simplex = 0.15 * simplex.get([point.x, point.y, point.z]);
@@ -42,18 +203,47 @@ impl Pattern {
             None => point,
         };
 
-        match &self.pattern_type {
+        match &*self.pattern_type {
             PatternType::StripePattern(stripe) => stripe.color_at(point),
             PatternType::GradientPattern(gradient) => gradient.color_at(point),
             PatternType::RingPattern(ring) => ring.color_at(point),
             PatternType::CheckerPattern(checker) => checker.color_at(point),
             PatternType::RadialGradientPattern(radial_gradient) => radial_gradient.color_at(point),
+            PatternType::PolkaDotPattern(polka_dot) => polka_dot.color_at(point),
+            PatternType::HexagonPattern(hexagon) => hexagon.color_at(point),
+            PatternType::BlendPattern(blend) => blend.color_at(point),
+            PatternType::BrickPattern(brick) => brick.color_at(point),
+            PatternType::FractalPattern(fractal) => fractal.color_at(point),
+            PatternType::AlignCheckPattern(align_check) => align_check.color_at(point),
+            PatternType::CubeMapPattern(cube_map) => cube_map.color_at(point),
+            #[cfg(feature = "noise")]
+            PatternType::MarblePattern(marble) => marble.color_at(point),
+            #[cfg(feature = "noise")]
+            PatternType::WoodPattern(wood) => wood.color_at(point),
+            // Needs the object, see pattern_at_object
+            PatternType::VertexColorPattern(_) => WHITE,
             PatternType::TestPattern(_) => Color::new(point.x, point.y, point.z),
         }
     }
 
     pub fn pattern_at_object(&self, object: &Object, point: Tuple) -> Color {
-        let object_point = object.transform.inverse() * point;
+        let object_point = object.inverse() * point;
+        if let PatternType::VertexColorPattern(_) = *self.pattern_type {
+            return VertexColorPattern::color_at_object(object, object_point);
+        }
+        self.color_at(object_point)
+    }
+
+    // The color at a point in the space of whatever the pattern is on, going through
+    // the mapping and transform. Vertex colors need an object and are white here
+    pub fn color_at(&self, object_point: Tuple) -> Color {
+        let object_point = match self.mapping {
+            Some(mapping) => {
+                let (u, v) = mapping.uv(object_point);
+                Tuple::point(u, 0., v)
+            }
+            None => object_point,
+        };
         let pattern_point = self.transform.inverse() * object_point;
         self.pattern_at(pattern_point)
     }
@@ -63,93 +253,563 @@ impl Pattern {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct StripePattern {
-    pub colors: Vec<Color>,
+    pub colors: Vec<Paint>,
 }
 
 impl StripePattern {
-    pub fn new(colors: Vec<Color>) -> Pattern {
+    pub fn new<T: Into<Paint>>(colors: Vec<T>) -> Pattern {
+        let colors = colors.into_iter().map(Into::into).collect();
         Pattern::new(PatternType::StripePattern(StripePattern { colors }))
     }
 
     pub fn color_at(&self, point: Tuple) -> Color {
-        self.colors[point.x.floor().abs() as usize % self.colors.len()]
+        self.colors[point.x.floor().abs() as usize % self.colors.len()].at(point)
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct GradientPattern {
-    pub a: Color,
-    pub b: Color,
+    pub a: Paint,
+    pub b: Paint,
 }
 
 impl GradientPattern {
-    pub fn new(a: Color, b: Color) -> Pattern {
-        Pattern::new(PatternType::GradientPattern(GradientPattern { a, b }))
+    pub fn new(a: impl Into<Paint>, b: impl Into<Paint>) -> Pattern {
+        Pattern::new(PatternType::GradientPattern(GradientPattern {
+            a: a.into(),
+            b: b.into(),
+        }))
     }
 
     pub fn color_at(&self, point: Tuple) -> Color {
-        self.a + (self.b - self.a) * (point.x - point.x.floor())
+        let (a, b) = (self.a.at(point), self.b.at(point));
+        a + (b - a) * (point.x - point.x.floor())
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct RingPattern {
-    pub colors: Vec<Color>,
+    pub colors: Vec<Paint>,
 }
 
 impl RingPattern {
-    pub fn new(colors: Vec<Color>) -> Pattern {
+    pub fn new<T: Into<Paint>>(colors: Vec<T>) -> Pattern {
+        let colors = colors.into_iter().map(Into::into).collect();
         Pattern::new(PatternType::RingPattern(RingPattern { colors }))
     }
 
     pub fn color_at(&self, point: Tuple) -> Color {
-        self.colors
-            [(point.x * point.x + point.z * point.z).sqrt().floor() as usize % self.colors.len()]
+        let ring = (point.x * point.x + point.z * point.z).sqrt().floor() as usize;
+        self.colors[ring % self.colors.len()].at(point)
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct CheckerPattern {
-    pub a: Color,
-    pub b: Color,
+    pub a: Paint,
+    pub b: Paint,
 }
 
 impl CheckerPattern {
-    pub fn new(a: Color, b: Color) -> Pattern {
-        Pattern::new(PatternType::CheckerPattern(CheckerPattern { a, b }))
+    pub fn new(a: impl Into<Paint>, b: impl Into<Paint>) -> Pattern {
+        Pattern::new(PatternType::CheckerPattern(CheckerPattern {
+            a: a.into(),
+            b: b.into(),
+        }))
     }
 
     pub fn color_at(&self, point: Tuple) -> Color {
         match (point.x.floor() + point.y.floor() + point.z.floor()) as i64 % 2 {
-            0 => self.a,
-            _ => self.b,
+            0 => self.a.at(point),
+            _ => self.b.at(point),
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct RadialGradientPattern {
-    pub a: Color,
-    pub b: Color,
+    pub a: Paint,
+    pub b: Paint,
 }
 
 impl RadialGradientPattern {
-    pub fn new(a: Color, b: Color) -> Pattern {
+    pub fn new(a: impl Into<Paint>, b: impl Into<Paint>) -> Pattern {
         Pattern::new(PatternType::RadialGradientPattern(RadialGradientPattern {
-            a,
-            b,
+            a: a.into(),
+            b: b.into(),
         }))
     }
 
     pub fn color_at(&self, point: Tuple) -> Color {
         let dist = (point.x * point.x + point.z * point.z).sqrt();
-        self.a + (self.b - self.a) * (dist - dist.floor())
+        let (a, b) = (self.a.at(point), self.b.at(point));
+        a + (b - a) * (dist - dist.floor())
+    }
+}
+
+// Dots of color a on a background of color b, centered on a lattice of the given spacing
+#[derive(Debug, Clone, PartialEq)]
+pub struct PolkaDotPattern {
+    pub radius: f64,
+    pub spacing: f64,
+    pub a: Paint,
+    pub b: Paint,
+}
+
+impl PolkaDotPattern {
+    pub fn new(radius: f64, spacing: f64, a: impl Into<Paint>, b: impl Into<Paint>) -> Pattern {
+        Pattern::new(PatternType::PolkaDotPattern(PolkaDotPattern {
+            radius,
+            spacing,
+            a: a.into(),
+            b: b.into(),
+        }))
+    }
+
+    pub fn color_at(&self, point: Tuple) -> Color {
+        let offset = |v: f64| v - self.spacing * (v / self.spacing).round();
+        let (dx, dy, dz) = (offset(point.x), offset(point.y), offset(point.z));
+        if dx * dx + dy * dy + dz * dz <= self.radius * self.radius {
+            self.a.at(point)
+        } else {
+            self.b.at(point)
+        }
+    }
+}
+
+// Tiles the xz plane with unit hexagons. With three or more colors, neighbouring
+// hexagons are always different colors
+#[derive(Debug, Clone, PartialEq)]
+pub struct HexagonPattern {
+    pub colors: Vec<Paint>,
+}
+
+impl HexagonPattern {
+    pub fn new<T: Into<Paint>>(colors: Vec<T>) -> Pattern {
+        let colors = colors.into_iter().map(Into::into).collect();
+        Pattern::new(PatternType::HexagonPattern(HexagonPattern { colors }))
+    }
+
+    // Axial coordinates of the pointy-topped hexagon containing the point
+    fn cell(point: Tuple) -> (i64, i64) {
+        let q = 3_f64.sqrt() / 3. * point.x - point.z / 3.;
+        let r = 2. / 3. * point.z;
+        let s = -q - r;
+
+        let (mut rq, mut rr, rs) = (q.round(), r.round(), s.round());
+        let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+        if dq > dr && dq > ds {
+            rq = -rr - rs;
+        } else if dr > ds {
+            rr = -rq - rs;
+        }
+        (rq as i64, rr as i64)
+    }
+
+    pub fn color_at(&self, point: Tuple) -> Color {
+        let (q, r) = HexagonPattern::cell(point);
+        self.colors[(q - r).rem_euclid(self.colors.len() as i64) as usize].at(point)
+    }
+}
+
+// How the b layer of a blend combines with the a layer beneath it, per channel, as
+// in image editors
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum BlendMode {
+    // b on its own
+    #[default]
+    Mix,
+    // Darkens, like two slides projected through each other
+    Multiply,
+    // Lightens, like two projectors pointed at the same screen
+    Screen,
+    // Multiply where a is dark and screen where it is light, adding contrast
+    Overlay,
+    Add,
+}
+
+impl BlendMode {
+    fn channel(&self, a: f64, b: f64) -> f64 {
+        match self {
+            BlendMode::Mix => b,
+            BlendMode::Multiply => a * b,
+            BlendMode::Screen => 1. - (1. - a) * (1. - b),
+            BlendMode::Overlay if a < 0.5 => 2. * a * b,
+            BlendMode::Overlay => 1. - 2. * (1. - a) * (1. - b),
+            BlendMode::Add => a + b,
+        }
+    }
+
+    pub fn blend(&self, a: Color, b: Color) -> Color {
+        Color::new(
+            self.channel(a.red, b.red),
+            self.channel(a.green, b.green),
+            self.channel(a.blue, b.blue),
+        )
+    }
+}
+
+// Blends two patterns evaluated at the same point, with the blended result laid
+// `weight` of the way over a
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlendPattern {
+    pub a: Paint,
+    pub b: Paint,
+    pub weight: f64,
+    pub mode: BlendMode,
+}
+
+impl BlendPattern {
+    // Mixes the patterns, `weight` of the way from a to b
+    pub fn new(a: impl Into<Paint>, b: impl Into<Paint>, weight: f64) -> Pattern {
+        BlendPattern::with_mode(a, b, weight, BlendMode::Mix)
     }
+
+    pub fn with_mode(
+        a: impl Into<Paint>,
+        b: impl Into<Paint>,
+        weight: f64,
+        mode: BlendMode,
+    ) -> Pattern {
+        Pattern::new(PatternType::BlendPattern(BlendPattern {
+            a: a.into(),
+            b: b.into(),
+            weight,
+            mode,
+        }))
+    }
+
+    pub fn color_at(&self, point: Tuple) -> Color {
+        let a = self.a.at(point);
+        a * (1. - self.weight) + self.mode.blend(a, self.b.at(point)) * self.weight
+    }
+}
+
+// Bricks laid on the xz plane in rows along x, `width` by `height` including the
+// mortar around them, and each row shifted by `offset` of a brick from the one
+// before it, e.g. 0.5 for a running bond. With a UV mapping, rows run along u
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrickPattern {
+    pub width: f64,
+    pub height: f64,
+    pub mortar_width: f64,
+    pub offset: f64,
+    pub brick: Paint,
+    pub mortar: Paint,
+}
+
+impl BrickPattern {
+    pub fn new(
+        width: f64,
+        height: f64,
+        mortar_width: f64,
+        offset: f64,
+        brick: impl Into<Paint>,
+        mortar: impl Into<Paint>,
+    ) -> Pattern {
+        assert!(width > 0. && height > 0. && mortar_width >= 0.);
+        Pattern::new(PatternType::BrickPattern(BrickPattern {
+            width,
+            height,
+            mortar_width,
+            offset,
+            brick: brick.into(),
+            mortar: mortar.into(),
+        }))
+    }
+
+    pub fn color_at(&self, point: Tuple) -> Color {
+        let row = (point.z / self.height).floor();
+        let x = (point.x - row * self.offset * self.width).rem_euclid(self.width);
+        let z = point.z.rem_euclid(self.height);
+        // Half the mortar is on each side of a brick
+        let half = self.mortar_width / 2.;
+        let in_mortar = |t: f64, size: f64| t < half || t >= size - half;
+        if in_mortar(x, self.width) || in_mortar(z, self.height) {
+            self.mortar.at(point)
+        } else {
+            self.brick.at(point)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fractal {
+    // The point is c in z -> z^2 + c, starting from z = 0
+    Mandelbrot,
+    // The point is the starting z in z -> z^2 + c for the given c = re + im i
+    Julia { re: f64, im: f64 },
+}
+
+// Orbits escape once |z| passes this. Far beyond 2 so the smoothed escape time is
+// accurate
+const ESCAPE_RADIUS: f64 = 256.;
+
+// A fractal on the xz plane, with x the real part and z the imaginary part. Points
+// that escape are colored by how many iterations that took, smoothed, stepping
+// through the palette once per iteration and around again. Points still bounded
+// after `iterations` iterations are the `inside` color
+#[derive(Debug, Clone, PartialEq)]
+pub struct FractalPattern {
+    pub fractal: Fractal,
+    pub iterations: usize,
+    pub palette: Vec<Paint>,
+    pub inside: Paint,
+}
+
+impl FractalPattern {
+    pub fn new<T: Into<Paint>>(
+        fractal: Fractal,
+        iterations: usize,
+        palette: Vec<T>,
+        inside: impl Into<Paint>,
+    ) -> Pattern {
+        assert!(!palette.is_empty());
+        Pattern::new(PatternType::FractalPattern(FractalPattern {
+            fractal,
+            iterations,
+            palette: palette.into_iter().map(Into::into).collect(),
+            inside: inside.into(),
+        }))
+    }
+
+    // The smoothed number of iterations taken to escape, if the orbit does
+    pub fn escape_time(&self, x: f64, y: f64) -> Option<f64> {
+        let ((mut zr, mut zi), (cr, ci)) = match self.fractal {
+            Fractal::Mandelbrot => ((0., 0.), (x, y)),
+            Fractal::Julia { re, im } => ((x, y), (re, im)),
+        };
+        for n in 0..self.iterations {
+            let magnitude = zr * zr + zi * zi;
+            if magnitude > ESCAPE_RADIUS * ESCAPE_RADIUS {
+                return Some(n as f64 + 1. - magnitude.sqrt().ln().log2());
+            }
+            (zr, zi) = (zr * zr - zi * zi + cr, 2. * zr * zi + ci);
+        }
+        None
+    }
+
+    pub fn color_at(&self, point: Tuple) -> Color {
+        let Some(time) = self.escape_time(point.x, point.z) else {
+            return self.inside.at(point);
+        };
+        let time = time.max(0.);
+        let count = self.palette.len();
+        let step = time.floor() as usize;
+        let a = self.palette[step % count].at(point);
+        let b = self.palette[(step + 1) % count].at(point);
+        a + (b - a) * (time - time.floor())
+    }
+}
+
+// For checking how a UV mapping lays a texture on a surface: the main color on
+// each unit square of the xz plane, i.e. of (u, v), with a differently colored
+// square in each corner. Upper is towards +v and right towards +u
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlignCheckPattern {
+    pub main: Paint,
+    pub upper_left: Paint,
+    pub upper_right: Paint,
+    pub bottom_left: Paint,
+    pub bottom_right: Paint,
+}
+
+impl AlignCheckPattern {
+    pub fn new(
+        main: impl Into<Paint>,
+        upper_left: impl Into<Paint>,
+        upper_right: impl Into<Paint>,
+        bottom_left: impl Into<Paint>,
+        bottom_right: impl Into<Paint>,
+    ) -> Pattern {
+        Pattern::new(PatternType::AlignCheckPattern(AlignCheckPattern {
+            main: main.into(),
+            upper_left: upper_left.into(),
+            upper_right: upper_right.into(),
+            bottom_left: bottom_left.into(),
+            bottom_right: bottom_right.into(),
+        }))
+    }
+
+    pub fn color_at(&self, point: Tuple) -> Color {
+        let (u, v) = (point.x.rem_euclid(1.), point.z.rem_euclid(1.));
+        let paint = match (u < 0.2, u > 0.8, v < 0.2, v > 0.8) {
+            (true, _, _, true) => &self.upper_left,
+            (_, true, _, true) => &self.upper_right,
+            (true, _, true, _) => &self.bottom_left,
+            (_, true, true, _) => &self.bottom_right,
+            _ => &self.main,
+        };
+        paint.at(point)
+    }
+}
+
+// A pattern for each face of a cube, in the order of UvMapping::Cube: +x, -x, +y,
+// -y, +z, -z. The face patterns see that face's (u, v) from 0 to 1
+#[derive(Debug, Clone, PartialEq)]
+pub struct CubeMapPattern {
+    pub faces: [Paint; 6],
 }
 
+impl CubeMapPattern {
+    // Comes with the cube mapping already set
+    pub fn new<T: Into<Paint>>(faces: [T; 6]) -> Pattern {
+        let mut pattern = Pattern::new(PatternType::CubeMapPattern(CubeMapPattern {
+            faces: faces.map(Into::into),
+        }));
+        pattern.set_mapping(UvMapping::Cube);
+        pattern
+    }
+
+    pub fn color_at(&self, point: Tuple) -> Color {
+        let face = point.x.floor();
+        let on_face = Tuple::point(point.x - face, point.y, point.z);
+        self.faces[face.rem_euclid(6.) as usize].at(on_face)
+    }
+}
+
+// Sum of the magnitudes of `octaves` layers of noise, each at twice the frequency
+// and half the amplitude of the last, so from 0 to just under 2
+#[cfg(feature = "noise")]
+fn turbulence(noise: &SuperSimplex, point: Tuple, octaves: usize) -> f64 {
+    let mut total = 0.;
+    let mut scale = 1.;
+    for _ in 0..octaves {
+        total += noise
+            .get([point.x * scale, point.y * scale, point.z * scale])
+            .abs()
+            / scale;
+        scale *= 2.;
+    }
+    total
+}
+
+// Veins of color b through color a, running across x once a unit and distorted by
+// turbulence. More octaves add finer detail to the veins, and the turbulence sets
+// how far they wander
+#[cfg(feature = "noise")]
+#[derive(Debug, Clone)]
+pub struct MarblePattern {
+    pub a: Paint,
+    pub b: Paint,
+    pub octaves: usize,
+    pub turbulence: f64,
+    noise: SuperSimplex,
+}
+
+// The noise is the same for every pattern
+#[cfg(feature = "noise")]
+impl PartialEq for MarblePattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.a == other.a
+            && self.b == other.b
+            && self.octaves == other.octaves
+            && self.turbulence == other.turbulence
+    }
+}
+
+#[cfg(feature = "noise")]
+impl MarblePattern {
+    pub fn new(
+        a: impl Into<Paint>,
+        b: impl Into<Paint>,
+        octaves: usize,
+        turbulence: f64,
+    ) -> Pattern {
+        Pattern::new(PatternType::MarblePattern(MarblePattern {
+            a: a.into(),
+            b: b.into(),
+            octaves,
+            turbulence,
+            noise: SuperSimplex::new(),
+        }))
+    }
+
+    pub fn color_at(&self, point: Tuple) -> Color {
+        let offset = self.turbulence * turbulence(&self.noise, point, self.octaves);
+        // Sharp dark veins between broad light bands
+        let vein = (1. - ((point.x + offset) * PI).sin().abs()).powi(3);
+        let (a, b) = (self.a.at(point), self.b.at(point));
+        a + (b - a) * vein
+    }
+}
+
+// Growth rings around the y axis, one a unit, shading from color a at the start of
+// each ring to color b at its end. Turbulence warps the rings like grain
+#[cfg(feature = "noise")]
 #[derive(Debug, Clone)]
+pub struct WoodPattern {
+    pub a: Paint,
+    pub b: Paint,
+    pub octaves: usize,
+    pub turbulence: f64,
+    noise: SuperSimplex,
+}
+
+#[cfg(feature = "noise")]
+impl PartialEq for WoodPattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.a == other.a
+            && self.b == other.b
+            && self.octaves == other.octaves
+            && self.turbulence == other.turbulence
+    }
+}
+
+#[cfg(feature = "noise")]
+impl WoodPattern {
+    pub fn new(
+        a: impl Into<Paint>,
+        b: impl Into<Paint>,
+        octaves: usize,
+        turbulence: f64,
+    ) -> Pattern {
+        Pattern::new(PatternType::WoodPattern(WoodPattern {
+            a: a.into(),
+            b: b.into(),
+            octaves,
+            turbulence,
+            noise: SuperSimplex::new(),
+        }))
+    }
+
+    pub fn color_at(&self, point: Tuple) -> Color {
+        let distance = (point.x * point.x + point.z * point.z).sqrt()
+            + self.turbulence * turbulence(&self.noise, point, self.octaves);
+        let t = distance - distance.floor();
+        let (a, b) = (self.a.at(point), self.b.at(point));
+        a + (b - a) * t
+    }
+}
+
+// Interpolates the vertex colors of the triangle or mesh it's applied to, as loaded
+// from a PLY file. Shapes without vertex colors are white
+#[derive(Debug, Clone, PartialEq)]
+pub struct VertexColorPattern;
+
+impl VertexColorPattern {
+    pub fn new() -> Pattern {
+        Pattern::new(PatternType::VertexColorPattern(VertexColorPattern))
+    }
+
+    fn color_at_object(object: &Object, object_point: Tuple) -> Color {
+        match object.shape {
+            ShapeType::Triangle(ref triangle) => match triangle.colors.as_deref() {
+                Some(&[c1, c2, c3]) => {
+                    let (u, v) = triangle.barycentric(object_point);
+                    c1 * (1. - u - v) + c2 * u + c3 * v
+                }
+                None => WHITE,
+            },
+            ShapeType::TriangleMesh(ref mesh) => mesh.color_at(object_point).unwrap_or(WHITE),
+            _ => WHITE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct TestPattern;
 
 impl TestPattern {
@@ -160,12 +820,13 @@ impl TestPattern {
 
 #[cfg(test)]
 mod tests {
-    use crate::color::{BLACK, WHITE};
+    use crate::color::{BLACK, BLUE, GREEN, RED, WHITE};
     use crate::material::Material;
-    use crate::shapes::Sphere;
+    use crate::shapes::{Cube, Sphere, Triangle};
 
     use super::StripePattern;
     use super::*;
+    use crate::assert_approx_eq;
 
     #[test]
     fn stripe() {
@@ -195,7 +856,7 @@ mod tests {
     #[test]
     fn stripe_at() {
         let mut object = Sphere::new(None);
-        object.transform = Matrix::scaling(2., 2., 2.);
+        object.set_transform(&Matrix::scaling(2., 2., 2.));
         let pattern = StripePattern::new(vec![WHITE, BLACK]);
         let c = pattern.pattern_at_object(&object, Tuple::point(1.5, 0., 0.));
         assert_eq!(c, WHITE);
@@ -206,7 +867,7 @@ mod tests {
         assert_eq!(c, WHITE);
 
         let mut object = Sphere::new(None);
-        object.transform = Matrix::scaling(2., 2., 2.);
+        object.set_transform(&Matrix::scaling(2., 2., 2.));
         let mut pattern = StripePattern::new(vec![WHITE, BLACK]);
         pattern.set_transform(&Matrix::scaling(0.5, 0.5, 0.5));
         let c = pattern.pattern_at_object(&object, Tuple::point(2.5, 0., 0.));
@@ -262,7 +923,7 @@ mod tests {
         let mut material = Material::new();
         material.pattern = Some(pattern.clone());
         let mut s = Sphere::new(Some(material));
-        s.transform = Matrix::scaling(2., 2., 2.);
+        s.set_transform(&Matrix::scaling(2., 2., 2.));
         assert_eq!(
             pattern.pattern_at_object(&s, Tuple::point(2., 3., 4.)),
             Color::new(1., 1.5, 2.)
@@ -283,10 +944,341 @@ mod tests {
         let mut material = Material::new();
         material.pattern = Some(pattern.clone());
         let mut s = Sphere::new(Some(material));
-        s.transform = Matrix::scaling(2., 2., 2.);
+        s.set_transform(&Matrix::scaling(2., 2., 2.));
         assert_eq!(
             pattern.pattern_at_object(&s, Tuple::point(2., 3., 4.)),
             Color::new(0.5, 0.75, 1.)
         );
     }
+
+    #[test]
+    fn polka_dot_pattern() {
+        let pattern = PolkaDotPattern::new(0.25, 1., WHITE, BLACK);
+        assert_eq!(pattern.pattern_at(Tuple::point(0., 0., 0.)), WHITE);
+        assert_eq!(pattern.pattern_at(Tuple::point(0.2, 0., 0.)), WHITE);
+        assert_eq!(pattern.pattern_at(Tuple::point(0.3, 0., 0.)), BLACK);
+        assert_eq!(pattern.pattern_at(Tuple::point(0.2, 0., 0.2)), BLACK);
+        assert_eq!(pattern.pattern_at(Tuple::point(2.1, -1., 2.9)), WHITE);
+        assert_eq!(pattern.pattern_at(Tuple::point(0.5, 0.5, 0.5)), BLACK);
+    }
+
+    #[test]
+    fn vertex_colors() {
+        let mut triangle = Triangle::new(
+            Tuple::point(0., 1., 0.),
+            Tuple::point(-1., 0., 0.),
+            Tuple::point(1., 0., 0.),
+            None,
+        );
+        let pattern = VertexColorPattern::new();
+        let point = Tuple::point(-0.2, 0.3, 0.);
+        assert_eq!(pattern.pattern_at_object(&triangle, point), WHITE);
+
+        let red = Color::new(1., 0., 0.);
+        let blue = Color::new(0., 0., 1.);
+        if let ShapeType::Triangle(ref mut t) = triangle.shape {
+            t.colors = Some(Box::new([WHITE, red, blue]));
+        }
+        assert_eq!(
+            pattern.pattern_at_object(&triangle, Tuple::point(-1., 0., 0.)),
+            red
+        );
+        assert_eq!(
+            pattern.pattern_at_object(&triangle, point),
+            Color::new(0.75, 0.3, 0.55)
+        );
+        assert_eq!(
+            pattern.pattern_at_object(&Sphere::new(None), Tuple::point(0., 0., -1.)),
+            WHITE
+        );
+    }
+
+    #[test]
+    fn nested_patterns() {
+        // Stripes alternating between a gradient along z and plain black
+        let mut gradient = GradientPattern::new(WHITE, BLACK);
+        gradient.set_transform(&Matrix::rotation_y(-PI / 2.));
+        let pattern = StripePattern::new(vec![Paint::from(gradient), Paint::from(BLACK)]);
+        assert_eq!(
+            pattern.pattern_at(Tuple::point(0.5, 0., 0.25)),
+            Color::new(0.75, 0.75, 0.75)
+        );
+        assert_eq!(pattern.pattern_at(Tuple::point(1.5, 0., 0.25)), BLACK);
+
+        // Nested patterns are scaled along with the outer one
+        let mut checkers = CheckerPattern::new(StripePattern::new(vec![WHITE, BLACK]), BLACK);
+        checkers.set_transform(&Matrix::scaling(4., 4., 4.));
+        let sphere = Sphere::new(None);
+        assert_eq!(
+            checkers.pattern_at_object(&sphere, Tuple::point(1., 0., 0.)),
+            WHITE
+        );
+        assert_eq!(
+            checkers.pattern_at_object(&sphere, Tuple::point(5., 0., 0.)),
+            BLACK
+        );
+    }
+
+    #[test]
+    fn blend_pattern() {
+        let mut horizontal = StripePattern::new(vec![WHITE, BLACK]);
+        horizontal.set_transform(&Matrix::rotation_y(PI / 2.));
+        let vertical = StripePattern::new(vec![WHITE, BLACK]);
+        let blend = BlendPattern::new(horizontal, vertical, 0.5);
+        let grey = Color::new(0.5, 0.5, 0.5);
+        assert_eq!(blend.pattern_at(Tuple::point(0.5, 0., -0.5)), WHITE);
+        assert_approx_eq!(blend.pattern_at(Tuple::point(1.5, 0., -0.5)), grey);
+        assert_approx_eq!(blend.pattern_at(Tuple::point(0.5, 0., 0.5)), grey);
+        assert_eq!(blend.pattern_at(Tuple::point(1.5, 0., 0.5)), BLACK);
+
+        let blend = BlendPattern::new(WHITE, BLACK, 0.25);
+        assert_eq!(
+            blend.pattern_at(Tuple::point(3., 2., 1.)),
+            Color::new(0.75, 0.75, 0.75)
+        );
+    }
+
+    #[test]
+    fn blend_modes() {
+        let (a, b) = (Color::new(0.2, 0.5, 0.8), Color::new(0.5, 0.5, 1.));
+        let blend = |mode: BlendMode| mode.blend(a, b);
+        assert_approx_eq!(blend(BlendMode::Mix), b);
+        assert_approx_eq!(blend(BlendMode::Multiply), Color::new(0.1, 0.25, 0.8));
+        assert_approx_eq!(blend(BlendMode::Screen), Color::new(0.6, 0.75, 1.));
+        assert_approx_eq!(blend(BlendMode::Overlay), Color::new(0.2, 0.5, 1.));
+        assert_approx_eq!(blend(BlendMode::Add), Color::new(0.7, 1., 1.8));
+
+        // Multiplying by the stripes darkens the gradient only on the black ones
+        let gradient = GradientPattern::new(BLACK, WHITE);
+        let stripes = StripePattern::new(vec![WHITE, BLACK]);
+        let pattern = BlendPattern::with_mode(gradient, stripes, 1., BlendMode::Multiply);
+        let grey = Color::new(0.5, 0.5, 0.5);
+        assert_approx_eq!(pattern.pattern_at(Tuple::point(0.5, 0., 0.)), grey);
+        assert_eq!(pattern.pattern_at(Tuple::point(1.5, 0., 0.)), BLACK);
+        let pattern = BlendPattern::with_mode(BLACK, WHITE, 0.5, BlendMode::Screen);
+        assert_approx_eq!(pattern.pattern_at(Tuple::point(1.5, 0., 0.)), grey);
+    }
+
+    #[test]
+    fn brick_pattern() {
+        let red = Color::new(0.6, 0.2, 0.1);
+        let pattern = BrickPattern::new(2., 1., 0.1, 0.5, red, WHITE);
+        assert_eq!(pattern.pattern_at(Tuple::point(1., 0., 0.5)), red);
+        assert_eq!(pattern.pattern_at(Tuple::point(0.02, 0., 0.5)), WHITE);
+        assert_eq!(pattern.pattern_at(Tuple::point(1.99, 0., 0.5)), WHITE);
+        assert_eq!(pattern.pattern_at(Tuple::point(1., 0., 0.98)), WHITE);
+        assert_eq!(pattern.pattern_at(Tuple::point(0., 5., -0.5)), red);
+
+        // The next row is shifted by half a brick, so its joint is in the middle of
+        // the bricks below
+        assert_eq!(pattern.pattern_at(Tuple::point(1., 0., 1.5)), WHITE);
+        assert_eq!(pattern.pattern_at(Tuple::point(0., 0., 1.5)), red);
+        assert_eq!(pattern.pattern_at(Tuple::point(-1., 0., -0.5)), WHITE);
+    }
+
+    #[test]
+    fn fractal_pattern() {
+        let red = Color::new(1., 0., 0.);
+        let pattern = FractalPattern::new(Fractal::Mandelbrot, 50, vec![WHITE, red], BLACK);
+        // Inside the main cardioid and the period 2 bulb
+        assert_eq!(pattern.pattern_at(Tuple::point(0., 0., 0.)), BLACK);
+        assert_eq!(pattern.pattern_at(Tuple::point(-1., 0., 0.1)), BLACK);
+        assert_ne!(pattern.pattern_at(Tuple::point(1., 0., 1.)), BLACK);
+        assert_ne!(pattern.pattern_at(Tuple::point(-2.5, 0., 0.)), BLACK);
+
+        if let PatternType::FractalPattern(ref fractal) = *pattern.pattern_type {
+            // Escape is quicker further out
+            let near = fractal.escape_time(0.5, 0.5).unwrap();
+            let far = fractal.escape_time(2., 2.).unwrap();
+            assert!(far < near);
+            assert_eq!(fractal.escape_time(-0.1, 0.1), None);
+        }
+
+        // c = 0 leaves the unit disc bounded and everything outside escapes
+        let julia = FractalPattern::new(Fractal::Julia { re: 0., im: 0. }, 50, vec![WHITE], BLACK);
+        assert_eq!(julia.pattern_at(Tuple::point(0.7, 0., -0.7)), BLACK);
+        assert_eq!(julia.pattern_at(Tuple::point(0.8, 0., -0.8)), WHITE);
+    }
+
+    #[test]
+    fn align_check_pattern() {
+        let (yellow, cyan) = (Color::new(1., 1., 0.), Color::new(0., 1., 1.));
+        let pattern = AlignCheckPattern::new(WHITE, RED, yellow, GREEN, cyan);
+        for ((u, v), expected) in [
+            ((0.5, 0.5), WHITE),
+            ((0.1, 0.9), RED),
+            ((0.9, 0.9), yellow),
+            ((0.1, 0.1), GREEN),
+            ((0.9, 0.1), cyan),
+            ((3.9, -0.9), cyan),
+        ] {
+            assert_eq!(pattern.pattern_at(Tuple::point(u, 0., v)), expected);
+        }
+    }
+
+    #[test]
+    fn cube_map_pattern() {
+        // Every face white with a different color in its upper left corner
+        let corners = [
+            RED,
+            GREEN,
+            BLUE,
+            BLACK,
+            Color::new(1., 1., 0.),
+            Color::new(0., 1., 1.),
+        ];
+        let faces =
+            corners.map(|corner| AlignCheckPattern::new(WHITE, corner, WHITE, WHITE, WHITE));
+        let pattern = CubeMapPattern::new(faces);
+        let cube = Cube::new(None);
+        for (point, expected) in [
+            ((1., 0.9, 0.9), RED),
+            ((-1., 0.9, -0.9), GREEN),
+            ((-0.9, 1., -0.9), BLUE),
+            ((-0.9, -1., 0.9), BLACK),
+            ((-0.9, 0.9, 1.), corners[4]),
+            ((0.9, 0.9, -1.), corners[5]),
+            ((0., 0., -1.), WHITE),
+            ((0.9, 0.9, 1.), WHITE),
+        ] {
+            let (x, y, z) = point;
+            assert_eq!(
+                pattern.pattern_at_object(&cube, Tuple::point(x, y, z)),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "noise")]
+    fn marble_pattern() {
+        // Without turbulence, straight veins where sin(x * PI) is 0
+        let pattern = MarblePattern::new(WHITE, BLACK, 4, 0.);
+        assert_eq!(pattern.pattern_at(Tuple::point(0.5, 3., 2.)), WHITE);
+        assert_eq!(pattern.pattern_at(Tuple::point(1., -1., 5.)), BLACK);
+
+        let pattern = MarblePattern::new(WHITE, BLACK, 4, 0.5);
+        let mut colors = vec![];
+        for i in 0..50 {
+            let color = pattern.pattern_at(Tuple::point(0.5, i as f64 * 0.37, 0.));
+            assert!((0. ..=1.).contains(&color.red));
+            colors.push(color);
+        }
+        // The veins wander away from x = 0.5
+        assert!(colors.iter().any(|c| *c != WHITE));
+        assert!(colors.windows(2).any(|w| w[0] != w[1]));
+    }
+
+    #[test]
+    #[cfg(feature = "noise")]
+    fn wood_pattern() {
+        let pattern = WoodPattern::new(WHITE, BLACK, 3, 0.);
+        assert_eq!(pattern.pattern_at(Tuple::point(0., 0., 0.)), WHITE);
+        assert_eq!(
+            pattern.pattern_at(Tuple::point(0., 7., 1.25)),
+            Color::new(0.75, 0.75, 0.75)
+        );
+        assert_eq!(pattern.pattern_at(Tuple::point(2., 0., 0.)), WHITE);
+
+        // Turbulence moves the rings, but only by up to twice its amount
+        let warped = WoodPattern::new(WHITE, BLACK, 3, 0.1);
+        let mut moved = false;
+        for i in 0..20 {
+            let point = Tuple::point(1.5, i as f64 * 0.3, 0.);
+            let (straight, grain) = (pattern.pattern_at(point), warped.pattern_at(point));
+            assert!((grain.red - straight.red).abs() <= 0.2);
+            moved |= grain != straight;
+        }
+        assert!(moved);
+    }
+
+    #[test]
+    fn uv_mappings() {
+        let spherical = [
+            ((0., 0., -1.), (0., 0.5)),
+            ((1., 0., 0.), (0.25, 0.5)),
+            ((0., 0., 1.), (0.5, 0.5)),
+            ((-1., 0., 0.), (0.75, 0.5)),
+            ((0., 1., 0.), (0.5, 1.)),
+            ((0., -1., 0.), (0.5, 0.)),
+        ];
+        for ((x, y, z), uv) in spherical {
+            let (u, v) = UvMapping::Spherical.uv(Tuple::point(x, y, z));
+            assert_approx_eq!(u.rem_euclid(1.), uv.0);
+            assert_approx_eq!(v, uv.1);
+        }
+        assert_approx_eq!(UvMapping::Planar.uv(Tuple::point(-0.25, 0.5, 1.75)).0, 0.75);
+        assert_approx_eq!(
+            UvMapping::Cylindrical.uv(Tuple::point(1., 2.25, 0.)).1,
+            0.25
+        );
+
+        let cube = [
+            ((1., 0.5, -0.5), (0.75, 0.75)),
+            ((-1., 0.5, -0.5), (1.25, 0.75)),
+            ((-0.5, 1., -0.5), (2.25, 0.75)),
+            ((-0.5, -1., 0.5), (3.25, 0.75)),
+            ((-0.5, 0.5, 1.), (4.25, 0.75)),
+            ((0.5, -0.5, -1.), (5.25, 0.25)),
+        ];
+        for ((x, y, z), (u, v)) in cube {
+            let uv = UvMapping::Cube.uv(Tuple::point(x, y, z));
+            assert_approx_eq!(uv.0, u);
+            assert_approx_eq!(uv.1, v);
+        }
+    }
+
+    #[test]
+    fn mapped_checkers() {
+        // 8 checks around the sphere and 4 from pole to pole
+        let mut pattern = CheckerPattern::new(WHITE, BLACK);
+        pattern.set_mapping(UvMapping::Spherical);
+        pattern.set_transform(&Matrix::scaling(1. / 8., 1., 1. / 4.));
+        let mut sphere = Sphere::new(None);
+        sphere.set_transform(&Matrix::scaling(2., 2., 2.));
+        let at = |x: f64, y: f64, z: f64| {
+            let p = Tuple::vector(x, y, z).normalize() * 2.;
+            pattern.pattern_at_object(&sphere, Tuple::point(p.x, p.y, p.z))
+        };
+        let (a, b) = (at(0.1, 0.1, -1.), at(1.5, 0.1, -1.));
+        assert_ne!(a, b);
+        // A quarter turn is two checks along, and so the same color
+        assert_eq!(at(1., 0.1, 0.1), a);
+        // Checks keep their size towards the poles instead of pinching
+        assert_ne!(at(0.1, 1., -0.2), at(0.1, -1., -0.2));
+    }
+
+    #[test]
+    fn hexagon_pattern() {
+        let red = Color::new(1., 0., 0.);
+        let pattern = HexagonPattern::new(vec![WHITE, BLACK, red]);
+        assert_eq!(pattern.pattern_at(Tuple::point(0., 0., 0.)), WHITE);
+        assert_eq!(pattern.pattern_at(Tuple::point(0.5, 5., 0.5)), WHITE);
+
+        // Each of the six neighbours of the center hexagon differs from it
+        let width = 3_f64.sqrt();
+        let neighbours = [
+            (width, 0.),
+            (-width, 0.),
+            (width / 2., 1.5),
+            (-width / 2., 1.5),
+            (width / 2., -1.5),
+            (-width / 2., -1.5),
+        ];
+        for (x, z) in neighbours {
+            assert_ne!(pattern.pattern_at(Tuple::point(x, 0., z)), WHITE);
+        }
+        assert_eq!(pattern.pattern_at(Tuple::point(width, 0., 0.)), BLACK);
+        assert_eq!(pattern.pattern_at(Tuple::point(-width, 0., 0.)), red);
+    }
+
+    #[test]
+    fn clones_share_pattern() {
+        let pattern = FractalPattern::new(Fractal::Mandelbrot, 50, vec![RED, GREEN], BLACK);
+        let mut material = Material::new();
+        material.pattern = Some(pattern);
+        let copy = material.clone();
+        let (a, b) = (material.pattern.unwrap(), copy.pattern.unwrap());
+        assert!(Arc::ptr_eq(&a.pattern_type, &b.pattern_type));
+    }
 }
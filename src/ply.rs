@@ -0,0 +1,473 @@
+// Loads Stanford PLY meshes, ASCII or binary, into a TriangleMesh. Vertex positions
+// are required, while normals (nx, ny, nz) make the triangles smooth and colors (red,
+// green, blue) are interpolated across them with a VertexColorPattern. Faces with
+// more than three vertices are split into a fan of triangles. With the rayon feature
+// records are parsed and faces split in parallel
+use crate::{
+    color::Color, material::Material, pattern::VertexColorPattern, shape::Object,
+    shapes::TriangleMesh, tuple::Tuple,
+};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scalar {
+    Char,
+    UChar,
+    Short,
+    UShort,
+    Int,
+    UInt,
+    Float,
+    Double,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "char" | "int8" => Ok(Scalar::Char),
+            "uchar" | "uint8" => Ok(Scalar::UChar),
+            "short" | "int16" => Ok(Scalar::Short),
+            "ushort" | "uint16" => Ok(Scalar::UShort),
+            "int" | "int32" => Ok(Scalar::Int),
+            "uint" | "uint32" => Ok(Scalar::UInt),
+            "float" | "float32" => Ok(Scalar::Float),
+            "double" | "float64" => Ok(Scalar::Double),
+            _ => Err(format!("unknown property type {}", name)),
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::Char | Scalar::UChar => 1,
+            Scalar::Short | Scalar::UShort => 2,
+            Scalar::Int | Scalar::UInt | Scalar::Float => 4,
+            Scalar::Double => 8,
+        }
+    }
+
+    // Integer colors span the whole range of their type
+    fn color_scale(self) -> f64 {
+        match self {
+            Scalar::UChar => 255.,
+            Scalar::UShort => 65535.,
+            _ => 1.,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Property {
+    Scalar(String, Scalar),
+    List(String, Scalar, Scalar),
+}
+
+#[derive(Debug, Clone)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+// Reads the values of the body one at a time, whatever the format
+struct Values<'a> {
+    format: Format,
+    body: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Values<'a> {
+    fn next(&mut self, scalar: Scalar) -> Result<f64, String> {
+        if self.format == Format::Ascii {
+            let rest = &self.body[self.position..];
+            let start = rest
+                .iter()
+                .position(|b| !b.is_ascii_whitespace())
+                .ok_or("unexpected end of file")?;
+            let length = rest[start..]
+                .iter()
+                .position(|b| b.is_ascii_whitespace())
+                .unwrap_or(rest.len() - start);
+            self.position += start + length;
+            let token =
+                std::str::from_utf8(&rest[start..start + length]).map_err(|_| "invalid number")?;
+            return token
+                .parse()
+                .map_err(|_| format!("invalid number {}", token));
+        }
+
+        let size = scalar.size();
+        let mut bytes = self
+            .body
+            .get(self.position..self.position + size)
+            .ok_or("unexpected end of file")?
+            .to_vec();
+        self.position += size;
+        if self.format == Format::BinaryLittleEndian {
+            bytes.reverse();
+        }
+        let mut array = [0; 8];
+        array[8 - size..].copy_from_slice(&bytes);
+        let value = u64::from_be_bytes(array);
+        Ok(match scalar {
+            Scalar::Char => value as u8 as i8 as f64,
+            Scalar::UChar => value as u8 as f64,
+            Scalar::Short => value as u16 as i16 as f64,
+            Scalar::UShort => value as u16 as f64,
+            Scalar::Int => value as u32 as i32 as f64,
+            Scalar::UInt => value as u32 as f64,
+            Scalar::Float => f32::from_bits(value as u32) as f64,
+            Scalar::Double => f64::from_bits(value),
+        })
+    }
+}
+
+fn parse_header(data: &[u8]) -> Result<(Format, Vec<Element>, usize), String> {
+    let mut format = None;
+    let mut elements: Vec<Element> = vec![];
+    let mut position = 0;
+    let mut lines = data.split(|b| *b == b'\n');
+    match lines.next() {
+        Some(line) if line.trim_ascii() == b"ply" => position += line.len() + 1,
+        _ => return Err(String::from("not a PLY file")),
+    }
+
+    for line in lines {
+        position += line.len() + 1;
+        let line = std::str::from_utf8(line).map_err(|_| "header isn't text")?;
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["format", name, _version] => {
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(format!("unknown format {}", name)),
+                })
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| format!("invalid element count {}", count))?,
+                properties: vec![],
+            }),
+            ["property", "list", count, item, name] => elements
+                .last_mut()
+                .ok_or("property before any element")?
+                .properties
+                .push(Property::List(
+                    name.to_string(),
+                    Scalar::parse(count)?,
+                    Scalar::parse(item)?,
+                )),
+            ["property", scalar, name] => elements
+                .last_mut()
+                .ok_or("property before any element")?
+                .properties
+                .push(Property::Scalar(name.to_string(), Scalar::parse(scalar)?)),
+            ["end_header"] => {
+                let format = format.ok_or("missing format")?;
+                return Ok((format, elements, position));
+            }
+            _ => (),
+        }
+    }
+    Err(String::from("missing end_header"))
+}
+
+// Names of the vertex properties parse_ply reads, in the order of its slots
+const VERTEX_SLOTS: [&str; 9] = ["x", "y", "z", "nx", "ny", "nz", "red", "green", "blue"];
+
+// One record of an element: the vertex properties in their slots, NaN where
+// missing so partial normals and colors can be ignored, and the vertex indices of
+// a face
+type Record = ([f64; 9], Vec<usize>);
+
+fn read_record(values: &mut Values, properties: &[Property]) -> Result<Record, String> {
+    let mut vertex = [f64::NAN; 9];
+    let mut face = vec![];
+    for property in properties.iter() {
+        match property {
+            Property::Scalar(name, scalar) => {
+                let value = values.next(*scalar)?;
+                if let Some(slot) = VERTEX_SLOTS.iter().position(|n| n == name) {
+                    vertex[slot] = value / if slot >= 6 { scalar.color_scale() } else { 1. };
+                }
+            }
+            Property::List(name, count, item) => {
+                let count = values.next(*count)? as usize;
+                for _ in 0..count {
+                    face.push(values.next(*item)? as usize);
+                }
+                if name != "vertex_indices" && name != "vertex_index" {
+                    face.clear();
+                }
+            }
+        }
+    }
+    Ok((vertex, face))
+}
+
+// Where each record of the element starts and ends in the body, when that can be
+// told without reading them: ASCII files have a line per record, and binary
+// records without lists all have the same size
+fn record_spans(values: &Values, element: &Element) -> Option<Vec<(usize, usize)>> {
+    let mut spans = Vec::with_capacity(element.count);
+    let mut start = values.position;
+    if values.format == Format::Ascii {
+        while spans.len() < element.count {
+            let rest = values.body.get(start..)?;
+            let length = rest.iter().position(|b| *b == b'\n').unwrap_or(rest.len());
+            if length == 0 && rest.is_empty() {
+                return None;
+            }
+            if !rest[..length].trim_ascii().is_empty() {
+                spans.push((start, start + length));
+            }
+            start += length + 1;
+        }
+        return Some(spans);
+    }
+    let mut size = 0;
+    for property in element.properties.iter() {
+        match property {
+            Property::Scalar(_, scalar) => size += scalar.size(),
+            Property::List(..) => return None,
+        }
+    }
+    for _ in 0..element.count {
+        spans.push((start, start + size));
+        start += size;
+    }
+    Some(spans)
+}
+
+fn read_element(values: &mut Values, element: &Element) -> Result<Vec<Record>, String> {
+    let Some(spans) = record_spans(values, element) else {
+        return (0..element.count)
+            .map(|_| read_record(values, &element.properties))
+            .collect();
+    };
+    let (format, body) = (values.format, values.body);
+    let read = |&(start, end): &(usize, usize)| {
+        let mut record = Values {
+            format,
+            body: body.get(start..end).ok_or("unexpected end of file")?,
+            position: 0,
+        };
+        read_record(&mut record, &element.properties)
+    };
+    #[cfg(feature = "rayon")]
+    let records = spans.par_iter().map(read).collect();
+    #[cfg(not(feature = "rayon"))]
+    let records = spans.iter().map(read).collect();
+    values.position = spans.last().map_or(values.position, |(_, end)| *end);
+    records
+}
+
+// The whole mesh as one object with the given material
+pub fn parse_ply(data: &[u8], material: Option<Material>) -> Result<Object, String> {
+    let (format, elements, header_length) = parse_header(data)?;
+    let mut values = Values {
+        format,
+        body: &data[header_length.min(data.len())..],
+        position: 0,
+    };
+
+    let mut points = vec![];
+    let mut normals = vec![];
+    let mut colors = vec![];
+    let mut faces = vec![];
+    for element in elements.iter() {
+        let records = read_element(&mut values, element)?;
+        match element.name.as_str() {
+            "vertex" => {
+                for (vertex, _) in records {
+                    let [x, y, z, nx, ny, nz, red, green, blue] = vertex;
+                    if x.is_nan() || y.is_nan() || z.is_nan() {
+                        return Err(String::from("vertex without a position"));
+                    }
+                    points.push(Tuple::point(x, y, z));
+                    normals.push(Tuple::vector(nx, ny, nz));
+                    colors.push(Color::new(red, green, blue));
+                }
+            }
+            "face" => faces.extend(records.into_iter().map(|(_, face)| face)),
+            _ => (),
+        }
+    }
+
+    let has_normals = normals
+        .iter()
+        .all(|n| !n.x.is_nan() && !n.y.is_nan() && !n.z.is_nan());
+    let has_colors = colors
+        .iter()
+        .all(|c| !c.red.is_nan() && !c.green.is_nan() && !c.blue.is_nan());
+    let mut material = material.unwrap_or_default();
+    if has_colors && !points.is_empty() {
+        material.pattern = Some(VertexColorPattern::new());
+    }
+
+    let fan = |face: &Vec<usize>| -> Result<Vec<[usize; 3]>, String> {
+        if let Some(index) = face.iter().find(|i| **i >= points.len()) {
+            return Err(format!("face refers to missing vertex {}", index));
+        }
+        Ok((1..face.len().saturating_sub(1))
+            .map(|i| [face[0], face[i], face[i + 1]])
+            .collect())
+    };
+    #[cfg(feature = "rayon")]
+    let fans: Result<Vec<_>, String> = faces.par_iter().map(fan).collect();
+    #[cfg(not(feature = "rayon"))]
+    let fans: Result<Vec<_>, String> = faces.iter().map(fan).collect();
+    Ok(TriangleMesh::new(
+        points,
+        has_normals.then_some(normals),
+        has_colors.then_some(colors),
+        fans?.into_iter().flatten(),
+        Some(material),
+    ))
+}
+
+pub fn load_ply(path: &str, material: Option<Material>) -> Result<Object, String> {
+    let data = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    parse_ply(&data, material)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assert_approx_eq, shape::ShapeType};
+
+    const SQUARE: &str = "ply
+format ascii 1.0
+comment a unit square in the xz plane
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 255 0 0
+1 0 0 0 255 0
+1 0 1 0 0 255
+0 0 1 255 255 255
+4 0 1 2 3
+";
+
+    fn mesh(object: &Object) -> &TriangleMesh {
+        match object.shape {
+            ShapeType::TriangleMesh(ref mesh) => mesh,
+            _ => panic!("not a mesh"),
+        }
+    }
+
+    #[test]
+    fn ascii() {
+        let object = parse_ply(SQUARE.as_bytes(), None).unwrap();
+        let square = mesh(&object);
+        assert_eq!(square.faces(), [[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(square.corners(0)[2], Tuple::point(1., 0., 1.));
+        assert_eq!(square.normals(), None);
+        assert_eq!(square.colors().unwrap()[1], Color::new(0., 1., 0.));
+        assert!(object.material.pattern.is_some());
+        assert_eq!(
+            square.color_at(Tuple::point(1., 0., 0.)),
+            Some(Color::new(0., 1., 0.))
+        );
+    }
+
+    #[test]
+    fn binary_with_normals() {
+        let mut data = b"ply
+format binary_little_endian 1.0
+element vertex 3
+property float x
+property float y
+property float z
+property float nx
+property float ny
+property float nz
+element face 1
+property list uchar uint vertex_indices
+end_header
+"
+        .to_vec();
+        for vertex in [[0., 1., 0.], [-1., 0., 0.], [1., 0., 0.]] {
+            for value in vertex.iter().chain(vertex.iter()) {
+                data.extend((*value as f32).to_le_bytes());
+            }
+        }
+        data.push(3);
+        for index in [0_u32, 1, 2] {
+            data.extend(index.to_le_bytes());
+        }
+
+        let object = parse_ply(&data, None).unwrap();
+        assert_eq!(mesh(&object).faces().len(), 1);
+        assert!(object.material.pattern.is_none());
+        assert_approx_eq!(
+            object.normal_at(Tuple::point(-0.2, 0.3, 0.)),
+            Tuple::vector(-0.5547, 0.83205, 0.)
+        );
+    }
+
+    #[test]
+    fn ascii_and_binary_agree() {
+        // A strip of quads, enough records to be split between threads
+        let n = 200;
+        let header = |format: &str| {
+            format!(
+                "ply\nformat {} 1.0\nelement vertex {}\nproperty double x\nproperty double y\n\
+                 property double z\nelement face {}\nproperty list uchar int vertex_indices\n\
+                 end_header\n",
+                format,
+                2 * n,
+                n - 1
+            )
+        };
+        let mut ascii = header("ascii");
+        let mut binary = header("binary_big_endian").into_bytes();
+        for i in 0..n {
+            for z in [0., 1.] {
+                ascii += &format!("{} 0 {}\n", i, z);
+                for value in [i as f64, 0., z] {
+                    binary.extend(value.to_be_bytes());
+                }
+            }
+        }
+        for i in 0..n - 1 {
+            let face = [2 * i, 2 * i + 2, 2 * i + 3, 2 * i + 1];
+            ascii += &format!("4 {} {} {} {}\n", face[0], face[1], face[2], face[3]);
+            binary.push(4);
+            for index in face {
+                binary.extend((index as i32).to_be_bytes());
+            }
+        }
+
+        let from_ascii = parse_ply(ascii.as_bytes(), None).unwrap();
+        let from_binary = parse_ply(&binary, None).unwrap();
+        assert_eq!(mesh(&from_ascii).faces().len(), 2 * (n - 1));
+        assert_eq!(from_ascii, from_binary);
+        assert_eq!(mesh(&from_ascii).corners(100)[0], Tuple::point(50., 0., 0.));
+    }
+
+    #[test]
+    fn errors() {
+        assert!(parse_ply(b"obj", None).is_err());
+        assert!(parse_ply(SQUARE.replace("4 0 1 2 3", "3 0 1 7").as_bytes(), None).is_err());
+        assert!(parse_ply(&SQUARE.as_bytes()[..SQUARE.len() - 10], None).is_err());
+    }
+}
@@ -1,7 +1,10 @@
 use crate::color::Color;
-use crate::intersection::IntersectionList;
+use crate::intersection::{Intersection, IntersectionList, Intersections};
+use crate::light::PointLight;
 use crate::matrix::Matrix;
+use crate::rng::Sampler;
 use crate::shape::Object;
+use crate::stats::Counter;
 use crate::tuple::Tuple;
 use crate::world::World;
 
@@ -9,13 +12,20 @@ use crate::world::World;
 pub struct Ray {
     pub origin: Tuple,
     pub direction: Tuple,
+    // Set once dispersion has split the light, to the one color channel the ray
+    // carries, whose wavelength decides the refractive indices it meets
+    pub channel: Option<usize>,
 }
 
 impl Ray {
     pub fn new(origin: Tuple, direction: Tuple) -> Self {
         assert!(origin.is_point());
         assert!(direction.is_vector());
-        Ray { origin, direction }
+        Ray {
+            origin,
+            direction,
+            channel: None,
+        }
     }
 
     pub fn position(&self, time: f64) -> Tuple {
@@ -23,37 +33,151 @@ impl Ray {
     }
 
     pub fn intersect_object<'a>(&self, object: &'a Object) -> IntersectionList<'a> {
-        object.intersect(&self)
+        object.intersect(self)
     }
 
+    // Intersections with every object rays can see
     pub fn intersect_world<'a>(&self, world: &'a World) -> IntersectionList<'a> {
-        world
-            .objects
-            .iter()
-            .map(|object| self.intersect_object(object))
-            .fold(IntersectionList::new(vec![]), |acc, i| acc + i)
+        self.intersect_objects(world, false, None)
+    }
+
+    // Intersections with every object that can block light, including shadow only ones
+    pub fn intersect_shadow_casters<'a>(&self, world: &'a World) -> IntersectionList<'a> {
+        self.intersect_objects(world, true, None)
+    }
+
+    // Same as `intersect_shadow_casters`, without the objects linked out of casting
+    // shadows from the light
+    pub fn intersect_light_blockers<'a>(
+        &self,
+        world: &'a World,
+        light: &PointLight,
+    ) -> IntersectionList<'a> {
+        self.intersect_objects(world, true, Some(light))
+    }
+
+    // Whether the intersection is with the back of a single sided object, that the
+    // ray should pass through
+    fn culled(&self, object: &Object, intersection: &Intersection, shadow: bool) -> bool {
+        object.single_sided
+            && (!shadow || object.cull_shadows)
+            && self
+                .direction
+                .dot(&intersection.object.normal_at(self.position(intersection.t)))
+                > 0.
+    }
+
+    // Intersections with the object, without the backs of single sided ones
+    pub(crate) fn intersect_sided<'a>(
+        &self,
+        object: &'a Object,
+        shadow: bool,
+    ) -> Intersections<'a> {
+        let mut xs = self.intersect_object(object).intersections;
+        if object.single_sided {
+            xs.retain(|i| !self.culled(object, i, shadow));
+        }
+        xs
+    }
+
+    // Shadow rays also see shadow only objects
+    fn intersect_objects<'a>(
+        &self,
+        world: &'a World,
+        shadow: bool,
+        light: Option<&PointLight>,
+    ) -> IntersectionList<'a> {
+        let mut intersections = Intersections::new();
+        self.for_each_intersection(world, shadow, light, |_, i| intersections.push(i));
+        IntersectionList::new(intersections)
+    }
+
+    // Calls `visit` with every intersection `intersect_objects` would find and the
+    // index in the world of the object it belongs to, in no particular order
+    pub(crate) fn for_each_intersection<'a>(
+        &self,
+        world: &'a World,
+        shadow: bool,
+        light: Option<&PointLight>,
+        mut visit: impl FnMut(usize, Intersection<'a>),
+    ) {
+        let included = |object: &Object| {
+            if shadow {
+                object.casts_shadow && light.is_none_or(|l| l.shadowed_by(object))
+            } else {
+                !object.shadow_only
+            }
+        };
+        let intersect = |object: &'a Object| self.intersect_sided(object, shadow);
+        if let Some(bvh) = world.bvh() {
+            let mut tests = 0;
+            let visited = bvh.candidates(self, |index| {
+                let object = &world.objects[index];
+                if included(object) {
+                    tests += 1;
+                    intersect(object).into_iter().for_each(|i| visit(index, i));
+                }
+            });
+            world.count(Counter::BvhNodeVisits, visited);
+            world.count(Counter::IntersectionTests, tests);
+            return;
+        }
+
+        world.count(Counter::IntersectionTests, world.objects.len() as u64);
+        let batch = world.sphere_batch();
+        if let Some(batch) = batch {
+            batch.intersect_indexed(self, &world.objects, |index, i| {
+                if included(i.object) && !self.culled(i.object, &i, shadow) {
+                    visit(index, i);
+                }
+            });
+        }
+        for (index, object) in world.objects.iter().enumerate() {
+            if included(object) && !batch.is_some_and(|batch| batch.contains(index)) {
+                intersect(object).into_iter().for_each(|i| visit(index, i));
+            }
+        }
     }
 
     pub fn color_hit(&self, world: &World, remaining: u8) -> Color {
+        self.color_hit_sampled(world, remaining, &mut Sampler::Unseeded)
+    }
+
+    // Same as `color_hit`, with the random numbers for soft shadows and the like
+    // drawn from the pixel's sampler so seeded renders come out the same every time
+    pub fn color_hit_sampled(&self, world: &World, remaining: u8, sampler: &mut Sampler) -> Color {
         let i = self.intersect_world(world);
-        let hit = i.hit();
+        let hit = i.visible_hit();
         match hit {
-            None => Color::new(0., 0., 0.),
-            Some(h) => h.context(self, Some(&i)).shade_hit(world, remaining),
+            None => world.background(self.direction),
+            Some(h) => h
+                .context(self, Some(&i))
+                .shade_hit(world, remaining, sampler),
         }
     }
 
     pub fn transform(&self, transformation: &Matrix) -> Self {
         let origin = transformation * self.origin;
         let direction = transformation * self.direction;
-        Ray { origin, direction }
+        Ray {
+            origin,
+            direction,
+            channel: self.channel,
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{light::PointLight, material::Material, shape::MAX_REFLECTIONS, shapes::Sphere};
+    use crate::{
+        assert_approx_eq,
+        environment::Environment,
+        light::PointLight,
+        material::Material,
+        shape::MAX_REFLECTIONS,
+        shapes::{Plane, Sphere},
+    };
 
     #[test]
     fn ray() {
@@ -140,6 +264,22 @@ mod tests {
         assert_eq!(c, Color::new(0.38066, 0.47583, 0.2855));
     }
 
+    #[test]
+    fn environment() {
+        let mut w = World::default();
+        w.environment = Some(Environment::uniform(Color::new(0.5, 0.5, 0.5)));
+        let r = Ray::new(Tuple::point(0., 0., -5.), Tuple::vector(0., 1., 0.));
+        assert_eq!(r.color_hit(&w, MAX_REFLECTIONS), Color::new(0.5, 0.5, 0.5));
+
+        // The surface's ambient term picks up the environment as well as the light
+        let r = Ray::new(Tuple::point(0., 0., -5.), Tuple::vector(0., 0., 1.));
+        assert_approx_eq!(
+            r.color_hit(&w, MAX_REFLECTIONS),
+            Color::new(0.42066, 0.52583, 0.3155),
+            epsilon = 0.005
+        );
+    }
+
     #[test]
     fn test_world_color_inner() {
         let light = PointLight::new(Tuple::point(-10., 10., -10.), Color::new(1., 1., 1.));
@@ -153,11 +293,30 @@ mod tests {
         let mut mat2 = Material::new();
         mat2.ambient = 1.;
         let mut s2 = Sphere::new(Some(mat2));
-        s2.transform = Matrix::scaling(0.5, 0.5, 0.5);
+        s2.set_transform(&Matrix::scaling(0.5, 0.5, 0.5));
 
         let w = World::new(vec![s1, s2], vec![light]);
         let r = Ray::new(Tuple::point(0., 0., 0.75), Tuple::vector(0., 0., -1.));
         let c = r.color_hit(&w, MAX_REFLECTIONS);
         assert_eq!(c, w.objects[1].material.color);
     }
+
+    #[test]
+    fn single_sided() {
+        let mut floor = Plane::new(None);
+        floor.single_sided = true;
+        let light = PointLight::new(Tuple::point(0., 10., 0.), Color::new(1., 1., 1.));
+        let mut w = World::new(vec![floor], vec![light]);
+        let down = Ray::new(Tuple::point(0., 1., 0.), Tuple::vector(0., -1., 0.));
+        let up = Ray::new(Tuple::point(0., -1., 0.), Tuple::vector(0., 1., 0.));
+        assert_eq!(down.intersect_world(&w).intersections.len(), 1);
+        assert_eq!(up.intersect_world(&w).intersections.len(), 0);
+
+        // The back still blocks light unless shadows are culled too
+        assert_eq!(up.intersect_shadow_casters(&w).intersections.len(), 1);
+        assert!(w.is_shadowed(&w.lights[0], Tuple::point(0., -1., 0.)));
+        w.objects[0].cull_shadows = true;
+        assert_eq!(up.intersect_shadow_casters(&w).intersections.len(), 0);
+        assert!(!w.is_shadowed(&w.lights[0], Tuple::point(0., -1., 0.)));
+    }
 }
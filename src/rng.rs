@@ -0,0 +1,155 @@
+// Random numbers for sampling. Uses `rand` when the feature is enabled, otherwise a
+// small per-thread xorshift generator so minimal builds can still jitter samples
+#[cfg(feature = "rand")]
+use rand::Rng;
+
+#[cfg(not(feature = "rand"))]
+use std::cell::RefCell;
+
+// A tiny seedable generator (xorshift64) for when results have to be reproducible
+#[derive(Debug, Clone)]
+pub struct XorShift {
+    state: u64,
+}
+
+impl XorShift {
+    pub fn new(seed: u64) -> Self {
+        // Zero is a fixed point of xorshift, so mix the seed into a non-zero state
+        Self {
+            state: seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1,
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.state = x;
+        x
+    }
+
+    // Uniformly distributed in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1_u64 << 53) as f64
+    }
+
+    // Uniformly distributed in [low, high)
+    pub fn range(&mut self, low: f64, high: f64) -> f64 {
+        low + (high - low) * self.next_f64()
+    }
+
+    // Uniformly distributed in low..high
+    pub fn index(&mut self, low: usize, high: usize) -> usize {
+        assert!(low < high);
+        low + (self.next_u64() % (high - low) as u64) as usize
+    }
+}
+
+// Scrambles every bit of the input into every bit of the output (splitmix64's finaliser)
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+// Random numbers for the samples of one pixel. With a seed, each pixel and pass gets
+// its own stream derived from its coordinates, so the result doesn't depend on
+// which thread traced it. Without one, it falls back to `random`
+#[derive(Debug, Clone)]
+pub enum Sampler {
+    Seeded(XorShift),
+    Unseeded,
+}
+
+impl Sampler {
+    pub fn for_pixel(seed: Option<u64>, x: usize, y: usize, pass: usize) -> Self {
+        match seed {
+            Some(seed) => {
+                let stream = mix(mix(mix(seed) ^ x as u64) ^ y as u64) ^ pass as u64;
+                Sampler::Seeded(XorShift::new(mix(stream)))
+            }
+            None => Sampler::Unseeded,
+        }
+    }
+
+    // Uniformly distributed in [0, 1)
+    pub fn random(&mut self) -> f64 {
+        match self {
+            Sampler::Seeded(generator) => generator.next_f64(),
+            Sampler::Unseeded => random(),
+        }
+    }
+}
+
+#[cfg(not(feature = "rand"))]
+thread_local! {
+    static GENERATOR: RefCell<XorShift> = RefCell::new(XorShift::new(0));
+}
+
+// Uniformly distributed in [0, 1)
+#[cfg(feature = "rand")]
+pub fn random() -> f64 {
+    rand::thread_rng().gen_range(0_f64..1.)
+}
+
+#[cfg(not(feature = "rand"))]
+pub fn random() -> f64 {
+    GENERATOR.with(|generator| generator.borrow_mut().next_f64())
+}
+
+#[cfg(feature = "rand")]
+pub fn random_u32() -> u32 {
+    rand::thread_rng().gen::<u32>()
+}
+
+#[cfg(not(feature = "rand"))]
+pub fn random_u32() -> u32 {
+    GENERATOR.with(|generator| (generator.borrow_mut().next_u64() >> 32) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn range() {
+        for _ in 0..1000 {
+            let r = random();
+            assert!((0. ..1.).contains(&r));
+        }
+        assert_ne!(random_u32(), random_u32());
+    }
+
+    #[test]
+    fn seeded() {
+        let (mut a, mut b) = (XorShift::new(7), XorShift::new(7));
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+        for _ in 0..100 {
+            let r = a.range(-2., 3.);
+            assert!((-2. ..3.).contains(&r));
+            let i = a.index(1, 4);
+            assert!((1..4).contains(&i));
+        }
+        assert_ne!(XorShift::new(0).next_u64(), XorShift::new(1).next_u64());
+    }
+
+    #[test]
+    fn pixel_streams() {
+        let draw = |x, y, pass| {
+            let mut sampler = Sampler::for_pixel(Some(42), x, y, pass);
+            (sampler.random(), sampler.random())
+        };
+        assert_eq!(draw(3, 4, 0), draw(3, 4, 0));
+        assert_ne!(draw(3, 4, 0), draw(4, 3, 0));
+        assert_ne!(draw(3, 4, 0), draw(3, 4, 1));
+        assert_ne!(
+            Sampler::for_pixel(Some(1), 0, 0, 0).random(),
+            Sampler::for_pixel(Some(2), 0, 0, 0).random()
+        );
+        let r = Sampler::for_pixel(None, 0, 0, 0).random();
+        assert!((0. ..1.).contains(&r));
+    }
+}
@@ -0,0 +1,400 @@
+// The demo scenes rendered by the binaries, each with the camera it is viewed from
+use crate::{
+    camera::{Camera, SuperSamplingMode},
+    color::{Color, BLACK, BLUE, GREEN, RED, WHITE},
+    light::PointLight,
+    material::Material,
+    matrix::Matrix,
+    pattern::{CheckerPattern, GradientPattern, RingPattern, StripePattern},
+    shapes::{Cube, Plane, Sdf, SdfShape, Sphere},
+    tuple::Tuple,
+    world::World,
+    PI,
+};
+
+// Three spheres of different sizes resting on a plane
+pub fn three_spheres() -> (World, Camera) {
+    let mut material = Material::new();
+    material.color = Color::new(1., 0.9, 0.9);
+    material.specular = 0.;
+    let floor = Plane::new(Some(material));
+
+    material = Material::new();
+    material.color = Color::new(0.1, 1., 0.5);
+    material.diffuse = 0.7;
+    material.specular = 0.3;
+    let mut middle = Sphere::new(Some(material));
+    middle.set_transform(&Matrix::translation(-0.5, 1., 0.5));
+
+    material = Material::new();
+    material.color = Color::new(0.5, 1., 0.1);
+    material.diffuse = 0.7;
+    material.specular = 0.3;
+    let mut right = Sphere::new(Some(material));
+    right.set_transform(&(Matrix::translation(1.5, 0.5, -0.5) * &Matrix::scaling(0.5, 0.5, 0.5)));
+
+    material = Material::new();
+    material.color = Color::new(1., 0.8, 0.1);
+    material.diffuse = 0.7;
+    material.specular = 0.3;
+    let mut left = Sphere::new(Some(material));
+    left.set_transform(
+        &(Matrix::translation(-1.5, 0.33, -0.75) * &Matrix::scaling(0.33, 0.33, 0.33)),
+    );
+
+    let light = PointLight::new(Tuple::point(-10., 10., -10.), Color::new(1., 1., 1.));
+
+    let world = World::new(vec![floor, middle, right, left], vec![light]);
+    let mut camera = Camera::new(800, 400, PI / 3., SuperSamplingMode::Stochastic);
+    camera.transform = Matrix::view_transform(
+        Tuple::point(0., 1.5, -5.),
+        Tuple::point(0., 1., 0.),
+        Tuple::vector(0., 1., 0.),
+    );
+
+    (world, camera)
+}
+
+// A reflective and a transparent sphere over a reflective checkered floor
+pub fn mirror_spheres() -> (World, Camera) {
+    let mut material = Material::new();
+    material.reflective = 0.3;
+    let pattern = CheckerPattern::new(WHITE, Color::new(0.5, 0.5, 0.5));
+    material.pattern = Some(pattern);
+    let floor = Plane::new(Some(material));
+
+    material = Material::new();
+    material.diffuse = 0.7;
+    material.specular = 0.3;
+    material.reflective = 1.;
+    let pattern = GradientPattern::new(BLUE, BLACK);
+    material.pattern = Some(pattern);
+    let mut sphere1 = Sphere::new(Some(material));
+    sphere1.set_transform(&Matrix::translation(-1.3, 1.5, -4.));
+
+    material = Material::new();
+    material.diffuse = 0.7;
+    material.specular = 0.3;
+    material.transparency = 0.5;
+    let mut sphere2 = Sphere::new(Some(material));
+    sphere2.set_transform(&Matrix::translation(0.0, 2., -6.));
+
+    let light = PointLight::new(Tuple::point(-5., 10., -10.), Color::new(1., 1., 1.));
+
+    let world = World::new(vec![floor, sphere1, sphere2], vec![light]);
+    let mut camera = Camera::new(400, 200, PI / 1.5, SuperSamplingMode::None);
+    camera.transform = Matrix::view_transform(
+        Tuple::point(-1., 2., -9.),
+        Tuple::point(0., 1., 0.),
+        Tuple::vector(0., 1., 0.),
+    );
+
+    (world, camera)
+}
+
+// Spheres and walls showing off the stripe, ring, gradient and checker patterns
+pub fn pattern_showcase() -> (World, Camera) {
+    let mut material = Material::new();
+    let pattern = CheckerPattern::new(WHITE, Color::new(0.5, 0.5, 0.5));
+    material.pattern = Some(pattern);
+    let floor = Plane::new(Some(material));
+
+    let mut material = Material::new();
+    let mut pattern = RingPattern::new(vec![
+        Color::new(0.5, 0.5, 0.5),
+        WHITE,
+        Color::new(0.7, 0.6, 0.7),
+    ]);
+    pattern.set_transform(&Matrix::shearing(1., 1., 0., 0., 0., 0.));
+    material.pattern = Some(pattern);
+    let mut wall = Plane::new(Some(material));
+    wall.set_transform(&(Matrix::translation(0., 0., 5.) * &Matrix::rotation_x(PI / 2.)));
+
+    let mut material = Material::new();
+    let mut pattern = StripePattern::new(vec![
+        Color::new(0.5, 0.5, 0.5),
+        WHITE,
+        Color::new(0.7, 0.6, 0.7),
+    ]);
+    pattern.set_transform(&Matrix::scaling(0.35, 0.35, 0.35));
+    material.diffuse = 0.7;
+    material.specular = 0.3;
+    material.pattern = Some(pattern);
+    let mut sphere1 = Sphere::new(Some(material));
+    sphere1.set_transform(&(Matrix::translation(3., 1.5, -4.) * &Matrix::scaling(1.5, 1.5, 1.5)));
+
+    material = Material::new();
+    let mut pattern = RingPattern::new(vec![WHITE, Color::new(0.7, 0.6, 0.7)]);
+    pattern.set_transform(&Matrix::scaling(0.2, 0.2, 0.2));
+    material.diffuse = 0.7;
+    material.specular = 0.3;
+    material.pattern = Some(pattern);
+    let mut sphere2 = Sphere::new(Some(material));
+    sphere2.set_transform(
+        &(Matrix::translation(-3., 1.5, -4.)
+            * &Matrix::rotation_x(PI / 2.)
+            * &Matrix::scaling(1.5, 1.5, 1.5)),
+    );
+
+    material = Material::new();
+    let mut pattern = GradientPattern::new(Color::new(0.7, 0.6, 0.7), BLACK);
+    pattern.set_transform(&(&Matrix::translation(-1., 0., 0.) * &Matrix::scaling(2., 1., 1.)));
+    material.diffuse = 0.7;
+    material.specular = 0.3;
+    material.pattern = Some(pattern);
+    let mut sphere3 = Sphere::new(Some(material));
+    sphere3.set_transform(&(Matrix::translation(0., 1., -7.) * &Matrix::scaling(0.33, 0.33, 0.33)));
+
+    let light = PointLight::new(Tuple::point(-7., 10., -10.), Color::new(1., 1., 1.));
+
+    let world = World::new(vec![floor, wall, sphere1, sphere2, sphere3], vec![light]);
+    let mut camera = Camera::new(800, 400, PI / 1.5, SuperSamplingMode::Stochastic);
+    camera.transform = Matrix::view_transform(
+        Tuple::point(-1., 2., -9.),
+        Tuple::point(0., 1., 0.),
+        Tuple::vector(0., 1., 0.),
+    );
+
+    (world, camera)
+}
+
+// A stack of gradient cubes in the corner of a checkered room
+pub fn cube_stack() -> (World, Camera) {
+    let mut material = Material::new();
+    material.reflective = 0.;
+    let pattern = CheckerPattern::new(WHITE, Color::new(0.5, 0.5, 0.5));
+    material.pattern = Some(pattern);
+    let floor = Plane::new(Some(material.clone()));
+
+    let mut left_wall = Plane::new(Some(material.clone()));
+    left_wall.set_transform(&(Matrix::translation(-15., 0., 0.) * &Matrix::rotation_z(PI / 2.)));
+
+    let mut right_wall = Plane::new(Some(material.clone()));
+    right_wall.set_transform(&(Matrix::translation(0., 0., 15.) * &Matrix::rotation_x(PI / 2.)));
+
+    material = Material::new();
+    material.diffuse = 0.7;
+    material.specular = 0.3;
+    material.reflective = 0.05;
+    let mut pattern = GradientPattern::new(BLUE, BLACK);
+    pattern.set_transform(&(&Matrix::translation(-1., 0., 0.) * &Matrix::scaling(2., 1., 1.)));
+    material.pattern = Some(pattern);
+    let mut cube1 = Cube::new(Some(material.clone()));
+    cube1.set_transform(&(Matrix::translation(0., 2., 0.) * &Matrix::scaling(2., 2., 2.)));
+
+    let mut pattern = GradientPattern::new(RED, BLACK);
+    pattern.set_transform(&(&Matrix::translation(-1., 0., 0.) * &Matrix::scaling(2., 1., 1.)));
+    material.pattern = Some(pattern);
+    let mut cube2 = Cube::new(Some(material.clone()));
+    cube2.set_transform(&(Matrix::translation(0., 5., 0.) * &Matrix::scaling(1., 1., 1.)));
+
+    let mut pattern = GradientPattern::new(GREEN, BLACK);
+    pattern.set_transform(&(&Matrix::translation(-1., 0., 0.) * &Matrix::scaling(2., 1., 1.)));
+    material.pattern = Some(pattern);
+    let mut cube3 = Cube::new(Some(material.clone()));
+    cube3.set_transform(&(Matrix::translation(0., 6.5, 0.) * &Matrix::scaling(0.5, 0.5, 0.5)));
+
+    let light = PointLight::new(Tuple::point(-5., 10., -10.), Color::new(1., 1., 1.));
+
+    let world = World::new(
+        vec![floor, left_wall, right_wall, cube1, cube2, cube3],
+        vec![light],
+    );
+    let mut camera = Camera::new(800, 400, PI / 1.9, SuperSamplingMode::Stochastic);
+    camera.transform = Matrix::view_transform(
+        Tuple::point(5., 2.5, -7.5),
+        Tuple::point(1.5, 3., 0.),
+        Tuple::vector(0., 1., 0.),
+    );
+
+    (world, camera)
+}
+
+// Glass spheres among coloured balls in a room with striped walls. The quarter turns
+// are the 1.5708 the scene was first written with, which renders of it match
+#[allow(clippy::approx_constant)]
+pub fn glass_room() -> (World, Camera) {
+    let mut wall_material = Material::new();
+    let mut wall_pattern = StripePattern::new(vec![
+        Color::new(0.45, 0.45, 0.45),
+        Color::new(0.55, 0.55, 0.55),
+    ]);
+    wall_pattern.set_transform(&(&Matrix::scaling(0.25, 0.25, 0.25) * &Matrix::rotation_y(1.5708)));
+    wall_material.pattern = Some(wall_pattern);
+    wall_material.ambient = 0.;
+    wall_material.diffuse = 0.4;
+    wall_material.specular = 0.;
+    wall_material.reflective = 0.3;
+
+    let mut floor_material = Material::new();
+    let floor_pattern =
+        CheckerPattern::new(Color::new(0.35, 0.35, 0.35), Color::new(0.65, 0.65, 0.65));
+    floor_material.specular = 0.;
+    floor_material.reflective = 0.;
+    floor_material.pattern = Some(floor_pattern);
+    let mut floor = Plane::new(Some(floor_material));
+    floor.set_transform(&Matrix::rotation_y(0.31415));
+
+    let mut ceiling_material = Material::new();
+    ceiling_material.color = Color::new(0.8, 0.8, 0.8);
+    ceiling_material.ambient = 0.3;
+    ceiling_material.specular = 0.;
+    let mut ceiling = Plane::new(Some(ceiling_material));
+    ceiling.set_transform(&Matrix::translation(0., 5., 0.));
+
+    let mut west_wall = Plane::new(Some(wall_material.clone()));
+    west_wall.set_transform(
+        &(Matrix::translation(-5., 0., 0.)
+            * &Matrix::rotation_z(1.5708)
+            * &Matrix::rotation_y(1.5708)),
+    );
+
+    let mut east_wall = Plane::new(Some(wall_material.clone()));
+    east_wall.set_transform(
+        &(Matrix::translation(5., 0., 0.)
+            * &Matrix::rotation_z(1.5708)
+            * &Matrix::rotation_y(1.5708)),
+    );
+
+    let mut north_wall = Plane::new(Some(wall_material.clone()));
+    north_wall.set_transform(&(Matrix::translation(0., 0., 5.) * &Matrix::rotation_x(1.5708)));
+
+    let mut south_wall = Plane::new(Some(wall_material.clone()));
+    south_wall.set_transform(&(Matrix::translation(0., 0., -5.) * &Matrix::rotation_x(1.5708)));
+
+    let mut sphere1_material = Material::new();
+    sphere1_material.color = Color::new(0.8, 0.5, 0.3);
+    sphere1_material.shininess = 50.;
+    let mut sphere1 = Sphere::new(Some(sphere1_material));
+    sphere1.set_transform(&(Matrix::translation(4.6, 0.4, 1.) * &Matrix::scaling(0.4, 0.4, 0.4)));
+
+    let mut sphere2_material = Material::new();
+    sphere2_material.color = Color::new(0.9, 0.4, 0.5);
+    sphere2_material.shininess = 50.;
+    let mut sphere2 = Sphere::new(Some(sphere2_material));
+    sphere2.set_transform(&(Matrix::translation(4.7, 0.3, 0.4) * &Matrix::scaling(0.3, 0.3, 0.3)));
+
+    let mut sphere3_material = Material::new();
+    sphere3_material.color = Color::new(0.4, 0.9, 0.6);
+    sphere3_material.shininess = 50.;
+    let mut sphere3 = Sphere::new(Some(sphere3_material));
+    sphere3.set_transform(&(Matrix::translation(-1., 0.5, 4.5) * &Matrix::scaling(0.5, 0.5, 0.5)));
+
+    let mut sphere4_material = Material::new();
+    sphere4_material.color = Color::new(0.4, 0.6, 0.9);
+    sphere4_material.shininess = 50.;
+    let mut sphere4 = Sphere::new(Some(sphere4_material));
+    sphere4.set_transform(&(Matrix::translation(-1.7, 0.3, 4.7) * &Matrix::scaling(0.3, 0.3, 0.3)));
+
+    let mut sphere5_material = Material::new();
+    sphere5_material.color = Color::new(1., 0.3, 0.2);
+    sphere5_material.specular = 0.4;
+    sphere5_material.shininess = 5.;
+    let mut sphere5 = Sphere::new(Some(sphere5_material));
+    sphere5.set_transform(&Matrix::translation(-0.6, 1., 0.6));
+
+    let mut sphere6_material = Material::new();
+    sphere6_material.color = Color::new(0., 0., 0.2);
+    sphere6_material.ambient = 0.;
+    sphere6_material.diffuse = 0.4;
+    sphere6_material.specular = 0.9;
+    sphere6_material.shininess = 300.;
+    sphere6_material.reflective = 0.9;
+    sphere6_material.transparency = 0.9;
+    sphere6_material.refractive_index = 1.5;
+    let mut sphere6 = Sphere::new(Some(sphere6_material));
+    sphere6.set_transform(&(Matrix::translation(0.6, 0.7, -0.6) * &Matrix::scaling(0.7, 0.7, 0.7)));
+
+    let mut sphere7_material = Material::new();
+    sphere7_material.color = Color::new(0., 0.2, 0.);
+    sphere7_material.ambient = 0.;
+    sphere7_material.diffuse = 0.4;
+    sphere7_material.specular = 0.9;
+    sphere7_material.shininess = 300.;
+    sphere7_material.reflective = 0.9;
+    sphere7_material.transparency = 0.9;
+    sphere7_material.refractive_index = 1.5;
+    let mut sphere7 = Sphere::new(Some(sphere7_material));
+    sphere7
+        .set_transform(&(Matrix::translation(-0.7, 0.5, -0.8) * &Matrix::scaling(0.5, 0.5, 0.5)));
+
+    let light = PointLight::new(Tuple::point(-4.9, 4.9, -1.), Color::new(1., 1., 1.));
+
+    let world = World::new(
+        vec![
+            floor, ceiling, west_wall, east_wall, north_wall, south_wall, sphere1, sphere2,
+            sphere3, sphere4, sphere5, sphere6, sphere7,
+        ],
+        vec![light],
+    );
+    let mut camera = Camera::new(800, 400, 1.152, SuperSamplingMode::Stochastic);
+    camera.transform = Matrix::view_transform(
+        Tuple::point(-2.6, 1.5, -3.9),
+        Tuple::point(-0.6, 1., -0.8),
+        Tuple::vector(0., 1., 0.),
+    );
+
+    (world, camera)
+}
+
+// A Menger sponge and a Mandelbulb on a checkered floor. Marching the fractals is far
+// slower than the other scenes, so this one isn't supersampled
+pub fn fractals() -> (World, Camera) {
+    let mut material = Material::new();
+    material.pattern = Some(CheckerPattern::new(WHITE, Color::new(0.5, 0.5, 0.5)));
+    material.reflective = 0.1;
+    let floor = Plane::new(Some(material));
+
+    material = Material::new();
+    material.color = Color::new(0.9, 0.6, 0.3);
+    material.specular = 0.2;
+    let sponge = SdfShape::new(Sdf::MengerSponge { iterations: 4 }, Some(material))
+        .with_transform(&(Matrix::translation(-1.3, 1., 0.) * &Matrix::rotation_y(PI / 6.)));
+
+    material = Material::new();
+    material.color = Color::new(0.4, 0.6, 1.);
+    material.specular = 0.5;
+    material.shininess = 50.;
+    let bulb = SdfShape::new(
+        Sdf::Mandelbulb {
+            power: 8.,
+            iterations: 10,
+            bailout: 2.,
+        },
+        Some(material),
+    )
+    .with_transform(&(Matrix::translation(1.3, 1.1, 0.) * &Matrix::rotation_x(-PI / 2.)));
+
+    let light = PointLight::new(Tuple::point(-6., 8., -10.), Color::new(1., 1., 1.));
+
+    let world = World::new(vec![floor, sponge, bulb], vec![light]);
+    let mut camera = Camera::new(800, 400, PI / 3., SuperSamplingMode::None);
+    camera.transform = Matrix::view_transform(
+        Tuple::point(0., 2.5, -6.),
+        Tuple::point(0., 1., 0.),
+        Tuple::vector(0., 1., 0.),
+    );
+
+    (world, camera)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scenes() {
+        for (scene, objects) in [
+            (three_spheres as fn() -> (World, Camera), 4),
+            (mirror_spheres, 3),
+            (pattern_showcase, 5),
+            (cube_stack, 6),
+            (glass_room, 13),
+            (fractals, 3),
+        ] {
+            let (world, _) = scene();
+            assert_eq!(world.objects.len(), objects);
+            assert_eq!(world.lights.len(), 1);
+        }
+    }
+}
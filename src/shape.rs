@@ -1,4 +1,5 @@
 use crate::{
+    bounds::BoundingBox,
     intersection::IntersectionList,
     material::Material,
     matrix::Matrix,
@@ -6,7 +7,8 @@ use crate::{
     shapes::Plane,
     shapes::{
         Cylinder,
-        Cube, Sphere,
+        ConvexPolyhedron, Csg, Cube, Deformed, Disc, Lens, Metaballs, Rectangle, SdfShape, Sphere, Triangle,
+        TriangleMesh,
     },
     tuple::Tuple,
 };
@@ -14,28 +16,149 @@ use crate::{
 pub const MAX_REFLECTIONS: u8 = 5;
 pub const MAX_REFRACTIONS: u8 = 5;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ShapeType {
     Sphere(Sphere),
     Plane(Plane),
     Cube(Cube),
     Cylinder(Cylinder),
+    Triangle(Triangle),
+    Csg(Csg),
+    Disc(Disc),
+    Sdf(SdfShape),
+    Metaballs(Metaballs),
+    Rectangle(Rectangle),
+    ConvexPolyhedron(ConvexPolyhedron),
+    Lens(Lens),
+    Deformed(Deformed),
+    TriangleMesh(TriangleMesh),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Object {
-    pub transform: Matrix,
+    // Kept private so the inverse always matches it, see set_transform
+    transform: Matrix,
+    inverse: Matrix,
     pub shape: ShapeType,
     pub material: Material,
+    // When transparent objects overlap, the medium with the highest priority wins and
+    // the boundaries of lower priority media inside it are ignored
+    pub media_priority: u32,
+    // Optional name used to refer to the object, e.g. from light linking
+    pub name: Option<String>,
+    // Render layer the object belongs to, see Camera::layers
+    pub layer: Option<String>,
+    // Casts shadows but is invisible to camera, reflected and refracted rays
+    pub shadow_only: bool,
+    // Whether the object blocks light at all. Turning it off keeps e.g. a glass floor
+    // from darkening everything under it
+    pub casts_shadow: bool,
+    // Only visible from the side its normals point to, so rays pass through it from
+    // behind. Shadow rays still hit the back unless cull_shadows is set too
+    pub single_sided: bool,
+    pub cull_shadows: bool,
 }
 
 impl Object {
-    fn local_intersect(&self, ray_obj_space: &Ray) -> IntersectionList {
+    pub fn new(shape: ShapeType, material: Material) -> Self {
+        Object {
+            transform: Matrix::identity(4),
+            inverse: Matrix::identity(4),
+            shape,
+            material,
+            media_priority: 0,
+            name: None,
+            layer: None,
+            shadow_only: false,
+            casts_shadow: true,
+            single_sided: false,
+            cull_shadows: false,
+        }
+    }
+
+    pub fn transform(&self) -> &Matrix {
+        &self.transform
+    }
+
+    pub fn inverse(&self) -> &Matrix {
+        &self.inverse
+    }
+
+    pub fn set_transform(&mut self, m: &Matrix) {
+        // CSG children live in world space, so they move by whatever takes the old
+        // transform to the new one
+        if let ShapeType::Csg(ref mut csg) = self.shape {
+            csg.transform_children(&(m * &self.inverse));
+        }
+        self.transform = m.clone();
+        self.inverse = m.inverse();
+    }
+
+    pub fn with_transform(mut self, m: &Matrix) -> Self {
+        self.set_transform(m);
+        self
+    }
+
+    pub fn with_material(mut self, material: Material) -> Self {
+        self.material = material;
+        self
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(String::from(name));
+        self
+    }
+
+    pub fn with_layer(mut self, layer: &str) -> Self {
+        self.layer = Some(String::from(layer));
+        self
+    }
+
+    // World space bounds
+    pub fn bounds(&self) -> BoundingBox {
+        let local = match &self.shape {
+            ShapeType::Sphere(ref sphere) => sphere.local_bounds(),
+            ShapeType::Plane(ref plane) => plane.local_bounds(),
+            ShapeType::Cube(ref cube) => cube.local_bounds(),
+            ShapeType::Cylinder(ref cylinder) => cylinder.local_bounds(),
+            ShapeType::Triangle(ref triangle) => triangle.local_bounds(),
+            ShapeType::Csg(ref csg) => return csg.bounds(),
+            ShapeType::Disc(ref disc) => disc.local_bounds(),
+            ShapeType::Sdf(ref sdf) => sdf.local_bounds(),
+            ShapeType::Metaballs(ref balls) => balls.local_bounds(),
+            ShapeType::Rectangle(ref rect) => rect.local_bounds(),
+            ShapeType::ConvexPolyhedron(ref poly) => poly.local_bounds(),
+            ShapeType::Lens(ref lens) => lens.local_bounds(),
+            ShapeType::Deformed(ref deformed) => deformed.local_bounds(),
+            ShapeType::TriangleMesh(ref mesh) => mesh.local_bounds(),
+        };
+        local.transform(&self.transform)
+    }
+
+    // Whether `other` is this object or, for CSG, one of the objects it is made of
+    pub fn includes(&self, other: &Object) -> bool {
+        match &self.shape {
+            ShapeType::Csg(csg) => csg.left().includes(other) || csg.right().includes(other),
+            _ => std::ptr::eq(self, other),
+        }
+    }
+
+    fn local_intersect(&self, ray_obj_space: &Ray) -> IntersectionList<'_> {
         match &self.shape {
             ShapeType::Sphere(ref sphere) => sphere.local_intersect(ray_obj_space, self),
             ShapeType::Plane(ref plane) => plane.local_intersect(ray_obj_space, self),
             ShapeType::Cube(ref cube) => cube.local_intersect(ray_obj_space, self),
             ShapeType::Cylinder(ref cylinder) => cylinder.local_intersect(ray_obj_space, self),
+            ShapeType::Triangle(ref triangle) => triangle.local_intersect(ray_obj_space, self),
+            ShapeType::Csg(ref csg) => csg.local_intersect(ray_obj_space, self),
+            ShapeType::Disc(ref disc) => disc.local_intersect(ray_obj_space, self),
+            ShapeType::Sdf(ref sdf) => sdf.local_intersect(ray_obj_space, self),
+            ShapeType::Metaballs(ref balls) => balls.local_intersect(ray_obj_space, self),
+            ShapeType::Rectangle(ref rect) => rect.local_intersect(ray_obj_space, self),
+            ShapeType::ConvexPolyhedron(ref poly) => poly.local_intersect(ray_obj_space, self),
+            ShapeType::Lens(ref lens) => lens.local_intersect(ray_obj_space, self),
+            ShapeType::Deformed(ref deformed) => deformed.local_intersect(ray_obj_space, self),
+            ShapeType::TriangleMesh(ref mesh) => mesh.local_intersect(ray_obj_space, self),
         }
     }
 
@@ -45,19 +168,29 @@ impl Object {
             ShapeType::Plane(ref plane) => plane.local_normal_at(point),
             ShapeType::Cube(ref cube) => cube.local_normal_at(point),
             ShapeType::Cylinder(ref cylinder) => cylinder.local_normal_at(point),
+            ShapeType::Triangle(ref triangle) => triangle.local_normal_at(point),
+            ShapeType::Csg(ref csg) => csg.local_normal_at(point),
+            ShapeType::Disc(ref disc) => disc.local_normal_at(point),
+            ShapeType::Sdf(ref sdf) => sdf.local_normal_at(point),
+            ShapeType::Metaballs(ref balls) => balls.local_normal_at(point),
+            ShapeType::Rectangle(ref rect) => rect.local_normal_at(point),
+            ShapeType::ConvexPolyhedron(ref poly) => poly.local_normal_at(point),
+            ShapeType::Lens(ref lens) => lens.local_normal_at(point),
+            ShapeType::Deformed(ref deformed) => deformed.local_normal_at(point),
+            ShapeType::TriangleMesh(ref mesh) => mesh.local_normal_at(point),
         }
     }
 
-    pub fn intersect(&self, ray: &Ray) -> IntersectionList {
-        let ray_obj_space = ray.transform(&(self.transform.inverse()));
+    pub fn intersect(&self, ray: &Ray) -> IntersectionList<'_> {
+        let ray_obj_space = ray.transform(&self.inverse);
         self.local_intersect(&ray_obj_space)
     }
 
     pub fn normal_at(&self, point: Tuple) -> Tuple {
         assert!(point.is_point());
-        let object_space_point = self.transform.inverse() * point;
+        let object_space_point = &self.inverse * point;
         let object_normal = self.local_normal_at(object_space_point);
-        let mut world_normal = self.transform.inverse().transpose() * object_normal;
+        let mut world_normal = self.inverse.transpose() * object_normal;
         world_normal.w = 0.;
         world_normal.normalize()
     }
@@ -68,7 +201,7 @@ mod tests {
     use super::*;
     use crate::{
         color::Color, intersection::Intersection, light::PointLight, matrix::Matrix,
-        shapes::Sphere, world::World, EPSILON,
+        rng::Sampler, shapes::Sphere, world::World, EPSILON,
     };
 
     #[test]
@@ -129,7 +262,7 @@ mod tests {
         assert_eq!(c.point, Tuple::point(0., 0., -1.));
         assert_eq!(c.eye_vector, Tuple::vector(0., 0., -1.));
         assert_eq!(c.normal_vector, Tuple::vector(0., 0., -1.));
-        assert_eq!(c.inside, false);
+        assert!(!c.inside);
 
         let r = Ray::new(Tuple::point(0., 0., 0.), Tuple::vector(0., 0., 1.));
         let shape = Sphere::new(None);
@@ -141,7 +274,7 @@ mod tests {
         assert_eq!(c.point, Tuple::point(0., 0., 1.));
         assert_eq!(c.eye_vector, Tuple::vector(0., 0., -1.));
         assert_eq!(c.normal_vector, Tuple::vector(0., 0., -1.));
-        assert_eq!(c.inside, true);
+        assert!(c.inside);
     }
 
     #[test]
@@ -151,7 +284,7 @@ mod tests {
         let shape = &w.objects[0];
         let i = r.intersect_object(shape);
         let i = i.hit().unwrap();
-        let c = i.context(&r, None).shade_hit(&w, MAX_REFLECTIONS);
+        let c = i.context(&r, None).shade_hit(&w, MAX_REFLECTIONS, &mut Sampler::Unseeded);
         assert_eq!(c, Color::new(0.38066, 0.47583, 0.2855));
     }
 
@@ -163,7 +296,7 @@ mod tests {
         let shape = &w.objects[1];
         let i = r.intersect_object(shape);
         let i = i.hit().unwrap();
-        let c = i.context(&r, None).shade_hit(&w, MAX_REFLECTIONS);
+        let c = i.context(&r, None).shade_hit(&w, MAX_REFLECTIONS, &mut Sampler::Unseeded);
         assert_eq!(c, Color::new(0.90498, 0.90498, 0.90498));
     }
 
@@ -171,7 +304,7 @@ mod tests {
     fn hit_offset_point() {
         let r = Ray::new(Tuple::point(0., 0., -5.), Tuple::vector(0., 0., 1.));
         let mut shape = Sphere::new(None);
-        shape.transform = Matrix::translation(0., 0., 1.);
+        shape.set_transform(&Matrix::translation(0., 0., 1.));
         let i = r.intersect_object(&shape);
         let hit = i.hit().unwrap();
         let c = hit.context(&r, None);
@@ -191,4 +324,21 @@ mod tests {
         m.ambient = 1.;
         assert_eq!(s.material, m);
     }
+
+    #[test]
+    fn builder() {
+        let mut m = Material::new();
+        m.ambient = 1.;
+        let mut s = Sphere::new(None)
+            .with_transform(&Matrix::scaling(2., 2., 2.))
+            .with_material(m.clone())
+            .with_name("ball");
+        assert_eq!(*s.transform(), Matrix::scaling(2., 2., 2.));
+        assert_eq!(*s.inverse(), Matrix::scaling(0.5, 0.5, 0.5));
+        assert_eq!(s.material, m);
+        assert_eq!(s.name.as_deref(), Some("ball"));
+
+        s.set_transform(&Matrix::translation(1., 0., 0.));
+        assert_eq!(*s.inverse(), Matrix::translation(-1., 0., 0.));
+    }
 }
@@ -0,0 +1,237 @@
+use crate::bounds::BoundingBox;
+use crate::intersection::{IntersectionList, Intersections};
+use crate::material::Material;
+use crate::matrix::Matrix;
+use crate::ray::Ray;
+use crate::shape::{Object, ShapeType};
+use crate::tuple::Tuple;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    Difference,
+}
+
+// Boolean combination of two objects. The children keep their own materials and are
+// stored in world space, so transforming the CSG object transforms them along with it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Csg {
+    pub operation: CsgOperation,
+    left: Box<Object>,
+    right: Box<Object>,
+}
+
+impl Csg {
+    pub fn new(operation: CsgOperation, left: Object, right: Object) -> Object {
+        Object::new(
+            ShapeType::Csg(Csg {
+                operation,
+                left: Box::new(left),
+                right: Box::new(right),
+            }),
+            Material::default(),
+        )
+    }
+
+    pub fn left(&self) -> &Object {
+        &self.left
+    }
+
+    pub fn right(&self) -> &Object {
+        &self.right
+    }
+
+    pub(crate) fn children_mut(&mut self) -> [&mut Object; 2] {
+        [&mut self.left, &mut self.right]
+    }
+
+    // Applies a transformation on top of the children's current ones
+    pub(crate) fn transform_children(&mut self, m: &Matrix) {
+        for child in self.children_mut() {
+            let transform = m * child.transform();
+            child.set_transform(&transform);
+        }
+    }
+
+    // Whether an intersection belongs on the combined surface, given which child it is
+    // on and whether it is inside each child at that point
+    fn intersection_allowed(&self, left_hit: bool, in_left: bool, in_right: bool) -> bool {
+        match self.operation {
+            CsgOperation::Union => (left_hit && !in_right) || (!left_hit && !in_left),
+            CsgOperation::Intersection => (left_hit && in_right) || (!left_hit && in_left),
+            CsgOperation::Difference => (left_hit && !in_right) || (!left_hit && in_left),
+        }
+    }
+
+    fn filter_intersections<'a>(&self, xs: IntersectionList<'a>) -> IntersectionList<'a> {
+        let (mut in_left, mut in_right) = (false, false);
+        let mut result = Intersections::new();
+        for i in xs.intersections {
+            let left_hit = self.left.includes(i.object);
+            if self.intersection_allowed(left_hit, in_left, in_right) {
+                result.push(i);
+            }
+            if left_hit {
+                in_left = !in_left;
+            } else {
+                in_right = !in_right;
+            }
+        }
+        IntersectionList::new(result)
+    }
+
+    pub fn local_intersect<'a>(
+        &'a self,
+        ray_obj_space: &Ray,
+        object: &'a Object,
+    ) -> IntersectionList<'a> {
+        // Back to world space, where the children are
+        let ray = ray_obj_space.transform(object.transform());
+        let xs = self.left.intersect(&ray) + self.right.intersect(&ray);
+        self.filter_intersections(xs)
+    }
+
+    // Already in world space, like the children
+    pub fn bounds(&self) -> BoundingBox {
+        let mut bounds = self.left.bounds();
+        bounds.merge(&self.right.bounds());
+        bounds
+    }
+
+    pub fn local_normal_at(&self, _object_space_point: Tuple) -> Tuple {
+        unreachable!("intersections are always with one of the children")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        intersection::Intersection,
+        shapes::{Cube, Sphere},
+    };
+
+    #[test]
+    fn rules() {
+        let csg = |operation| match Csg::new(operation, Sphere::new(None), Cube::new(None)).shape {
+            ShapeType::Csg(csg) => csg,
+            _ => unreachable!(),
+        };
+        // (left hit, in left, in right) for every combination, in order
+        let cases = [
+            (true, true, true),
+            (true, true, false),
+            (true, false, true),
+            (true, false, false),
+            (false, true, true),
+            (false, true, false),
+            (false, false, true),
+            (false, false, false),
+        ];
+        for (operation, expected) in [
+            (
+                CsgOperation::Union,
+                [false, true, false, true, false, false, true, true],
+            ),
+            (
+                CsgOperation::Intersection,
+                [true, false, true, false, true, true, false, false],
+            ),
+            (
+                CsgOperation::Difference,
+                [false, true, false, true, true, true, false, false],
+            ),
+        ] {
+            let csg = csg(operation);
+            for ((left_hit, in_left, in_right), allowed) in cases.iter().zip(expected) {
+                assert_eq!(
+                    csg.intersection_allowed(*left_hit, *in_left, *in_right),
+                    allowed
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn filtering() {
+        for (operation, kept) in [
+            (CsgOperation::Union, [0, 3]),
+            (CsgOperation::Intersection, [1, 2]),
+            (CsgOperation::Difference, [0, 1]),
+        ] {
+            let object = Csg::new(operation, Sphere::new(None), Cube::new(None));
+            let ShapeType::Csg(ref csg) = object.shape else {
+                unreachable!()
+            };
+            let xs = IntersectionList::new([
+                Intersection::new(1., csg.left()),
+                Intersection::new(2., csg.right()),
+                Intersection::new(3., csg.left()),
+                Intersection::new(4., csg.right()),
+            ]);
+            let ts: Vec<f64> = csg
+                .filter_intersections(xs)
+                .intersections
+                .iter()
+                .map(|i| i.t)
+                .collect();
+            assert_eq!(ts, kept.map(|k| k as f64 + 1.));
+        }
+    }
+
+    #[test]
+    fn intersect() {
+        let object = Csg::new(
+            CsgOperation::Union,
+            Sphere::new(None),
+            Sphere::new(None).with_transform(&Matrix::translation(0., 0., 0.5)),
+        );
+        let r = Ray::new(Tuple::point(0., 2., -5.), Tuple::vector(0., 0., 1.));
+        assert_eq!(r.intersect_object(&object).intersections.len(), 0);
+
+        let r = Ray::new(Tuple::point(0., 0., -5.), Tuple::vector(0., 0., 1.));
+        let xs = r.intersect_object(&object);
+        let ShapeType::Csg(ref csg) = object.shape else {
+            unreachable!()
+        };
+        assert_eq!(xs.intersections.len(), 2);
+        assert_eq!(xs.intersections[0].t, 4.);
+        assert!(std::ptr::eq(xs.intersections[0].object, csg.left()));
+        assert_eq!(xs.intersections[1].t, 6.5);
+        assert!(std::ptr::eq(xs.intersections[1].object, csg.right()));
+    }
+
+    #[test]
+    fn transformed() {
+        // A sphere with a cube carved out of its lower half, moved along x
+        let object = Csg::new(
+            CsgOperation::Difference,
+            Sphere::new(None),
+            Cube::new(None).with_transform(&Matrix::translation(0., -1., 0.)),
+        )
+        .with_transform(&Matrix::translation(5., 0., 0.));
+        let ShapeType::Csg(ref csg) = object.shape else {
+            unreachable!()
+        };
+        assert!(object.includes(csg.right()));
+        assert_eq!(csg.right().transform(), &Matrix::translation(5., -1., 0.));
+
+        let r = Ray::new(Tuple::point(5., 5., 0.), Tuple::vector(0., -1., 0.));
+        let xs = r.intersect_object(&object);
+        let ts: Vec<f64> = xs.intersections.iter().map(|i| i.t).collect();
+        assert_eq!(ts, [4., 5.]);
+        // The cut face is the top of the cube, which faces up
+        let cut = &xs.intersections[1];
+        assert_eq!(
+            cut.object.normal_at(r.position(cut.t)),
+            Tuple::vector(0., 1., 0.)
+        );
+        assert_eq!(
+            xs.intersections[0]
+                .object
+                .normal_at(Tuple::point(5., 1., 0.)),
+            Tuple::vector(0., 1., 0.)
+        );
+    }
+}
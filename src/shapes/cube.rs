@@ -1,26 +1,19 @@
+use crate::bounds::BoundingBox;
 use crate::intersection::{Intersection, IntersectionList};
 use crate::material::Material;
-use crate::matrix::Matrix;
 use crate::ray::Ray;
 use crate::shape::{Object, ShapeType};
 use crate::tuple::Tuple;
 
 // A unit cube
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Cube;
 
 impl Cube {
     pub fn new(material_opt: Option<Material>) -> Object {
-        let material = match material_opt {
-            Some(x) => x,
-            None => Material::new(),
-        };
-
-        Object {
-            transform: Matrix::identity(4),
-            shape: ShapeType::Cube(Cube),
-            material,
-        }
+        let material = material_opt.unwrap_or_default();
+
+        Object::new(ShapeType::Cube(Cube), material)
     }
 
     fn check_axis(&self, origin: f64, direction: f64) -> (f64, f64) {
@@ -45,31 +38,29 @@ impl Cube {
         let (xtmin, xtmax) = self.check_axis(ray_obj_space.origin.x, ray_obj_space.direction.x);
         let (ytmin, ytmax) = self.check_axis(ray_obj_space.origin.y, ray_obj_space.direction.y);
         let (ztmin, ztmax) = self.check_axis(ray_obj_space.origin.z, ray_obj_space.direction.z);
-        let tmin = vec![xtmin, ytmin, ztmin]
-            .into_iter()
-            .fold(f64::NEG_INFINITY, f64::max);
-        let tmax = vec![xtmax, ytmax, ztmax]
-            .into_iter()
-            .fold(f64::INFINITY, f64::min);
+        let tmin = xtmin.max(ytmin).max(ztmin);
+        let tmax = xtmax.min(ytmax).min(ztmax);
 
         if tmin > tmax {
-            return IntersectionList::new(vec![]);
+            IntersectionList::new([])
         } else {
-            IntersectionList::new(vec![
+            IntersectionList::new([
                 Intersection::new(tmin, object),
                 Intersection::new(tmax, object),
             ])
         }
     }
 
+    pub fn local_bounds(&self) -> BoundingBox {
+        BoundingBox::new(Tuple::point(-1., -1., -1.), Tuple::point(1., 1., 1.))
+    }
+
     pub fn local_normal_at(&self, object_space_point: Tuple) -> Tuple {
-        let maxc = vec![
-            object_space_point.x.abs(),
-            object_space_point.y.abs(),
-            object_space_point.z.abs(),
-        ]
-        .into_iter()
-        .fold(f64::NEG_INFINITY, f64::max);
+        let maxc = object_space_point
+            .x
+            .abs()
+            .max(object_space_point.y.abs())
+            .max(object_space_point.z.abs());
 
         if maxc == object_space_point.x.abs() {
             Tuple::vector(object_space_point.x, 0., 0.)
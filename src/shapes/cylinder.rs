@@ -1,34 +1,64 @@
 use float_cmp::approx_eq;
 
-use crate::EPSILON;
-use crate::intersection::{Intersection, IntersectionList};
+use crate::bounds::BoundingBox;
+use crate::intersection::{Intersection, IntersectionList, Intersections};
 use crate::material::Material;
-use crate::matrix::Matrix;
 use crate::ray::Ray;
 use crate::shape::{Object, ShapeType};
 use crate::tuple::Tuple;
+use crate::EPSILON;
 
-// A unit cube
-#[derive(Debug, PartialEq)]
+// A unit radius cylinder around the y axis, cut off below `minimum` and above
+// `maximum` (both exclusive). Closed cylinders have caps at both ends
+#[derive(Debug, Clone, PartialEq)]
 pub struct Cylinder {
-  minimum: f64,
-  maximum: f64,
+    pub minimum: f64,
+    pub maximum: f64,
+    pub closed: bool,
 }
 
 impl Cylinder {
+    // An infinitely long, open cylinder
     pub fn new(material_opt: Option<Material>) -> Object {
-        let material = match material_opt {
-            Some(x) => x,
-            None => Material::new(),
-        };
-
-        Object {
-            transform: Matrix::identity(4),
-            shape: ShapeType::Cylinder(Cylinder {
-              minimum: -f64::NEG_INFINITY,
-              maximum: f64::INFINITY
+        Cylinder::truncated(f64::NEG_INFINITY, f64::INFINITY, false, material_opt)
+    }
+
+    pub fn truncated(
+        minimum: f64,
+        maximum: f64,
+        closed: bool,
+        material_opt: Option<Material>,
+    ) -> Object {
+        let material = material_opt.unwrap_or_default();
+
+        Object::new(
+            ShapeType::Cylinder(Cylinder {
+                minimum,
+                maximum,
+                closed,
             }),
             material,
+        )
+    }
+
+    // Whether the ray is within the radius of the cylinder at t
+    fn check_cap(ray: &Ray, t: f64) -> bool {
+        let x = ray.origin.x + t * ray.direction.x;
+        let z = ray.origin.z + t * ray.direction.z;
+        x * x + z * z <= 1.
+    }
+
+    fn intersect_caps<'a>(&self, ray: &Ray, object: &'a Object, xs: &mut Intersections<'a>) {
+        // Caps only matter on closed cylinders, and only rays that aren't parallel to
+        // them can hit them
+        if !self.closed || approx_eq!(f64, ray.direction.y, 0., epsilon = EPSILON) {
+            return;
+        }
+        for cap in [self.minimum, self.maximum] {
+            let t = (cap - ray.origin.y) / ray.direction.y;
+            if Cylinder::check_cap(ray, t) {
+                xs.push(Intersection::new(t, object));
+            }
         }
     }
 
@@ -37,41 +67,161 @@ impl Cylinder {
         ray_obj_space: &Ray,
         object: &'a Object,
     ) -> IntersectionList<'a> {
-      let a = ray_obj_space.direction.x * ray_obj_space.direction.x + ray_obj_space.direction.z * ray_obj_space.direction.z;
+        let mut xs = Intersections::new();
+        let a = ray_obj_space.direction.x * ray_obj_space.direction.x
+            + ray_obj_space.direction.z * ray_obj_space.direction.z;
 
-      if approx_eq!(f64, a, 0., epsilon = EPSILON) {
-        return IntersectionList::new(vec![]);
-      }
+        // Rays parallel to the y axis can only hit the caps
+        if !approx_eq!(f64, a, 0., epsilon = EPSILON) {
+            let b = 2. * ray_obj_space.origin.x * ray_obj_space.direction.x
+                + 2. * ray_obj_space.origin.z * ray_obj_space.direction.z;
+            let c = ray_obj_space.origin.x * ray_obj_space.origin.x
+                + ray_obj_space.origin.z * ray_obj_space.origin.z
+                - 1.;
+            let discriminant = b * b - 4. * a * c;
 
-      let b = 2. * ray_obj_space.origin.x * ray_obj_space.direction.x + 2. * ray_obj_space.origin.z * ray_obj_space.direction.z;
-      let c = ray_obj_space.origin.x * ray_obj_space.origin.x + ray_obj_space.origin.z * ray_obj_space.origin.z - 1.;
-      let discriminant = b*b - 4. * a * c;
+            if discriminant < 0. {
+                return IntersectionList::new([]);
+            }
 
-      if discriminant < 0. {
-        return IntersectionList::new(vec![])
-      }
+            let t0 = (-b - discriminant.sqrt()) / (2. * a);
+            let t1 = (-b + discriminant.sqrt()) / (2. * a);
+            for t in [t0, t1] {
+                let y = ray_obj_space.origin.y + t * ray_obj_space.direction.y;
+                if self.minimum < y && y < self.maximum {
+                    xs.push(Intersection::new(t, object));
+                }
+            }
+        }
 
-      let t0 = -b - discriminant.sqrt() / (2. * a);
-      let t1 = -b + discriminant.sqrt() / (2. * a);
+        self.intersect_caps(ray_obj_space, object, &mut xs);
+        IntersectionList::new(xs)
+    }
 
-      return IntersectionList::new(vec![Intersection::new(t0, object), Intersection::new(t1, object)]);
+    pub fn local_bounds(&self) -> BoundingBox {
+        BoundingBox::new(
+            Tuple::point(-1., self.minimum, -1.),
+            Tuple::point(1., self.maximum, 1.),
+        )
     }
 
     pub fn local_normal_at(&self, object_space_point: Tuple) -> Tuple {
-        Tuple::vector(object_space_point.x, 0., object_space_point.z)
+        let distance = object_space_point.x * object_space_point.x
+            + object_space_point.z * object_space_point.z;
+        if distance < 1. && object_space_point.y >= self.maximum - EPSILON {
+            Tuple::vector(0., 1., 0.)
+        } else if distance < 1. && object_space_point.y <= self.minimum + EPSILON {
+            Tuple::vector(0., -1., 0.)
+        } else {
+            Tuple::vector(object_space_point.x, 0., object_space_point.z)
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::assert_approx_eq;
+
+    fn ts(object: &Object, origin: Tuple, direction: Tuple) -> Vec<f64> {
+        let r = Ray::new(origin, direction.normalize());
+        r.intersect_object(object)
+            .intersections
+            .iter()
+            .map(|i| i.t)
+            .collect()
+    }
 
     #[test]
-    fn intersect() {}
+    fn intersect() {
+        let c = Cylinder::new(None);
+        let cases = [
+            (Tuple::point(1., 0., -5.), Tuple::vector(0., 0., 1.), 5., 5.),
+            (Tuple::point(0., 0., -5.), Tuple::vector(0., 0., 1.), 4., 6.),
+            (
+                Tuple::point(0.5, 0., -5.),
+                Tuple::vector(0.1, 1., 1.),
+                6.80798,
+                7.08872,
+            ),
+        ];
+        for (origin, direction, t0, t1) in cases {
+            let xs = ts(&c, origin, direction);
+            assert_eq!(xs.len(), 2);
+            assert_approx_eq!(xs[0], t0);
+            assert_approx_eq!(xs[1], t1);
+        }
+    }
+
+    #[test]
+    fn misses() {
+        let c = Cylinder::new(None);
+        for (origin, direction) in [
+            (Tuple::point(1., 0., 0.), Tuple::vector(0., 1., 0.)),
+            (Tuple::point(0., 0., 0.), Tuple::vector(0., 1., 0.)),
+            (Tuple::point(0., 0., -5.), Tuple::vector(1., 1., 1.)),
+        ] {
+            assert!(ts(&c, origin, direction).is_empty());
+        }
+    }
 
     #[test]
-    fn misses() {}
+    fn normal() {
+        let c = Cylinder::new(None);
+        assert_eq!(
+            c.normal_at(Tuple::point(1., 0., 0.)),
+            Tuple::vector(1., 0., 0.)
+        );
+        assert_eq!(
+            c.normal_at(Tuple::point(0., 5., -1.)),
+            Tuple::vector(0., 0., -1.)
+        );
+        assert_eq!(
+            c.normal_at(Tuple::point(-1., 1., 0.)),
+            Tuple::vector(-1., 0., 0.)
+        );
+    }
 
     #[test]
-    fn normal() {}
+    fn truncated() {
+        let c = Cylinder::truncated(1., 2., false, None);
+        let cases = [
+            (Tuple::point(0., 1.5, 0.), Tuple::vector(0.1, 1., 0.), 0),
+            (Tuple::point(0., 3., -5.), Tuple::vector(0., 0., 1.), 0),
+            (Tuple::point(0., 0., -5.), Tuple::vector(0., 0., 1.), 0),
+            (Tuple::point(0., 2., -5.), Tuple::vector(0., 0., 1.), 0),
+            (Tuple::point(0., 1., -5.), Tuple::vector(0., 0., 1.), 0),
+            (Tuple::point(0., 1.5, -2.), Tuple::vector(0., 0., 1.), 2),
+        ];
+        for (origin, direction, count) in cases {
+            assert_eq!(ts(&c, origin, direction).len(), count);
+        }
+    }
+
+    #[test]
+    fn caps() {
+        let c = Cylinder::truncated(1., 2., true, None);
+        let cases = [
+            (Tuple::point(0., 3., 0.), Tuple::vector(0., -1., 0.), 2),
+            (Tuple::point(0., 3., -2.), Tuple::vector(0., -1., 2.), 2),
+            (Tuple::point(0., 4., -2.), Tuple::vector(0., -1., 1.), 2),
+            (Tuple::point(0., 0., -2.), Tuple::vector(0., 1., 2.), 2),
+            (Tuple::point(0., -1., -2.), Tuple::vector(0., 1., 1.), 2),
+        ];
+        for (origin, direction, count) in cases {
+            assert_eq!(ts(&c, origin, direction).len(), count);
+        }
+
+        let normals = [
+            (Tuple::point(0., 1., 0.), Tuple::vector(0., -1., 0.)),
+            (Tuple::point(0.5, 1., 0.), Tuple::vector(0., -1., 0.)),
+            (Tuple::point(0., 1., 0.5), Tuple::vector(0., -1., 0.)),
+            (Tuple::point(0., 2., 0.), Tuple::vector(0., 1., 0.)),
+            (Tuple::point(0.5, 2., 0.), Tuple::vector(0., 1., 0.)),
+            (Tuple::point(0., 2., 0.5), Tuple::vector(0., 1., 0.)),
+        ];
+        for (point, normal) in normals {
+            assert_eq!(c.normal_at(point), normal);
+        }
+    }
 }
@@ -0,0 +1,279 @@
+use crate::bounds::BoundingBox;
+use crate::intersection::{Intersection, IntersectionList};
+use crate::ray::Ray;
+use crate::shape::{Object, ShapeType};
+use crate::shapes::sdf::march;
+use crate::tuple::Tuple;
+
+// Samples taken along the longest diagonal of the bounds while marching. Features
+// thinner than the spacing can be missed
+const SAMPLES: f64 = 256.;
+// Points per axis warped to find the bounds
+const BOUNDS_SAMPLES: usize = 9;
+// Offset used for the differences that estimate the warp's derivatives
+const JACOBIAN_DELTA: f64 = 1e-5;
+
+// Warps of space around the y axis
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Deformation {
+    // Turns `rate` radians around the y axis for every unit up it
+    Twist { rate: f64 },
+    // Scales x and z by 1 + rate * y
+    Taper { rate: f64 },
+    // Curls the y axis into an arc of curvature `rate` towards +x
+    Bend { rate: f64 },
+}
+
+impl Deformation {
+    // Where a point of the undeformed shape ends up
+    pub fn forward(&self, p: Tuple) -> Tuple {
+        match *self {
+            Deformation::Twist { rate } => rotate_y(p, rate * p.y),
+            Deformation::Taper { rate } => {
+                let s = 1. + rate * p.y;
+                Tuple::point(p.x * s, p.y, p.z * s)
+            }
+            Deformation::Bend { rate: 0. } => p,
+            Deformation::Bend { rate } => {
+                // Bending towards -x is the mirror image of bending towards +x
+                let (side, radius) = (rate.signum(), 1. / rate.abs());
+                let angle = p.y / radius;
+                let out = radius - side * p.x;
+                Tuple::point(side * (radius - out * angle.cos()), out * angle.sin(), p.z)
+            }
+        }
+    }
+
+    // Where a point of the deformed shape came from
+    pub fn inverse(&self, p: Tuple) -> Tuple {
+        match *self {
+            Deformation::Twist { rate } => rotate_y(p, -rate * p.y),
+            Deformation::Taper { rate } => {
+                let s = 1. + rate * p.y;
+                Tuple::point(p.x / s, p.y, p.z / s)
+            }
+            Deformation::Bend { rate: 0. } => p,
+            Deformation::Bend { rate } => {
+                let (side, radius) = (rate.signum(), 1. / rate.abs());
+                let (x, y) = (radius - side * p.x, p.y);
+                let angle = y.atan2(x);
+                Tuple::point(
+                    side * (radius - (x * x + y * y).sqrt()),
+                    angle * radius,
+                    p.z,
+                )
+            }
+        }
+    }
+}
+
+fn rotate_y(p: Tuple, angle: f64) -> Tuple {
+    let (sin, cos) = angle.sin_cos();
+    Tuple::point(p.x * cos + p.z * sin, p.y, -p.x * sin + p.z * cos)
+}
+
+// A shape seen through a deformation. Rays stay straight in deformed space, so they
+// are marched, checking whether each sample maps back inside the shape, which has
+// to be closed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Deformed {
+    inner: Box<Object>,
+    pub deformation: Deformation,
+    bounds: BoundingBox,
+}
+
+impl Deformed {
+    // Shaded with the shape's own material
+    pub fn new(inner: Object, deformation: Deformation) -> Object {
+        let inner_bounds = inner.bounds();
+        assert!(
+            inner_bounds.is_finite(),
+            "only bounded shapes can be deformed"
+        );
+        if let Deformation::Taper { rate } = deformation {
+            let (low, high) = (
+                1. + rate * inner_bounds.min.y,
+                1. + rate * inner_bounds.max.y,
+            );
+            assert!(
+                low > 0. && high > 0.,
+                "the taper turns the shape inside out"
+            );
+        }
+        let bounds = Deformed::warp_bounds(&inner_bounds, deformation);
+        let material = inner.material.clone();
+
+        Object::new(
+            ShapeType::Deformed(Deformed {
+                inner: Box::new(inner),
+                deformation,
+                bounds,
+            }),
+            material,
+        )
+    }
+
+    pub fn inner(&self) -> &Object {
+        &self.inner
+    }
+
+    // Bounds of a grid of warped points, padded by a spacing of the grid to cover
+    // any bulges between them
+    fn warp_bounds(inner: &BoundingBox, deformation: Deformation) -> BoundingBox {
+        let size = inner.max - inner.min;
+        let steps = (BOUNDS_SAMPLES - 1) as f64;
+        let mut bounds = BoundingBox::empty();
+        for i in 0..BOUNDS_SAMPLES {
+            for j in 0..BOUNDS_SAMPLES {
+                for k in 0..BOUNDS_SAMPLES {
+                    let p = Tuple::point(
+                        inner.min.x + size.x * i as f64 / steps,
+                        inner.min.y + size.y * j as f64 / steps,
+                        inner.min.z + size.z * k as f64 / steps,
+                    );
+                    bounds.add_point(deformation.forward(p));
+                }
+            }
+        }
+        let pad = (bounds.max - bounds.min) / steps;
+        BoundingBox::new(bounds.min - pad, bounds.max + pad)
+    }
+
+    // Counts crossings along an arbitrary direction, odd counts are inside
+    fn contains(&self, point: Tuple) -> bool {
+        let r = Ray::new(point, Tuple::vector(0.5773, 0.5774, 0.5775));
+        let crossings = self
+            .inner
+            .intersect(&r)
+            .intersections
+            .iter()
+            .filter(|i| i.t > 0.)
+            .count();
+        crossings % 2 == 1
+    }
+
+    pub fn local_intersect<'a>(
+        &self,
+        ray_obj_space: &Ray,
+        object: &'a Object,
+    ) -> IntersectionList<'a> {
+        let Some((start, end)) = self.bounds.range(ray_obj_space) else {
+            return IntersectionList::new([]);
+        };
+        let diagonal = (self.bounds.max - self.bounds.min).magnitude();
+        let step = diagonal / SAMPLES / ray_obj_space.direction.magnitude();
+        let crossings = march(
+            start,
+            end,
+            |t| {
+                let p = self.deformation.inverse(ray_obj_space.position(t));
+                if self.contains(p) {
+                    -1.
+                } else {
+                    1.
+                }
+            },
+            |_| step,
+        );
+        IntersectionList::new(crossings.into_iter().map(|t| Intersection::new(t, object)))
+    }
+
+    pub fn local_bounds(&self) -> BoundingBox {
+        self.bounds
+    }
+
+    // The shape's normal, carried through the transpose of the inverse warp's
+    // derivatives
+    pub fn local_normal_at(&self, object_space_point: Tuple) -> Tuple {
+        let q = self.deformation.inverse(object_space_point);
+        let n = self.inner.normal_at(q);
+        let column = |axis: Tuple| {
+            let plus = self
+                .deformation
+                .inverse(object_space_point + axis * JACOBIAN_DELTA);
+            let minus = self
+                .deformation
+                .inverse(object_space_point - axis * JACOBIAN_DELTA);
+            n.dot(&(plus - minus))
+        };
+        Tuple::vector(
+            column(Tuple::vector(1., 0., 0.)),
+            column(Tuple::vector(0., 1., 0.)),
+            column(Tuple::vector(0., 0., 1.)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_approx_eq;
+    use crate::shapes::{Cube, Sphere};
+    use crate::PI;
+
+    #[test]
+    fn warps() {
+        let deformations = [
+            Deformation::Twist { rate: 1. },
+            Deformation::Taper { rate: 0.3 },
+            Deformation::Bend { rate: 0.5 },
+            Deformation::Bend { rate: -0.5 },
+        ];
+        let p = Tuple::point(0.3, 0.7, -0.2);
+        for deformation in deformations {
+            assert_approx_eq!(deformation.inverse(deformation.forward(p)), p);
+        }
+
+        // A quarter turn of the unit circle the y axis bends into
+        let bend = Deformation::Bend { rate: 1. };
+        assert_approx_eq!(
+            bend.forward(Tuple::point(0., PI / 2., 0.)),
+            Tuple::point(1., 1., 0.)
+        );
+        let twist = Deformation::Twist { rate: PI / 2. };
+        assert_approx_eq!(
+            twist.forward(Tuple::point(1., 1., 0.)),
+            Tuple::point(0., 1., -1.)
+        );
+    }
+
+    #[test]
+    fn twisted_cube() {
+        let cube = Deformed::new(Cube::new(None), Deformation::Twist { rate: PI / 2. });
+        // Half way up, the square has turned by 45 degrees
+        let r = Ray::new(Tuple::point(0.3, 0.5, -5.), Tuple::vector(0., 0., 1.));
+        let xs = r.intersect_object(&cube).intersections;
+        assert_eq!(xs.len(), 2);
+        assert_approx_eq!(xs[0].t, 5. - (2_f64.sqrt() - 0.3), epsilon = 1e-3);
+
+        let n = cube.normal_at(r.position(xs[0].t));
+        assert!(n.x > 0.5 && n.z < -0.5);
+        assert!(cube.bounds().max.x >= 2_f64.sqrt());
+    }
+
+    #[test]
+    fn tapered_sphere() {
+        let sphere = Deformed::new(Sphere::new(None), Deformation::Taper { rate: 0.5 });
+        let r = Ray::new(Tuple::point(-5., 0., 0.), Tuple::vector(1., 0., 0.));
+        let xs = r.intersect_object(&sphere).intersections;
+        assert_approx_eq!(xs[0].t, 4., epsilon = 1e-3);
+        // The sides lean out as the sphere widens, so the normal tilts down
+        assert_approx_eq!(
+            sphere.normal_at(Tuple::point(-1., 0., 0.)),
+            Tuple::vector(-1., -0.5, 0.).normalize(),
+            epsilon = 1e-3
+        );
+
+        // Wider towards the top
+        let r = Ray::new(Tuple::point(-5., 0.5, 0.), Tuple::vector(1., 0., 0.));
+        let xs = r.intersect_object(&sphere).intersections;
+        let width = 1.25 * 0.75_f64.sqrt();
+        assert_approx_eq!(xs[0].t, 5. - width, epsilon = 1e-3);
+    }
+
+    #[test]
+    #[should_panic(expected = "inside out")]
+    fn inverted_taper() {
+        Deformed::new(Sphere::new(None), Deformation::Taper { rate: 2. });
+    }
+}
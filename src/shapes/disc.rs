@@ -0,0 +1,104 @@
+use crate::bounds::BoundingBox;
+use crate::intersection::{Intersection, IntersectionList};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::shape::{Object, ShapeType};
+use crate::tuple::Tuple;
+use crate::EPSILON;
+
+// A flat ring in the XZ plane around the origin, between the inner and outer radius.
+// An inner radius of 0 makes it a solid disc
+#[derive(Debug, Clone, PartialEq)]
+pub struct Disc {
+    pub inner_radius: f64,
+    pub outer_radius: f64,
+}
+
+impl Disc {
+    // A solid disc of radius 1
+    pub fn new(material_opt: Option<Material>) -> Object {
+        Disc::annulus(0., 1., material_opt)
+    }
+
+    pub fn annulus(inner_radius: f64, outer_radius: f64, material_opt: Option<Material>) -> Object {
+        assert!(0. <= inner_radius && inner_radius < outer_radius);
+        let material = material_opt.unwrap_or_default();
+
+        Object::new(
+            ShapeType::Disc(Disc {
+                inner_radius,
+                outer_radius,
+            }),
+            material,
+        )
+    }
+
+    pub fn local_intersect<'a>(
+        &self,
+        ray_obj_space: &Ray,
+        object: &'a Object,
+    ) -> IntersectionList<'a> {
+        if ray_obj_space.direction.y.abs() < EPSILON {
+            return IntersectionList::new([]);
+        }
+
+        let t = -ray_obj_space.origin.y / ray_obj_space.direction.y;
+        let point = ray_obj_space.position(t);
+        let distance = point.x * point.x + point.z * point.z;
+        if distance < self.inner_radius * self.inner_radius
+            || distance > self.outer_radius * self.outer_radius
+        {
+            IntersectionList::new([])
+        } else {
+            IntersectionList::new([Intersection::new(t, object)])
+        }
+    }
+
+    pub fn local_bounds(&self) -> BoundingBox {
+        BoundingBox::new(
+            Tuple::point(-self.outer_radius, 0., -self.outer_radius),
+            Tuple::point(self.outer_radius, 0., self.outer_radius),
+        )
+    }
+
+    pub fn local_normal_at(&self, _object_space_point: Tuple) -> Tuple {
+        Tuple::vector(0., 1., 0.)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hits(object: &Object, x: f64) -> usize {
+        let r = Ray::new(Tuple::point(x, 1., 0.), Tuple::vector(0., -1., 0.));
+        r.intersect_object(object).intersections.len()
+    }
+
+    #[test]
+    fn intersect() {
+        let disc = Disc::new(None);
+        assert_eq!(hits(&disc, 0.), 1);
+        assert_eq!(hits(&disc, 0.99), 1);
+        assert_eq!(hits(&disc, 1.01), 0);
+        let r = Ray::new(Tuple::point(0., 1., -5.), Tuple::vector(0., 0., 1.));
+        assert_eq!(r.intersect_object(&disc).intersections.len(), 0);
+
+        let ring = Disc::annulus(0.5, 2., None);
+        assert_eq!(hits(&ring, 0.), 0);
+        assert_eq!(hits(&ring, 0.49), 0);
+        assert_eq!(hits(&ring, 0.5), 1);
+        assert_eq!(hits(&ring, 1.5), 1);
+        assert_eq!(hits(&ring, -2.), 1);
+        assert_eq!(hits(&ring, 2.01), 0);
+    }
+
+    #[test]
+    fn normal() {
+        let ring = Disc::annulus(0.5, 2., None);
+        assert_eq!(
+            ring.normal_at(Tuple::point(1., 0., 0.)),
+            Tuple::vector(0., 1., 0.)
+        );
+    }
+}
@@ -0,0 +1,254 @@
+use crate::bounds::BoundingBox;
+use crate::intersection::{Intersection, IntersectionList};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::shape::{Object, ShapeType};
+use crate::tuple::Tuple;
+use crate::EPSILON;
+
+// A glass lens around the y axis, `thickness` thick at the centre and `aperture` in
+// radius. Each face is a piece of a sphere: positive radii bulge outwards, negative
+// ones curve inwards and infinite ones are flat
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lens {
+    pub top_radius: f64,
+    pub bottom_radius: f64,
+    pub thickness: f64,
+    pub aperture: f64,
+}
+
+// How far a face of the given radius rises above its rim
+fn sag(radius: f64, aperture: f64) -> f64 {
+    if radius.is_infinite() {
+        0.
+    } else {
+        radius - radius.signum() * (radius * radius - aperture * aperture).sqrt()
+    }
+}
+
+impl Lens {
+    pub fn new(
+        top_radius: f64,
+        bottom_radius: f64,
+        thickness: f64,
+        aperture: f64,
+        material_opt: Option<Material>,
+    ) -> Object {
+        assert!(thickness > 0. && aperture > 0.);
+        assert!(aperture <= top_radius.abs() && aperture <= bottom_radius.abs());
+        let edge = thickness - sag(top_radius, aperture) - sag(bottom_radius, aperture);
+        assert!(edge >= -EPSILON, "the faces of the lens cross");
+        let material = material_opt.unwrap_or_default();
+
+        Object::new(
+            ShapeType::Lens(Lens {
+                top_radius,
+                bottom_radius,
+                thickness,
+                aperture,
+            }),
+            material,
+        )
+    }
+
+    // A symmetric lens as wide as it can be, so the faces meet in a sharp rim
+    pub fn biconvex(radius: f64, thickness: f64, material_opt: Option<Material>) -> Object {
+        assert!(0. < thickness && thickness <= 2. * radius);
+        let rest = radius - thickness / 2.;
+        let aperture = (radius * radius - rest * rest).sqrt();
+        Lens::new(radius, radius, thickness, aperture, material_opt)
+    }
+
+    // The face's radius, and which way is out of the lens through it
+    fn face(&self, top: bool) -> (f64, f64) {
+        if top {
+            (self.top_radius, 1.)
+        } else {
+            (self.bottom_radius, -1.)
+        }
+    }
+
+    fn centre(&self, top: bool) -> Tuple {
+        let (radius, side) = self.face(top);
+        Tuple::point(0., side * (self.thickness / 2. - radius), 0.)
+    }
+
+    // Negative on the lens' side of the face
+    fn face_value(&self, top: bool, point: Tuple) -> f64 {
+        let (radius, side) = self.face(top);
+        if radius.is_infinite() {
+            side * point.y - self.thickness / 2.
+        } else {
+            radius.signum() * ((point - self.centre(top)).magnitude() - radius.abs())
+        }
+    }
+
+    fn face_normal(&self, top: bool, point: Tuple) -> Tuple {
+        let (radius, side) = self.face(top);
+        if radius.is_infinite() {
+            Tuple::vector(0., side, 0.)
+        } else {
+            (point - self.centre(top)) * radius.signum()
+        }
+    }
+
+    fn contains(&self, point: Tuple) -> bool {
+        let bounds = self.local_bounds();
+        point.x * point.x + point.z * point.z <= self.aperture * self.aperture + EPSILON
+            && point.y >= bounds.min.y - EPSILON
+            && point.y <= bounds.max.y + EPSILON
+            && self.face_value(true, point) <= EPSILON
+            && self.face_value(false, point) <= EPSILON
+    }
+
+    pub fn local_intersect<'a>(
+        &self,
+        ray_obj_space: &Ray,
+        object: &'a Object,
+    ) -> IntersectionList<'a> {
+        let (origin, direction) = (ray_obj_space.origin, ray_obj_space.direction);
+        let mut candidates = vec![];
+        let mut quadratic = |a: f64, b: f64, c: f64| {
+            let discriminant = b * b - 4. * a * c;
+            if a.abs() > EPSILON && discriminant >= 0. {
+                let root = discriminant.sqrt();
+                candidates.push((-b - root) / (2. * a));
+                candidates.push((-b + root) / (2. * a));
+            }
+        };
+
+        // The side of the rim
+        quadratic(
+            direction.x * direction.x + direction.z * direction.z,
+            2. * (origin.x * direction.x + origin.z * direction.z),
+            origin.x * origin.x + origin.z * origin.z - self.aperture * self.aperture,
+        );
+        let mut planes = vec![];
+        for top in [true, false] {
+            let (radius, side) = self.face(top);
+            if radius.is_infinite() {
+                planes.push(side * self.thickness / 2.);
+            } else {
+                let to_origin = origin - self.centre(top);
+                quadratic(
+                    direction.dot(&direction),
+                    2. * direction.dot(&to_origin),
+                    to_origin.dot(&to_origin) - radius * radius,
+                );
+            }
+        }
+        if direction.y.abs() > EPSILON {
+            candidates.extend(planes.iter().map(|y| (y - origin.y) / direction.y));
+        }
+
+        // Only the parts of each surface that bound the lens count, once each where
+        // two of them meet at the rim
+        candidates.retain(|t| self.contains(ray_obj_space.position(*t)));
+        candidates.sort_by(f64::total_cmp);
+        candidates.dedup_by(|a, b| (*a - *b).abs() < EPSILON);
+        IntersectionList::new(candidates.into_iter().map(|t| Intersection::new(t, object)))
+    }
+
+    pub fn local_bounds(&self) -> BoundingBox {
+        let half = self.thickness / 2.;
+        let top = half.max(half - sag(self.top_radius, self.aperture));
+        let bottom = (-half).min(sag(self.bottom_radius, self.aperture) - half);
+        BoundingBox::new(
+            Tuple::point(-self.aperture, bottom, -self.aperture),
+            Tuple::point(self.aperture, top, self.aperture),
+        )
+    }
+
+    // The normal of whichever surface the point is closest to
+    pub fn local_normal_at(&self, object_space_point: Tuple) -> Tuple {
+        let p = object_space_point;
+        let rim = ((p.x * p.x + p.z * p.z).sqrt() - self.aperture).abs();
+        let top = self.face_value(true, p).abs();
+        let bottom = self.face_value(false, p).abs();
+        if rim < top && rim < bottom {
+            Tuple::vector(p.x, 0., p.z)
+        } else {
+            self.face_normal(top < bottom, p)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_approx_eq;
+
+    fn ts(object: &Object, origin: Tuple, direction: Tuple) -> Vec<f64> {
+        let r = Ray::new(origin, direction);
+        r.intersect_object(object)
+            .intersections
+            .iter()
+            .map(|i| i.t)
+            .collect()
+    }
+
+    #[test]
+    fn biconvex() {
+        let lens = Lens::biconvex(2., 1., None);
+        let down = Tuple::vector(0., -1., 0.);
+        let xs = ts(&lens, Tuple::point(0., 5., 0.), down);
+        assert_eq!(xs.len(), 2);
+        assert_approx_eq!(xs[0], 4.5);
+        assert_approx_eq!(xs[1], 5.5);
+        assert!(ts(&lens, Tuple::point(1.4, 5., 0.), down).is_empty());
+
+        assert_approx_eq!(
+            lens.normal_at(Tuple::point(0., 0.5, 0.)),
+            Tuple::vector(0., 1., 0.)
+        );
+        assert_approx_eq!(
+            lens.normal_at(Tuple::point(0., -0.5, 0.)),
+            Tuple::vector(0., -1., 0.)
+        );
+        assert_approx_eq!(lens.bounds().max.y, 0.5);
+        assert_approx_eq!(lens.bounds().max.x, 1.75_f64.sqrt());
+    }
+
+    #[test]
+    fn biconcave() {
+        let lens = Lens::new(-2., -2., 0.2, 1., None);
+        let down = Tuple::vector(0., -1., 0.);
+        let xs = ts(&lens, Tuple::point(0., 5., 0.), down);
+        assert_approx_eq!(xs[0], 4.9);
+        assert_approx_eq!(xs[1], 5.1);
+
+        // Thicker away from the axis, with faces tilted towards it
+        let surface = 2.1 - (4_f64 - 0.81).sqrt();
+        let xs = ts(&lens, Tuple::point(0.9, 5., 0.), down);
+        assert_approx_eq!(xs[0], 5. - surface);
+        let n = lens.normal_at(Tuple::point(0.9, surface, 0.));
+        assert!(n.x < 0. && n.y > 0.);
+
+        // Through the rim
+        let xs = ts(&lens, Tuple::point(-5., 0., 0.), Tuple::vector(1., 0., 0.));
+        assert_eq!(xs.len(), 2);
+        assert_approx_eq!(xs[0], 4.);
+        assert_approx_eq!(
+            lens.normal_at(Tuple::point(-1., 0., 0.)),
+            Tuple::vector(-1., 0., 0.)
+        );
+    }
+
+    #[test]
+    fn plano_convex() {
+        let lens = Lens::new(3., f64::INFINITY, 0.5, 1., None);
+        let xs = ts(&lens, Tuple::point(0., -5., 0.), Tuple::vector(0., 1., 0.));
+        assert_approx_eq!(xs[0], 4.75);
+        assert_approx_eq!(xs[1], 5.25);
+        assert_approx_eq!(
+            lens.normal_at(Tuple::point(0.5, -0.25, 0.)),
+            Tuple::vector(0., -1., 0.)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "cross")]
+    fn too_thin() {
+        Lens::new(2., 2., 0.1, 1.5, None);
+    }
+}
@@ -0,0 +1,171 @@
+use crate::bounds::BoundingBox;
+use crate::intersection::{Intersection, IntersectionList};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::shape::{Object, ShapeType};
+use crate::shapes::sdf::march;
+use crate::tuple::Tuple;
+
+// Steps per radius of the smallest ball while marching. The field has no distance
+// bound, so thin features between samples can be missed
+const STEPS_PER_RADIUS: f64 = 16.;
+
+// A center whose field is `weight` at its center and falls smoothly to zero at `radius`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metaball {
+    pub center: Tuple,
+    pub radius: f64,
+    pub weight: f64,
+}
+
+impl Metaball {
+    pub fn new(center: Tuple, radius: f64, weight: f64) -> Self {
+        assert!(center.is_point() && radius > 0.);
+        Self {
+            center,
+            radius,
+            weight,
+        }
+    }
+
+    // Wyvill falloff (1 - r²)³, so the field and its gradient vanish at the radius
+    fn field(&self, p: Tuple) -> f64 {
+        let r2 = (p - self.center).dot(&(p - self.center)) / (self.radius * self.radius);
+        if r2 >= 1. {
+            0.
+        } else {
+            self.weight * (1. - r2).powi(3)
+        }
+    }
+
+    fn gradient(&self, p: Tuple) -> Tuple {
+        let offset = p - self.center;
+        let r2 = offset.dot(&offset) / (self.radius * self.radius);
+        if r2 >= 1. {
+            Tuple::vector(0., 0., 0.)
+        } else {
+            offset * (-6. * self.weight * (1. - r2).powi(2) / (self.radius * self.radius))
+        }
+    }
+}
+
+// The surface where the summed fields of the balls reach `threshold`, so balls near
+// each other melt together
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metaballs {
+    pub balls: Vec<Metaball>,
+    pub threshold: f64,
+}
+
+impl Metaballs {
+    pub fn new(balls: Vec<Metaball>, threshold: f64, material_opt: Option<Material>) -> Object {
+        assert!(!balls.is_empty() && threshold > 0.);
+        let material = material_opt.unwrap_or_default();
+
+        Object::new(
+            ShapeType::Metaballs(Metaballs { balls, threshold }),
+            material,
+        )
+    }
+
+    // Negative inside the surface
+    fn value(&self, p: Tuple) -> f64 {
+        self.threshold - self.balls.iter().map(|ball| ball.field(p)).sum::<f64>()
+    }
+
+    pub fn local_intersect<'a>(
+        &self,
+        ray_obj_space: &Ray,
+        object: &'a Object,
+    ) -> IntersectionList<'a> {
+        let Some((start, end)) = self.local_bounds().range(ray_obj_space) else {
+            return IntersectionList::new([]);
+        };
+        let smallest = self
+            .balls
+            .iter()
+            .map(|ball| ball.radius)
+            .fold(f64::INFINITY, f64::min);
+        let step = smallest / STEPS_PER_RADIUS / ray_obj_space.direction.magnitude();
+        let crossings = march(
+            start,
+            end,
+            |t| self.value(ray_obj_space.position(t)),
+            |_| step,
+        );
+        IntersectionList::new(crossings.into_iter().map(|t| Intersection::new(t, object)))
+    }
+
+    // The field is zero outside every ball
+    pub fn local_bounds(&self) -> BoundingBox {
+        let mut bounds = BoundingBox::empty();
+        for ball in self.balls.iter() {
+            let r = Tuple::vector(ball.radius, ball.radius, ball.radius);
+            bounds.add_point(ball.center - r);
+            bounds.add_point(ball.center + r);
+        }
+        bounds
+    }
+
+    pub fn local_normal_at(&self, object_space_point: Tuple) -> Tuple {
+        // The field grows inwards, so the outward normal is against its gradient
+        let gradient = self
+            .balls
+            .iter()
+            .fold(Tuple::vector(0., 0., 0.), |sum, ball| {
+                sum + ball.gradient(object_space_point)
+            });
+        -gradient
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_approx_eq;
+
+    fn ts(object: &Object, origin: Tuple, direction: Tuple) -> Vec<f64> {
+        let r = Ray::new(origin, direction);
+        r.intersect_object(object)
+            .intersections
+            .iter()
+            .map(|i| i.t)
+            .collect()
+    }
+
+    #[test]
+    fn single_ball() {
+        let balls = Metaballs::new(
+            vec![Metaball::new(Tuple::point(0., 0., 0.), 1., 1.)],
+            0.5,
+            None,
+        );
+        // The surface is where (1 - r²)³ = 0.5
+        let radius = (1. - 0.5_f64.cbrt()).sqrt();
+        let xs = ts(&balls, Tuple::point(0., 0., -5.), Tuple::vector(0., 0., 1.));
+        assert_eq!(xs.len(), 2);
+        assert_approx_eq!(xs[0], 5. - radius);
+        assert_approx_eq!(xs[1], 5. + radius);
+        assert!(ts(
+            &balls,
+            Tuple::point(0., 0.5, -5.),
+            Tuple::vector(0., 0., 1.)
+        )
+        .is_empty());
+
+        let n = balls.normal_at(Tuple::point(radius, 0., 0.));
+        assert_approx_eq!(n, Tuple::vector(1., 0., 0.));
+    }
+
+    #[test]
+    fn blending() {
+        let ball = |x| Metaball::new(Tuple::point(x, 0., 0.), 1., 1.);
+        let single = Metaballs::new(vec![ball(-0.5)], 0.5, None);
+        let pair = Metaballs::new(vec![ball(-0.5), ball(0.5)], 0.5, None);
+        // Between the two centres, neither ball alone reaches the threshold
+        let origin = Tuple::point(0., 0., -5.);
+        let direction = Tuple::vector(0., 0., 1.);
+        assert!(ts(&single, origin, direction).is_empty());
+        assert_eq!(ts(&pair, origin, direction).len(), 2);
+    }
+}
@@ -1,9 +1,29 @@
+pub mod csg;
 pub mod cube;
 pub mod cylinder;
+pub mod deformed;
+pub mod disc;
+pub mod lens;
+pub mod metaballs;
 pub mod plane;
+pub mod polyhedron;
+pub mod rectangle;
+pub mod sdf;
 pub mod sphere;
+pub mod triangle;
+pub mod triangle_mesh;
 
+pub use csg::{Csg, CsgOperation};
 pub use cube::Cube;
 pub use cylinder::Cylinder;
+pub use deformed::{Deformation, Deformed};
+pub use disc::Disc;
+pub use lens::Lens;
+pub use metaballs::{Metaball, Metaballs};
 pub use plane::Plane;
+pub use polyhedron::{ConvexPolyhedron, HalfSpace};
+pub use rectangle::Rectangle;
+pub use sdf::{DistanceFn, Sdf, SdfShape};
 pub use sphere::Sphere;
+pub use triangle::Triangle;
+pub use triangle_mesh::TriangleMesh;
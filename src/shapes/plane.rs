@@ -1,27 +1,20 @@
+use crate::bounds::BoundingBox;
 use crate::intersection::{Intersection, IntersectionList};
 use crate::material::Material;
-use crate::matrix::Matrix;
 use crate::ray::Ray;
 use crate::shape::{Object, ShapeType};
 use crate::tuple::Tuple;
 use crate::EPSILON;
 
 // An XZ plane
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Plane;
 
 impl Plane {
     pub fn new(material_opt: Option<Material>) -> Object {
-        let material = match material_opt {
-            Some(x) => x,
-            None => Material::new(),
-        };
+        let material = material_opt.unwrap_or_default();
 
-        Object {
-            transform: Matrix::identity(4),
-            shape: ShapeType::Plane(Plane),
-            material,
-        }
+        Object::new(ShapeType::Plane(Plane), material)
     }
 
     pub fn local_intersect<'a>(
@@ -30,13 +23,20 @@ impl Plane {
         object: &'a Object,
     ) -> IntersectionList<'a> {
         if ray_obj_space.direction.y.abs() < EPSILON {
-            IntersectionList::new(vec![])
+            IntersectionList::new([])
         } else {
             let t = -ray_obj_space.origin.y / ray_obj_space.direction.y;
-            IntersectionList::new(vec![Intersection::new(t, object)])
+            IntersectionList::new([Intersection::new(t, object)])
         }
     }
 
+    pub fn local_bounds(&self) -> BoundingBox {
+        BoundingBox::new(
+            Tuple::point(f64::NEG_INFINITY, 0., f64::NEG_INFINITY),
+            Tuple::point(f64::INFINITY, 0., f64::INFINITY),
+        )
+    }
+
     pub fn local_normal_at(&self, _object_space_point: Tuple) -> Tuple {
         Tuple::vector(0., 1., 0.)
     }
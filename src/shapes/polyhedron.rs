@@ -0,0 +1,256 @@
+use crate::bounds::BoundingBox;
+use crate::intersection::{Intersection, IntersectionList};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::shape::{Object, ShapeType};
+use crate::tuple::Tuple;
+use crate::EPSILON;
+
+// The points p with normal . p <= offset
+#[derive(Debug, Clone, PartialEq)]
+pub struct HalfSpace {
+    pub normal: Tuple,
+    pub offset: f64,
+}
+
+impl HalfSpace {
+    pub fn new(normal: Tuple, offset: f64) -> Self {
+        assert!(normal.is_vector());
+        // Keep offsets as distances from the origin, so they compare between planes
+        let length = normal.magnitude();
+        Self {
+            normal: normal / length,
+            offset: offset / length,
+        }
+    }
+
+    // Positive outside, negative inside
+    fn signed_distance(&self, point: Tuple) -> f64 {
+        point.dot(&self.normal) - self.offset
+    }
+}
+
+// The intersection of a set of half spaces, such as a prism, frustum or gem cut. The
+// planes have to enclose a finite volume
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConvexPolyhedron {
+    planes: Vec<HalfSpace>,
+    bounds: BoundingBox,
+}
+
+impl ConvexPolyhedron {
+    pub fn new(planes: Vec<HalfSpace>, material_opt: Option<Material>) -> Object {
+        let bounds = vertex_bounds(&planes);
+        assert!(bounds.is_finite(), "the planes don't enclose a volume");
+        let material = material_opt.unwrap_or_default();
+
+        Object::new(
+            ShapeType::ConvexPolyhedron(ConvexPolyhedron { planes, bounds }),
+            material,
+        )
+    }
+
+    // A prism with a regular polygon of `sides` sides, with corners on the unit
+    // circle in XZ, extruded from y = -1 to 1. Three sides give the classic glass
+    // prism
+    pub fn prism(sides: usize, material_opt: Option<Material>) -> Object {
+        assert!(sides >= 3);
+        let apothem = (std::f64::consts::PI / sides as f64).cos();
+        let mut planes: Vec<_> = (0..sides)
+            .map(|i| {
+                let angle = (i as f64 + 0.5) * 2. * std::f64::consts::PI / sides as f64;
+                HalfSpace::new(Tuple::vector(angle.cos(), 0., angle.sin()), apothem)
+            })
+            .collect();
+        planes.push(HalfSpace::new(Tuple::vector(0., 1., 0.), 1.));
+        planes.push(HalfSpace::new(Tuple::vector(0., -1., 0.), 1.));
+        ConvexPolyhedron::new(planes, material_opt)
+    }
+
+    // The cube from -1 to 1 cut in half along the diagonal from its top left to its
+    // bottom right edge, leaving a ramp that rises towards -x
+    pub fn wedge(material_opt: Option<Material>) -> Object {
+        let planes = vec![
+            HalfSpace::new(Tuple::vector(-1., 0., 0.), 1.),
+            HalfSpace::new(Tuple::vector(0., -1., 0.), 1.),
+            HalfSpace::new(Tuple::vector(0., 0., -1.), 1.),
+            HalfSpace::new(Tuple::vector(0., 0., 1.), 1.),
+            HalfSpace::new(Tuple::vector(1., 1., 0.), 0.),
+        ];
+        ConvexPolyhedron::new(planes, material_opt)
+    }
+
+    pub fn planes(&self) -> &[HalfSpace] {
+        &self.planes
+    }
+
+    pub fn local_intersect<'a>(
+        &self,
+        ray_obj_space: &Ray,
+        object: &'a Object,
+    ) -> IntersectionList<'a> {
+        // Clip the ray against each plane in turn, like the slabs of a cube
+        let (mut t_enter, mut t_exit) = (f64::NEG_INFINITY, f64::INFINITY);
+        for plane in self.planes.iter() {
+            let distance = plane.signed_distance(ray_obj_space.origin);
+            let rate = ray_obj_space.direction.dot(&plane.normal);
+            if rate.abs() < EPSILON {
+                if distance > 0. {
+                    return IntersectionList::new([]);
+                }
+                continue;
+            }
+            let t = -distance / rate;
+            if rate < 0. {
+                t_enter = t_enter.max(t);
+            } else {
+                t_exit = t_exit.min(t);
+            }
+            if t_enter > t_exit {
+                return IntersectionList::new([]);
+            }
+        }
+        IntersectionList::new([
+            Intersection::new(t_enter, object),
+            Intersection::new(t_exit, object),
+        ])
+    }
+
+    pub fn local_bounds(&self) -> BoundingBox {
+        self.bounds
+    }
+
+    // The normal of the plane the point is closest to
+    pub fn local_normal_at(&self, object_space_point: Tuple) -> Tuple {
+        self.planes
+            .iter()
+            .max_by(|a, b| {
+                a.signed_distance(object_space_point)
+                    .total_cmp(&b.signed_distance(object_space_point))
+            })
+            .map(|plane| plane.normal)
+            .unwrap()
+    }
+}
+
+// Bounds of the corners, found where each three planes meet inside all the others.
+// Returns infinite bounds when there are too few corners to enclose anything
+fn vertex_bounds(planes: &[HalfSpace]) -> BoundingBox {
+    let mut bounds = BoundingBox::empty();
+    let mut corners = 0;
+    for (i, a) in planes.iter().enumerate() {
+        for (j, b) in planes.iter().enumerate().skip(i + 1) {
+            for c in planes.iter().skip(j + 1) {
+                let det = a.normal.dot(&b.normal.cross(&c.normal));
+                if det.abs() < EPSILON {
+                    continue;
+                }
+                let v = (b.normal.cross(&c.normal) * a.offset
+                    + c.normal.cross(&a.normal) * b.offset
+                    + a.normal.cross(&b.normal) * c.offset)
+                    / det;
+                let corner = Tuple::point(v.x, v.y, v.z);
+                if planes.iter().all(|p| p.signed_distance(corner) < EPSILON) {
+                    bounds.add_point(corner);
+                    corners += 1;
+                }
+            }
+        }
+    }
+    if corners < 4 {
+        BoundingBox::infinite()
+    } else {
+        bounds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_approx_eq;
+
+    fn octahedron() -> Object {
+        let mut planes = vec![];
+        for x in [-1., 1.] {
+            for y in [-1., 1.] {
+                for z in [-1., 1.] {
+                    planes.push(HalfSpace::new(Tuple::vector(x, y, z), 1.));
+                }
+            }
+        }
+        ConvexPolyhedron::new(planes, None)
+    }
+
+    #[test]
+    fn intersect() {
+        let shape = octahedron();
+        let r = Ray::new(Tuple::point(0., 0., -5.), Tuple::vector(0., 0., 1.));
+        let xs = r.intersect_object(&shape).intersections;
+        assert_eq!(xs.len(), 2);
+        assert_approx_eq!(xs[0].t, 4.);
+        assert_approx_eq!(xs[1].t, 6.);
+
+        // Inside the cube that bounds it but outside the octahedron
+        let r = Ray::new(Tuple::point(0.6, 0.6, -5.), Tuple::vector(0., 0., 1.));
+        assert!(r.intersect_object(&shape).intersections.is_empty());
+
+        let r = Ray::new(Tuple::point(0., 0., 0.), Tuple::vector(1., 0., 0.));
+        let xs = r.intersect_object(&shape).intersections;
+        assert_approx_eq!(xs[0].t, -1.);
+        assert_approx_eq!(xs[1].t, 1.);
+    }
+
+    #[test]
+    fn normals_and_bounds() {
+        let shape = octahedron();
+        let third = 1. / 3_f64.sqrt();
+        assert_approx_eq!(
+            shape.normal_at(Tuple::point(1. / 3., 1. / 3., 1. / 3.)),
+            Tuple::vector(third, third, third)
+        );
+        assert_approx_eq!(shape.bounds().min, Tuple::point(-1., -1., -1.));
+        assert_approx_eq!(shape.bounds().max, Tuple::point(1., 1., 1.));
+    }
+
+    #[test]
+    fn prism() {
+        let hexagon = ConvexPolyhedron::prism(6, None);
+        let r = Ray::new(Tuple::point(-5., 0.5, 0.), Tuple::vector(1., 0., 0.));
+        let xs = r.intersect_object(&hexagon).intersections;
+        assert_approx_eq!(xs[0].t, 4.);
+        assert_approx_eq!(xs[1].t, 6.);
+        assert_approx_eq!(hexagon.bounds().max.y, 1.);
+    }
+
+    #[test]
+    fn wedge() {
+        let wedge = ConvexPolyhedron::wedge(None);
+        let down = Tuple::vector(0., -1., 0.);
+        // The ramp is at height -x
+        for x in [-0.5, 0., 0.5] {
+            let r = Ray::new(Tuple::point(x, 5., 0.), down);
+            let xs = r.intersect_object(&wedge).intersections;
+            assert_approx_eq!(xs[0].t, 5. + x);
+            assert_approx_eq!(xs[1].t, 6.);
+        }
+        let r = Ray::new(Tuple::point(1.5, 5., 0.), down);
+        assert!(r.intersect_object(&wedge).intersections.is_empty());
+
+        let half = 1. / 2_f64.sqrt();
+        assert_approx_eq!(
+            wedge.normal_at(Tuple::point(0.2, -0.2, 0.5)),
+            Tuple::vector(half, half, 0.)
+        );
+        assert_approx_eq!(
+            wedge.normal_at(Tuple::point(-1., 0.5, 0.)),
+            Tuple::vector(-1., 0., 0.)
+        );
+        assert_approx_eq!(wedge.bounds().max, Tuple::point(1., 1., 1.));
+    }
+
+    #[test]
+    #[should_panic(expected = "enclose")]
+    fn unbounded() {
+        ConvexPolyhedron::new(vec![HalfSpace::new(Tuple::vector(0., 1., 0.), 1.)], None);
+    }
+}
@@ -0,0 +1,99 @@
+use crate::bounds::BoundingBox;
+use crate::intersection::{Intersection, IntersectionList};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::shape::{Object, ShapeType};
+use crate::tuple::Tuple;
+use crate::EPSILON;
+
+// A finite piece of the XZ plane, from -x_extent to x_extent and -z_extent to
+// z_extent, for walls, floors and light panels that shouldn't reach to infinity
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rectangle {
+    pub x_extent: f64,
+    pub z_extent: f64,
+}
+
+impl Rectangle {
+    // A 2x2 square around the origin
+    pub fn new(material_opt: Option<Material>) -> Object {
+        Rectangle::sized(1., 1., material_opt)
+    }
+
+    pub fn sized(x_extent: f64, z_extent: f64, material_opt: Option<Material>) -> Object {
+        assert!(x_extent > 0. && z_extent > 0.);
+        let material = material_opt.unwrap_or_default();
+
+        Object::new(
+            ShapeType::Rectangle(Rectangle { x_extent, z_extent }),
+            material,
+        )
+    }
+
+    pub fn local_intersect<'a>(
+        &self,
+        ray_obj_space: &Ray,
+        object: &'a Object,
+    ) -> IntersectionList<'a> {
+        if ray_obj_space.direction.y.abs() < EPSILON {
+            return IntersectionList::new([]);
+        }
+
+        let t = -ray_obj_space.origin.y / ray_obj_space.direction.y;
+        let point = ray_obj_space.position(t);
+        if point.x.abs() > self.x_extent || point.z.abs() > self.z_extent {
+            IntersectionList::new([])
+        } else {
+            IntersectionList::new([Intersection::new(t, object)])
+        }
+    }
+
+    pub fn local_bounds(&self) -> BoundingBox {
+        BoundingBox::new(
+            Tuple::point(-self.x_extent, 0., -self.z_extent),
+            Tuple::point(self.x_extent, 0., self.z_extent),
+        )
+    }
+
+    pub fn local_normal_at(&self, _object_space_point: Tuple) -> Tuple {
+        Tuple::vector(0., 1., 0.)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hits(object: &Object, x: f64, z: f64) -> usize {
+        let r = Ray::new(Tuple::point(x, 1., z), Tuple::vector(0., -1., 0.));
+        r.intersect_object(object).intersections.len()
+    }
+
+    #[test]
+    fn intersect() {
+        let square = Rectangle::new(None);
+        assert_eq!(hits(&square, 0., 0.), 1);
+        assert_eq!(hits(&square, 0.99, -0.99), 1);
+        assert_eq!(hits(&square, 1.01, 0.), 0);
+        assert_eq!(hits(&square, 0., -1.01), 0);
+
+        let wide = Rectangle::sized(3., 0.5, None);
+        assert_eq!(hits(&wide, 2.5, 0.), 1);
+        assert_eq!(hits(&wide, 0., 0.6), 0);
+        let r = Ray::new(Tuple::point(0., 1., -5.), Tuple::vector(0., 0., 1.));
+        assert_eq!(r.intersect_object(&wide).intersections.len(), 0);
+
+        let r = Ray::new(Tuple::point(0.5, 2., 0.), Tuple::vector(0., -1., 0.));
+        assert_eq!(r.intersect_object(&square).intersections[0].t, 2.);
+    }
+
+    #[test]
+    fn bounds_and_normal() {
+        let wide = Rectangle::sized(3., 0.5, None);
+        assert!(wide.bounds().is_finite());
+        assert_eq!(
+            wide.normal_at(Tuple::point(1., 0., 0.)),
+            Tuple::vector(0., 1., 0.)
+        );
+    }
+}
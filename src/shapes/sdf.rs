@@ -0,0 +1,432 @@
+use std::{fmt, sync::Arc};
+
+use crate::bounds::BoundingBox;
+use crate::intersection::{Intersection, IntersectionList};
+use crate::material::Material;
+use crate::pattern::Displacement;
+use crate::ray::Ray;
+use crate::shape::{Object, ShapeType};
+use crate::tuple::Tuple;
+
+// Smallest step taken along the ray, so marching still advances at the surface
+const MIN_STEP: f64 = 1e-3;
+const MAX_STEPS: usize = 1024;
+// Bisection steps used to pin down a surface crossing between two samples
+const REFINE_STEPS: usize = 20;
+// Offset used for the central differences that estimate normals
+const NORMAL_DELTA: f64 = 1e-5;
+// Fractal distance estimates never go negative, so their surface is taken to be
+// everything this close to the set
+const FRACTAL_DETAIL: f64 = 1e-3;
+// Displacement can bring the surface nearer than the distance says, so displaced
+// fields are marched in steps this much smaller
+const DISPLACED_STEP: f64 = 0.5;
+
+// User provided signed distance, negative inside the surface
+#[derive(Clone)]
+pub struct DistanceFn(pub Arc<dyn Fn(Tuple) -> f64 + Send + Sync>);
+
+impl fmt::Debug for DistanceFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("DistanceFn")
+    }
+}
+
+// Closures can't be compared, so only the same closure is equal to itself
+impl PartialEq for DistanceFn {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+// Signed distance functions, in object space and centred on the origin
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sdf {
+    Sphere {
+        radius: f64,
+    },
+    // Half of the box's size along each axis
+    Box {
+        half_extents: Tuple,
+    },
+    // Around the y axis
+    Torus {
+        major_radius: f64,
+        minor_radius: f64,
+    },
+    // Blends the two surfaces together over roughly `smoothness` units
+    SmoothUnion(Box<Sdf>, Box<Sdf>, f64),
+    // The cube from -1 to 1 with its middle thirds carved out, recursively
+    MengerSponge {
+        iterations: usize,
+    },
+    // The 3D Mandelbrot set of z -> z^power + c in spherical coordinates. Points
+    // whose orbit grows past `bailout` are outside
+    Mandelbulb {
+        power: f64,
+        iterations: usize,
+        bailout: f64,
+    },
+    // The surface moved out along its normal by the height of the displacement,
+    // which is evaluated at points in object space
+    Displaced(Box<Sdf>, Displacement),
+    // Any distance function, along with a box it lies within
+    Custom(DistanceFn, BoundingBox),
+}
+
+impl Sdf {
+    pub fn distance(&self, p: Tuple) -> f64 {
+        match self {
+            Sdf::Sphere { radius } => p.magnitude() - radius,
+            Sdf::Box { half_extents } => {
+                let q = Tuple::vector(
+                    p.x.abs() - half_extents.x,
+                    p.y.abs() - half_extents.y,
+                    p.z.abs() - half_extents.z,
+                );
+                let outside = Tuple::vector(q.x.max(0.), q.y.max(0.), q.z.max(0.)).magnitude();
+                outside + q.x.max(q.y).max(q.z).min(0.)
+            }
+            Sdf::Torus {
+                major_radius,
+                minor_radius,
+            } => {
+                let ring = (p.x * p.x + p.z * p.z).sqrt() - major_radius;
+                (ring * ring + p.y * p.y).sqrt() - minor_radius
+            }
+            Sdf::SmoothUnion(a, b, smoothness) => {
+                let (a, b) = (a.distance(p), b.distance(p));
+                let h = (0.5 + 0.5 * (b - a) / smoothness).clamp(0., 1.);
+                b + (a - b) * h - smoothness * h * (1. - h)
+            }
+            Sdf::MengerSponge { iterations } => {
+                let mut d = Sdf::Box {
+                    half_extents: Tuple::vector(1., 1., 1.),
+                }
+                .distance(p);
+                let mut scale = 1.;
+                for _ in 0..*iterations {
+                    // Distance to the cross shaped hole through the nearest sub-cube
+                    let r = |c: f64| {
+                        let a = (c * scale).rem_euclid(2.) - 1.;
+                        (1. - 3. * a.abs()).abs()
+                    };
+                    let (rx, ry, rz) = (r(p.x), r(p.y), r(p.z));
+                    scale *= 3.;
+                    let hole = (rx.max(ry).min(ry.max(rz)).min(rz.max(rx)) - 1.) / scale;
+                    d = d.max(hole);
+                }
+                d
+            }
+            Sdf::Mandelbulb {
+                power,
+                iterations,
+                bailout,
+            } => {
+                let c = Tuple::vector(p.x, p.y, p.z);
+                let mut z = c;
+                let mut dr = 1.;
+                let mut r = 0.;
+                for _ in 0..*iterations {
+                    r = z.magnitude();
+                    if r > *bailout {
+                        break;
+                    }
+                    let theta = if r > 0. { (z.z / r).acos() } else { 0. } * power;
+                    let phi = z.y.atan2(z.x) * power;
+                    dr = r.powf(power - 1.) * power * dr + 1.;
+                    z = Tuple::vector(
+                        theta.sin() * phi.cos(),
+                        phi.sin() * theta.sin(),
+                        theta.cos(),
+                    ) * r.powf(*power)
+                        + c;
+                }
+                let r = r.max(f64::MIN_POSITIVE);
+                0.5 * r.ln() * r / dr - FRACTAL_DETAIL
+            }
+            Sdf::Displaced(sdf, displacement) => sdf.distance(p) - displacement.height(p),
+            Sdf::Custom(f, _) => (f.0)(p),
+        }
+    }
+
+    // How much of the distance can safely be stepped along a ray
+    fn step_scale(&self) -> f64 {
+        match self {
+            Sdf::SmoothUnion(a, b, _) => a.step_scale().min(b.step_scale()),
+            Sdf::Displaced(sdf, _) => sdf.step_scale() * DISPLACED_STEP,
+            _ => 1.,
+        }
+    }
+
+    pub fn bounds(&self) -> BoundingBox {
+        match self {
+            Sdf::Sphere { radius } => BoundingBox::new(
+                Tuple::point(-radius, -radius, -radius),
+                Tuple::point(*radius, *radius, *radius),
+            ),
+            Sdf::Box { half_extents: h } => {
+                BoundingBox::new(Tuple::point(-h.x, -h.y, -h.z), Tuple::point(h.x, h.y, h.z))
+            }
+            Sdf::Torus {
+                major_radius,
+                minor_radius,
+            } => {
+                let r = major_radius + minor_radius;
+                BoundingBox::new(
+                    Tuple::point(-r, -minor_radius, -r),
+                    Tuple::point(r, *minor_radius, r),
+                )
+            }
+            Sdf::SmoothUnion(a, b, smoothness) => {
+                // Blending only ever adds material near where the two surfaces meet
+                let mut bounds = a.bounds();
+                bounds.merge(&b.bounds());
+                let m = Tuple::vector(*smoothness, *smoothness, *smoothness);
+                BoundingBox::new(bounds.min - m, bounds.max + m)
+            }
+            Sdf::MengerSponge { .. } => {
+                BoundingBox::new(Tuple::point(-1., -1., -1.), Tuple::point(1., 1., 1.))
+            }
+            // Orbits starting further than 2 from the origin always escape
+            Sdf::Mandelbulb { bailout, .. } => {
+                let r = bailout.min(2.);
+                BoundingBox::new(Tuple::point(-r, -r, -r), Tuple::point(r, r, r))
+            }
+            Sdf::Displaced(sdf, displacement) => {
+                let bounds = sdf.bounds();
+                let h = displacement.scale.max(0.);
+                let m = Tuple::vector(h, h, h);
+                BoundingBox::new(bounds.min - m, bounds.max + m)
+            }
+            Sdf::Custom(_, bounds) => *bounds,
+        }
+    }
+}
+
+// Walks from start to end, stepping by `step` of the current value, and returns the
+// points where `value` changes sign, each refined by bisection
+pub(crate) fn march(
+    start: f64,
+    end: f64,
+    value: impl Fn(f64) -> f64,
+    step: impl Fn(f64) -> f64,
+) -> Vec<f64> {
+    let mut crossings = vec![];
+    let mut t = start;
+    let mut v = value(t);
+    for _ in 0..MAX_STEPS {
+        if t >= end {
+            break;
+        }
+        let next = (t + step(v)).min(end);
+        let next_v = value(next);
+        if (v < 0.) != (next_v < 0.) {
+            let (mut low, mut high) = (t, next);
+            for _ in 0..REFINE_STEPS {
+                let mid = (low + high) / 2.;
+                if (value(mid) < 0.) == (v < 0.) {
+                    low = mid;
+                } else {
+                    high = mid;
+                }
+            }
+            crossings.push((low + high) / 2.);
+        }
+        t = next;
+        v = next_v;
+    }
+    crossings
+}
+
+// A surface given by a signed distance function, intersected by sphere tracing:
+// stepping along the ray by the distance to the surface, which can't overshoot it
+#[derive(Debug, Clone, PartialEq)]
+pub struct SdfShape {
+    pub sdf: Sdf,
+}
+
+impl SdfShape {
+    pub fn new(sdf: Sdf, material_opt: Option<Material>) -> Object {
+        assert!(sdf.bounds().is_finite());
+        let material = material_opt.unwrap_or_default();
+
+        Object::new(ShapeType::Sdf(SdfShape { sdf }), material)
+    }
+
+    pub fn local_intersect<'a>(
+        &self,
+        ray_obj_space: &Ray,
+        object: &'a Object,
+    ) -> IntersectionList<'a> {
+        let Some((start, end)) = self.sdf.bounds().range(ray_obj_space) else {
+            return IntersectionList::new([]);
+        };
+        // Object space rays aren't normalised, so distances have to be scaled into t
+        let speed = ray_obj_space.direction.magnitude();
+        let scale = self.sdf.step_scale();
+        let crossings = march(
+            start,
+            end,
+            |t| self.sdf.distance(ray_obj_space.position(t)),
+            |d| (d.abs() * scale).max(MIN_STEP) / speed,
+        );
+        IntersectionList::new(crossings.into_iter().map(|t| Intersection::new(t, object)))
+    }
+
+    pub fn local_bounds(&self) -> BoundingBox {
+        self.sdf.bounds()
+    }
+
+    pub fn local_normal_at(&self, object_space_point: Tuple) -> Tuple {
+        let gradient = |axis: Tuple| {
+            self.sdf.distance(object_space_point + axis * NORMAL_DELTA)
+                - self.sdf.distance(object_space_point - axis * NORMAL_DELTA)
+        };
+        Tuple::vector(
+            gradient(Tuple::vector(1., 0., 0.)),
+            gradient(Tuple::vector(0., 1., 0.)),
+            gradient(Tuple::vector(0., 0., 1.)),
+        )
+        .normalize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        assert_approx_eq,
+        color::{BLACK, WHITE},
+        pattern::StripePattern,
+    };
+
+    fn ts(object: &Object, origin: Tuple, direction: Tuple) -> Vec<f64> {
+        let r = Ray::new(origin, direction);
+        r.intersect_object(object)
+            .intersections
+            .iter()
+            .map(|i| i.t)
+            .collect()
+    }
+
+    #[test]
+    fn sphere() {
+        let s = SdfShape::new(Sdf::Sphere { radius: 1. }, None);
+        let xs = ts(&s, Tuple::point(0., 0., -5.), Tuple::vector(0., 0., 1.));
+        assert_eq!(xs.len(), 2);
+        assert_approx_eq!(xs[0], 4.);
+        assert_approx_eq!(xs[1], 6.);
+        assert!(ts(&s, Tuple::point(0., 2., -5.), Tuple::vector(0., 0., 1.)).is_empty());
+
+        // Scaled objects march an unnormalised ray in object space
+        let s = s.with_transform(&crate::matrix::Matrix::scaling(2., 2., 2.));
+        let xs = ts(&s, Tuple::point(0., 0., -5.), Tuple::vector(0., 0., 1.));
+        assert_approx_eq!(xs[0], 3.);
+        assert_approx_eq!(xs[1], 7.);
+
+        let n = s.normal_at(Tuple::point(0., 2., 0.));
+        assert_approx_eq!(n, Tuple::vector(0., 1., 0.));
+    }
+
+    #[test]
+    fn built_ins() {
+        let b = Sdf::Box {
+            half_extents: Tuple::vector(1., 2., 3.),
+        };
+        assert_approx_eq!(b.distance(Tuple::point(0., 0., 0.)), -1.);
+        assert_approx_eq!(b.distance(Tuple::point(4., 0., 0.)), 3.);
+        assert_approx_eq!(b.distance(Tuple::point(4., 6., 0.)), 5.);
+
+        let torus = Sdf::Torus {
+            major_radius: 2.,
+            minor_radius: 0.5,
+        };
+        assert_approx_eq!(torus.distance(Tuple::point(2., 0., 0.)), -0.5);
+        assert_approx_eq!(torus.distance(Tuple::point(0., 0., 0.)), 1.5);
+        // A ray down the middle of the ring misses, one through the tube hits twice
+        let t = SdfShape::new(torus, None);
+        assert!(ts(&t, Tuple::point(0., 5., 0.), Tuple::vector(0., -1., 0.)).is_empty());
+        let xs = ts(&t, Tuple::point(2., 5., 0.), Tuple::vector(0., -1., 0.));
+        assert_eq!(xs.len(), 2);
+        assert_approx_eq!(xs[0], 4.5);
+
+        // Blending fills in the space between two spheres
+        let a = Sdf::Sphere { radius: 1. };
+        let shifted = Sdf::Custom(
+            DistanceFn(Arc::new(|p: Tuple| {
+                (p - Tuple::vector(2.2, 0., 0.)).magnitude() - 1.
+            })),
+            BoundingBox::new(Tuple::point(1.2, -1., -1.), Tuple::point(3.2, 1., 1.)),
+        );
+        let blend = Sdf::SmoothUnion(Box::new(a.clone()), Box::new(shifted.clone()), 1.);
+        let middle = Tuple::point(1.1, 0., 0.);
+        assert!(a.distance(middle) > 0. && shifted.distance(middle) > 0.);
+        assert!(blend.distance(middle) < 0.);
+        assert!(blend.bounds().max.x >= 4.2);
+    }
+
+    #[test]
+    fn displaced() {
+        // Raised everywhere by the same height
+        let flat = Displacement::new(StripePattern::new(vec![WHITE]), 0.5);
+        let sphere = Sdf::Sphere { radius: 1. };
+        let raised = Sdf::Displaced(Box::new(sphere.clone()), flat);
+        assert_approx_eq!(raised.distance(Tuple::point(0., 0., 0.)), -1.5);
+        assert_eq!(raised.bounds().max, Tuple::point(1.5, 1.5, 1.5));
+        let xs = ts(
+            &SdfShape::new(raised, None),
+            Tuple::point(0., 0., -5.),
+            Tuple::vector(0., 0., 1.),
+        );
+        assert_approx_eq!(xs[0], 3.5);
+
+        // Displacement changes the silhouette: a ray passing the sphere at x = 1.2
+        // only hits it when the stripe there is raised
+        let passing = |displacement| {
+            let shape = SdfShape::new(Sdf::Displaced(Box::new(sphere.clone()), displacement), None);
+            ts(
+                &shape,
+                Tuple::point(1.2, 0., -5.),
+                Tuple::vector(0., 0., 1.),
+            )
+        };
+        let stripes = Displacement::new(StripePattern::new(vec![WHITE, BLACK]), 0.3);
+        assert!(passing(stripes.clone()).is_empty());
+        let shifted = Displacement::new(StripePattern::new(vec![BLACK, WHITE]), 0.3);
+        assert_eq!(passing(shifted).len(), 2);
+        assert_eq!(stripes, stripes.clone());
+    }
+
+    #[test]
+    fn fractals() {
+        let solid = Sdf::MengerSponge { iterations: 0 };
+        assert_approx_eq!(solid.distance(Tuple::point(2., 0., 0.)), 1.);
+        let sponge = Sdf::MengerSponge { iterations: 3 };
+        assert!(sponge.distance(Tuple::point(0.99, 0.99, 0.99)) < 0.);
+        assert!(sponge.distance(Tuple::point(0., 0., 0.9)) > 0.);
+
+        // The middle of each face is carved all the way through
+        let down_the_hole = |sdf| {
+            let shape = SdfShape::new(sdf, None);
+            ts(&shape, Tuple::point(0., 0., -5.), Tuple::vector(0., 0., 1.))
+        };
+        assert_eq!(down_the_hole(solid).len(), 2);
+        assert!(down_the_hole(sponge).is_empty());
+
+        let bulb = Sdf::Mandelbulb {
+            power: 8.,
+            iterations: 12,
+            bailout: 2.,
+        };
+        assert!(bulb.distance(Tuple::point(0., 0., 0.)) < 0.);
+        assert!(bulb.distance(Tuple::point(1.5, 0., 0.)) > 0.);
+        let shape = SdfShape::new(bulb, None);
+        let xs = ts(&shape, Tuple::point(0., 0., -3.), Tuple::vector(0., 0., 1.));
+        assert!(!xs.is_empty() && xs[0] > 1.5 && xs[0] < 2.5);
+        let n = shape.normal_at(
+            Ray::new(Tuple::point(0., 0., -3.), Tuple::vector(0., 0., 1.)).position(xs[0]),
+        );
+        assert!(!n.x.is_nan() && n.z < 0.);
+    }
+}
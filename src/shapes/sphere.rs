@@ -1,26 +1,19 @@
+use crate::bounds::BoundingBox;
 use crate::intersection::{Intersection, IntersectionList};
 use crate::material::Material;
-use crate::matrix::Matrix;
 use crate::ray::Ray;
 use crate::shape::{Object, ShapeType};
 use crate::tuple::Tuple;
 
 // A Unit Sphere
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Sphere;
 
 impl Sphere {
     pub fn new(material_opt: Option<Material>) -> Object {
-        let material = match material_opt {
-            Some(x) => x,
-            None => Material::new(),
-        };
-
-        Object {
-            shape: ShapeType::Sphere(Sphere),
-            transform: Matrix::identity(4),
-            material,
-        }
+        let material = material_opt.unwrap_or_default();
+
+        Object::new(ShapeType::Sphere(Sphere), material)
     }
 
     pub fn glass_new() -> Object {
@@ -42,17 +35,18 @@ impl Sphere {
         let discriminant = b * b - 4. * a * c;
 
         if discriminant < 0. {
-            IntersectionList::new(vec![])
+            IntersectionList::new([])
         } else {
             let t1 = (-b - discriminant.sqrt()) / (2. * a);
             let t2 = (-b + discriminant.sqrt()) / (2. * a);
-            IntersectionList::new(vec![
-                Intersection::new(t1, object),
-                Intersection::new(t2, object),
-            ])
+            IntersectionList::new([Intersection::new(t1, object), Intersection::new(t2, object)])
         }
     }
 
+    pub fn local_bounds(&self) -> BoundingBox {
+        BoundingBox::new(Tuple::point(-1., -1., -1.), Tuple::point(1., 1., 1.))
+    }
+
     pub fn local_normal_at(&self, object_space_point: Tuple) -> Tuple {
         Tuple::vector(
             object_space_point.x,
@@ -65,16 +59,16 @@ impl Sphere {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::PI;
+    use crate::{matrix::Matrix, PI};
 
     #[test]
     fn sphere() {
         let mut s = Sphere::new(None);
-        assert_eq!(s.transform, Matrix::identity(4));
+        assert_eq!(*s.transform(), Matrix::identity(4));
         assert_eq!(s.material, Material::new());
         let m = Matrix::translation(2., 3., 4.);
-        s.transform = m.clone();
-        assert_eq!(s.transform, m);
+        s.set_transform(&m.clone());
+        assert_eq!(*s.transform(), m);
         let mut m = Material::new();
         m.ambient = 1.;
         s.material = m.clone();
@@ -85,13 +79,15 @@ mod tests {
     fn ray_sphere_intersection() {
         let r = Ray::new(Tuple::point(0., 0., -5.), Tuple::vector(0., 0., 1.));
         let mut s = Sphere::new(None);
-        s.transform = Matrix::scaling(2., 2., 2.);
-        let i = r.intersect_object(&s);
-        assert_eq!(i.intersections.len(), 2);
-        assert_eq!(i.intersections[0].t, 3.);
-        assert_eq!(i.intersections[1].t, 7.);
+        s.set_transform(&Matrix::scaling(2., 2., 2.));
+        {
+            let i = r.intersect_object(&s);
+            assert_eq!(i.intersections.len(), 2);
+            assert_eq!(i.intersections[0].t, 3.);
+            assert_eq!(i.intersections[1].t, 7.);
+        }
 
-        s.transform = Matrix::translation(5., 0., 0.);
+        s.set_transform(&Matrix::translation(5., 0., 0.));
         let i = r.intersect_object(&s);
         assert_eq!(i.intersections.len(), 0);
     }
@@ -124,15 +120,16 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn normal_translated() {
         let mut s = Sphere::new(None);
-        s.transform = Matrix::translation(0., 1., 0.);
+        s.set_transform(&Matrix::translation(0., 1., 0.));
         assert_eq!(
             s.normal_at(Tuple::point(0., 1.70711, -0.70711)),
             Tuple::vector(0., 0.70711, -0.70711)
         );
 
-        s.transform = &Matrix::scaling(1., 0.5, 1.) * &Matrix::rotation_z(PI / 5.);
+        s.set_transform(&(Matrix::scaling(1., 0.5, 1.) * &Matrix::rotation_z(PI / 5.)));
         assert_eq!(
             s.normal_at(Tuple::point(0., 2_f64.sqrt() / 2., -2_f64.sqrt() / 2.)),
             Tuple::vector(0., 0.97014, -0.24254)
@@ -0,0 +1,208 @@
+use crate::bounds::BoundingBox;
+use crate::color::Color;
+use crate::intersection::{Intersection, IntersectionList};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::shape::{Object, ShapeType};
+use crate::tuple::Tuple;
+use crate::EPSILON;
+
+// The weights of p2 and p3 for a point on the triangle with corner p1 and edges e1
+// and e2, p1 gets the rest
+pub(crate) fn barycentric(p1: Tuple, e1: Tuple, e2: Tuple, point: Tuple) -> (f64, f64) {
+    let to_point = point - p1;
+    let (d11, d12, d22) = (e1.dot(&e1), e1.dot(&e2), e2.dot(&e2));
+    let (p1, p2) = (to_point.dot(&e1), to_point.dot(&e2));
+    let denominator = d11 * d22 - d12 * d12;
+    (
+        (d22 * p1 - d12 * p2) / denominator,
+        (d11 * p2 - d12 * p1) / denominator,
+    )
+}
+
+// Möller–Trumbore: solves for the barycentric coordinates of the hit directly,
+// without intersecting the triangle's plane first. Gives the ray's t at the hit
+pub(crate) fn hit(p1: Tuple, e1: Tuple, e2: Tuple, ray: &Ray) -> Option<f64> {
+    let dir_cross_e2 = ray.direction.cross(&e2);
+    let det = e1.dot(&dir_cross_e2);
+    // The ray is parallel to the triangle
+    if det.abs() < EPSILON {
+        return None;
+    }
+
+    let f = 1. / det;
+    let p1_to_origin = ray.origin - p1;
+    let u = f * p1_to_origin.dot(&dir_cross_e2);
+    if !(0. ..=1.).contains(&u) {
+        return None;
+    }
+
+    let origin_cross_e1 = p1_to_origin.cross(&e1);
+    let v = f * ray.direction.dot(&origin_cross_e1);
+    if v < 0. || u + v > 1. {
+        return None;
+    }
+    Some(f * e2.dot(&origin_cross_e1))
+}
+
+// A triangle. The edges and normal are precomputed since every ray needs them. With
+// vertex normals the normal is interpolated across the face so meshes look smooth,
+// and vertex colors are interpolated the same way by VertexColorPattern. Both are
+// boxed since most triangles have neither
+#[derive(Debug, Clone, PartialEq)]
+pub struct Triangle {
+    pub p1: Tuple,
+    pub p2: Tuple,
+    pub p3: Tuple,
+    pub e1: Tuple,
+    pub e2: Tuple,
+    pub normal: Tuple,
+    pub normals: Option<Box<[Tuple; 3]>>,
+    pub colors: Option<Box<[Color; 3]>>,
+}
+
+impl Triangle {
+    pub fn new(p1: Tuple, p2: Tuple, p3: Tuple, material_opt: Option<Material>) -> Object {
+        let material = material_opt.unwrap_or_default();
+        let e1 = p2 - p1;
+        let e2 = p3 - p1;
+        let normal = e2.cross(&e1).normalize();
+
+        Object::new(
+            ShapeType::Triangle(Triangle {
+                p1,
+                p2,
+                p3,
+                e1,
+                e2,
+                normal,
+                normals: None,
+                colors: None,
+            }),
+            material,
+        )
+    }
+
+    pub fn smooth(
+        [p1, p2, p3]: [Tuple; 3],
+        normals: [Tuple; 3],
+        material_opt: Option<Material>,
+    ) -> Object {
+        assert!(normals.iter().all(|n| n.is_vector()));
+        let mut object = Triangle::new(p1, p2, p3, material_opt);
+        if let ShapeType::Triangle(ref mut triangle) = object.shape {
+            triangle.normals = Some(Box::new(normals));
+        }
+        object
+    }
+
+    // The weights of p2 and p3 for a point on the triangle, p1 gets the rest
+    pub fn barycentric(&self, point: Tuple) -> (f64, f64) {
+        barycentric(self.p1, self.e1, self.e2, point)
+    }
+
+    pub fn local_intersect<'a>(
+        &self,
+        ray_obj_space: &Ray,
+        object: &'a Object,
+    ) -> IntersectionList<'a> {
+        match hit(self.p1, self.e1, self.e2, ray_obj_space) {
+            Some(t) => IntersectionList::new([Intersection::new(t, object)]),
+            None => IntersectionList::new([]),
+        }
+    }
+
+    pub fn local_bounds(&self) -> BoundingBox {
+        let mut bounds = BoundingBox::empty();
+        for point in [self.p1, self.p2, self.p3] {
+            bounds.add_point(point);
+        }
+        bounds
+    }
+
+    pub fn local_normal_at(&self, object_space_point: Tuple) -> Tuple {
+        match self.normals.as_deref() {
+            None => self.normal,
+            Some(&[n1, n2, n3]) => {
+                let (u, v) = self.barycentric(object_space_point);
+                n1 * (1. - u - v) + n2 * u + n3 * v
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_approx_eq;
+
+    fn triangle() -> Object {
+        Triangle::new(
+            Tuple::point(0., 1., 0.),
+            Tuple::point(-1., 0., 0.),
+            Tuple::point(1., 0., 0.),
+            None,
+        )
+    }
+
+    #[test]
+    fn construct() {
+        let t = triangle();
+        let ShapeType::Triangle(ref tri) = t.shape else {
+            panic!("not a triangle");
+        };
+        assert_eq!(tri.e1, Tuple::vector(-1., -1., 0.));
+        assert_eq!(tri.e2, Tuple::vector(1., -1., 0.));
+        assert_eq!(tri.normal, Tuple::vector(0., 0., -1.));
+        assert_eq!(t.normal_at(Tuple::point(0., 0.5, 0.)), tri.normal);
+        assert_eq!(t.normal_at(Tuple::point(-0.5, 0.75, 0.)), tri.normal);
+    }
+
+    #[test]
+    fn smooth() {
+        let t = Triangle::smooth(
+            [
+                Tuple::point(0., 1., 0.),
+                Tuple::point(-1., 0., 0.),
+                Tuple::point(1., 0., 0.),
+            ],
+            [
+                Tuple::vector(0., 1., 0.),
+                Tuple::vector(-1., 0., 0.),
+                Tuple::vector(1., 0., 0.),
+            ],
+            None,
+        );
+        let ShapeType::Triangle(ref tri) = t.shape else {
+            panic!("not a triangle");
+        };
+        let point = Tuple::point(-0.2, 0.3, 0.);
+        let (u, v) = tri.barycentric(point);
+        assert_approx_eq!(u, 0.45);
+        assert_approx_eq!(v, 0.25);
+        assert_approx_eq!(t.normal_at(point), Tuple::vector(-0.5547, 0.83205, 0.));
+    }
+
+    #[test]
+    fn intersect() {
+        let t = triangle();
+        // Parallel to the triangle
+        let r = Ray::new(Tuple::point(0., -1., -2.), Tuple::vector(0., 1., 0.));
+        assert_eq!(r.intersect_object(&t).intersections.len(), 0);
+
+        // Past each of the three edges
+        for origin in [
+            Tuple::point(1., 1., -2.),
+            Tuple::point(-1., 1., -2.),
+            Tuple::point(0., -1., -2.),
+        ] {
+            let r = Ray::new(origin, Tuple::vector(0., 0., 1.));
+            assert_eq!(r.intersect_object(&t).intersections.len(), 0);
+        }
+
+        let r = Ray::new(Tuple::point(0., 0.5, -2.), Tuple::vector(0., 0., 1.));
+        let xs = r.intersect_object(&t);
+        assert_eq!(xs.intersections.len(), 1);
+        assert_eq!(xs.intersections[0].t, 2.);
+    }
+}
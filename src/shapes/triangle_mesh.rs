@@ -0,0 +1,271 @@
+use crate::bounds::BoundingBox;
+use crate::bvh::Bvh;
+use crate::color::Color;
+use crate::intersection::{Intersection, IntersectionList};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::shape::{Object, ShapeType};
+use crate::shapes::triangle::{barycentric, hit};
+use crate::tuple::Tuple;
+use crate::EPSILON;
+use std::sync::Arc;
+
+#[derive(Debug)]
+struct MeshData {
+    points: Vec<Tuple>,
+    normals: Option<Vec<Tuple>>,
+    colors: Option<Vec<Color>>,
+    faces: Vec<[u32; 3]>,
+    bvh: Bvh,
+    bounds: BoundingBox,
+}
+
+// A whole triangle mesh as one object with one material. The vertices live in shared
+// buffers and each face is three indices into them, with a BVH over the faces, so a
+// triangle costs 12 bytes rather than an Object of its own. Clones share the buffers.
+// Vertex normals make it smooth and vertex colors are interpolated by
+// VertexColorPattern, the same as for Triangle
+#[derive(Debug, Clone)]
+pub struct TriangleMesh {
+    data: Arc<MeshData>,
+}
+
+impl PartialEq for TriangleMesh {
+    fn eq(&self, other: &Self) -> bool {
+        let (a, b) = (&self.data, &other.data);
+        Arc::ptr_eq(a, b)
+            || (a.points == b.points
+                && a.normals == b.normals
+                && a.colors == b.colors
+                && a.faces == b.faces)
+    }
+}
+
+impl TriangleMesh {
+    // Zero area faces have no normal and are dropped
+    pub fn new(
+        points: Vec<Tuple>,
+        normals: Option<Vec<Tuple>>,
+        colors: Option<Vec<Color>>,
+        faces: impl IntoIterator<Item = [usize; 3]>,
+        material_opt: Option<Material>,
+    ) -> Object {
+        assert!(u32::try_from(points.len()).is_ok(), "too many vertices");
+        if let Some(normals) = normals.as_ref() {
+            assert!(normals.len() == points.len() && normals.iter().all(|n| n.is_vector()));
+        }
+        if let Some(colors) = colors.as_ref() {
+            assert_eq!(colors.len(), points.len());
+        }
+        let faces: Vec<[u32; 3]> = faces
+            .into_iter()
+            .filter(|corners| {
+                assert!(corners.iter().all(|c| *c < points.len()));
+                let [p1, p2, p3] = corners.map(|c| points[c]);
+                (p2 - p1).cross(&(p3 - p1)).magnitude() >= EPSILON * EPSILON
+            })
+            .map(|corners| corners.map(|c| c as u32))
+            .collect();
+
+        let mut bounds = BoundingBox::empty();
+        let face_bounds = faces.iter().map(|face| {
+            let mut face_bounds = BoundingBox::empty();
+            for c in face {
+                face_bounds.add_point(points[*c as usize]);
+            }
+            bounds.merge(&face_bounds);
+            face_bounds
+        });
+        let bvh = Bvh::from_bounds(face_bounds.collect::<Vec<_>>());
+
+        let data = MeshData {
+            points,
+            normals,
+            colors,
+            faces,
+            bvh,
+            bounds,
+        };
+        Object::new(
+            ShapeType::TriangleMesh(TriangleMesh {
+                data: Arc::new(data),
+            }),
+            material_opt.unwrap_or_default(),
+        )
+    }
+
+    pub fn points(&self) -> &[Tuple] {
+        &self.data.points
+    }
+
+    pub fn normals(&self) -> Option<&[Tuple]> {
+        self.data.normals.as_deref()
+    }
+
+    pub fn colors(&self) -> Option<&[Color]> {
+        self.data.colors.as_deref()
+    }
+
+    // Indices into the points for each face
+    pub fn faces(&self) -> &[[u32; 3]] {
+        &self.data.faces
+    }
+
+    pub fn corners(&self, face: usize) -> [Tuple; 3] {
+        self.data.faces[face].map(|c| self.data.points[c as usize])
+    }
+
+    // The first point and the two edges from it, as Triangle stores them
+    fn edges(&self, face: usize) -> (Tuple, Tuple, Tuple) {
+        let [p1, p2, p3] = self.corners(face);
+        (p1, p2 - p1, p3 - p1)
+    }
+
+    // The face a point on the mesh lies on, with the barycentric weights of its second
+    // and third corners. Faces the point is inside of win, then the closest plane
+    fn face_at(&self, point: Tuple) -> Option<(usize, f64, f64)> {
+        let mut best: Option<(bool, f64, (usize, f64, f64))> = None;
+        self.data.bvh.candidates_at(point, |face| {
+            let (p1, e1, e2) = self.edges(face);
+            let distance = (point - p1).dot(&e2.cross(&e1).normalize()).abs();
+            let (u, v) = barycentric(p1, e1, e2, point);
+            let inside = u >= -EPSILON && v >= -EPSILON && u + v <= 1. + EPSILON;
+            let better = match best {
+                None => true,
+                Some((best_inside, best_distance, _)) => {
+                    (inside && !best_inside) || (inside == best_inside && distance < best_distance)
+                }
+            };
+            if better {
+                best = Some((inside, distance, (face, u, v)));
+            }
+        });
+        best.map(|(_, _, found)| found)
+    }
+
+    // Interpolated vertex color at a point on the mesh
+    pub fn color_at(&self, point: Tuple) -> Option<Color> {
+        let colors = self.data.colors.as_ref()?;
+        let (face, u, v) = self.face_at(point)?;
+        let [c1, c2, c3] = self.data.faces[face].map(|c| colors[c as usize]);
+        Some(c1 * (1. - u - v) + c2 * u + c3 * v)
+    }
+
+    pub fn local_intersect<'a>(
+        &self,
+        ray_obj_space: &Ray,
+        object: &'a Object,
+    ) -> IntersectionList<'a> {
+        let mut xs = vec![];
+        self.data.bvh.candidates(ray_obj_space, |face| {
+            let (p1, e1, e2) = self.edges(face);
+            if let Some(t) = hit(p1, e1, e2, ray_obj_space) {
+                xs.push(Intersection::new(t, object));
+            }
+        });
+        IntersectionList::new(xs)
+    }
+
+    pub fn local_bounds(&self) -> BoundingBox {
+        self.data.bounds
+    }
+
+    pub fn local_normal_at(&self, object_space_point: Tuple) -> Tuple {
+        let Some((face, u, v)) = self.face_at(object_space_point) else {
+            return Tuple::vector(0., 1., 0.);
+        };
+        match self.data.normals.as_ref() {
+            None => {
+                let (_, e1, e2) = self.edges(face);
+                e2.cross(&e1).normalize()
+            }
+            Some(normals) => {
+                let [n1, n2, n3] = self.data.faces[face].map(|c| normals[c as usize]);
+                n1 * (1. - u - v) + n2 * u + n3 * v
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_approx_eq;
+
+    // Two triangles making a tent along the z axis, peaked at y = 1
+    fn tent(normals: Option<Vec<Tuple>>) -> Object {
+        TriangleMesh::new(
+            vec![
+                Tuple::point(-1., 0., 0.),
+                Tuple::point(0., 1., 0.),
+                Tuple::point(1., 0., 0.),
+                Tuple::point(0., 1., 1.),
+            ],
+            normals,
+            None,
+            [[0, 1, 3], [1, 2, 3]],
+            None,
+        )
+    }
+
+    #[test]
+    fn intersect() {
+        let mesh = tent(None);
+        let r = Ray::new(Tuple::point(-0.5, 5., 0.5), Tuple::vector(0., -1., 0.));
+        let xs = r.intersect_object(&mesh);
+        assert_eq!(xs.intersections.len(), 1);
+        assert_approx_eq!(xs.intersections[0].t, 4.5);
+
+        // Straight through both sides
+        let r = Ray::new(Tuple::point(-2., 0.5, 0.5), Tuple::vector(1., 0., 0.));
+        let xs = r.intersect_object(&mesh);
+        assert_eq!(xs.intersections.len(), 2);
+        assert_approx_eq!(xs.intersections[1].t, 2.5);
+
+        let r = Ray::new(Tuple::point(0., 5., 2.), Tuple::vector(0., -1., 0.));
+        assert_eq!(r.intersect_object(&mesh).intersections.len(), 0);
+    }
+
+    #[test]
+    fn normals() {
+        let mesh = tent(None);
+        let half = 0.5_f64.sqrt();
+        assert_approx_eq!(
+            mesh.normal_at(Tuple::point(-0.5, 0.5, 0.5)),
+            Tuple::vector(-half, half, 0.)
+        );
+        assert_approx_eq!(
+            mesh.normal_at(Tuple::point(0.5, 0.5, 0.5)),
+            Tuple::vector(half, half, 0.)
+        );
+
+        // Smooth normals blend towards the ridge
+        let up = Tuple::vector(0., 1., 0.);
+        let smooth = tent(Some(vec![
+            Tuple::vector(-1., 0., 0.),
+            up,
+            Tuple::vector(1., 0., 0.),
+            up,
+        ]));
+        assert_approx_eq!(smooth.normal_at(Tuple::point(0., 1., 0.5)), up);
+    }
+
+    #[test]
+    fn degenerate_faces_are_dropped() {
+        let mesh = TriangleMesh::new(
+            vec![
+                Tuple::point(0., 0., 0.),
+                Tuple::point(1., 0., 0.),
+                Tuple::point(2., 0., 0.),
+            ],
+            None,
+            None,
+            [[0, 1, 2]],
+            None,
+        );
+        let ShapeType::TriangleMesh(ref mesh) = mesh.shape else {
+            panic!("not a mesh");
+        };
+        assert!(mesh.faces().is_empty());
+    }
+}
@@ -0,0 +1,199 @@
+use crate::{
+    intersection::{Intersection, Intersections},
+    ray::Ray,
+    shape::{Object, ShapeType},
+};
+use std::array::from_fn;
+
+// Number of spheres tested against a ray at once
+pub const LANES: usize = 4;
+
+// Up to LANES spheres with the top three rows of their inverse transforms stored
+// lane-wise (structure of arrays), so that every step of the intersection test is a
+// straight-line operation over fixed size arrays that the compiler can vectorize
+#[derive(Debug, Clone)]
+struct SphereChunk {
+    inverse: [[f64; LANES]; 12],
+    indices: [usize; LANES],
+    len: usize,
+}
+
+impl SphereChunk {
+    fn new() -> Self {
+        Self {
+            inverse: [[0.; LANES]; 12],
+            indices: [0; LANES],
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, index: usize, object: &Object) {
+        let inverse = object.inverse();
+        for row in 0..3 {
+            for col in 0..4 {
+                self.inverse[row * 4 + col][self.len] = inverse[(row, col)];
+            }
+        }
+        self.indices[self.len] = index;
+        self.len += 1;
+    }
+
+    // Calls `visit` with the index of the sphere for each intersection
+    fn intersect<'a>(
+        &self,
+        ray: &Ray,
+        objects: &'a [Object],
+        visit: &mut impl FnMut(usize, Intersection<'a>),
+    ) {
+        let m = &self.inverse;
+        let (o, d) = (ray.origin, ray.direction);
+
+        // Ray in each sphere's object space
+        let ox: [f64; LANES] = from_fn(|l| m[0][l] * o.x + m[1][l] * o.y + m[2][l] * o.z + m[3][l]);
+        let oy: [f64; LANES] = from_fn(|l| m[4][l] * o.x + m[5][l] * o.y + m[6][l] * o.z + m[7][l]);
+        let oz: [f64; LANES] =
+            from_fn(|l| m[8][l] * o.x + m[9][l] * o.y + m[10][l] * o.z + m[11][l]);
+        let dx: [f64; LANES] = from_fn(|l| m[0][l] * d.x + m[1][l] * d.y + m[2][l] * d.z);
+        let dy: [f64; LANES] = from_fn(|l| m[4][l] * d.x + m[5][l] * d.y + m[6][l] * d.z);
+        let dz: [f64; LANES] = from_fn(|l| m[8][l] * d.x + m[9][l] * d.y + m[10][l] * d.z);
+
+        let a: [f64; LANES] = from_fn(|l| dx[l] * dx[l] + dy[l] * dy[l] + dz[l] * dz[l]);
+        let b: [f64; LANES] = from_fn(|l| 2. * (dx[l] * ox[l] + dy[l] * oy[l] + dz[l] * oz[l]));
+        let c: [f64; LANES] = from_fn(|l| ox[l] * ox[l] + oy[l] * oy[l] + oz[l] * oz[l] - 1.);
+        let discriminant: [f64; LANES] = from_fn(|l| b[l] * b[l] - 4. * a[l] * c[l]);
+
+        for lane in 0..self.len {
+            if discriminant[lane] >= 0. {
+                let index = self.indices[lane];
+                let object = &objects[index];
+                let root = discriminant[lane].sqrt();
+                visit(
+                    index,
+                    Intersection::new((-b[lane] - root) / (2. * a[lane]), object),
+                );
+                visit(
+                    index,
+                    Intersection::new((-b[lane] + root) / (2. * a[lane]), object),
+                );
+            }
+        }
+    }
+}
+
+// All the spheres of a world packed for batched intersection tests
+#[derive(Debug, Clone)]
+pub struct SphereBatch {
+    chunks: Vec<SphereChunk>,
+    // Whether each object of the world the batch was built from is in the batch
+    members: Vec<bool>,
+}
+
+impl SphereBatch {
+    pub fn new(objects: &[Object]) -> Self {
+        let mut chunks: Vec<SphereChunk> = vec![];
+        let mut members = vec![false; objects.len()];
+        for (index, object) in objects.iter().enumerate() {
+            if let ShapeType::Sphere(_) = object.shape {
+                match chunks.last_mut() {
+                    Some(chunk) if chunk.len < LANES => chunk.push(index, object),
+                    _ => {
+                        let mut chunk = SphereChunk::new();
+                        chunk.push(index, object);
+                        chunks.push(chunk);
+                    }
+                }
+                members[index] = true;
+            }
+        }
+        Self { chunks, members }
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.iter().map(|chunk| chunk.len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        self.members.get(index).copied().unwrap_or(false)
+    }
+
+    pub fn intersect<'a>(&self, ray: &Ray, objects: &'a [Object], out: &mut Intersections<'a>) {
+        self.intersect_indexed(ray, objects, |_, i| out.push(i));
+    }
+
+    // Same as `intersect`, also giving the index of the sphere each intersection is with
+    pub fn intersect_indexed<'a>(
+        &self,
+        ray: &Ray,
+        objects: &'a [Object],
+        mut visit: impl FnMut(usize, Intersection<'a>),
+    ) {
+        for chunk in self.chunks.iter() {
+            chunk.intersect(ray, objects, &mut visit);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        intersection::IntersectionList,
+        matrix::Matrix,
+        shapes::{Cube, Sphere},
+        tuple::Tuple,
+    };
+
+    fn spheres() -> Vec<Object> {
+        let mut objects = vec![];
+        for i in 0..6 {
+            let mut s = Sphere::new(None);
+            s.set_transform(
+                &(Matrix::translation(i as f64 * 0.3 - 0.8, 0.1 * i as f64, i as f64)
+                    * &Matrix::scaling(1., 0.5 + 0.1 * i as f64, 1.)),
+            );
+            objects.push(s);
+        }
+        objects.insert(2, Cube::new(None));
+        objects
+    }
+
+    #[test]
+    fn batch() {
+        let objects = spheres();
+        let batch = SphereBatch::new(&objects);
+        assert_eq!(batch.len(), 6);
+        assert!(batch.contains(0));
+        assert!(!batch.contains(2));
+        assert!(!batch.contains(10));
+        assert!(SphereBatch::new(&objects[2..3]).is_empty());
+    }
+
+    #[test]
+    fn matches_scalar_intersection() {
+        let objects = spheres();
+        let batch = SphereBatch::new(&objects);
+        for ray in [
+            Ray::new(Tuple::point(0., 0., -5.), Tuple::vector(0., 0., 1.)),
+            Ray::new(
+                Tuple::point(-3., 0.2, 1.),
+                Tuple::vector(1., 0., 0.2).normalize(),
+            ),
+            Ray::new(Tuple::point(0., 5., 0.), Tuple::vector(0., 1., 0.)),
+        ] {
+            let mut batched = Intersections::new();
+            batch.intersect(&ray, &objects, &mut batched);
+            let batched = IntersectionList::new(batched);
+            let scalar = IntersectionList::new(
+                objects
+                    .iter()
+                    .filter(|o| matches!(o.shape, ShapeType::Sphere(_)))
+                    .flat_map(|o| ray.intersect_object(o).intersections),
+            );
+            assert_eq!(batched.intersections, scalar.intersections);
+        }
+    }
+}
@@ -0,0 +1,174 @@
+// Daylight from the Preetham model (A Practical Analytic Model for Daylight, 1999).
+// The sky's brightness and tint are fitted functions of the angles to the zenith and
+// the sun, and of the turbidity: about 2 for a clear day, 10 for a hazy one
+use crate::{color::Color, environment::Environment, light::PointLight, tuple::Tuple, PI};
+
+// Coefficients A to E of the Perez distribution for luminance and the x and y
+// chromaticities, each as (slope, intercept) in the turbidity
+const PEREZ_Y: [(f64, f64); 5] = [
+    (0.1787, -1.4630),
+    (-0.3554, 0.4275),
+    (-0.0227, 5.3251),
+    (0.1206, -2.5771),
+    (-0.0670, 0.3703),
+];
+const PEREZ_X: [(f64, f64); 5] = [
+    (-0.0193, -0.2592),
+    (-0.0665, 0.0008),
+    (-0.0004, 0.2125),
+    (-0.0641, -0.8989),
+    (-0.0033, 0.0452),
+];
+const PEREZ_CHROMA_Y: [(f64, f64); 5] = [
+    (-0.0167, -0.2608),
+    (-0.0950, 0.0092),
+    (-0.0079, 0.2102),
+    (-0.0441, -1.6537),
+    (-0.0109, 0.0529),
+];
+
+// Zenith chromaticity as polynomials in the sun's zenith angle, cubic term first,
+// for the turbidity squared, the turbidity and the constant term
+const ZENITH_X: [[f64; 4]; 3] = [
+    [0.00166, -0.00375, 0.00209, 0.],
+    [-0.02903, 0.06377, -0.03202, 0.00394],
+    [0.11693, -0.21196, 0.06052, 0.25886],
+];
+const ZENITH_Y: [[f64; 4]; 3] = [
+    [0.00275, -0.00610, 0.00317, 0.],
+    [-0.04214, 0.08970, -0.04153, 0.00516],
+    [0.15346, -0.26756, 0.06670, 0.26688],
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sky {
+    // Angles in radians. Elevation is above the horizon, azimuth is around the y axis
+    // from +z towards +x
+    pub elevation: f64,
+    pub azimuth: f64,
+    pub turbidity: f64,
+    // Multiplies the sky, which is otherwise 1 at the zenith
+    pub intensity: f64,
+    // Seen by rays below the horizon
+    pub ground: Color,
+}
+
+fn perez(coefficients: &[(f64, f64); 5], turbidity: f64, theta: f64, gamma: f64) -> f64 {
+    let [a, b, c, d, e] = coefficients.map(|(slope, intercept)| slope * turbidity + intercept);
+    (1. + a * (b / theta.cos()).exp()) * (1. + c * (d * gamma).exp() + e * gamma.cos().powi(2))
+}
+
+fn zenith_chromaticity(polynomials: &[[f64; 4]; 3], turbidity: f64, theta: f64) -> f64 {
+    let powers = [theta.powi(3), theta.powi(2), theta, 1.];
+    let [squared, linear, constant] =
+        polynomials.map(|p| p.iter().zip(powers.iter()).map(|(c, t)| c * t).sum::<f64>());
+    turbidity * turbidity * squared + turbidity * linear + constant
+}
+
+// Linear sRGB for a luminance and chromaticity
+fn xyy_to_rgb(luminance: f64, x: f64, y: f64) -> Color {
+    let big_x = x * luminance / y;
+    let big_z = (1. - x - y) * luminance / y;
+    Color::new(
+        3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z,
+        -0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z,
+        0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z,
+    )
+}
+
+impl Sky {
+    pub fn new(elevation: f64, azimuth: f64, turbidity: f64) -> Self {
+        assert!((1.7..=10.).contains(&turbidity));
+        Sky {
+            elevation,
+            azimuth,
+            turbidity,
+            intensity: 1.,
+            ground: Color::new(0.2, 0.2, 0.2),
+        }
+    }
+
+    // Unit vector pointing at the sun
+    pub fn sun_direction(&self) -> Tuple {
+        let (sin_e, cos_e) = self.elevation.sin_cos();
+        let (sin_a, cos_a) = self.azimuth.sin_cos();
+        Tuple::vector(cos_e * sin_a, sin_e, cos_e * cos_a)
+    }
+
+    // Light from the sky in the given direction
+    pub fn radiance(&self, direction: Tuple) -> Color {
+        let d = direction.normalize();
+        if d.y < 0. {
+            return self.ground * self.intensity;
+        }
+        let turbidity = self.turbidity;
+        // The fit is only made for suns up to the horizon
+        let sun_theta = (PI / 2. - self.elevation).clamp(0., PI / 2. - 0.001);
+        let theta = d.y.acos().min(PI / 2. - 0.001);
+        let gamma = d.dot(&self.sun_direction()).clamp(-1., 1.).acos();
+
+        let relative = |coefficients| {
+            perez(coefficients, turbidity, theta, gamma)
+                / perez(coefficients, turbidity, 0., sun_theta)
+        };
+        let x = zenith_chromaticity(&ZENITH_X, turbidity, sun_theta) * relative(&PEREZ_X);
+        let y = zenith_chromaticity(&ZENITH_Y, turbidity, sun_theta) * relative(&PEREZ_CHROMA_Y);
+        let luminance = relative(&PEREZ_Y);
+        xyy_to_rgb(luminance, x, y) * self.intensity
+    }
+
+    // The sky baked into a map, to use as the world's environment
+    pub fn environment(&self, width: usize, height: usize) -> Environment {
+        Environment::from_fn(width, height, |direction| self.radiance(direction))
+    }
+
+    // A point light far away towards the sun, colored like the sky around it and
+    // as bright as `intensity` in its brightest channel
+    pub fn sun(&self, distance: f64, intensity: f64) -> PointLight {
+        let color = self.radiance(self.sun_direction());
+        let brightest = color.red.max(color.green).max(color.blue);
+        PointLight::new(
+            Tuple::point(0., 0., 0.) + self.sun_direction() * distance,
+            color * (intensity / brightest),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_approx_eq;
+
+    #[test]
+    fn sky() {
+        let sky = Sky::new(PI / 4., PI / 2., 2.5);
+        assert_approx_eq!(sky.sun_direction(), Tuple::vector(1., 1., 0.).normalize());
+
+        // Blue overhead, brighter around the sun, and the ground below the horizon
+        let zenith = sky.radiance(Tuple::vector(0., 1., 0.));
+        assert_approx_eq!(
+            zenith.red * 0.2126 + zenith.green * 0.7152 + zenith.blue * 0.0722,
+            1.,
+            epsilon = 0.01
+        );
+        assert!(zenith.blue > zenith.red);
+        let near_sun = sky.radiance(Tuple::vector(1., 1.1, 0.));
+        let away = sky.radiance(Tuple::vector(-1., 1., 0.));
+        assert!(near_sun.green > 2. * away.green);
+        assert_eq!(sky.radiance(Tuple::vector(0., -1., 0.)), sky.ground);
+    }
+
+    #[test]
+    fn sunset() {
+        let noon = Sky::new(PI / 2.5, 0., 3.).sun(100., 1.);
+        let sunset = Sky::new(0.05, 0., 3.).sun(100., 1.);
+        assert_approx_eq!(
+            sunset.position,
+            Tuple::point(0., 100. * 0.05_f64.sin(), 100. * 0.05_f64.cos())
+        );
+        // Low suns shine through more air, so they are redder
+        let warmth = |light: &PointLight| light.intensity.red / light.intensity.blue;
+        assert!(warmth(&sunset) > warmth(&noon));
+        assert_approx_eq!(sunset.intensity.red, 1.);
+    }
+}
@@ -0,0 +1,123 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+// Things counted while rendering a world with stats enabled
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Counter {
+    PrimaryRays,
+    ShadowRays,
+    SecondaryRays,
+    IntersectionTests,
+    // Only counted when the world has a BVH, see World::build_bvh
+    BvhNodeVisits,
+}
+
+const COUNTERS: usize = 5;
+
+// Shared between the render threads, so every count is a relaxed atomic add
+#[derive(Debug, Default)]
+pub struct StatCounters {
+    counts: [AtomicU64; COUNTERS],
+}
+
+// A copy starts from the counts of the original
+impl Clone for StatCounters {
+    fn clone(&self) -> Self {
+        let counters = Self::new();
+        for (count, other) in counters.counts.iter().zip(self.counts.iter()) {
+            count.store(other.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+        counters
+    }
+}
+
+impl StatCounters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&self, counter: Counter, n: u64) {
+        self.counts[counter as usize].fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn get(&self, counter: Counter) -> u64 {
+        self.counts[counter as usize].load(Ordering::Relaxed)
+    }
+
+    pub fn reset(&self) {
+        for count in self.counts.iter() {
+            count.store(0, Ordering::Relaxed);
+        }
+    }
+
+    pub fn snapshot(&self, wall_time: Duration) -> RenderStats {
+        RenderStats {
+            primary_rays: self.get(Counter::PrimaryRays),
+            shadow_rays: self.get(Counter::ShadowRays),
+            secondary_rays: self.get(Counter::SecondaryRays),
+            intersection_tests: self.get(Counter::IntersectionTests),
+            bvh_node_visits: self.get(Counter::BvhNodeVisits),
+            wall_time,
+        }
+    }
+}
+
+// Totals for a single render
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RenderStats {
+    pub primary_rays: u64,
+    pub shadow_rays: u64,
+    pub secondary_rays: u64,
+    pub intersection_tests: u64,
+    pub bvh_node_visits: u64,
+    pub wall_time: Duration,
+}
+
+impl RenderStats {
+    pub fn rays(&self) -> u64 {
+        self.primary_rays + self.shadow_rays + self.secondary_rays
+    }
+
+    pub fn rays_per_second(&self) -> f64 {
+        let seconds = self.wall_time.as_secs_f64();
+        if seconds > 0. {
+            self.rays() as f64 / seconds
+        } else {
+            0.
+        }
+    }
+
+    pub fn intersections_per_ray(&self) -> f64 {
+        match self.rays() {
+            0 => 0.,
+            rays => self.intersection_tests as f64 / rays as f64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counters() {
+        let counters = StatCounters::new();
+        counters.add(Counter::PrimaryRays, 4);
+        counters.add(Counter::ShadowRays, 2);
+        counters.add(Counter::SecondaryRays, 2);
+        counters.add(Counter::IntersectionTests, 16);
+        let stats = counters.snapshot(Duration::from_secs(2));
+        assert_eq!(stats.rays(), 8);
+        assert_eq!(stats.rays_per_second(), 4.);
+        assert_eq!(stats.intersections_per_ray(), 2.);
+        assert_eq!(stats.bvh_node_visits, 0);
+
+        counters.reset();
+        let stats = counters.snapshot(Duration::ZERO);
+        assert_eq!(stats, RenderStats::default());
+        assert_eq!(stats.rays_per_second(), 0.);
+        assert_eq!(stats.intersections_per_ray(), 0.);
+    }
+}
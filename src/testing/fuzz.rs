@@ -0,0 +1,287 @@
+// Renders randomly generated scenes and checks properties every render should have,
+// whatever the scene contains
+use crate::{
+    camera::{Camera, SuperSamplingMode},
+    color::Color,
+    intersection::Intersection,
+    light::{PointLight, SphereLight, SpotLight},
+    material::Material,
+    matrix::Matrix,
+    rng::XorShift,
+    shape::{Object, MAX_REFLECTIONS},
+    shapes::{
+        Csg, CsgOperation, Cube, Cylinder, Disc, Metaball, Metaballs, Plane, Rectangle, Sdf,
+        SdfShape, Sphere, Triangle, TriangleMesh,
+    },
+    tuple::Tuple,
+    world::World,
+    EPSILON,
+};
+
+pub const FUZZ_WIDTH: usize = 16;
+pub const FUZZ_HEIGHT: usize = 12;
+
+fn random_material(rng: &mut XorShift) -> Material {
+    let mut material = Material::new();
+    material.color = Color::new(rng.next_f64(), rng.next_f64(), rng.next_f64());
+    material.ambient = rng.next_f64();
+    material.diffuse = rng.next_f64();
+    material.specular = rng.next_f64();
+    material.shininess = rng.range(1., 300.);
+    material.reflective = rng.next_f64();
+    material.transparency = rng.next_f64();
+    material.refractive_index = rng.range(1., 2.5);
+    material
+}
+
+fn random_point(rng: &mut XorShift, extent: f64) -> Tuple {
+    Tuple::point(
+        rng.range(-extent, extent),
+        rng.range(-extent, extent),
+        rng.range(-extent, extent),
+    )
+}
+
+fn random_object(rng: &mut XorShift) -> Object {
+    let material = random_material(rng);
+    let object = match rng.index(0, 11) {
+        0 => Sphere::new(Some(material)),
+        1 => Plane::new(Some(material)),
+        2 => Cube::new(Some(material)),
+        3 => Cylinder::new(Some(material)),
+        4 => Disc::new(Some(material)),
+        5 => Rectangle::new(Some(material)),
+        6 => Triangle::new(
+            random_point(rng, 1.),
+            random_point(rng, 1.),
+            random_point(rng, 1.),
+            Some(material),
+        ),
+        // A tetrahedron
+        7 => TriangleMesh::new(
+            (0..4).map(|_| random_point(rng, 1.)).collect(),
+            None,
+            None,
+            [[0, 1, 2], [0, 1, 3], [0, 2, 3], [1, 2, 3]],
+            Some(material),
+        ),
+        // The children shade with their own materials, which the CSG object is given
+        // too so that energy_bound sees it
+        8 => {
+            let mut csg = Csg::new(
+                CsgOperation::Difference,
+                Cube::new(Some(material.clone())),
+                Sphere::new(Some(material.clone())).with_transform(&Matrix::scaling(1.3, 1.3, 1.3)),
+            );
+            csg.material = material;
+            csg
+        }
+        9 => Metaballs::new(
+            (0..2)
+                .map(|_| Metaball::new(random_point(rng, 0.5), rng.range(0.5, 1.), 1.))
+                .collect(),
+            0.5,
+            Some(material),
+        ),
+        _ => SdfShape::new(
+            Sdf::Torus {
+                major_radius: 1.,
+                minor_radius: rng.range(0.1, 0.5),
+            },
+            Some(material),
+        ),
+    };
+    let mut scale = || rng.range(0.2, 2.);
+    let scaling = Matrix::scaling(scale(), scale(), scale());
+    let transform = Matrix::translation(rng.range(-3., 3.), rng.range(-3., 3.), rng.range(-3., 3.))
+        * &Matrix::rotation_y(rng.range(0., 6.3))
+        * &Matrix::rotation_x(rng.range(0., 6.3))
+        * &scaling;
+    object.with_transform(&transform)
+}
+
+fn random_light(rng: &mut XorShift) -> PointLight {
+    let position = random_point(rng, 10.);
+    let intensity = Color::new(rng.next_f64(), rng.next_f64(), rng.next_f64());
+    match rng.index(0, 3) {
+        0 => PointLight::new(position, intensity),
+        1 => SphereLight::new(position, rng.range(0.1, 2.), intensity),
+        _ => SpotLight::new(
+            position,
+            Tuple::point(0., 0., 0.) - position,
+            intensity,
+            rng.range(0.1, 0.5),
+            rng.range(0.5, 1.),
+        ),
+    }
+}
+
+// A random scene made of 1 to 5 objects and 1 to 3 point, sphere or spot lights,
+// with a camera outside of them looking towards the origin. The camera supersamples
+// with a seed, so renders are noisy but the same every time. The same seed always
+// gives the same scene
+pub fn random_scene(seed: u64) -> (World, Camera) {
+    let mut rng = XorShift::new(seed);
+    let objects = (0..rng.index(1, 6))
+        .map(|_| random_object(&mut rng))
+        .collect();
+    let lights = (0..rng.index(1, 4))
+        .map(|_| random_light(&mut rng))
+        .collect();
+
+    let mut camera = Camera::new(
+        FUZZ_WIDTH,
+        FUZZ_HEIGHT,
+        rng.range(0.5, 2.),
+        SuperSamplingMode::Stochastic,
+    );
+    camera.sampling.samples = 2;
+    camera.sampling.seed = Some(seed);
+    let from = Tuple::point(rng.range(-8., 8.), rng.range(-8., 8.), -10.);
+    camera.transform =
+        Matrix::view_transform(from, Tuple::point(0., 0., 0.), Tuple::vector(0., 1., 0.));
+    (World::new(objects, lights), camera)
+}
+
+// Largest value a channel can reach without emissive surfaces. Each hit adds at most
+// ambient + diffuse + specular of every light, for the scene's largest such sum, and
+// passes on what its reflection and refraction bring. Surfaces that do only one of
+// those scale it by reflective or transparency, and ones that do both split it
+// between them by Fresnel, so at most the larger of the two passes on
+fn energy_bound(world: &World) -> f64 {
+    let lights: f64 = world
+        .lights
+        .iter()
+        .map(|light| {
+            let i = light.intensity;
+            i.red.max(i.green).max(i.blue)
+        })
+        .sum();
+    let largest = |f: fn(&Material) -> f64| {
+        world
+            .objects
+            .iter()
+            .map(|o| f(&o.material))
+            .fold(0., f64::max)
+    };
+    let surface = lights * largest(|m| m.ambient + m.diffuse + m.specular);
+    let passed_on = largest(|m| m.reflective.max(m.transparency));
+    (0..=MAX_REFLECTIONS as i32)
+        .map(|depth| surface * passed_on.powi(depth))
+        .sum()
+}
+
+// Describes every invariant the render of a world breaks, if any
+pub fn violations(world: &World, camera: &Camera) -> Vec<String> {
+    let mut violations = vec![];
+    let canvas = camera.render(world);
+    let bound = energy_bound(world);
+    for (index, pixel) in canvas.pixels.iter().enumerate() {
+        let (x, y) = (index % canvas.width, index / canvas.width);
+        let channels = [pixel.red, pixel.green, pixel.blue];
+        if channels.iter().any(|c| c.is_nan()) {
+            violations.push(format!("pixel ({}, {}) is NaN", x, y));
+        } else if channels.iter().any(|c| *c < 0. || *c > bound) {
+            violations.push(format!(
+                "pixel ({}, {}) is {:?}, outside 0..{}",
+                x, y, pixel, bound
+            ));
+        }
+
+        // Every intersection the camera ray finds, not just the visible hit
+        let ray = camera.project_ray(x, y);
+        let list = ray.intersect_world(world);
+        let behind = |hit: Option<&Intersection>| hit.is_some_and(|hit| hit.t < 0.);
+        if behind(list.hit()) || behind(list.visible_hit()) {
+            violations.push(format!("camera ray ({}, {}) hits behind the camera", x, y));
+        }
+        let xs = list.intersections;
+        if xs.iter().any(|i| i.t.is_nan()) {
+            violations.push(format!("camera ray ({}, {}) has a NaN t", x, y));
+        } else if xs.windows(2).any(|pair| pair[0].t > pair[1].t) {
+            violations.push(format!("camera ray ({}, {}) is out of order", x, y));
+        }
+        for i in xs.iter().filter(|i| i.t.is_finite()) {
+            let length = i.object.normal_at(ray.position(i.t)).magnitude();
+            if (length - 1.).abs() > EPSILON {
+                violations.push(format!(
+                    "camera ray ({}, {}) has a normal {} long at t = {}",
+                    x, y, length, i.t
+                ));
+            }
+        }
+    }
+
+    // Everything random in a render comes from the camera's seed
+    let again = camera.render(world);
+    let same = canvas.pixels.iter().zip(again.pixels.iter()).all(|(a, b)| {
+        a.red.to_bits() == b.red.to_bits()
+            && a.green.to_bits() == b.green.to_bits()
+            && a.blue.to_bits() == b.blue.to_bits()
+    });
+    if !same {
+        violations.push(String::from("rendering twice gave different images"));
+    }
+    violations
+}
+
+// Checks the scenes for every seed, returning the seeds that broke an invariant
+pub fn fuzz(seeds: std::ops::Range<u64>) -> Vec<(u64, Vec<String>)> {
+    seeds
+        .filter_map(|seed| {
+            let (world, camera) = random_scene(seed);
+            let violations = violations(&world, &camera);
+            (!violations.is_empty()).then_some((seed, violations))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reproducible_scenes() {
+        let (a, _) = random_scene(3);
+        let (b, _) = random_scene(3);
+        let (c, _) = random_scene(4);
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn random_scenes_hold_invariants() {
+        let failures = fuzz(0..25);
+        assert!(failures.is_empty(), "{:#?}", failures);
+    }
+
+    #[test]
+    fn catches_energy_gain() {
+        // As if shading added light of its own on top of what the lights give. The
+        // scene has a plane filling whole pixels
+        let (mut world, camera) = random_scene(1);
+        let extra = energy_bound(&world);
+        let before = violations(&world, &camera);
+        for object in world.objects.iter_mut() {
+            object.material.emission = Color::new(extra, extra, extra);
+        }
+        assert!(violations(&world, &camera).len() > before.len());
+    }
+
+    #[test]
+    fn catches_nondeterminism() {
+        // As if some sampling ignored the seed
+        let (world, mut camera) = random_scene(1);
+        camera.sampling.seed = None;
+        assert!(violations(&world, &camera)
+            .iter()
+            .any(|v| v == "rendering twice gave different images"));
+    }
+
+    #[test]
+    fn catches_nan() {
+        let (mut world, camera) = random_scene(0);
+        world.lights[0].intensity = Color::new(f64::NAN, 0., 0.);
+        assert!(!violations(&world, &camera).is_empty());
+    }
+}
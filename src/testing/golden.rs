@@ -0,0 +1,129 @@
+// Golden image regression tests: the reference scenes are rendered small and compared
+// against images stored in tests/golden. Run with UPDATE_GOLDEN=1 to (re)write them
+use crate::{
+    camera::{Camera, SuperSamplingMode},
+    canvas::Canvas,
+    scenes,
+    world::World,
+};
+use std::path::PathBuf;
+
+pub const GOLDEN_WIDTH: usize = 48;
+pub const GOLDEN_HEIGHT: usize = 24;
+// Renders less structurally similar to their reference than this fail, in luminance
+// or in any one channel
+pub const MIN_SSIM: f64 = 0.98;
+
+pub const SCENES: [&str; 5] = [
+    "three_spheres",
+    "mirror_spheres",
+    "pattern_showcase",
+    "cube_stack",
+    "glass_room",
+];
+
+#[derive(Debug)]
+pub struct GoldenFailure {
+    pub name: String,
+    pub ssim: f64,
+    pub diff: Canvas,
+}
+
+pub fn scene(name: &str) -> Option<(World, Camera)> {
+    match name {
+        "three_spheres" => Some(scenes::three_spheres()),
+        "mirror_spheres" => Some(scenes::mirror_spheres()),
+        "pattern_showcase" => Some(scenes::pattern_showcase()),
+        "cube_stack" => Some(scenes::cube_stack()),
+        "glass_room" => Some(scenes::glass_room()),
+        _ => None,
+    }
+}
+
+pub fn golden_path(name: &str, suffix: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}{}.ppm", name, suffix))
+}
+
+// Renders a reference scene at golden size with one ray through the centre of each
+// pixel, so the result doesn't depend on random sampling
+pub fn render(name: &str) -> Canvas {
+    let (world, camera) = scene(name).unwrap_or_else(|| panic!("no golden scene {}", name));
+    let mut small = Camera::new(
+        GOLDEN_WIDTH,
+        GOLDEN_HEIGHT,
+        camera.field_of_view(),
+        SuperSamplingMode::None,
+    );
+    small.transform = camera.transform.clone();
+    let mut canvas = small.render(&world);
+    canvas.metadata = None;
+    canvas
+}
+
+// Compares a fresh render against the stored reference, giving the lowest of the
+// luminance and per channel similarities. With UPDATE_GOLDEN set the reference is
+// (re)written instead, and without it a missing reference is a failure. On failure
+// the render and a heatmap of the differences are saved next to the reference as
+// <name>.actual.ppm and <name>.diff.ppm
+pub fn check(name: &str) -> Result<f64, Box<GoldenFailure>> {
+    let actual = render(name);
+    let reference_path = golden_path(name, "");
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&reference_path, actual.to_ppm()).unwrap();
+        return Ok(1.);
+    }
+    let reference = std::fs::read_to_string(&reference_path)
+        .ok()
+        .and_then(|ppm| Canvas::from_ppm(&ppm))
+        .unwrap_or_else(|| Canvas::new(0, 0));
+
+    let same_size = (reference.width, reference.height) == (actual.width, actual.height);
+    let ssim = if same_size {
+        let channels = actual.channel_ssim(&reference);
+        channels.into_iter().fold(actual.ssim(&reference), f64::min)
+    } else {
+        f64::NEG_INFINITY
+    };
+    if ssim >= MIN_SSIM {
+        return Ok(ssim);
+    }
+
+    let diff = if same_size {
+        actual.diff(&reference).0
+    } else {
+        Canvas::new(actual.width, actual.height)
+    };
+    std::fs::write(golden_path(name, ".actual"), actual.to_ppm()).unwrap();
+    std::fs::write(golden_path(name, ".diff"), diff.to_ppm()).unwrap();
+    Err(Box::new(GoldenFailure {
+        name: String::from(name),
+        ssim,
+        diff,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn golden_scenes() {
+        for name in SCENES {
+            if let Err(failure) = check(name) {
+                panic!(
+                    "{} differs from its golden image or has none (ssim {:.4}), see {}",
+                    failure.name,
+                    failure.ssim,
+                    golden_path(name, ".diff").display()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_scene() {
+        assert!(scene("nope").is_none());
+    }
+}
@@ -0,0 +1,3 @@
+// Helpers for testing renders end to end
+pub mod fuzz;
+pub mod golden;
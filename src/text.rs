@@ -0,0 +1,264 @@
+// Extruded 3D text. Glyph outlines from a TrueType or OpenType font are flattened,
+// their faces filled with triangles and their edges turned into walls, giving flat
+// shaded triangles that can be added to a world like any other objects
+use crate::{material::Material, shape::Object, shapes::Triangle, tuple::Tuple};
+use lyon_tessellation::{
+    math::{point, Point},
+    path::{iterator::PathIterator, Event, Path},
+    BuffersBuilder, FillOptions, FillRule, FillTessellator, FillVertex, VertexBuffers,
+};
+use ttf_parser::{Face, OutlineBuilder};
+
+// Largest distance flattened curves stray from the outline, as a fraction of the em
+const TOLERANCE: f32 = 0.002;
+
+// Builds a lyon path from a glyph outline, moved along by the pen position
+struct Outline {
+    builder: lyon_tessellation::path::path::Builder,
+    offset: (f32, f32),
+}
+
+impl Outline {
+    fn at(&self, x: f32, y: f32) -> Point {
+        point(x + self.offset.0, y + self.offset.1)
+    }
+}
+
+impl OutlineBuilder for Outline {
+    fn move_to(&mut self, x: f32, y: f32) {
+        let at = self.at(x, y);
+        self.builder.begin(at);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        let to = self.at(x, y);
+        self.builder.line_to(to);
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let (ctrl, to) = (self.at(x1, y1), self.at(x, y));
+        self.builder.quadratic_bezier_to(ctrl, to);
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let (ctrl1, ctrl2, to) = (self.at(x1, y1), self.at(x2, y2), self.at(x, y));
+        self.builder.cubic_bezier_to(ctrl1, ctrl2, to);
+    }
+
+    fn close(&mut self) {
+        self.builder.end(true);
+    }
+}
+
+// Pushes the triangle unless it has no area, since it would have no normal
+fn push_triangle(triangles: &mut Vec<Object>, points: [Tuple; 3], material: &Material) {
+    let [p1, p2, p3] = points;
+    if (p2 - p1).cross(&(p3 - p1)).magnitude() > f64::EPSILON {
+        triangles.push(Triangle::new(p1, p2, p3, Some(material.clone())));
+    }
+}
+
+// Triangles for the outlines filled with the nonzero rule, with the front face at
+// z = 0 facing -z and the back face at z = depth. Outline units are multiplied by
+// `scale`
+fn extrude(
+    path: &Path,
+    tolerance: f32,
+    scale: f64,
+    depth: f64,
+    material: &Material,
+) -> Vec<Object> {
+    let at = |p: Point, z: f64| Tuple::point(p.x as f64 * scale, p.y as f64 * scale, z);
+    let mut triangles = vec![];
+
+    let mut fill: VertexBuffers<Point, u32> = VertexBuffers::new();
+    let options = FillOptions::tolerance(tolerance).with_fill_rule(FillRule::NonZero);
+    let result = FillTessellator::new().tessellate_path(
+        path,
+        &options,
+        &mut BuffersBuilder::new(&mut fill, |vertex: FillVertex| vertex.position()),
+    );
+    if result.is_err() {
+        return triangles;
+    }
+    for corners in fill.indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| fill.vertices[corners[i] as usize]);
+        // Counter clockwise seen from the front, so the front normal points to -z
+        let (b, c) = if (b - a).cross(c - a) > 0. {
+            (b, c)
+        } else {
+            (c, b)
+        };
+        push_triangle(&mut triangles, [at(a, 0.), at(b, 0.), at(c, 0.)], material);
+        push_triangle(
+            &mut triangles,
+            [at(a, depth), at(c, depth), at(b, depth)],
+            material,
+        );
+    }
+
+    // The filled side of TrueType outlines is on the right of the direction they're
+    // drawn in, while PostScript outlines are the other way around. The outer
+    // outlines decide the sign of the total area
+    let mut segments = vec![];
+    for event in path.iter().flattened(tolerance) {
+        match event {
+            Event::Line { from, to } => segments.push((from, to)),
+            Event::End { last, first, .. } if last != first => segments.push((last, first)),
+            _ => (),
+        }
+    }
+    let area: f32 = segments
+        .iter()
+        .map(|(from, to)| from.to_vector().cross(to.to_vector()))
+        .sum();
+    for (from, to) in segments {
+        let (from, to) = if area < 0. { (from, to) } else { (to, from) };
+        push_triangle(
+            &mut triangles,
+            [at(from, 0.), at(to, 0.), at(from, depth)],
+            material,
+        );
+        push_triangle(
+            &mut triangles,
+            [at(to, 0.), at(to, depth), at(from, depth)],
+            material,
+        );
+    }
+    triangles
+}
+
+// The string set in the font with its baseline along the x axis from the origin, one
+// unit per em, extruded `depth` units into +z. Lines are split at newlines
+pub fn text(
+    font_data: &[u8],
+    string: &str,
+    depth: f64,
+    material: Option<Material>,
+) -> Result<Vec<Object>, String> {
+    let face = Face::parse(font_data, 0).map_err(|e| format!("can't read font: {}", e))?;
+    let em = face.units_per_em() as f32;
+    let line_height = (face.ascender() - face.descender() + face.line_gap()) as f32;
+
+    let mut outline = Outline {
+        builder: Path::builder(),
+        offset: (0., 0.),
+    };
+    for c in string.chars() {
+        if c == '\n' {
+            outline.offset = (0., outline.offset.1 - line_height);
+            continue;
+        }
+        let Some(glyph) = face.glyph_index(c) else {
+            continue;
+        };
+        face.outline_glyph(glyph, &mut outline);
+        outline.offset.0 += face.glyph_hor_advance(glyph).unwrap_or(0) as f32;
+    }
+
+    let path = outline.builder.build();
+    let material = material.unwrap_or_default();
+    Ok(extrude(
+        &path,
+        TOLERANCE * em,
+        1. / em as f64,
+        depth,
+        &material,
+    ))
+}
+
+pub fn load_text(
+    font_path: &str,
+    string: &str,
+    depth: f64,
+    material: Option<Material>,
+) -> Result<Vec<Object>, String> {
+    let data = std::fs::read(font_path).map_err(|e| format!("{}: {}", font_path, e))?;
+    text(&data, string, depth, material)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ray::Ray, world::World};
+
+    fn square(builder: &mut lyon_tessellation::path::path::Builder, size: f32, clockwise: bool) {
+        let mut corners = [(0., 0.), (0., 1.), (1., 1.), (1., 0.)];
+        if !clockwise {
+            corners.reverse();
+        }
+        let offset = (1. - size) / 2.;
+        let at = |(x, y): (f32, f32)| point(offset + x * size, offset + y * size);
+        builder.begin(at(corners[0]));
+        for corner in &corners[1..] {
+            builder.line_to(at(*corner));
+        }
+        builder.end(true);
+    }
+
+    #[test]
+    fn extrude_with_hole() {
+        let mut builder = Path::builder();
+        square(&mut builder, 1., true);
+        square(&mut builder, 0.5, false);
+        let triangles = extrude(&builder.build(), 0.01, 2., 0.5, &Material::new());
+        // 8 walls of 2 triangles, and both faces of the frame
+        assert_eq!(triangles.len(), 16 + 2 * 8);
+
+        let world = World::new(triangles, vec![]);
+        let hits = |x, y| {
+            let r = Ray::new(Tuple::point(x, y, -5.), Tuple::vector(0., 0., 1.));
+            r.intersect_world(&world)
+                .intersections
+                .iter()
+                .map(|i| i.t)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(hits(0.2, 0.9), vec![5., 5.5]);
+        assert!(hits(1., 1.).is_empty());
+        assert!(hits(2.5, 1.).is_empty());
+
+        // Normals point out of the solid
+        let front = world
+            .objects
+            .iter()
+            .find(|o| o.bounds().max.z == 0.)
+            .unwrap();
+        assert_eq!(
+            front.normal_at(Tuple::point(0.2, 0.9, 0.)),
+            Tuple::vector(0., 0., -1.)
+        );
+        let wall = world
+            .objects
+            .iter()
+            .find(|o| o.bounds().max.x == 0. && o.bounds().min.x == 0.)
+            .unwrap();
+        assert_eq!(
+            wall.normal_at(Tuple::point(0., 1., 0.25)),
+            Tuple::vector(-1., 0., 0.)
+        );
+    }
+
+    #[test]
+    fn font() {
+        assert!(text(b"not a font", "A", 0.1, None).is_err());
+        // Only run where the font is installed
+        let Ok(triangles) = load_text(
+            "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+            "Hi\nA",
+            0.1,
+            None,
+        ) else {
+            return;
+        };
+        assert!(!triangles.is_empty());
+        let mut bounds = triangles[0].bounds();
+        for triangle in triangles.iter() {
+            bounds.merge(&triangle.bounds());
+        }
+        // Two lines, each less than an em high
+        assert!(bounds.min.x >= 0. && bounds.max.x < 2.);
+        assert!(bounds.max.y < 1. && bounds.min.y < -0.5);
+        assert_eq!((bounds.min.z, bounds.max.z), (0., 0.1));
+    }
+}
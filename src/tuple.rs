@@ -1,5 +1,4 @@
-use crate::EPSILON;
-use float_cmp::approx_eq;
+use crate::approx::ApproxEq;
 use std::{
     ops::{Add, AddAssign, Div, Mul, Neg, Sub},
     vec,
@@ -19,11 +18,11 @@ impl Tuple {
     }
 
     pub fn is_vector(&self) -> bool {
-        approx_eq!(f64, self.w, 0., epsilon = EPSILON)
+        self.w.approx_eq(&0.)
     }
 
     pub fn is_point(&self) -> bool {
-        approx_eq!(f64, self.w, 1., epsilon = EPSILON)
+        self.w.approx_eq(&1.)
     }
 
     pub fn point(x: f64, y: f64, z: f64) -> Self {
@@ -39,7 +38,7 @@ impl Tuple {
     }
 
     pub fn normalize(&self) -> Self {
-        self.clone() / self.magnitude()
+        *self / self.magnitude()
     }
 
     pub fn dot(&self, rhs: &Tuple) -> f64 {
@@ -66,12 +65,18 @@ impl Tuple {
     }
 }
 
+impl ApproxEq for Tuple {
+    fn approx_eq_eps(&self, other: &Self, epsilon: f64) -> bool {
+        self.x.approx_eq_eps(&other.x, epsilon)
+            && self.y.approx_eq_eps(&other.y, epsilon)
+            && self.z.approx_eq_eps(&other.z, epsilon)
+            && self.w.approx_eq_eps(&other.w, epsilon)
+    }
+}
+
 impl PartialEq for Tuple {
     fn eq(&self, other: &Self) -> bool {
-        approx_eq!(f64, self.x, other.x, epsilon = 0.00001)
-            && approx_eq!(f64, self.y, other.y, epsilon = 0.00001)
-            && approx_eq!(f64, self.z, other.z, epsilon = 0.00001)
-            && approx_eq!(f64, self.w, other.w, epsilon = 0.00001)
+        self.approx_eq(other)
     }
 }
 
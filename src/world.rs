@@ -1,58 +1,426 @@
 use crate::{
-    color::Color, light::PointLight, material::Material, matrix::Matrix, ray::Ray, shape::Object,
-    shapes::Sphere, tuple::Tuple,
+    bvh::{Bvh, BVH_MIN_OBJECTS},
+    color::{Color, BLACK, WHITE},
+    environment::{Environment, Portal},
+    light::{AmbientLight, PointLight},
+    material::{Material, ShadingPrecision},
+    matrix::Matrix,
+    ray::Ray,
+    rng::Sampler,
+    shape::{Object, ShapeType},
+    shapes::Sphere,
+    simd::SphereBatch,
+    stats::{Counter, StatCounters},
+    tuple::Tuple,
+    PI,
 };
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    ops::{Deref, DerefMut},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+thread_local! {
+    // Index of the object that last blocked a shadow ray, per light, for the objects
+    // of the generation alongside. Any object that blocks the ray is proof of shadow,
+    // so a stale entry only costs a wasted test. Objects of another generation belong
+    // to another world or an edited one, and start the cache afresh
+    static SHADOW_BLOCKERS: RefCell<(u64, HashMap<usize, usize>)> =
+        RefCell::new((0, HashMap::new()));
+}
+
+// What shadow rays make of transparent objects
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ShadowMode {
+    // Everything blocks light completely
+    #[default]
+    Opaque,
+    // Transparent objects let their transparency's worth of light through
+    Transparent,
+    // As above, with the light taking on the object's color
+    Tinted,
+}
+
+// The objects of a world. Any mutable access to them counts as an edit and gives
+// them a new generation, so snapshots of the layout like the BVH can tell when they
+// are out of date
+#[derive(Debug, Clone)]
+pub struct Objects {
+    objects: Vec<Object>,
+    generation: u64,
+}
+
+static GENERATIONS: AtomicU64 = AtomicU64::new(0);
+
+fn next_generation() -> u64 {
+    GENERATIONS.fetch_add(1, Ordering::Relaxed)
+}
+
+impl Objects {
+    // Unique across every world, so objects replaced wholesale never match a snapshot
+    // of the ones before them
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+}
 
+impl From<Vec<Object>> for Objects {
+    fn from(objects: Vec<Object>) -> Self {
+        Objects {
+            objects,
+            generation: next_generation(),
+        }
+    }
+}
+
+impl Deref for Objects {
+    type Target = Vec<Object>;
+
+    fn deref(&self) -> &Vec<Object> {
+        &self.objects
+    }
+}
+
+impl DerefMut for Objects {
+    fn deref_mut(&mut self) -> &mut Vec<Object> {
+        self.generation = next_generation();
+        &mut self.objects
+    }
+}
+
+impl PartialEq for Objects {
+    fn eq(&self, other: &Self) -> bool {
+        self.objects == other.objects
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct World {
-    pub objects: Vec<Object>,
+    pub objects: Objects,
     pub lights: Vec<PointLight>,
+    // Seen by rays that miss everything and lights surfaces from every direction.
+    // Without it the background is black
+    pub environment: Option<Environment>,
+    // Openings the environment lights the scene through, see Portal
+    pub portals: Vec<Portal>,
+    // Fill light added to every surface on top of the lights
+    pub ambient_light: Option<AmbientLight>,
+    pub shading_precision: ShadingPrecision,
+    pub shadow_mode: ShadowMode,
+    // Both with the generation of the objects they were built from
+    sphere_batch: Option<(u64, SphereBatch)>,
+    bvh: Option<(u64, Bvh)>,
+    // Shared between clones, so a world derived for one render (e.g. with render
+    // layers applied) counts into the counters of the original
+    stats: Option<Arc<StatCounters>>,
 }
 
 impl World {
     pub fn new(objects: Vec<Object>, lights: Vec<PointLight>) -> Self {
-        World { objects, lights }
+        World {
+            objects: objects.into(),
+            lights,
+            environment: None,
+            portals: vec![],
+            ambient_light: None,
+            shading_precision: ShadingPrecision::default(),
+            shadow_mode: ShadowMode::default(),
+            sphere_batch: None,
+            bvh: None,
+            stats: None,
+        }
     }
 
-    pub fn default() -> Self {
-        let light = PointLight::new(Tuple::point(-10., 10., -10.), Color::new(1., 1., 1.));
-        let mut mat1 = Material::new();
-        mat1.color = Color::new(0.8, 1., 0.6);
-        mat1.diffuse = 0.7;
-        mat1.specular = 0.2;
-        let s1 = Sphere::new(Some(mat1));
+    // Bakes everything that only depends on the scene layout ahead of rendering, so
+    // rays don't recompute it. Needs calling again after objects are edited.
+    // Large worlds get a BVH, small ones are faster tested in order with their
+    // spheres batched
+    pub fn prepare(&mut self) {
+        if self.objects.len() >= BVH_MIN_OBJECTS {
+            self.build_bvh();
+        } else {
+            self.batch_spheres();
+        }
+    }
 
-        let mut s2 = Sphere::new(None);
-        s2.transform = Matrix::scaling(0.5, 0.5, 0.5);
+    // Builds a bounding volume hierarchy so rays only test objects near their path.
+    // Like the sphere batch this is a snapshot, and takes priority over it. Once the
+    // objects are edited it is ignored until built again
+    pub fn build_bvh(&mut self) {
+        self.bvh = Some((self.objects.generation(), Bvh::new(&self.objects)));
+    }
 
-        World::new(vec![s1, s2], vec![light])
+    pub fn bvh(&self) -> Option<&Bvh> {
+        match &self.bvh {
+            Some((generation, bvh)) if *generation == self.objects.generation() => Some(bvh),
+            _ => None,
+        }
+    }
+
+    // Packs the world's spheres so rays test several of them at once. This is a
+    // snapshot of their transforms, ignored once the objects are edited
+    pub fn batch_spheres(&mut self) {
+        self.sphere_batch = Some((self.objects.generation(), SphereBatch::new(&self.objects)));
+    }
+
+    pub fn sphere_batch(&self) -> Option<&SphereBatch> {
+        match &self.sphere_batch {
+            Some((generation, batch)) if *generation == self.objects.generation() => Some(batch),
+            _ => None,
+        }
+    }
+
+    // Materials are copied into each object, so scenes share one by giving the copies
+    // the same name. This edits every object, including those inside CSG objects,
+    // whose material has the name, and returns how many there were
+    pub fn update_material(&mut self, name: &str, mut edit: impl FnMut(&mut Material)) -> usize {
+        fn update(object: &mut Object, name: &str, edit: &mut dyn FnMut(&mut Material)) -> usize {
+            let mut count = 0;
+            if let ShapeType::Csg(ref mut csg) = object.shape {
+                for child in csg.children_mut() {
+                    count += update(child, name, edit);
+                }
+            }
+            if object.material.name.as_deref() == Some(name) {
+                edit(&mut object.material);
+                count += 1;
+            }
+            count
+        }
+        // Materials don't change the layout, so this isn't an edit the BVH needs to
+        // know about
+        self.objects
+            .objects
+            .iter_mut()
+            .map(|object| update(object, name, &mut edit))
+            .sum()
     }
 
-    pub fn is_shadowed(&self, point: Tuple) -> bool {
+    // Opts in to counting rays and intersection tests during renders
+    pub fn enable_stats(&mut self) {
+        self.stats = Some(Arc::new(StatCounters::new()));
+    }
+
+    pub fn stats(&self) -> Option<&StatCounters> {
+        self.stats.as_deref()
+    }
+
+    pub fn count(&self, counter: Counter, n: u64) {
+        if let Some(stats) = &self.stats {
+            stats.add(counter, n);
+        }
+    }
+
+    // What a ray heading off in the direction sees when it hits nothing
+    pub fn background(&self, direction: Tuple) -> Color {
+        match &self.environment {
+            Some(environment) => environment.radiance(direction),
+            None => Color::new(0., 0., 0.),
+        }
+    }
+
+    // Light the environment gives a white matte surface at the point facing the
+    // normal, as Environment::diffuse, or only what comes through the portals when
+    // there are any
+    pub fn environment_diffuse(&self, point: Tuple, normal: Tuple, sampler: &mut Sampler) -> Color {
+        let Some(environment) = &self.environment else {
+            return BLACK;
+        };
+        if self.portals.is_empty() {
+            return environment.diffuse(normal);
+        }
+        let mut total = BLACK;
+        for portal in self.portals.iter() {
+            let targets = portal.sample_points(sampler);
+            // Each sample covers its share of the portal's area, and the solid angle
+            // that subtends shrinks with the square of the distance
+            let share = portal.area() / (PI * targets.len() as f64);
+            for target in targets {
+                let v = target - point;
+                let distance_squared = v.dot(&v);
+                let direction = v / distance_squared.sqrt();
+                let cos = direction.dot(&normal);
+                if cos <= 0. {
+                    continue;
+                }
+                let through = self.unblocked(point, target);
+                if through == BLACK {
+                    continue;
+                }
+                let weight = cos * direction.dot(&portal.normal()).abs() * share;
+                total =
+                    total + environment.radiance(direction) * through * (weight / distance_squared);
+            }
+        }
+        total
+    }
+
+    // How much light gets from the target to the point past shadow casting objects,
+    // with transparent ones letting their transparency through
+    fn unblocked(&self, point: Tuple, target: Tuple) -> Color {
+        let v = target - point;
+        let distance = v.magnitude();
+        let r = Ray::new(point, v / distance);
+        self.count(Counter::ShadowRays, 1);
+        let mut filter = WHITE;
+        for i in r.intersect_world(self).intersections.iter() {
+            if i.t <= 0. || i.t >= distance || !i.object.casts_shadow {
+                continue;
+            }
+            if i.object.material.transparency == 0. {
+                return BLACK;
+            }
+            filter = filter * i.object.material.transparency;
+        }
+        filter
+    }
+
+    pub fn is_shadowed(&self, light: &PointLight, point: Tuple) -> bool {
         assert!(point.is_point());
-        assert_eq!(self.lights.len(), 1);
-        let v = self.lights[0].position - point;
+        // Points outside a spot light's cone are always in the dark
+        if light.cone(point) == 0. {
+            return true;
+        }
+        self.transmitted(light, point, light.position) == BLACK
+    }
+
+    // The fraction of the light that reaches the point, per channel. Sphere lights
+    // cast a shadow ray to each of their samples, so the shadow softens towards its
+    // edges. A spot light's gobo shades it like an occluder would
+    pub fn visibility(&self, light: &PointLight, point: Tuple, sampler: &mut Sampler) -> Color {
+        if light.cone(point) == 0. {
+            return BLACK;
+        }
+        let gobo = light.gobo(point);
+        if gobo == BLACK {
+            return BLACK;
+        }
+        let Some(sphere) = &light.sphere else {
+            return self.transmitted(light, point, light.position) * gobo;
+        };
+        let targets = sphere.samples_towards(light.position, point, sampler);
+        let total = targets
+            .iter()
+            .map(|target| self.transmitted(light, point, *target))
+            .fold(BLACK, |a, b| a + b);
+        total * (1. / targets.len() as f64) * gobo
+    }
+
+    // How much of the light gets from a target on the light to the point, through
+    // whatever is in between
+    fn transmitted(&self, light: &PointLight, point: Tuple, target: Tuple) -> Color {
+        let v = target - point;
         let distance = v.magnitude();
         let direction = v.normalize();
+        let opaque = |object: &Object| {
+            self.shadow_mode == ShadowMode::Opaque || object.material.transparency == 0.
+        };
 
         let r = Ray::new(point, direction);
-        let i = r.intersect_world(&self);
-        let hit = i.hit();
-        match hit {
-            Some(h) => {
-                if h.t < distance {
-                    true
-                } else {
-                    false
-                }
+        self.count(Counter::ShadowRays, 1);
+        let blocks = |object: &Object| {
+            self.count(Counter::IntersectionTests, 1);
+            r.intersect_sided(object, true)
+                .iter()
+                .any(|i| i.t > 0. && i.t < distance && opaque(i.object))
+        };
+
+        // Neighbouring shadow rays are usually blocked by the same object, so try the
+        // last blocker for this light first
+        let key = light as *const PointLight as usize;
+        let generation = self.objects.generation();
+        let last = SHADOW_BLOCKERS.with(|cache| {
+            let mut cache = cache.borrow_mut();
+            if cache.0 != generation {
+                *cache = (generation, HashMap::new());
+            }
+            cache.1.get(&key).copied()
+        });
+        if let Some(object) = last.and_then(|index| self.objects.get(index)) {
+            if object.casts_shadow && light.shadowed_by(object) && blocks(object) {
+                return BLACK;
+            }
+        }
+
+        // Transparent objects filter the light once each, however many of their
+        // surfaces the ray crosses
+        let mut blocker = None;
+        let mut filter = WHITE;
+        let mut crossed: Vec<&Object> = vec![];
+        r.for_each_intersection(self, true, Some(light), |index, i| {
+            if blocker.is_some() || i.t <= 0. || i.t >= distance {
+                return;
+            }
+            if opaque(i.object) {
+                blocker = Some(index);
+                return;
+            }
+            if crossed.iter().any(|o| std::ptr::eq(*o, i.object)) {
+                return;
+            }
+            crossed.push(i.object);
+            let position = r.position(i.t);
+            let material = i.object.material.at(i.object, position);
+            filter = filter
+                * match self.shadow_mode {
+                    ShadowMode::Tinted => {
+                        material.color_at(i.object, position) * material.transparency
+                    }
+                    _ => WHITE * material.transparency,
+                };
+        });
+        match blocker {
+            Some(index) => {
+                SHADOW_BLOCKERS.with(|cache| cache.borrow_mut().1.insert(key, index));
+                BLACK
             }
-            None => false,
+            None => filter,
         }
     }
 }
 
+// Worlds are equal when they hold the same scene, whatever has been baked for it
+impl PartialEq for World {
+    fn eq(&self, other: &Self) -> bool {
+        self.objects == other.objects
+            && self.lights == other.lights
+            && self.environment == other.environment
+            && self.portals == other.portals
+            && self.ambient_light == other.ambient_light
+            && self.shading_precision == other.shading_precision
+            && self.shadow_mode == other.shadow_mode
+    }
+}
+
+impl Default for World {
+    fn default() -> Self {
+        let light = PointLight::new(Tuple::point(-10., 10., -10.), Color::new(1., 1., 1.));
+        let mut mat1 = Material::new();
+        mat1.color = Color::new(0.8, 1., 0.6);
+        mat1.diffuse = 0.7;
+        mat1.specular = 0.2;
+        let s1 = Sphere::new(Some(mat1));
+
+        let mut s2 = Sphere::new(None);
+        s2.set_transform(&Matrix::scaling(0.5, 0.5, 0.5));
+
+        World::new(vec![s1, s2], vec![light])
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::ray::Ray;
+    use crate::{
+        assert_approx_eq,
+        light::{SphereLight, SpotLight},
+        pattern::CheckerPattern,
+        ray::Ray,
+        shapes::{Csg, CsgOperation, Cube, Plane},
+    };
 
     use super::*;
     #[test]
@@ -68,7 +436,7 @@ mod tests {
         mat1.specular = 0.2;
         let s1 = Sphere::new(Some(mat1));
         let mut s2 = Sphere::new(None);
-        s2.transform = Matrix::scaling(0.5, 0.5, 0.5);
+        s2.set_transform(&Matrix::scaling(0.5, 0.5, 0.5));
         assert_eq!(w.objects[0], s1);
         assert_eq!(w.objects[1], s2);
     }
@@ -88,13 +456,376 @@ mod tests {
     #[test]
     fn shadows() {
         let w = World::default();
+        let light = &w.lights[0];
         let p = Tuple::point(0., 10., 0.);
-        assert!(!w.is_shadowed(p));
+        assert!(!w.is_shadowed(light, p));
         let p = Tuple::point(10., -10., 10.);
-        assert!(w.is_shadowed(p));
+        assert!(w.is_shadowed(light, p));
         let p = Tuple::point(-20., -20., -20.);
-        assert!(!w.is_shadowed(p));
+        assert!(!w.is_shadowed(light, p));
         let p = Tuple::point(-2., 2., 2.);
-        assert!(!w.is_shadowed(p));
+        assert!(!w.is_shadowed(light, p));
+
+        // Nothing is in the way, but the point is outside the spot light's cone
+        let spot = SpotLight::new(
+            light.position,
+            Tuple::vector(1., 0., 0.),
+            light.intensity,
+            0.5,
+            1.,
+        );
+        assert!(w.is_shadowed(&spot, p));
+    }
+
+    #[test]
+    fn casts_shadow() {
+        let mut w = World::default();
+        let p = Tuple::point(10., -10., 10.);
+        w.objects[0].casts_shadow = false;
+        assert!(w.is_shadowed(&w.lights[0], p));
+        w.objects[1].casts_shadow = false;
+        assert!(!w.is_shadowed(&w.lights[0], p));
+
+        // Still visible to the camera
+        let r = Ray::new(Tuple::point(0., 0., -5.), Tuple::vector(0., 0., 1.));
+        assert_eq!(r.intersect_world(&w).intersections.len(), 4);
+    }
+
+    #[test]
+    fn shadow_linking() {
+        let mut w = World::default();
+        w.objects[0].name = Some(String::from("hero"));
+        let p = Tuple::point(10., -10., 10.);
+        assert!(w.is_shadowed(&w.lights[0], p));
+
+        // The smaller sphere inside the hero isn't named, so it still casts a shadow
+        w.lights[0].shadow_exclude = vec![String::from("hero")];
+        assert!(w.is_shadowed(&w.lights[0], p));
+        w.objects[1].set_transform(&Matrix::translation(5., 5., 5.));
+        assert!(!w.is_shadowed(&w.lights[0], p));
+
+        w.lights[0].shadow_exclude = vec![];
+        w.lights[0].shadow_include = vec![String::from("hero")];
+        assert!(w.is_shadowed(&w.lights[0], p));
+        w.objects[0].set_transform(&Matrix::translation(5., 0., 0.));
+        w.objects[1].set_transform(&Matrix::identity(4));
+        assert!(!w.is_shadowed(&w.lights[0], p));
+    }
+
+    #[test]
+    fn transmissive_shadows() {
+        let mut w = World::default();
+        let p = Tuple::point(10., -10., 10.);
+        for object in w.objects.iter_mut() {
+            object.material.transparency = 0.5;
+            object.material.color = Color::new(1., 0.5, 0.);
+        }
+        assert_eq!(w.visibility(&w.lights[0], p, &mut Sampler::Unseeded), BLACK);
+
+        // Each sphere lets half the light through, however many surfaces it has
+        w.shadow_mode = ShadowMode::Transparent;
+        assert_eq!(
+            w.visibility(&w.lights[0], p, &mut Sampler::Unseeded),
+            Color::new(0.25, 0.25, 0.25)
+        );
+        assert!(!w.is_shadowed(&w.lights[0], p));
+        w.shadow_mode = ShadowMode::Tinted;
+        assert_eq!(
+            w.visibility(&w.lights[0], p, &mut Sampler::Unseeded),
+            Color::new(0.25, 0.0625, 0.)
+        );
+
+        // Opaque objects still block everything
+        w.objects[1].material.transparency = 0.;
+        assert_eq!(w.visibility(&w.lights[0], p, &mut Sampler::Unseeded), BLACK);
+    }
+
+    #[test]
+    fn soft_shadows() {
+        let light = SphereLight::new(Tuple::point(0., 10., 0.), 2., Color::new(1., 1., 1.));
+        let blocker = Sphere::new(None)
+            .with_transform(&(Matrix::translation(0., 5., 0.) * &Matrix::scaling(1.5, 1.5, 1.5)));
+        let w = World::new(vec![blocker], vec![light]);
+        let light = &w.lights[0];
+
+        // The blocker hides all of the light, some of it, or none of it
+        assert_eq!(
+            w.visibility(light, Tuple::point(0., 0., 0.), &mut Sampler::Unseeded),
+            BLACK
+        );
+        let penumbra = w
+            .visibility(light, Tuple::point(3., 0., 0.), &mut Sampler::Unseeded)
+            .red;
+        assert!(0. < penumbra && penumbra < 1.);
+        assert_eq!(
+            w.visibility(light, Tuple::point(20., 0., 0.), &mut Sampler::Unseeded),
+            WHITE
+        );
+    }
+
+    #[test]
+    fn portals() {
+        let mut w = World::new(vec![], vec![]);
+        w.environment = Some(Environment::uniform(WHITE));
+        let (point, up) = (Tuple::point(0., 0., 0.), Tuple::vector(0., 1., 0.));
+        assert_approx_eq!(
+            w.environment_diffuse(point, up, &mut Sampler::Unseeded).red,
+            1.,
+            epsilon = 0.01
+        );
+
+        // A 2x2 skylight a unit above covers this much of the sky, weighted by cosine
+        let mut skylight = Portal::new(
+            Tuple::point(-1., 1., -1.),
+            Tuple::vector(2., 0., 0.),
+            Tuple::vector(0., 0., 2.),
+        );
+        skylight.samples = 1024;
+        w.portals.push(skylight);
+        let corner = (0.5_f64.sqrt() * 0.5_f64.sqrt().atan()) / PI;
+        assert_approx_eq!(
+            w.environment_diffuse(point, up, &mut Sampler::Unseeded).red,
+            4. * corner,
+            epsilon = 0.02
+        );
+        // Nothing comes in from behind the surface
+        assert_eq!(
+            w.environment_diffuse(point, -up, &mut Sampler::Unseeded),
+            BLACK
+        );
+
+        // A ceiling under the skylight shuts it out, unless it lets light through
+        w.objects
+            .push(Plane::new(None).with_transform(&Matrix::translation(0., 0.5, 0.)));
+        assert_eq!(
+            w.environment_diffuse(point, up, &mut Sampler::Unseeded),
+            BLACK
+        );
+        w.objects[0].material.transparency = 0.5;
+        assert_approx_eq!(
+            w.environment_diffuse(point, up, &mut Sampler::Unseeded).red,
+            2. * corner,
+            epsilon = 0.01
+        );
+
+        // The portal samples come from the sampler, so a seed repeats them exactly
+        w.portals[0].samples = 4;
+        let seeded = || Sampler::for_pixel(Some(42), 3, 5, 0);
+        let light = w.environment_diffuse(point, up, &mut seeded()).red;
+        assert_eq!(
+            light.to_bits(),
+            w.environment_diffuse(point, up, &mut seeded())
+                .red
+                .to_bits()
+        );
+    }
+
+    #[test]
+    fn gobo_shadows() {
+        // Checkers projected down, as if through a grille, so they grow further away
+        let mut light = SpotLight::new(
+            Tuple::point(0., 10., 0.),
+            Tuple::vector(0., -1., 0.),
+            Color::new(1., 1., 1.),
+            PI / 3.,
+            PI / 3.,
+        );
+        let mut grille = CheckerPattern::new(WHITE, BLACK);
+        grille.set_transform(&Matrix::scaling(0.1, 0.1, 0.1));
+        light.spot.as_mut().unwrap().gobo = Some(grille);
+        let w = World::new(vec![], vec![light]);
+        let light = &w.lights[0];
+        assert_eq!(
+            w.visibility(light, Tuple::point(-1.5, 0., -0.5), &mut Sampler::Unseeded),
+            WHITE
+        );
+        assert_eq!(
+            w.visibility(light, Tuple::point(-0.5, 0., -0.5), &mut Sampler::Unseeded),
+            BLACK
+        );
+        assert_eq!(
+            w.visibility(
+                light,
+                Tuple::point(-1.5, -10., -0.5),
+                &mut Sampler::Unseeded
+            ),
+            BLACK
+        );
+    }
+
+    #[test]
+    fn batched_spheres() {
+        let mut w = World::default();
+        let r = Ray::new(Tuple::point(0., 0., -5.), Tuple::vector(0., 0., 1.));
+        let ts = |w: &World| -> Vec<f64> {
+            r.intersect_world(w)
+                .intersections
+                .iter()
+                .map(|i| i.t)
+                .collect()
+        };
+        let unbatched = ts(&w);
+        w.batch_spheres();
+        assert_eq!(w.sphere_batch().unwrap().len(), 2);
+        assert_eq!(ts(&w), unbatched);
+
+        // A stale batch is ignored rather than producing wrong results
+        w.objects.push(Sphere::new(None));
+        assert!(w.sphere_batch().is_none());
+        assert_eq!(r.intersect_world(&w).intersections.len(), 6);
+
+        // So is one whose spheres have moved
+        let mut w = World::default();
+        w.batch_spheres();
+        w.objects[1].set_transform(&Matrix::translation(0., 5., 0.));
+        assert!(w.sphere_batch().is_none());
+        let xs = r.intersect_world(&w);
+        assert_eq!((xs.intersections.len(), xs.intersections[0].t), (2, 4.));
+    }
+
+    #[test]
+    fn prepare() {
+        let mut w = World::default();
+        w.prepare();
+        assert!(w.sphere_batch().is_some());
+        let r = Ray::new(Tuple::point(0., 0., -5.), Tuple::vector(0., 0., 1.));
+        let c = r.color_hit(&w, crate::shape::MAX_REFLECTIONS);
+        assert_eq!(c, Color::new(0.38066, 0.47583, 0.2855));
+    }
+
+    #[test]
+    fn bvh() {
+        // A row of spheres along z, behind a floor
+        let mut objects =
+            vec![crate::shapes::Plane::new(None).with_transform(&Matrix::translation(0., -1., 0.))];
+        for z in 0..BVH_MIN_OBJECTS {
+            let transform = Matrix::translation(0., 0., z as f64 * 3.);
+            objects.push(Sphere::new(None).with_transform(&transform));
+        }
+        let lights = World::default().lights;
+        let mut w = World::new(objects, lights);
+        w.enable_stats();
+
+        let rays = [
+            Ray::new(Tuple::point(0., 0., -5.), Tuple::vector(0., 0., 1.)),
+            Ray::new(Tuple::point(-5., 0., 9.), Tuple::vector(1., 0., 0.)),
+            Ray::new(Tuple::point(-5., 5., 9.), Tuple::vector(1., -1., 0.)),
+        ];
+        let ts = |w: &World| -> Vec<Vec<f64>> {
+            rays.iter()
+                .map(|r| {
+                    r.intersect_world(w)
+                        .intersections
+                        .iter()
+                        .map(|i| i.t)
+                        .collect()
+                })
+                .collect()
+        };
+        let linear = ts(&w);
+        w.prepare();
+        assert!(w.bvh().is_some());
+        w.stats().unwrap().reset();
+        assert_eq!(ts(&w), linear);
+
+        // Rays across the row only test the sphere they cross and the floor
+        let stats = w.stats().unwrap();
+        assert!(stats.get(Counter::BvhNodeVisits) > 0);
+        assert!(stats.get(Counter::IntersectionTests) < 3 * w.objects.len() as u64);
+        let colors: Vec<Color> = rays.iter().map(|r| r.color_hit(&w, 5)).collect();
+        w.bvh = None;
+        for (r, c) in rays.iter().zip(colors) {
+            assert_eq!(r.color_hit(&w, 5), c);
+        }
+    }
+
+    #[test]
+    fn stale_bvh() {
+        let objects = (0..20)
+            .map(|z| Sphere::new(None).with_transform(&Matrix::translation(0., 0., z as f64 * 3.)))
+            .collect();
+        let mut w = World::new(objects, vec![]);
+        w.prepare();
+        assert!(w.bvh().is_some());
+
+        // Moving a sphere after preparing leaves the BVH out of date, so it's ignored
+        w.objects[5].set_transform(&Matrix::translation(10., 0., 0.));
+        assert!(w.bvh().is_none());
+        let r = Ray::new(Tuple::point(10., 0., -5.), Tuple::vector(0., 0., 1.));
+        assert_eq!(r.intersect_world(&w).intersections.len(), 2);
+        w.prepare();
+        assert!(w.bvh().is_some());
+        assert_eq!(r.intersect_world(&w).intersections.len(), 2);
+
+        // Editing materials by name keeps it
+        w.update_material("wall", |m| m.ambient = 1.);
+        assert!(w.bvh().is_some());
+    }
+
+    #[test]
+    fn shadow_blocker_cache() {
+        let w = World::default();
+        let light = &w.lights[0];
+        let key = light as *const PointLight as usize;
+        assert!(w.is_shadowed(light, Tuple::point(10., -10., 10.)));
+        let blocker = SHADOW_BLOCKERS.with(|cache| cache.borrow().1[&key]);
+        assert!(blocker < w.objects.len());
+
+        // The cached blocker doesn't shadow points it isn't in front of
+        assert!(!w.is_shadowed(light, Tuple::point(-2., 2., -2.)));
+        assert!(w.is_shadowed(light, Tuple::point(10., -10., 10.)));
+
+        // Another world starts afresh, even if its light reuses the address
+        let other = World::new(vec![], w.lights.clone());
+        assert!(!other.is_shadowed(&other.lights[0], Tuple::point(10., -10., 10.)));
+        SHADOW_BLOCKERS.with(|cache| {
+            let cache = cache.borrow();
+            assert_eq!(cache.0, other.objects.generation());
+            assert!(cache.1.is_empty());
+        });
+    }
+
+    #[test]
+    fn clone_world() {
+        let mut w = World::default();
+        w.prepare();
+        let mut frame = w.clone();
+        assert_eq!(frame, w);
+        assert!(frame.sphere_batch().is_some());
+
+        frame.objects[1].set_transform(&Matrix::translation(0., 1., 0.));
+        frame.lights[0].intensity = Color::new(0.5, 0.5, 0.5);
+        assert_ne!(frame, w);
+        assert_eq!(*w.objects[1].transform(), Matrix::scaling(0.5, 0.5, 0.5));
+        assert_eq!(w.lights[0].intensity, Color::new(1., 1., 1.));
+    }
+
+    #[test]
+    fn update_material() {
+        let mut wall = Material::new();
+        wall.name = Some(String::from("wall"));
+        let left = Plane::new(Some(wall.clone()));
+        let right = Plane::new(Some(wall.clone()));
+        let csg = Csg::new(
+            CsgOperation::Difference,
+            Cube::new(Some(wall)),
+            Sphere::new(None),
+        );
+        let mut w = World::new(vec![left, right, csg, Sphere::new(None)], vec![]);
+
+        let red = Color::new(1., 0., 0.);
+        assert_eq!(w.update_material("wall", |m| m.color = red), 3);
+        assert_eq!(w.update_material("floor", |m| m.color = red), 0);
+        let ShapeType::Csg(ref csg) = w.objects[2].shape else {
+            unreachable!()
+        };
+        for object in [&w.objects[0], &w.objects[1], csg.left()] {
+            assert_eq!(
+                object.material.color_at(object, Tuple::point(0., 0., 0.)),
+                red
+            );
+        }
+        assert_eq!(csg.right().material, Material::new());
+        assert_eq!(w.objects[3].material, Material::new());
     }
 }